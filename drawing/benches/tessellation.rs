@@ -0,0 +1,66 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use drawing::path::FlattenedPath;
+use drawing::primitive::*;
+use drawing::primitive_extensions::*;
+use drawing::units::*;
+
+fn star_path(points: usize) -> Vec<PathElement> {
+    let mut path = Vec::with_capacity(points * 2 + 2);
+    for i in 0..points * 2 {
+        let angle = i as f32 * std::f32::consts::PI / points as f32;
+        let radius = if i % 2 == 0 { 400.0f32 } else { 150.0f32 };
+        let point = PixelPoint::new(500.0 + angle.cos() * radius, 500.0 + angle.sin() * radius);
+        if i == 0 {
+            path.push(PathElement::MoveTo(point));
+        } else {
+            path.push(PathElement::LineTo(point));
+        }
+    }
+    path.push(PathElement::ClosePath);
+    path
+}
+
+fn dashboard_path() -> Vec<PathElement> {
+    let mut path = Vec::new();
+    for y in 0..10 {
+        for x in 0..10 {
+            path.extend(rect_path_rounded(
+                PixelRect::new(
+                    PixelPoint::new(x as f32 * 100.0, y as f32 * 100.0),
+                    PixelSize::new(90.0, 90.0),
+                ),
+                10.0,
+            ));
+            path.extend(circle_path(
+                PixelPoint::new(x as f32 * 100.0 + 45.0, y as f32 * 100.0 + 45.0),
+                30.0,
+            ));
+        }
+    }
+    path
+}
+
+fn tessellation(c: &mut Criterion) {
+    let paths = [("star", star_path(1000)), ("dashboard", dashboard_path())];
+
+    for (name, path) in paths.iter() {
+        c.bench_function(&format!("fill {}", name), |b| {
+            b.iter(|| {
+                let mut flattened_path = FlattenedPath::new(black_box(path), 0.01, 0.25);
+                flattened_path.expand_fill(1.0, LineJoin::Miter, 2.4, 1.0);
+                flattened_path
+            })
+        });
+
+        c.bench_function(&format!("stroke {}", name), |b| {
+            b.iter(|| {
+                let mut flattened_path = FlattenedPath::new(black_box(path), 0.01, 0.25);
+                flattened_path.expand_stroke(2.0, 1.0, LineCap::Round, LineJoin::Round, 10.0, 0.25);
+                flattened_path
+            })
+        });
+    }
+}
+
+criterion_group!(benches, tessellation);
+criterion_main!(benches);
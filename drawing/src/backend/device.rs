@@ -1,7 +1,7 @@
 use anyhow::Result;
 use core::marker::Sized;
 use core::option::Option;
-use crate::backend::{RenderTarget, Texture};
+use crate::backend::{DistanceFieldParams, GlyphPaint, RenderTarget, Texture};
 use crate::backend::colored_vertex::ColoredVertex;
 use crate::backend::textured_vertex::TexturedVertex;
 use crate::backend::textured_y8_vertex::TexturedY8Vertex;
@@ -9,7 +9,7 @@ use crate::clipping::Scissor;
 use crate::color::{Color, ColorFormat};
 use crate::composite_operation_state::CompositeOperationState;
 use crate::paint::Paint;
-use crate::path::{Bounds, Path};
+use crate::path::FlattenedPath;
 use crate::primitive::PathElement;
 use crate::units::{DeviceThickness, PixelRect, Point, Rect, UnknownToDeviceTransform};
 
@@ -63,6 +63,41 @@ pub trait Device {
         transform: UnknownToDeviceTransform,
     );
 
+    /// Draws glyphs stored as signed distance fields (8-bit grayscale texture).
+    /// The color of the vertices is the fill color.
+    fn triangles_distance_field(
+        &mut self,
+        target: &Self::RenderTarget,
+        texture: &Self::Texture,
+        vertices: &[TexturedY8Vertex],
+        params: &DistanceFieldParams,
+        transform: UnknownToDeviceTransform,
+    );
+
+    /// Draws glyphs rasterized for LCD screens (RGBA texture with the coverage
+    /// of the red, green and blue subpixels). The color of the vertices is the text color.
+    ///
+    /// Every color channel of the target is blended with its own coverage.
+    fn triangles_textured_lcd(
+        &mut self,
+        target: &Self::RenderTarget,
+        texture: &Self::Texture,
+        vertices: &[TexturedY8Vertex],
+        transform: UnknownToDeviceTransform,
+    );
+
+    /// Draws glyphs (8-bit grayscale coverage texture) filled with the paint
+    /// the same way `fill()` fills paths. The color of the vertices is ignored.
+    fn triangles_textured_y8_paint(
+        &mut self,
+        target: &Self::RenderTarget,
+        texture: &Self::Texture,
+        filtering: bool,
+        vertices: &[TexturedY8Vertex],
+        paint: &GlyphPaint<Self::Texture>,
+        transform: UnknownToDeviceTransform,
+    );
+
     fn line(
         &mut self,
         target: &Self::RenderTarget,
@@ -172,7 +207,7 @@ pub trait Device {
         paint: &Paint,
         texture: Option<&Self::Texture>,
         filtering: bool,
-        paths: &[Path],
+        flattened_path: &FlattenedPath,
         thickness: f32,
         fringe_width: f32,
         antialiasing: bool,
@@ -187,8 +222,7 @@ pub trait Device {
         paint: &Paint,
         texture: Option<&Self::Texture>,
         filtering: bool,
-        paths: &[Path],
-        bounds: Bounds,
+        flattened_path: &FlattenedPath,
         fringe_width: f32,
         antialiasing: bool,
         scissor: Scissor,
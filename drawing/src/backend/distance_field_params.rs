@@ -0,0 +1,18 @@
+use crate::color::Color;
+
+/// Parameters of text rendered from signed distance fields.
+///
+/// Distances are in texels of the distance field texture.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct DistanceFieldParams {
+    /// Distance between texture values 0.0 and 1.0 (value 0.5 is the edge of the glyph).
+    pub distance_range: f32,
+    pub outline_width: f32,
+    pub outline_color: Color,
+    /// Width of the glow outside of the glyph (and the outline).
+    pub glow_width: f32,
+    pub glow_color: Color,
+    /// Width of the edge smoothing (blurs the text like its shadow, zero
+    /// for the antialiasing only).
+    pub softness: f32,
+}
@@ -0,0 +1,20 @@
+use crate::clipping::Scissor;
+use crate::paint::Paint;
+
+/// Paint the glyphs are filled with by `Device::triangles_textured_y8_paint()`.
+pub struct GlyphPaint<'a, T> {
+    /// Evaluated at the pixels of the vertex positions (the same way `Device::fill()`
+    /// evaluates it).
+    pub paint: &'a Paint,
+    /// Image of the image pattern paint.
+    pub texture: Option<&'a T>,
+    pub scissor: Scissor,
+}
+
+impl<T> Clone for GlyphPaint<'_, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for GlyphPaint<'_, T> {}
@@ -7,6 +7,12 @@ pub use textured_vertex::*;
 mod textured_y8_vertex;
 pub use textured_y8_vertex::*;
 
+mod distance_field_params;
+pub use distance_field_params::*;
+
+mod glyph_paint;
+pub use glyph_paint::*;
+
 mod device;
 pub use device::*;
 
@@ -15,3 +21,8 @@ pub use texture::*;
 
 mod render_target;
 pub use render_target::*;
+
+#[cfg(test)]
+mod test_device;
+#[cfg(test)]
+pub(crate) use test_device::*;
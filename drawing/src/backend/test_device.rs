@@ -0,0 +1,191 @@
+//! Device that draws nothing, for tests of code generic over `Device`.
+
+use crate::backend::*;
+use crate::clipping::Scissor;
+use crate::color::{Color, ColorFormat};
+use crate::composite_operation_state::CompositeOperationState;
+use crate::paint::Paint;
+use crate::path::FlattenedPath;
+use crate::units::{DeviceThickness, PixelToDeviceTransform, Point, UnknownToDeviceTransform};
+use anyhow::Result;
+
+pub struct TestTexture {
+    width: u16,
+    height: u16,
+}
+
+impl Texture for TestTexture {
+    fn get_size(&self) -> (u16, u16) {
+        (self.width, self.height)
+    }
+
+    fn update(
+        &mut self,
+        _memory: &[u8],
+        _offset_x: u16,
+        _offset_y: u16,
+        _width: u16,
+        _height: u16,
+    ) -> Result<()> {
+        Ok(())
+    }
+}
+
+pub struct TestRenderTarget {
+    width: u16,
+    height: u16,
+}
+
+impl RenderTarget for TestRenderTarget {
+    fn update_size(&mut self, width: u16, height: u16) {
+        self.width = width;
+        self.height = height;
+    }
+
+    fn get_size(&self) -> (u16, u16) {
+        (self.width, self.height)
+    }
+
+    fn get_aspect_ratio(&self) -> f32 {
+        1.0
+    }
+
+    fn get_device_transform(&self) -> PixelToDeviceTransform {
+        PixelToDeviceTransform::identity()
+    }
+}
+
+pub struct TestDevice;
+
+impl Device for TestDevice {
+    type Texture = TestTexture;
+    type RenderTarget = TestRenderTarget;
+
+    fn new() -> Result<Self> {
+        Ok(TestDevice)
+    }
+
+    fn create_texture(
+        &mut self,
+        _memory: Option<&[u8]>,
+        width: u16,
+        height: u16,
+        _format: ColorFormat,
+        _updatable: bool,
+    ) -> Result<Self::Texture> {
+        Ok(TestTexture { width, height })
+    }
+
+    fn create_render_target(
+        &mut self,
+        width: u16,
+        height: u16,
+    ) -> Result<(Self::Texture, Self::RenderTarget)> {
+        Ok((
+            TestTexture { width, height },
+            TestRenderTarget { width, height },
+        ))
+    }
+
+    fn clear(&mut self, _target: &Self::RenderTarget, _color: &Color) {}
+
+    fn triangles_colored(
+        &mut self,
+        _target: &Self::RenderTarget,
+        _vertices: &[ColoredVertex],
+        _transform: UnknownToDeviceTransform,
+    ) {
+    }
+
+    fn triangles_textured(
+        &mut self,
+        _target: &Self::RenderTarget,
+        _texture: &Self::Texture,
+        _filtering: bool,
+        _vertices: &[TexturedVertex],
+        _transform: UnknownToDeviceTransform,
+    ) {
+    }
+
+    fn triangles_textured_y8(
+        &mut self,
+        _target: &Self::RenderTarget,
+        _texture: &Self::Texture,
+        _filtering: bool,
+        _vertices: &[TexturedY8Vertex],
+        _transform: UnknownToDeviceTransform,
+    ) {
+    }
+
+    fn triangles_distance_field(
+        &mut self,
+        _target: &Self::RenderTarget,
+        _texture: &Self::Texture,
+        _vertices: &[TexturedY8Vertex],
+        _params: &DistanceFieldParams,
+        _transform: UnknownToDeviceTransform,
+    ) {
+    }
+
+    fn triangles_textured_lcd(
+        &mut self,
+        _target: &Self::RenderTarget,
+        _texture: &Self::Texture,
+        _vertices: &[TexturedY8Vertex],
+        _transform: UnknownToDeviceTransform,
+    ) {
+    }
+
+    fn triangles_textured_y8_paint(
+        &mut self,
+        _target: &Self::RenderTarget,
+        _texture: &Self::Texture,
+        _filtering: bool,
+        _vertices: &[TexturedY8Vertex],
+        _paint: &GlyphPaint<Self::Texture>,
+        _transform: UnknownToDeviceTransform,
+    ) {
+    }
+
+    fn line(
+        &mut self,
+        _target: &Self::RenderTarget,
+        _color: &Color,
+        _thickness: DeviceThickness,
+        _start_point: Point,
+        _end_point: Point,
+        _transform: UnknownToDeviceTransform,
+    ) {
+    }
+
+    fn stroke(
+        &mut self,
+        _target: &Self::RenderTarget,
+        _paint: &Paint,
+        _texture: Option<&Self::Texture>,
+        _filtering: bool,
+        _flattened_path: &FlattenedPath,
+        _thickness: f32,
+        _fringe_width: f32,
+        _antialiasing: bool,
+        _scissor: Scissor,
+        _composite_operation_state: CompositeOperationState,
+        _transform: UnknownToDeviceTransform,
+    ) {
+    }
+
+    fn fill(
+        &mut self,
+        _target: &Self::RenderTarget,
+        _paint: &Paint,
+        _texture: Option<&Self::Texture>,
+        _filtering: bool,
+        _flattened_path: &FlattenedPath,
+        _fringe_width: f32,
+        _antialiasing: bool,
+        _scissor: Scissor,
+        _composite_operation_state: CompositeOperationState,
+        _transform: UnknownToDeviceTransform,
+    ) {
+    }
+}
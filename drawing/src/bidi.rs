@@ -0,0 +1,196 @@
+//! Bidirectional text (Unicode Bidirectional Algorithm).
+//!
+//! Text is stored in logical order (the order it is typed). Every line is split
+//! into runs of the same embedding level which are drawn in visual order
+//! (left to right), with the characters of the right-to-left runs reversed.
+
+use std::ops::Range;
+use unicode_bidi::{Level, ParagraphBidiInfo};
+
+/// Base direction of the paragraph.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub enum TextDirection {
+    /// Taken from the first strong directional character (left-to-right if there is none).
+    #[default]
+    Auto,
+    LeftToRight,
+    RightToLeft,
+}
+
+impl TextDirection {
+    /// Returns the direction of characters with the given embedding level.
+    pub fn from_level(level: u8) -> Self {
+        if level % 2 == 1 {
+            TextDirection::RightToLeft
+        } else {
+            TextDirection::LeftToRight
+        }
+    }
+
+    fn level(self) -> Option<Level> {
+        match self {
+            TextDirection::Auto => None,
+            TextDirection::LeftToRight => Some(Level::ltr()),
+            TextDirection::RightToLeft => Some(Level::rtl()),
+        }
+    }
+}
+
+/// Characters of the same embedding level.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BidiRun {
+    /// Byte range in the paragraph.
+    pub range: Range<usize>,
+    pub level: u8,
+}
+
+impl BidiRun {
+    pub fn is_rtl(&self) -> bool {
+        self.level % 2 == 1
+    }
+
+    /// Direction the run has to be shaped with.
+    pub fn direction(&self) -> TextDirection {
+        TextDirection::from_level(self.level)
+    }
+}
+
+/// Embedding levels of a paragraph (text without new line characters).
+pub struct BidiParagraph<'a> {
+    info: ParagraphBidiInfo<'a>,
+}
+
+impl<'a> BidiParagraph<'a> {
+    pub fn new(text: &'a str, direction: TextDirection) -> Self {
+        BidiParagraph {
+            info: ParagraphBidiInfo::new(text, direction.level()),
+        }
+    }
+
+    /// Returns `true` if the resolved base direction is right-to-left.
+    pub fn is_rtl(&self) -> bool {
+        self.info.paragraph_level.is_rtl()
+    }
+
+    /// Returns the embedding level of every character of the line
+    /// (a byte range of the paragraph).
+    ///
+    /// Trailing white spaces of the line get the paragraph level.
+    pub fn line_levels(&self, line: Range<usize>) -> Vec<u8> {
+        if self.info.is_pure_ltr && !self.is_rtl() {
+            return vec![0; self.info.text[line].chars().count()];
+        }
+        let levels = self.info.reordered_levels(line.clone());
+        self.info.text[line.clone()]
+            .char_indices()
+            .map(|(pos, _)| levels[line.start + pos].number())
+            .collect()
+    }
+
+    /// Splits the line (a byte range of the paragraph) into runs
+    /// of the same embedding level in logical order.
+    pub fn line_runs(&self, line: Range<usize>) -> Vec<BidiRun> {
+        let text = self.info.text;
+        let levels = self.line_levels(line.clone());
+        let mut runs: Vec<BidiRun> = Vec::new();
+        for ((pos, ch), level) in text[line.clone()].char_indices().zip(levels) {
+            let start = line.start + pos;
+            let end = start + ch.len_utf8();
+            match runs.last_mut() {
+                Some(run) if run.level == level => run.range.end = end,
+                _ => runs.push(BidiRun {
+                    range: start..end,
+                    level,
+                }),
+            }
+        }
+        runs
+    }
+}
+
+/// Returns the logical indices of items (characters or runs)
+/// with the given embedding levels in visual order.
+pub fn visual_order(levels: &[u8]) -> Vec<usize> {
+    let levels: Vec<Level> = levels
+        .iter()
+        .map(|&level| Level::new(level).unwrap_or_else(|_| Level::ltr()))
+        .collect();
+    ParagraphBidiInfo::reorder_visual(&levels)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(range: Range<usize>, level: u8) -> BidiRun {
+        BidiRun { range, level }
+    }
+
+    #[test]
+    fn auto_direction_is_taken_from_first_strong_character() {
+        assert!(!BidiParagraph::new("abc", TextDirection::Auto).is_rtl());
+        assert!(!BidiParagraph::new("123", TextDirection::Auto).is_rtl());
+        assert!(BidiParagraph::new("12 אב abc", TextDirection::Auto).is_rtl());
+        assert!(!BidiParagraph::new("abc אב", TextDirection::Auto).is_rtl());
+        assert!(BidiParagraph::new("abc", TextDirection::RightToLeft).is_rtl());
+        assert!(!BidiParagraph::new("אב", TextDirection::LeftToRight).is_rtl());
+    }
+
+    #[test]
+    fn pure_ltr_text_is_one_run() {
+        let text = "abc def";
+        let paragraph = BidiParagraph::new(text, TextDirection::Auto);
+        assert_eq!(paragraph.line_levels(0..text.len()), vec![0; 7]);
+        assert_eq!(paragraph.line_runs(0..text.len()), vec![run(0..7, 0)]);
+    }
+
+    #[test]
+    fn rtl_run_in_ltr_paragraph() {
+        let text = "abc אבג def";
+        let paragraph = BidiParagraph::new(text, TextDirection::Auto);
+        let runs = paragraph.line_runs(0..text.len());
+        assert_eq!(runs, vec![run(0..4, 0), run(4..10, 1), run(10..14, 0)]);
+        assert!(runs[1].is_rtl());
+        assert_eq!(runs[1].direction(), TextDirection::RightToLeft);
+
+        let levels: Vec<u8> = runs.iter().map(|run| run.level).collect();
+        assert_eq!(visual_order(&levels), vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn ltr_run_in_rtl_paragraph_is_placed_first() {
+        let text = "אב abc";
+        let paragraph = BidiParagraph::new(text, TextDirection::Auto);
+        let runs = paragraph.line_runs(0..text.len());
+        assert_eq!(runs, vec![run(0..5, 1), run(5..8, 2)]);
+        assert_eq!(runs[1].direction(), TextDirection::LeftToRight);
+
+        let levels: Vec<u8> = runs.iter().map(|run| run.level).collect();
+        assert_eq!(visual_order(&levels), vec![1, 0]);
+    }
+
+    #[test]
+    fn runs_of_line_are_in_paragraph_offsets() {
+        let text = "abc אבג def";
+        let paragraph = BidiParagraph::new(text, TextDirection::Auto);
+        assert_eq!(
+            paragraph.line_runs(4..14),
+            vec![run(4..10, 1), run(10..14, 0)]
+        );
+    }
+
+    #[test]
+    fn trailing_white_space_gets_paragraph_level() {
+        let text = "abc ";
+        let paragraph = BidiParagraph::new(text, TextDirection::RightToLeft);
+        assert_eq!(paragraph.line_levels(0..text.len()), vec![2, 2, 2, 1]);
+    }
+
+    #[test]
+    fn visual_order_reverses_rtl_characters() {
+        assert_eq!(visual_order(&[0, 1, 1, 0]), vec![0, 2, 1, 3]);
+        assert_eq!(visual_order(&[1, 2, 2, 1]), vec![3, 1, 2, 0]);
+        assert_eq!(visual_order(&[1, 1, 1]), vec![2, 1, 0]);
+        assert_eq!(visual_order(&[]), Vec::<usize>::new());
+    }
+}
@@ -93,6 +93,7 @@ impl Clipping for Vec<Primitive> {
                     position,
                     clipping_rect: rect,
                     text,
+                    style,
                 } => {
                     if let Some(clipped) = clip_rect(
                         rect.origin.x,
@@ -114,6 +115,63 @@ impl Clipping for Vec<Primitive> {
                                 PixelSize::new(clipped.2, clipped.3),
                             ),
                             text,
+                            style,
+                        });
+                    }
+                }
+
+                Primitive::TextLayout {
+                    layout,
+                    color,
+                    position,
+                    clipping_rect: rect,
+                } => {
+                    if let Some(clipped) = clip_rect(
+                        rect.origin.x,
+                        rect.origin.y,
+                        rect.size.width,
+                        rect.size.height,
+                        clipping_rect.origin.x,
+                        clipping_rect.origin.y,
+                        clipping_rect.size.width,
+                        clipping_rect.size.height,
+                    ) {
+                        res.push(Primitive::TextLayout {
+                            layout,
+                            color,
+                            position,
+                            clipping_rect: PixelRect::new(
+                                PixelPoint::new(clipped.0, clipped.1),
+                                PixelSize::new(clipped.2, clipped.3),
+                            ),
+                        });
+                    }
+                }
+
+                Primitive::RichText {
+                    spans,
+                    direction,
+                    position,
+                    clipping_rect: rect,
+                } => {
+                    if let Some(clipped) = clip_rect(
+                        rect.origin.x,
+                        rect.origin.y,
+                        rect.size.width,
+                        rect.size.height,
+                        clipping_rect.origin.x,
+                        clipping_rect.origin.y,
+                        clipping_rect.size.width,
+                        clipping_rect.size.height,
+                    ) {
+                        res.push(Primitive::RichText {
+                            spans,
+                            direction,
+                            position,
+                            clipping_rect: PixelRect::new(
+                                PixelPoint::new(clipped.0, clipped.1),
+                                PixelSize::new(clipped.2, clipped.3),
+                            ),
                         });
                     }
                 }
@@ -1,5 +1,6 @@
 pub type Color = [f32; 4]; // R, G, B, A
 
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
 pub enum ColorFormat {
     // for color images, 24-bit color with 8-bit alpha channel
     RGBA,
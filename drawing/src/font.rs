@@ -1,12 +1,450 @@
 use anyhow::Result;
 
 use crate::backend::Device;
+use crate::bidi::TextDirection;
+use crate::clipping::Scissor;
 use crate::color::*;
+use crate::paint::Paint;
+use crate::primitive::{PathElement, TextPath};
+use crate::shaping::GlyphBounds;
 use crate::units::*;
+use crate::vertical::WritingMode;
 
-#[derive(PartialEq, Eq, Hash, Copy, Clone)]
+use std::hash::{Hash, Hasher};
+
+/// Font size rounded to 1/8 of a pixel.
+///
+/// Identifies glyphs rasterized for the size, so sizes that differ
+/// by a small fraction of a pixel share the same glyphs.
+#[derive(Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Copy, Clone)]
+pub struct FontSize(u32);
+
+impl FontSize {
+    /// Number of size steps in one pixel.
+    pub const STEPS_PER_PIXEL: u32 = 8;
+
+    pub fn new(size: f32) -> Self {
+        FontSize((size.max(0.0) * Self::STEPS_PER_PIXEL as f32).round() as u32)
+    }
+
+    /// Returns the size in pixels.
+    pub fn get(self) -> f32 {
+        self.0 as f32 / Self::STEPS_PER_PIXEL as f32
+    }
+
+    /// Returns the size in whole pixels if it has no fractional part.
+    pub fn to_pixels(self) -> Option<u32> {
+        let pixels = self.0 / Self::STEPS_PER_PIXEL;
+        if pixels * Self::STEPS_PER_PIXEL == self.0 {
+            Some(pixels)
+        } else {
+            None
+        }
+    }
+}
+
+/// Tag of the variation axis of a variable font (like `*b"wght"`).
+pub type AxisTag = [u8; 4];
+
+/// Values of the variation axes of a variable font.
+///
+/// Values are rounded to 16.16 fixed point numbers (the precision of the font
+/// coordinates). Axes that are not set have their default values.
+#[derive(Debug, PartialEq, Eq, Hash, Copy, Clone, Default)]
+pub struct FontVariations {
+    len: u8,
+    // sorted by tag, so the same values are always equal
+    axes: [(AxisTag, i32); FontVariations::MAX_AXES],
+}
+
+impl FontVariations {
+    /// Max number of axes that can be set.
+    pub const MAX_AXES: usize = 8;
+
+    pub fn new() -> Self {
+        FontVariations::default()
+    }
+
+    /// Returns the variations with the value of the axis set.
+    ///
+    /// The value is ignored when `MAX_AXES` other axes are set already.
+    pub fn with(mut self, tag: AxisTag, value: f32) -> Self {
+        let value = (value * 65536.0).round() as i32;
+        let axes = &mut self.axes[..self.len as usize];
+        match axes.binary_search_by_key(&tag, |axis| axis.0) {
+            Ok(index) => axes[index].1 = value,
+            Err(index) if (self.len as usize) < Self::MAX_AXES => {
+                self.len += 1;
+                self.axes[index..self.len as usize].rotate_right(1);
+                self.axes[index] = (tag, value);
+            }
+            Err(_) => (),
+        }
+        self
+    }
+
+    /// Returns the value of the axis if it's set.
+    pub fn get(&self, tag: AxisTag) -> Option<f32> {
+        self.iter().find(|axis| axis.0 == tag).map(|axis| axis.1)
+    }
+
+    /// Returns the axes that are set (sorted by tag).
+    pub fn iter(&self) -> impl Iterator<Item = (AxisTag, f32)> + '_ {
+        self.axes[..self.len as usize]
+            .iter()
+            .map(|&(tag, value)| (tag, value as f32 / 65536.0))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+/// Styles synthesized from the outlines of the face (for fonts without
+/// the bold or italic face).
+#[derive(Debug, PartialEq, Eq, Hash, Copy, Clone, Default)]
+pub struct FontSynthesis {
+    /// Glyphs are made bolder by widening their outlines and advances (by 1/24 em).
+    pub bold: bool,
+    /// Glyphs are slanted to the right.
+    pub oblique: bool,
+}
+
+/// Face of the font file the text is drawn with.
+#[derive(Debug, PartialEq, Eq, Hash, Copy, Clone, Default)]
+pub struct FontFace {
+    /// Index of the face in the font collection (`.ttc`), zero for other fonts.
+    pub index: u32,
+    /// Values of the variation axes (of variable fonts).
+    pub variations: FontVariations,
+    pub synthesis: FontSynthesis,
+}
+
+/// Outline, shadow and glow drawn behind the text (like captions over video).
+///
+/// Widths and offsets are in pixels. Effects with zero width or transparent color
+/// are not drawn, the shadow is drawn if its color is not transparent.
+/// The shadow and the glow are drawn around the outline.
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub struct TextEffects {
+    pub outline_width: f32,
+    pub outline_color: Color,
+    /// Offset of the shadow from the text (positive values move it right and down).
+    pub shadow_offset: [f32; 2],
+    /// Radius of the shadow blur (zero for a sharp shadow).
+    pub shadow_blur: f32,
+    pub shadow_color: Color,
+    /// Width of the glow fading out from the edge of the glyphs.
+    pub glow_width: f32,
+    pub glow_color: Color,
+}
+
+impl TextEffects {
+    pub fn has_outline(&self) -> bool {
+        self.outline_width > 0.0 && self.outline_color[3] > 0.0
+    }
+
+    pub fn has_shadow(&self) -> bool {
+        self.shadow_color[3] > 0.0
+    }
+
+    pub fn has_glow(&self) -> bool {
+        self.glow_width > 0.0 && self.glow_color[3] > 0.0
+    }
+}
+
+/// Font parameters.
+///
+/// Two parameter sets are equal when their sizes are equal after rounding to `FontSize`
+/// and their directions, writing modes and faces are equal (effects don't change
+/// the glyphs or their positions, so they are not compared).
+#[derive(Debug, Copy, Clone)]
 pub struct FontParams {
-    pub size: u8,
+    /// Font size in pixels.
+    pub size: f32,
+    /// Base direction of the text.
+    pub direction: TextDirection,
+    /// Horizontal lines or vertical columns (see the `vertical` module).
+    pub writing_mode: WritingMode,
+    /// Face of the font collection and its variation (like the weight of the text).
+    pub face: FontFace,
+    pub effects: TextEffects,
+}
+
+impl FontParams {
+    pub fn new(size: f32) -> Self {
+        FontParams {
+            size,
+            direction: TextDirection::Auto,
+            writing_mode: WritingMode::HorizontalTb,
+            face: FontFace::default(),
+            effects: TextEffects::default(),
+        }
+    }
+
+    pub fn with_direction(self, direction: TextDirection) -> Self {
+        FontParams { direction, ..self }
+    }
+
+    pub fn with_writing_mode(self, writing_mode: WritingMode) -> Self {
+        FontParams {
+            writing_mode,
+            ..self
+        }
+    }
+
+    /// Selects the face of the font collection.
+    pub fn with_face_index(mut self, index: u32) -> Self {
+        self.face.index = index;
+        self
+    }
+
+    /// Sets the value of the variation axis, for example
+    /// `params.with_variation(*b"wght", 700.0)` for the bold text.
+    pub fn with_variation(mut self, tag: AxisTag, value: f32) -> Self {
+        self.face.variations = self.face.variations.with(tag, value);
+        self
+    }
+
+    /// Synthesizes the bold or oblique style the font has no face for.
+    pub fn with_synthesis(mut self, synthesis: FontSynthesis) -> Self {
+        self.face.synthesis = synthesis;
+        self
+    }
+
+    /// Draws the outline, shadow or glow behind the text.
+    pub fn with_effects(self, effects: TextEffects) -> Self {
+        FontParams { effects, ..self }
+    }
+
+    pub fn font_size(&self) -> FontSize {
+        FontSize::new(self.size)
+    }
+}
+
+/// Integer pixel sizes (as used by the previous versions) are rendered the same way as before.
+impl From<u8> for FontParams {
+    fn from(size: u8) -> Self {
+        FontParams::new(size as f32)
+    }
+}
+
+impl PartialEq for FontParams {
+    fn eq(&self, other: &Self) -> bool {
+        self.font_size() == other.font_size()
+            && self.direction == other.direction
+            && self.writing_mode == other.writing_mode
+            && self.face == other.face
+    }
+}
+
+impl Eq for FontParams {}
+
+impl Hash for FontParams {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.font_size().hash(state);
+        self.direction.hash(state);
+        self.writing_mode.hash(state);
+        self.face.hash(state);
+    }
+}
+
+/// Position, clipping and parameters of the drawn text.
+#[derive(Debug, Copy, Clone)]
+pub struct TextDrawParams<'a> {
+    /// Top left corner of the text.
+    pub pos: Point,
+    pub clipping_rect: Rect,
+    /// Scissor of the paint the text is filled with (see `Font::draw_paint()`).
+    pub scissor: Scissor,
+    pub font_params: FontParams,
+    pub transform: UnknownToDeviceTransform,
+    /// Path the text is drawn along by `Resources::draw_text()`
+    /// and `Resources::draw_text_paint()`, fonts ignore it.
+    pub path: Option<&'a TextPath>,
+}
+
+impl<'a> TextDrawParams<'a> {
+    pub fn new(
+        pos: Point,
+        clipping_rect: Rect,
+        font_params: FontParams,
+        transform: UnknownToDeviceTransform,
+    ) -> Self {
+        TextDrawParams {
+            pos,
+            clipping_rect,
+            scissor: Scissor::empty(),
+            font_params,
+            transform,
+            path: None,
+        }
+    }
+
+    pub fn with_pos(self, pos: Point) -> Self {
+        TextDrawParams { pos, ..self }
+    }
+
+    pub fn with_clipping_rect(self, clipping_rect: Rect) -> Self {
+        TextDrawParams {
+            clipping_rect,
+            ..self
+        }
+    }
+
+    pub fn with_scissor(self, scissor: Scissor) -> Self {
+        TextDrawParams { scissor, ..self }
+    }
+
+    pub fn with_font_params(self, font_params: FontParams) -> Self {
+        TextDrawParams {
+            font_params,
+            ..self
+        }
+    }
+
+    pub fn with_path(self, path: Option<&'a TextPath>) -> Self {
+        TextDrawParams { path, ..self }
+    }
+}
+
+/// Color or paint the glyphs are filled with (see `Font::draw_glyphs_transformed()`).
+pub enum GlyphFill<'a, T> {
+    Color(&'a Color),
+    /// Paint with the texture of its image (see `Paint::from_brush()`)
+    /// clipped by `TextDrawParams::scissor`.
+    Paint(&'a Paint, Option<&'a T>),
+}
+
+impl<T> GlyphFill<'_, T> {
+    /// Returns the color of the glyphs drawn by fonts that can't evaluate paints
+    /// (the inner color of the paint).
+    pub fn color(&self) -> Color {
+        match *self {
+            GlyphFill::Color(color) => *color,
+            GlyphFill::Paint(paint, _) => paint.inner_color,
+        }
+    }
+}
+
+impl<T> Clone for GlyphFill<'_, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for GlyphFill<'_, T> {}
+
+/// Vertical metrics of the font of some size (in pixels).
+///
+/// Distances are measured from the baseline, which is `baseline` pixels
+/// below the top of the line (the position the text is drawn at).
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub struct FontMetrics {
+    /// Distance from the top of the line to the baseline.
+    pub baseline: f32,
+    /// Height of the highest glyphs above the baseline.
+    pub ascent: f32,
+    /// Depth of the lowest glyphs below the baseline (positive).
+    pub descent: f32,
+    /// Extra space between the descent of a line and the ascent of the next one.
+    pub line_gap: f32,
+    /// Distance between the tops of two consecutive lines.
+    pub line_height: f32,
+    /// Distance from the baseline down to the center of the underline.
+    pub underline_position: f32,
+    pub underline_thickness: f32,
+    /// Height of the lowercase 'x' (zero if the font has no such glyph).
+    pub x_height: f32,
+    /// Height of the uppercase 'H' (zero if the font has no such glyph).
+    pub cap_height: f32,
+}
+
+impl FontMetrics {
+    /// Returns the metrics of the font scaled by the factor.
+    pub fn scale(&self, factor: f32) -> FontMetrics {
+        FontMetrics {
+            baseline: self.baseline * factor,
+            ascent: self.ascent * factor,
+            descent: self.descent * factor,
+            line_gap: self.line_gap * factor,
+            line_height: self.line_height * factor,
+            underline_position: self.underline_position * factor,
+            underline_thickness: self.underline_thickness * factor,
+            x_height: self.x_height * factor,
+            cap_height: self.cap_height * factor,
+        }
+    }
+}
+
+/// Antialiasing of rasterized glyphs.
+#[derive(Debug, PartialEq, Eq, Hash, Copy, Clone, Default)]
+pub enum TextAntialiasing {
+    /// Coverage of the whole pixel.
+    #[default]
+    Grayscale,
+    /// Coverage of every subpixel of LCD screens with horizontal RGB subpixels.
+    LcdRgb,
+    /// Coverage of every subpixel of LCD screens with horizontal BGR subpixels.
+    LcdBgr,
+}
+
+/// Fitting of the glyph outlines to the pixel grid.
+#[derive(Debug, PartialEq, Eq, Hash, Copy, Clone, Default)]
+pub enum FontHinting {
+    /// Outlines are not changed (the shapes and advances are exactly as designed).
+    None,
+    /// Outlines are fitted vertically only, the horizontal shapes are kept
+    /// (similar to the macOS and the modern Windows text).
+    Light,
+    /// Hinting instructions of the font are used (FreeType autohinter for fonts
+    /// without them).
+    #[default]
+    Full,
+    /// FreeType autohinter is used even for fonts with hinting instructions.
+    Auto,
+}
+
+/// How glyphs are rasterized (by fonts that rasterize glyphs into bitmaps).
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct TextRendering {
+    /// Number of horizontal positions within a pixel every glyph is rasterized for.
+    /// With 1 glyphs are placed at whole pixels.
+    pub subpixel_positions: u8,
+    pub antialiasing: TextAntialiasing,
+    pub hinting: FontHinting,
+    /// Gamma of the glyph coverage. Values above 1.0 make the text look bolder,
+    /// values below 1.0 make it look thinner.
+    pub gamma: f32,
+    /// Sharpens the edges of glyphs (0.0 keeps the coverage as rasterized, 1.0 doubles
+    /// the steepness of the edges).
+    pub contrast: f32,
+}
+
+impl Default for TextRendering {
+    fn default() -> Self {
+        TextRendering {
+            subpixel_positions: 4,
+            antialiasing: TextAntialiasing::Grayscale,
+            hinting: FontHinting::Full,
+            gamma: 1.0,
+            contrast: 0.0,
+        }
+    }
+}
+
+impl TextRendering {
+    /// Returns the coverage of the rasterized glyph adjusted by the gamma and contrast
+    /// for every coverage value.
+    pub fn coverage_table(&self) -> [u8; 256] {
+        let mut table = [0; 256];
+        for (value, adjusted) in table.iter_mut().enumerate() {
+            let coverage = (value as f32 / 255.0).powf(1.0 / self.gamma.max(0.01));
+            let coverage = ((coverage - 0.5) * (1.0 + self.contrast) + 0.5).clamp(0.0, 1.0);
+            *adjusted = (coverage * 255.0).round() as u8;
+        }
+        table
+    }
 }
 
 pub trait Font<D: Device> {
@@ -31,6 +469,86 @@ pub trait Font<D: Device> {
         text: &str,
     ) -> Result<(Vec<i16>, u16)>;
 
+    /// Returns the vertical metrics of the font of the given size.
+    ///
+    /// Safe to call from any thread for any device type (even for OpenGL).
+    fn get_metrics(&mut self, params: FontParams) -> Result<FontMetrics>;
+
+    /// Returns the bounds of every glyph of the text in visual order, line by line,
+    /// relative to the position the text would be drawn at.
+    ///
+    /// Safe to call from any thread for any device type (even for OpenGL).
+    /// To achieve it the device specific resources creation (like texture)
+    /// is delayed to the first draw() call.
+    fn get_glyph_bounds(&mut self, params: FontParams, text: &str) -> Result<Vec<GlyphBounds>>;
+
+    /// Returns the byte offset of the caret position closest to the horizontal offset
+    /// in the first line of the text.
+    ///
+    /// Glyphs made of several characters (ligatures) are divided equally between them.
+    fn get_char_index_at(&mut self, params: FontParams, text: &str, x: f32) -> Result<usize> {
+        let line_end = text.find('\n').unwrap_or(text.len());
+        let line = &text[..line_end];
+        let bounds: Vec<GlyphBounds> = self
+            .get_glyph_bounds(params, line)?
+            .into_iter()
+            .filter(|glyph| glyph.advance_rect.size.width > 0.0)
+            .collect();
+        let (first, last) = match (bounds.first(), bounds.last()) {
+            (Some(first), Some(last)) => (first, last),
+            _ => return Ok(0),
+        };
+        let x = x
+            .max(first.advance_rect.min_x())
+            .min(last.advance_rect.max_x());
+        let glyph = bounds
+            .iter()
+            .find(|glyph| x < glyph.advance_rect.max_x())
+            .unwrap_or(last);
+
+        // characters of the glyph cluster
+        let cluster_end = bounds
+            .iter()
+            .map(|other| other.cluster)
+            .filter(|&cluster| cluster > glyph.cluster)
+            .min()
+            .unwrap_or(line_end);
+        let offsets: Vec<usize> = line[glyph.cluster..cluster_end]
+            .char_indices()
+            .map(|(pos, _)| glyph.cluster + pos)
+            .chain(std::iter::once(cluster_end))
+            .collect();
+
+        let rect = glyph.advance_rect;
+        let mut fraction = (x - rect.min_x()) / rect.size.width;
+        if glyph.rtl {
+            fraction = 1.0 - fraction;
+        }
+        let char_count = offsets.len() - 1;
+        let index = (fraction * char_count as f32).round() as usize;
+        Ok(offsets[index.min(char_count)])
+    }
+
+    /// Returns the outlines of the text as if it was drawn at the position.
+    ///
+    /// The path can be filled or stroked with `Primitive::Fill`
+    /// and `Primitive::StrokeStyled` like any other path.
+    ///
+    /// Safe to call from any thread for any device type (even for OpenGL).
+    fn get_outline(
+        &mut self,
+        params: FontParams,
+        text: &str,
+        pos: PixelPoint,
+    ) -> Result<Vec<PathElement>>;
+
+    /// Returns true if the face of the font has a glyph for the character.
+    ///
+    /// Characters of the face are loaded on first use (faces that cannot be loaded
+    /// have no characters). Safe to call from any thread for any device type
+    /// (even for OpenGL).
+    fn has_char(&mut self, face: FontFace, ch: char) -> bool;
+
     // Not safe to call from any thread for some device types (like OpenGL).
     fn draw(
         &mut self,
@@ -43,4 +561,94 @@ pub trait Font<D: Device> {
         font_params: FontParams,
         transform: UnknownToDeviceTransform,
     ) -> Result<()>;
+
+    /// Draws the glyphs of the text, every glyph moved by its own transform
+    /// (like the text along a path, see `PathMeasure::glyph_transforms()`).
+    ///
+    /// `glyph_transforms` has the transform of every glyph returned by `get_glyph_bounds()`
+    /// (in the same order) from the position the glyph would have if the text was drawn
+    /// at the origin, the transformed glyphs are moved by `params.pos`. Glyphs without
+    /// a transform are not drawn. Glyphs are not cut by the clipping rectangle, glyphs
+    /// with the center outside of it are not drawn.
+    ///
+    /// The default implementation draws the characters of every glyph separately
+    /// with the color of the fill.
+    // Not safe to call from any thread for some device types (like OpenGL).
+    fn draw_glyphs_transformed(
+        &mut self,
+        device: &mut D,
+        target: &D::RenderTarget,
+        fill: GlyphFill<D::Texture>,
+        text: &str,
+        glyph_transforms: &[Option<PixelTransform>],
+        params: TextDrawParams,
+    ) -> Result<()> {
+        let offset = params.pos.to_vector().cast_unit();
+        let bounds = self.get_glyph_bounds(params.font_params, text)?;
+        let mut clusters: Vec<usize> = bounds.iter().map(|glyph| glyph.cluster).collect();
+        clusters.sort_unstable();
+        clusters.dedup();
+        let color = fill.color();
+        let mut drawn = Vec::new();
+        for (glyph, glyph_transform) in bounds.iter().zip(glyph_transforms) {
+            let glyph_transform = match glyph_transform {
+                Some(glyph_transform) if !drawn.contains(&glyph.cluster) => {
+                    glyph_transform.then_translate(offset)
+                }
+                _ => continue,
+            };
+            let center = glyph_transform
+                .transform_point(PixelPoint::from_untyped(glyph.advance_rect.center()));
+            if !params.clipping_rect.contains(center.to_untyped()) {
+                continue;
+            }
+            drawn.push(glyph.cluster);
+            let end = clusters
+                .iter()
+                .copied()
+                .find(|&cluster| cluster > glyph.cluster)
+                .unwrap_or(text.len());
+            let cluster_text = text[glyph.cluster..end].trim_end_matches('\n');
+            // the whole glyph is drawn (inside the rectangle of its line)
+            let rect = glyph.advance_rect;
+            let glyph_clip = rect.inflate(rect.size.height, rect.size.height);
+            self.draw(
+                device,
+                target,
+                &color,
+                cluster_text,
+                rect.origin,
+                glyph_clip,
+                params.font_params,
+                glyph_transform.to_untyped().then(&params.transform),
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Draws the text filled with the paint (a gradient or an image pattern)
+    /// clipped by `params.scissor`.
+    ///
+    /// Fonts that can't evaluate paints draw the text with the inner color of the paint.
+    // Not safe to call from any thread for some device types (like OpenGL).
+    fn draw_paint(
+        &mut self,
+        device: &mut D,
+        target: &D::RenderTarget,
+        paint: &Paint,
+        _paint_texture: Option<&D::Texture>,
+        text: &str,
+        params: TextDrawParams,
+    ) -> Result<()> {
+        self.draw(
+            device,
+            target,
+            &paint.inner_color,
+            text,
+            params.pos,
+            params.clipping_rect,
+            params.font_params,
+            params.transform,
+        )
+    }
 }
@@ -0,0 +1,1114 @@
+//! Selects fonts by family name, weight, stretch and style.
+//!
+//! `FontRegistry` indexes the faces of loaded font files (and optionally of the
+//! font files of local directories) using their `name`, `OS/2` and `fvar` tables
+//! and finds the face that best matches a `FontQuery` the same way as
+//! the CSS font matching algorithm does.
+//!
+//! Queries can be written as strings similar to the CSS `font` shorthand
+//! (without the size), for example `"bold italic 'Open Sans', sans-serif"`.
+//! `Resources` resolves resource keys that don't name a font with such a query.
+//!
+//! Bold and oblique styles are synthesized (as CSS `font-synthesis` does) when
+//! the family has no face that is bold or italic enough.
+
+use crate::font::{FontFace, FontSynthesis};
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+
+/// Style of the font face.
+#[derive(Debug, PartialEq, Eq, Hash, Copy, Clone, Default)]
+pub enum FontStyle {
+    #[default]
+    Normal,
+    Italic,
+    Oblique,
+}
+
+impl FontStyle {
+    // order of preference of the styles when the style is requested
+    fn rank(self, requested: FontStyle) -> u8 {
+        let order = match requested {
+            FontStyle::Normal => [FontStyle::Normal, FontStyle::Oblique, FontStyle::Italic],
+            FontStyle::Italic => [FontStyle::Italic, FontStyle::Oblique, FontStyle::Normal],
+            FontStyle::Oblique => [FontStyle::Oblique, FontStyle::Italic, FontStyle::Normal],
+        };
+        order.iter().position(|style| *style == self).unwrap_or(0) as u8
+    }
+}
+
+/// Font weight (from 1 to 1000, 400 is normal and 700 is bold).
+pub mod font_weight {
+    pub const THIN: f32 = 100.0;
+    pub const EXTRA_LIGHT: f32 = 200.0;
+    pub const LIGHT: f32 = 300.0;
+    pub const NORMAL: f32 = 400.0;
+    pub const MEDIUM: f32 = 500.0;
+    pub const SEMI_BOLD: f32 = 600.0;
+    pub const BOLD: f32 = 700.0;
+    pub const EXTRA_BOLD: f32 = 800.0;
+    pub const BLACK: f32 = 900.0;
+}
+
+/// Font stretch in percents of the normal width.
+pub mod font_stretch {
+    pub const ULTRA_CONDENSED: f32 = 50.0;
+    pub const EXTRA_CONDENSED: f32 = 62.5;
+    pub const CONDENSED: f32 = 75.0;
+    pub const SEMI_CONDENSED: f32 = 87.5;
+    pub const NORMAL: f32 = 100.0;
+    pub const SEMI_EXPANDED: f32 = 112.5;
+    pub const EXPANDED: f32 = 125.0;
+    pub const EXTRA_EXPANDED: f32 = 150.0;
+    pub const ULTRA_EXPANDED: f32 = 200.0;
+}
+
+const WEIGHT_KEYWORDS: [(&str, f32); 10] = [
+    ("thin", font_weight::THIN),
+    ("extra-light", font_weight::EXTRA_LIGHT),
+    ("light", font_weight::LIGHT),
+    ("regular", font_weight::NORMAL),
+    ("medium", font_weight::MEDIUM),
+    ("semi-bold", font_weight::SEMI_BOLD),
+    ("bold", font_weight::BOLD),
+    ("extra-bold", font_weight::EXTRA_BOLD),
+    ("black", font_weight::BLACK),
+    ("heavy", font_weight::BLACK),
+];
+
+const STRETCH_KEYWORDS: [(&str, f32); 8] = [
+    ("ultra-condensed", font_stretch::ULTRA_CONDENSED),
+    ("extra-condensed", font_stretch::EXTRA_CONDENSED),
+    ("condensed", font_stretch::CONDENSED),
+    ("semi-condensed", font_stretch::SEMI_CONDENSED),
+    ("semi-expanded", font_stretch::SEMI_EXPANDED),
+    ("expanded", font_stretch::EXPANDED),
+    ("extra-expanded", font_stretch::EXTRA_EXPANDED),
+    ("ultra-expanded", font_stretch::ULTRA_EXPANDED),
+];
+
+// OS/2 usWidthClass values 1 - 9 in percents
+const WIDTH_CLASSES: [f32; 9] = [50.0, 62.5, 75.0, 87.5, 100.0, 112.5, 125.0, 150.0, 200.0];
+
+/// Description of the wanted font face.
+///
+/// Families are tried in order, the first family that has any face is used.
+/// Generic families (like `sans-serif`) resolve to the families set with
+/// `FontRegistry::set_generic_family()`.
+///
+/// # Examples
+///
+/// ```ignore
+/// let query = FontQuery::new("Open Sans")
+///     .family("sans-serif")
+///     .weight(font_weight::BOLD)
+///     .style(FontStyle::Italic);
+/// assert_eq!(query, FontQuery::parse("italic 700 'Open Sans', sans-serif"));
+/// ```
+#[derive(Debug, PartialEq, Clone)]
+pub struct FontQuery {
+    pub families: Vec<String>,
+    pub weight: f32,
+    pub stretch: f32,
+    pub style: FontStyle,
+}
+
+impl FontQuery {
+    pub fn new(family: &str) -> Self {
+        FontQuery {
+            families: vec![family.to_string()],
+            weight: font_weight::NORMAL,
+            stretch: font_stretch::NORMAL,
+            style: FontStyle::Normal,
+        }
+    }
+
+    /// Adds the fallback family.
+    pub fn family(mut self, family: &str) -> Self {
+        self.families.push(family.to_string());
+        self
+    }
+
+    pub fn weight(mut self, weight: f32) -> Self {
+        self.weight = weight;
+        self
+    }
+
+    pub fn stretch(mut self, stretch: f32) -> Self {
+        self.stretch = stretch;
+        self
+    }
+
+    pub fn style(mut self, style: FontStyle) -> Self {
+        self.style = style;
+        self
+    }
+
+    /// Parses the query written like the CSS `font` shorthand without the size:
+    /// optional style, weight and stretch keywords followed by the comma separated
+    /// list of families. Family names with spaces may be quoted.
+    ///
+    /// Recognized keywords are `normal`, `italic`, `oblique`, weight names
+    /// (`thin`, `light`, `bold`, `semi-bold`, `black` etc.), numeric weights,
+    /// stretch names (`condensed`, `semi-expanded` etc.) and percentages.
+    pub fn parse(text: &str) -> Self {
+        let mut query = FontQuery::new("");
+        query.families.clear();
+
+        let mut parts = text.split(',');
+        let mut first = parts.next().unwrap_or("").trim();
+        while let Some(keyword) = first.split_whitespace().next() {
+            if !query.parse_keyword(&keyword.to_ascii_lowercase()) {
+                break;
+            }
+            first = first[keyword.len()..].trim_start();
+        }
+
+        for family in std::iter::once(first).chain(parts) {
+            let family = family.trim().trim_matches(|c| c == '"' || c == '\'').trim();
+            if !family.is_empty() {
+                query.families.push(family.to_string());
+            }
+        }
+        query
+    }
+
+    fn parse_keyword(&mut self, keyword: &str) -> bool {
+        match keyword {
+            "normal" => (),
+            "italic" => self.style = FontStyle::Italic,
+            "oblique" => self.style = FontStyle::Oblique,
+            _ => {
+                if let Some(&(_, weight)) = WEIGHT_KEYWORDS.iter().find(|(k, _)| *k == keyword) {
+                    self.weight = weight;
+                } else if let Some(&(_, stretch)) =
+                    STRETCH_KEYWORDS.iter().find(|(k, _)| *k == keyword)
+                {
+                    self.stretch = stretch;
+                } else if let Some(Ok(stretch)) = keyword.strip_suffix('%').map(str::parse::<f32>) {
+                    self.stretch = stretch;
+                } else if let Some(weight) = keyword
+                    .parse::<f32>()
+                    .ok()
+                    .filter(|weight| (1.0..=1000.0).contains(weight))
+                {
+                    self.weight = weight;
+                } else {
+                    return false;
+                }
+            }
+        }
+        true
+    }
+}
+
+impl fmt::Display for FontQuery {
+    /// Writes the query in the format accepted by `FontQuery::parse()`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.style {
+            FontStyle::Normal => (),
+            FontStyle::Italic => write!(f, "italic ")?,
+            FontStyle::Oblique => write!(f, "oblique ")?,
+        }
+        if self.weight != font_weight::NORMAL {
+            write!(f, "{} ", self.weight)?;
+        }
+        if self.stretch != font_stretch::NORMAL {
+            write!(f, "{}% ", self.stretch)?;
+        }
+        for (i, family) in self.families.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "'{}'", family)?;
+        }
+        Ok(())
+    }
+}
+
+/// Face of the font file known to the registry.
+#[derive(Debug, Clone)]
+pub struct FontDescriptor {
+    /// Family name (the typographic family if the font has one).
+    pub family: String,
+    /// Weights of the face (more than one for variable fonts with the `wght` axis).
+    pub weight: RangeInclusive<f32>,
+    /// Stretches of the face (more than one for variable fonts with the `wdth` axis).
+    pub stretch: RangeInclusive<f32>,
+    pub style: FontStyle,
+    /// Index of the face in the font collection.
+    pub face_index: u32,
+    /// Key of the font in `Resources`.
+    pub resource_key: String,
+    /// Font file of the face found by `FontRegistry::scan_directory()`.
+    pub path: Option<PathBuf>,
+}
+
+impl FontDescriptor {
+    // the face of the font file closest to the requested values
+    fn face_for(&self, query: &FontQuery) -> FontFace {
+        let mut face = FontFace {
+            index: self.face_index,
+            ..Default::default()
+        };
+        if self.weight.start() < self.weight.end() {
+            face.variations = face
+                .variations
+                .with(*b"wght", clamp(query.weight, &self.weight));
+        }
+        if self.stretch.start() < self.stretch.end() {
+            face.variations = face
+                .variations
+                .with(*b"wdth", clamp(query.stretch, &self.stretch));
+        }
+        face.synthesis = FontSynthesis {
+            bold: query.weight >= font_weight::SEMI_BOLD
+                && *self.weight.end() < font_weight::SEMI_BOLD,
+            oblique: query.style != FontStyle::Normal && self.style == FontStyle::Normal,
+        };
+        face
+    }
+}
+
+/// Face selected for the family of the query.
+#[derive(Debug, Clone)]
+pub struct FontMatch<'a> {
+    pub descriptor: &'a FontDescriptor,
+    /// Face index, variation and synthesized styles to draw the text with
+    /// (see `FontParams::face`).
+    pub face: FontFace,
+}
+
+/// Index of font faces.
+#[derive(Debug)]
+pub struct FontRegistry {
+    faces: Vec<FontDescriptor>,
+    generic_families: HashMap<String, Vec<String>>,
+}
+
+impl Default for FontRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FontRegistry {
+    /// Creates the registry with the `serif`, `sans-serif` and `monospace`
+    /// generic families set to common free fonts.
+    pub fn new() -> Self {
+        let mut registry = FontRegistry {
+            faces: Vec::new(),
+            generic_families: HashMap::new(),
+        };
+        registry.set_generic_family("serif", &["DejaVu Serif", "Noto Serif", "Liberation Serif"]);
+        registry.set_generic_family(
+            "sans-serif",
+            &["DejaVu Sans", "Noto Sans", "Liberation Sans", "Open Sans"],
+        );
+        registry.set_generic_family(
+            "monospace",
+            &["DejaVu Sans Mono", "Noto Sans Mono", "Liberation Mono"],
+        );
+        registry
+    }
+
+    /// Makes the generic family resolve to the first of the families that has any face.
+    pub fn set_generic_family(&mut self, generic_family: &str, families: &[&str]) {
+        self.generic_families.insert(
+            generic_family.to_ascii_lowercase(),
+            families.iter().map(|family| family.to_string()).collect(),
+        );
+    }
+
+    /// Returns all known faces.
+    pub fn faces(&self) -> &[FontDescriptor] {
+        &self.faces
+    }
+
+    /// Indexes all faces of the font file loaded with the resource key.
+    ///
+    /// Returns the number of faces found (zero if the data is not
+    /// a TrueType / OpenType font or collection).
+    pub fn add_font_data(&mut self, resource_key: &str, data: &[u8]) -> usize {
+        self.add_faces(resource_key, None, data)
+    }
+
+    /// Removes the faces of the font with the resource key.
+    pub fn remove_font(&mut self, resource_key: &str) {
+        self.faces.retain(|face| face.resource_key != resource_key);
+    }
+
+    /// Indexes the font files of the directory and its subdirectories.
+    ///
+    /// The fonts are not loaded, `Resources` loads them when they are used for the first time
+    /// with the path as the resource key. Only the tables describing the faces are read.
+    /// Files that can't be read are skipped, directories linked more than once are
+    /// scanned only once. Returns the number of faces found.
+    pub fn scan_directory(&mut self, path: &Path) -> usize {
+        self.scan_directory_once(path, &mut HashSet::new())
+    }
+
+    /// Indexes the font files of the usual font directories of the system.
+    ///
+    /// Only Linux (and other XDG systems) directories are known:
+    /// `/usr/share/fonts`, `/usr/local/share/fonts`, `$XDG_DATA_HOME/fonts`
+    /// and `~/.fonts`. Returns the number of faces found.
+    pub fn scan_system_fonts(&mut self) -> usize {
+        let mut visited = HashSet::new();
+        system_font_directories()
+            .iter()
+            .map(|dir| self.scan_directory_once(dir, &mut visited))
+            .sum()
+    }
+
+    /// Returns the best face of the first family of the query that has any face.
+    pub fn find(&self, query: &FontQuery) -> Option<FontMatch<'_>> {
+        self.find_all(query).into_iter().next()
+    }
+
+    /// Returns the best face of every family of the query that has any face
+    /// (in the order of the families).
+    pub fn find_all(&self, query: &FontQuery) -> Vec<FontMatch<'_>> {
+        let mut matches = Vec::new();
+        for family in &query.families {
+            let family = family.to_ascii_lowercase();
+            let best = match self.generic_families.get(&family) {
+                Some(families) => families
+                    .iter()
+                    .find_map(|family| self.find_in_family(&family.to_ascii_lowercase(), query)),
+                None => self.find_in_family(&family, query),
+            };
+            if let Some(best) = best {
+                if !matches.iter().any(|m: &FontMatch| {
+                    m.descriptor.resource_key == best.descriptor.resource_key && m.face == best.face
+                }) {
+                    matches.push(best);
+                }
+            }
+        }
+        matches
+    }
+
+    // CSS Fonts Level 4, 5.2 Matching font styles:
+    // the stretch is matched first, then the style and then the weight
+    fn find_in_family(&self, family: &str, query: &FontQuery) -> Option<FontMatch<'_>> {
+        let mut candidates: Vec<&FontDescriptor> = self
+            .faces
+            .iter()
+            .filter(|face| face.family.to_ascii_lowercase() == family)
+            .collect();
+
+        retain_best(&mut candidates, |face| {
+            stretch_rank(query.stretch, clamp(query.stretch, &face.stretch))
+        });
+        retain_best(&mut candidates, |face| (face.style.rank(query.style), 0.0));
+        retain_best(&mut candidates, |face| {
+            weight_rank(query.weight, clamp(query.weight, &face.weight))
+        });
+
+        candidates.first().map(|descriptor| FontMatch {
+            descriptor,
+            face: descriptor.face_for(query),
+        })
+    }
+
+    // Scans the directory unless it (or a link to it) is in `visited`.
+    fn scan_directory_once(&mut self, path: &Path, visited: &mut HashSet<PathBuf>) -> usize {
+        let is_new = match path.canonicalize() {
+            Ok(canonical) => visited.insert(canonical),
+            Err(_) => false,
+        };
+        if !is_new {
+            return 0;
+        }
+        let entries = match std::fs::read_dir(path) {
+            Ok(entries) => entries,
+            Err(_) => return 0,
+        };
+        let mut count = 0;
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.is_dir() {
+                count += self.scan_directory_once(&path, visited);
+                continue;
+            }
+            let is_font = path
+                .extension()
+                .and_then(|ext| ext.to_str())
+                .map(|ext| {
+                    matches!(
+                        ext.to_ascii_lowercase().as_str(),
+                        "ttf" | "otf" | "ttc" | "otc"
+                    )
+                })
+                .unwrap_or(false);
+            let resource_key = path.to_string_lossy().to_string();
+            if !is_font
+                || self
+                    .faces
+                    .iter()
+                    .any(|face| face.resource_key == resource_key)
+            {
+                continue;
+            }
+            count += self.add_font_file(&resource_key, &path).unwrap_or(0);
+        }
+        count
+    }
+
+    fn add_faces(&mut self, resource_key: &str, path: Option<&Path>, data: &[u8]) -> usize {
+        let mut count = 0;
+        for (face_index, offset) in face_offsets(data).into_iter().enumerate() {
+            let table = |tag: &[u8; 4]| find_table(data, offset as usize, tag);
+            if let Some(descriptor) = read_descriptor(table) {
+                self.add_face(resource_key, path, face_index, descriptor);
+                count += 1;
+            }
+        }
+        count
+    }
+
+    // Indexes the faces of the font file reading only the table directories
+    // and the tables describing the faces.
+    fn add_font_file(&mut self, resource_key: &str, path: &Path) -> io::Result<usize> {
+        let mut file = FontFile::open(path)?;
+        let header = file.read(0, 12)?;
+        let face_offsets = if header.starts_with(b"ttcf") {
+            let count = read_u32(&header, 8).unwrap_or(0) as usize;
+            let offsets = file.read(12, count * 4)?;
+            (0..count)
+                .filter_map(|i| read_u32(&offsets, i * 4))
+                .collect()
+        } else {
+            face_offsets(&header)
+        };
+
+        let mut count = 0;
+        for (face_index, offset) in face_offsets.into_iter().enumerate() {
+            let tables = file.read_tables(offset as u64, &DESCRIPTOR_TABLES)?;
+            let table = |tag: &[u8; 4]| tables.get(tag).map(Vec::as_slice);
+            if let Some(descriptor) = read_descriptor(table) {
+                self.add_face(resource_key, Some(path), face_index, descriptor);
+                count += 1;
+            }
+        }
+        Ok(count)
+    }
+
+    fn add_face(
+        &mut self,
+        resource_key: &str,
+        path: Option<&Path>,
+        face_index: usize,
+        descriptor: FontDescriptor,
+    ) {
+        self.faces.push(FontDescriptor {
+            face_index: face_index as u32,
+            resource_key: resource_key.to_string(),
+            path: path.map(Path::to_path_buf),
+            ..descriptor
+        });
+    }
+}
+
+/// Returns the usual font directories of the system (that exist).
+pub fn system_font_directories() -> Vec<PathBuf> {
+    let mut dirs = Vec::new();
+    if cfg!(all(unix, not(target_os = "macos"))) {
+        dirs.push(PathBuf::from("/usr/share/fonts"));
+        dirs.push(PathBuf::from("/usr/local/share/fonts"));
+        let home = std::env::var_os("HOME").map(PathBuf::from);
+        match std::env::var_os("XDG_DATA_HOME") {
+            Some(data_home) => dirs.push(PathBuf::from(data_home).join("fonts")),
+            None => {
+                if let Some(ref home) = home {
+                    dirs.push(home.join(".local/share/fonts"));
+                }
+            }
+        }
+        if let Some(home) = home {
+            dirs.push(home.join(".fonts"));
+        }
+    }
+    dirs.retain(|dir| dir.is_dir());
+    dirs
+}
+
+fn clamp(value: f32, range: &RangeInclusive<f32>) -> f32 {
+    value.max(*range.start()).min(*range.end())
+}
+
+// Keeps only the candidates of the lowest rank.
+fn retain_best(candidates: &mut Vec<&FontDescriptor>, rank: impl Fn(&FontDescriptor) -> (u8, f32)) {
+    let best = candidates
+        .iter()
+        .map(|face| rank(face))
+        .min_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    if let Some(best) = best {
+        candidates.retain(|face| rank(face) == best);
+    }
+}
+
+// narrower stretches are preferred for condensed text, wider ones for expanded text
+fn stretch_rank(requested: f32, value: f32) -> (u8, f32) {
+    let distance = (value - requested).abs();
+    if value == requested {
+        (0, 0.0)
+    } else if (requested <= font_stretch::NORMAL) == (value < requested) {
+        (1, distance)
+    } else {
+        (2, distance)
+    }
+}
+
+// weights between 400 and 500 prefer heavier weights up to 500 first,
+// lighter weights prefer lighter ones and heavier weights prefer heavier ones
+fn weight_rank(requested: f32, value: f32) -> (u8, f32) {
+    let distance = (value - requested).abs();
+    if value == requested {
+        (0, 0.0)
+    } else if (font_weight::NORMAL..=font_weight::MEDIUM).contains(&requested) {
+        if value > requested && value <= font_weight::MEDIUM {
+            (1, distance)
+        } else if value < requested {
+            (2, distance)
+        } else {
+            (3, distance)
+        }
+    } else if (requested < font_weight::NORMAL) == (value < requested) {
+        (1, distance)
+    } else {
+        (2, distance)
+    }
+}
+
+//
+// OpenType tables
+//
+
+fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+    data.get(offset..offset + 2)
+        .map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    data.get(offset..offset + 4)
+        .map(|bytes| u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+fn read_fixed(data: &[u8], offset: usize) -> Option<f32> {
+    read_u32(data, offset).map(|value| value as i32 as f32 / 65536.0)
+}
+
+// offsets of the table directories of all faces of the font file
+fn face_offsets(data: &[u8]) -> Vec<u32> {
+    if data.starts_with(b"ttcf") {
+        let count = read_u32(data, 8).unwrap_or(0) as usize;
+        (0..count)
+            .map_while(|i| read_u32(data, 12 + i * 4))
+            .collect()
+    } else {
+        match read_u32(data, 0) {
+            Some(0x00010000) | Some(0x4F54544F) | Some(0x74727565) => vec![0],
+            _ => Vec::new(),
+        }
+    }
+}
+
+// Font file read in parts (font collections may be big and only a few tables are needed).
+struct FontFile {
+    file: File,
+    length: u64,
+}
+
+impl FontFile {
+    fn open(path: &Path) -> io::Result<Self> {
+        let file = File::open(path)?;
+        let length = file.metadata()?.len();
+        Ok(FontFile { file, length })
+    }
+
+    fn read(&mut self, offset: u64, length: usize) -> io::Result<Vec<u8>> {
+        if offset.saturating_add(length as u64) > self.length {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        let mut bytes = vec![0; length];
+        self.file.seek(SeekFrom::Start(offset))?;
+        self.file.read_exact(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    // Reads the tables with the tags (that the face has) from the table directory
+    // of the face.
+    fn read_tables(
+        &mut self,
+        face_offset: u64,
+        tags: &[&[u8; 4]],
+    ) -> io::Result<HashMap<[u8; 4], Vec<u8>>> {
+        let header = self.read(face_offset, 12)?;
+        let num_tables = read_u16(&header, 4).unwrap_or(0) as usize;
+        let records = self.read(face_offset + 12, num_tables * 16)?;
+
+        let mut tables = HashMap::new();
+        for record in records.chunks_exact(16) {
+            let tag = [record[0], record[1], record[2], record[3]];
+            if tags.contains(&&tag) && !tables.contains_key(&tag) {
+                let offset = read_u32(record, 8).unwrap_or(0) as u64;
+                let length = read_u32(record, 12).unwrap_or(0) as usize;
+                tables.insert(tag, self.read(offset, length)?);
+            }
+        }
+        Ok(tables)
+    }
+}
+
+// tables read by read_descriptor()
+const DESCRIPTOR_TABLES: [&[u8; 4]; 4] = [b"name", b"OS/2", b"head", b"fvar"];
+
+fn find_table<'a>(data: &'a [u8], face_offset: usize, tag: &[u8; 4]) -> Option<&'a [u8]> {
+    let num_tables = read_u16(data, face_offset + 4)? as usize;
+    (0..num_tables).find_map(|i| {
+        let record = face_offset + 12 + i * 16;
+        if data.get(record..record + 4)? != tag {
+            return None;
+        }
+        let offset = read_u32(data, record + 8)? as usize;
+        let length = read_u32(data, record + 12)? as usize;
+        data.get(offset..offset.checked_add(length)?)
+    })
+}
+
+// Describes the face using its tables returned by the function.
+fn read_descriptor<'a>(table: impl Fn(&[u8; 4]) -> Option<&'a [u8]>) -> Option<FontDescriptor> {
+    let family = read_family_name(table(b"name")?)?;
+
+    let mut weight = font_weight::NORMAL;
+    let mut stretch = font_stretch::NORMAL;
+    let mut style = FontStyle::Normal;
+    if let Some(os2) = table(b"OS/2") {
+        weight = read_u16(os2, 4).unwrap_or(400).clamp(1, 1000) as f32;
+        let width_class = read_u16(os2, 6).unwrap_or(5).clamp(1, 9);
+        stretch = WIDTH_CLASSES[width_class as usize - 1];
+        let selection = read_u16(os2, 62).unwrap_or(0);
+        if selection & 0x0001 != 0 {
+            style = FontStyle::Italic;
+        } else if selection & 0x0200 != 0 {
+            style = FontStyle::Oblique;
+        }
+    } else if let Some(mac_style) = table(b"head").and_then(|head| read_u16(head, 44)) {
+        if mac_style & 0x0001 != 0 {
+            weight = font_weight::BOLD;
+        }
+        if mac_style & 0x0002 != 0 {
+            style = FontStyle::Italic;
+        }
+    }
+
+    let mut weight = weight..=weight;
+    let mut stretch = stretch..=stretch;
+    if let Some(fvar) = table(b"fvar") {
+        let axes_offset = read_u16(fvar, 4)? as usize;
+        let axis_count = read_u16(fvar, 8)? as usize;
+        let axis_size = read_u16(fvar, 10)? as usize;
+        for i in 0..axis_count {
+            let axis = axes_offset + i * axis_size;
+            let range = read_fixed(fvar, axis + 4)?..=read_fixed(fvar, axis + 12)?;
+            match fvar.get(axis..axis + 4)? {
+                b"wght" => weight = range,
+                b"wdth" => stretch = range,
+                _ => (),
+            }
+        }
+    }
+
+    Some(FontDescriptor {
+        family,
+        weight,
+        stretch,
+        style,
+        face_index: 0,
+        resource_key: String::new(),
+        path: None,
+    })
+}
+
+// typographic family (name ID 16) or font family (name ID 1),
+// English Windows names are preferred
+fn read_family_name(name: &[u8]) -> Option<String> {
+    let count = read_u16(name, 2)? as usize;
+    let storage = read_u16(name, 4)? as usize;
+    let mut best: Option<((u8, u8), &[u8], u16)> = None;
+    for i in 0..count {
+        let record = 6 + i * 12;
+        let platform = read_u16(name, record)?;
+        let encoding = read_u16(name, record + 2)?;
+        let language = read_u16(name, record + 4)?;
+        let name_id = read_u16(name, record + 6)?;
+        let length = read_u16(name, record + 8)? as usize;
+        let offset = storage + read_u16(name, record + 10)? as usize;
+
+        let id_rank = match name_id {
+            16 => 0,
+            1 => 1,
+            _ => continue,
+        };
+        let platform_rank = match (platform, encoding, language) {
+            (3, 0 | 1, 0x0409) => 0,
+            (3, 0 | 1, _) | (0, _, _) => 1,
+            (1, 0, _) => 2,
+            _ => continue,
+        };
+        let rank = (id_rank, platform_rank);
+        if best.map(|best| rank < best.0).unwrap_or(true) {
+            if let Some(bytes) = name.get(offset..offset + length) {
+                best = Some((rank, bytes, platform));
+            }
+        }
+    }
+
+    let (_, bytes, platform) = best?;
+    let family = if platform == 1 {
+        // Mac Roman, only the ASCII part is decoded properly
+        bytes.iter().map(|&b| b as char).collect()
+    } else {
+        let units = bytes
+            .chunks_exact(2)
+            .map(|unit| u16::from_be_bytes([unit[0], unit[1]]));
+        char::decode_utf16(units)
+            .map(|ch| ch.unwrap_or(char::REPLACEMENT_CHARACTER))
+            .collect::<String>()
+    };
+    Some(family.trim().to_string()).filter(|family| !family.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn face(
+        family: &str,
+        weight: RangeInclusive<f32>,
+        stretch: f32,
+        style: FontStyle,
+    ) -> FontDescriptor {
+        FontDescriptor {
+            family: family.to_string(),
+            resource_key: format!("{} {:?} {} {:?}", family, weight, stretch, style),
+            weight,
+            stretch: stretch..=stretch,
+            style,
+            face_index: 0,
+            path: None,
+        }
+    }
+
+    fn registry(faces: Vec<FontDescriptor>) -> FontRegistry {
+        let mut registry = FontRegistry::new();
+        registry.faces = faces;
+        registry
+    }
+
+    fn found(registry: &FontRegistry, query: &FontQuery) -> Option<String> {
+        registry
+            .find(query)
+            .map(|m| m.descriptor.resource_key.clone())
+    }
+
+    // TrueType font with the `name` table with the family name
+    // and the `OS/2` table with the weight, width class and selection flags
+    fn font_data(family: &str, weight: u16, width_class: u16, selection: u16) -> Vec<u8> {
+        let family: Vec<u8> = family
+            .encode_utf16()
+            .flat_map(|unit| unit.to_be_bytes())
+            .collect();
+        let mut name = Vec::new();
+        for value in [0, 1, 18, 3, 1, 0x0409, 1, family.len() as u16, 0] {
+            name.extend_from_slice(&value.to_be_bytes());
+        }
+        name.extend_from_slice(&family);
+
+        let mut os2 = vec![0; 78];
+        os2[4..6].copy_from_slice(&weight.to_be_bytes());
+        os2[6..8].copy_from_slice(&width_class.to_be_bytes());
+        os2[62..64].copy_from_slice(&selection.to_be_bytes());
+
+        let mut data = Vec::new();
+        data.extend_from_slice(&0x00010000u32.to_be_bytes());
+        data.extend_from_slice(&[0, 2, 0, 32, 0, 1, 0, 0]);
+        let mut offset = 12 + 2 * 16;
+        for (tag, table) in [(b"name", &name), (b"OS/2", &os2)] {
+            data.extend_from_slice(tag);
+            data.extend_from_slice(&0u32.to_be_bytes());
+            data.extend_from_slice(&(offset as u32).to_be_bytes());
+            data.extend_from_slice(&(table.len() as u32).to_be_bytes());
+            offset += table.len();
+        }
+        data.extend_from_slice(&name);
+        data.extend_from_slice(&os2);
+        data
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "drawing-font-registry-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn parse_keywords_and_families() {
+        assert_eq!(
+            FontQuery::parse("bold italic 'Open Sans', \"DejaVu Sans\" , sans-serif"),
+            FontQuery::new("Open Sans")
+                .family("DejaVu Sans")
+                .family("sans-serif")
+                .weight(font_weight::BOLD)
+                .style(FontStyle::Italic)
+        );
+        assert_eq!(
+            FontQuery::parse("Oblique 350 semi-condensed Noto Sans"),
+            FontQuery::new("Noto Sans")
+                .weight(350.0)
+                .stretch(font_stretch::SEMI_CONDENSED)
+                .style(FontStyle::Oblique)
+        );
+        assert_eq!(
+            FontQuery::parse("normal 110% serif"),
+            FontQuery::new("serif").stretch(110.0)
+        );
+    }
+
+    #[test]
+    fn parse_stops_at_first_family_word() {
+        // "Light" is a keyword only before the family name
+        assert_eq!(
+            FontQuery::parse("Roboto Light"),
+            FontQuery::new("Roboto Light")
+        );
+        // weights out of range are family names
+        assert_eq!(FontQuery::parse("1200"), FontQuery::new("1200"));
+        assert_eq!(FontQuery::parse("bold").families, Vec::<String>::new());
+        assert_eq!(FontQuery::parse("").families, Vec::<String>::new());
+    }
+
+    #[test]
+    fn display_is_parsed_back() {
+        let query = FontQuery::new("Open Sans")
+            .family("monospace")
+            .weight(font_weight::LIGHT)
+            .stretch(font_stretch::EXPANDED)
+            .style(FontStyle::Italic);
+        assert_eq!(
+            query.to_string(),
+            "italic 300 125% 'Open Sans', 'monospace'"
+        );
+        assert_eq!(FontQuery::parse(&query.to_string()), query);
+        assert_eq!(FontQuery::new("A").to_string(), "'A'");
+    }
+
+    #[test]
+    fn weights_are_matched_like_css() {
+        let registry = registry(vec![
+            face("F", 300.0..=300.0, 100.0, FontStyle::Normal),
+            face("F", 500.0..=500.0, 100.0, FontStyle::Normal),
+            face("F", 900.0..=900.0, 100.0, FontStyle::Normal),
+        ]);
+        let weight = |weight| {
+            let m = registry.find(&FontQuery::new("f").weight(weight)).unwrap();
+            *m.descriptor.weight.start()
+        };
+        // 400 prefers weights up to 500 first
+        assert_eq!(weight(400.0), 500.0);
+        // lighter weights prefer lighter faces
+        assert_eq!(weight(350.0), 300.0);
+        assert_eq!(weight(200.0), 300.0);
+        // heavier weights prefer heavier faces
+        assert_eq!(weight(600.0), 900.0);
+        assert_eq!(weight(1000.0), 900.0);
+        assert_eq!(weight(900.0), 900.0);
+    }
+
+    #[test]
+    fn stretch_is_matched_before_style() {
+        let registry = registry(vec![
+            face("F", 400.0..=400.0, 100.0, FontStyle::Italic),
+            face("F", 400.0..=400.0, 75.0, FontStyle::Normal),
+            face("F", 400.0..=400.0, 125.0, FontStyle::Normal),
+        ]);
+        let stretch = |query: FontQuery| {
+            let m = registry.find(&query).unwrap();
+            (*m.descriptor.stretch.start(), m.descriptor.style)
+        };
+        assert_eq!(
+            stretch(FontQuery::new("F").style(FontStyle::Italic)),
+            (100.0, FontStyle::Italic)
+        );
+        // narrower faces are preferred for condensed text
+        assert_eq!(
+            stretch(FontQuery::new("F").stretch(87.5)),
+            (75.0, FontStyle::Normal)
+        );
+        // wider faces are preferred for expanded text
+        assert_eq!(
+            stretch(FontQuery::new("F").stretch(112.5)),
+            (125.0, FontStyle::Normal)
+        );
+    }
+
+    #[test]
+    fn missing_styles_are_synthesized() {
+        let registry = registry(vec![
+            face("F", 400.0..=400.0, 100.0, FontStyle::Normal),
+            face("F", 400.0..=400.0, 100.0, FontStyle::Oblique),
+        ]);
+        let m = registry
+            .find(&FontQuery::new("F").style(FontStyle::Italic))
+            .unwrap();
+        // oblique is preferred to normal for italic
+        assert_eq!(m.descriptor.style, FontStyle::Oblique);
+        assert_eq!(m.face.synthesis, FontSynthesis::default());
+
+        let m = registry
+            .find(&FontQuery::new("F").weight(font_weight::BOLD))
+            .unwrap();
+        assert_eq!(m.descriptor.style, FontStyle::Normal);
+        assert_eq!(
+            m.face.synthesis,
+            FontSynthesis {
+                bold: true,
+                oblique: false
+            }
+        );
+    }
+
+    #[test]
+    fn variable_faces_get_clamped_variations() {
+        let registry = registry(vec![face("V", 100.0..=700.0, 100.0, FontStyle::Normal)]);
+        let m = registry
+            .find(&FontQuery::new("V").weight(font_weight::BLACK))
+            .unwrap();
+        assert_eq!(
+            m.face.variations.iter().collect::<Vec<_>>(),
+            vec![(*b"wght", 700.0)]
+        );
+        assert!(!m.face.synthesis.bold);
+
+        let m = registry
+            .find(&FontQuery::new("V").weight(font_weight::LIGHT))
+            .unwrap();
+        assert_eq!(
+            m.face.variations.iter().collect::<Vec<_>>(),
+            vec![(*b"wght", 300.0)]
+        );
+    }
+
+    #[test]
+    fn families_are_tried_in_order() {
+        let mut registry = registry(vec![
+            face("A", 400.0..=400.0, 100.0, FontStyle::Normal),
+            face("B", 400.0..=400.0, 100.0, FontStyle::Normal),
+        ]);
+        registry.set_generic_family("sans-serif", &["Missing", "B", "A"]);
+
+        let query = FontQuery::parse("Missing, sans-serif, a");
+        assert_eq!(
+            found(&registry, &query),
+            found(&registry, &FontQuery::new("B"))
+        );
+        let families: Vec<&str> = registry
+            .find_all(&query)
+            .iter()
+            .map(|m| m.descriptor.family.as_str())
+            .collect();
+        assert_eq!(families, vec!["B", "A"]);
+
+        // the same face is returned once
+        assert_eq!(registry.find_all(&FontQuery::parse("A, a")).len(), 1);
+        assert_eq!(found(&registry, &FontQuery::new("Missing")), None);
+    }
+
+    #[test]
+    fn faces_are_read_from_font_tables() {
+        let mut registry = registry(Vec::new());
+        assert_eq!(
+            registry.add_font_data("bold", &font_data("Test Sans", 700, 3, 0x0001)),
+            1
+        );
+        assert_eq!(registry.add_font_data("invalid", b"not a font"), 0);
+        // tables out of the data are ignored
+        let data = font_data("Test Sans", 400, 5, 0);
+        assert_eq!(registry.add_font_data("truncated", &data[..60]), 0);
+
+        let face = &registry.faces()[0];
+        assert_eq!(face.family, "Test Sans");
+        assert_eq!(face.weight, 700.0..=700.0);
+        assert_eq!(
+            face.stretch,
+            font_stretch::CONDENSED..=font_stretch::CONDENSED
+        );
+        assert_eq!(face.style, FontStyle::Italic);
+        assert_eq!(face.resource_key, "bold");
+
+        registry.remove_font("bold");
+        assert!(registry.faces().is_empty());
+    }
+
+    #[test]
+    fn scan_directory_reads_font_files() {
+        let dir = temp_dir("scan");
+        std::fs::create_dir(dir.join("sub")).unwrap();
+        std::fs::write(dir.join("a.ttf"), font_data("Scan A", 400, 5, 0)).unwrap();
+        std::fs::write(dir.join("sub/b.OTF"), font_data("Scan B", 400, 5, 0x0200)).unwrap();
+        std::fs::write(dir.join("c.txt"), font_data("Scan C", 400, 5, 0)).unwrap();
+        let data = font_data("Scan D", 400, 5, 0);
+        std::fs::write(dir.join("truncated.ttf"), &data[..data.len() - 1]).unwrap();
+
+        let mut registry = registry(Vec::new());
+        assert_eq!(registry.scan_directory(&dir), 2);
+        // files already known are skipped
+        assert_eq!(registry.scan_directory(&dir), 0);
+
+        let mut families: Vec<(&str, FontStyle)> = registry
+            .faces()
+            .iter()
+            .map(|face| (face.family.as_str(), face.style))
+            .collect();
+        families.sort_by_key(|family| family.0);
+        assert_eq!(
+            families,
+            vec![
+                ("Scan A", FontStyle::Normal),
+                ("Scan B", FontStyle::Oblique)
+            ]
+        );
+        let face = registry.find(&FontQuery::new("scan b")).unwrap().descriptor;
+        assert_eq!(face.path.as_deref(), Some(dir.join("sub/b.OTF").as_path()));
+        assert_eq!(face.resource_key, dir.join("sub/b.OTF").to_string_lossy());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn scan_directory_follows_symlink_loops_once() {
+        let dir = temp_dir("loop");
+        std::fs::create_dir(dir.join("sub")).unwrap();
+        std::fs::write(dir.join("sub/a.ttf"), font_data("Loop", 400, 5, 0)).unwrap();
+        std::os::unix::fs::symlink(&dir, dir.join("sub/parent")).unwrap();
+        std::os::unix::fs::symlink(dir.join("sub"), dir.join("link")).unwrap();
+
+        let mut registry = registry(Vec::new());
+        assert_eq!(registry.scan_directory(&dir), 1);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
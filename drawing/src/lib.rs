@@ -2,18 +2,30 @@
 extern crate bitflags;
 
 pub mod backend;
+pub mod bidi;
 pub mod clipping;
 pub mod color;
 pub mod composite_operation_state;
 pub mod font;
+pub mod font_registry;
 pub mod paint;
 pub mod path;
 pub mod primitive;
 pub mod primitive_extensions;
 pub mod renderer;
 pub mod resources;
+pub mod shaping;
+pub mod text_layout;
+pub mod text_path;
 pub mod transformation;
 pub mod units;
+pub mod vertical;
 
 mod texture_font;
+pub use texture_font::BmFont;
+pub use texture_font::BmFontError;
+pub use texture_font::GlyphAtlas;
+#[cfg(feature = "freetype")]
+pub use texture_font::SdfFont;
+#[cfg(feature = "freetype")]
 pub use texture_font::TextureFont;
@@ -1,2 +1,5 @@
+mod offset;
 mod path;
 pub use path::*;
+mod tessellation_cache;
+pub use tessellation_cache::*;
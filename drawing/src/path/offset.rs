@@ -0,0 +1,551 @@
+use super::path::*;
+use crate::primitive::LineJoin;
+use crate::primitive::PathElement;
+use crate::primitive::Solidity;
+use crate::units::Point;
+use std::collections::HashMap;
+
+type Vector = euclid::default::Vector2D<f32>;
+
+// Offsetting works in two steps. First every contour is offset on its own
+// which produces a "raw" polygon that may fold over itself at concave
+// corners or flip entirely when a part of the shape is inset to nothing.
+// Then all raw polygons are split at their intersections and only the edges
+// that separate the area with positive winding from the rest are kept.
+// Contours are oriented so that the filled area is on the left side
+// of every edge (negative `contour_area`).
+
+impl FlattenedPath {
+    /// Offsets all contours by `delta` and merges the results.
+    ///
+    /// Positive `delta` moves the edges away from the filled area, so solid
+    /// contours grow and holes shrink. Negative `delta` does the opposite.
+    /// Whether a contour is filled is taken from its `Solidity`, not from its
+    /// orientation.
+    ///
+    /// The returned contours don't intersect each other or themselves.
+    /// Outer boundaries of the filled area are marked as `Solidity::Solid`,
+    /// boundaries of the holes in it as `Solidity::Hole`. Parts of the shape
+    /// that are inset to nothing are dropped.
+    pub fn offset_contours(
+        &self,
+        delta: f32,
+        line_join: LineJoin,
+        miter_limit: f32,
+        tess_tol: f32,
+    ) -> Vec<PathElement> {
+        let mut raw = Vec::with_capacity(self.paths.len());
+        for path in &self.paths {
+            let mut pts: Vec<Point> = self.points[path.first..path.first + path.count]
+                .iter()
+                .map(|p| p.xy)
+                .collect();
+            if pts.len() < 3 {
+                continue;
+            }
+            let area = contour_area(&pts);
+            let reverse = match path.solidity {
+                Solidity::Solid => area > 0.0,
+                Solidity::Hole => area < 0.0,
+            };
+            if reverse {
+                pts.reverse();
+            }
+            raw.push(offset_contour(
+                &pts,
+                delta,
+                line_join,
+                miter_limit,
+                tess_tol,
+            ));
+        }
+
+        let mut res = Vec::new();
+        for contour in positive_contours(&raw) {
+            let solidity = if contour_area(&contour) < 0.0 {
+                Solidity::Solid
+            } else {
+                Solidity::Hole
+            };
+            add_contour(&mut res, &contour, solidity);
+        }
+        res
+    }
+}
+
+fn offset_contour(
+    pts: &[Point],
+    delta: f32,
+    line_join: LineJoin,
+    miter_limit: f32,
+    tess_tol: f32,
+) -> Vec<Point> {
+    let n = pts.len();
+    let mut res = Vec::with_capacity(n * 2);
+    for i in 0..n {
+        let p0 = pts[(i + n - 1) % n];
+        let p1 = pts[i];
+        let p2 = pts[(i + 1) % n];
+
+        let mut d0 = Point::new(p1.x - p0.x, p1.y - p0.y);
+        let mut d1 = Point::new(p2.x - p1.x, p2.y - p1.y);
+        d0.normalize();
+        d1.normalize();
+        let n0 = Vector::new(d0.y, -d0.x);
+        let n1 = Vector::new(d1.y, -d1.x);
+        let cross = d0.x * d1.y - d0.y * d1.x;
+        let dot = d0.x * d1.x + d0.y * d1.y;
+
+        if cross.abs() < 1e-6 && dot > 0.0 {
+            // straight continuation
+            res.push(p1 + n1 * delta);
+        } else if cross * delta <= 0.0 {
+            // offset edges overlap, the loop formed here is removed later
+            res.push(p1 + n0 * delta);
+            res.push(p1);
+            res.push(p1 + n1 * delta);
+        } else {
+            match line_join {
+                LineJoin::Miter if 2.0 / (1.0 + dot) <= miter_limit * miter_limit => {
+                    res.push(p1 + (n0 + n1) * (delta / (1.0 + dot)));
+                }
+                LineJoin::Round => {
+                    let a = cross.atan2(dot);
+                    let n = curve_divs(delta.abs(), a.abs(), tess_tol);
+                    for j in 0..=n {
+                        let (s, c) = (a * j as f32 / n as f32).sin_cos();
+                        let v = Vector::new(n0.x * c - n0.y * s, n0.x * s + n0.y * c);
+                        res.push(p1 + v * delta);
+                    }
+                }
+                _ => {
+                    res.push(p1 + n0 * delta);
+                    res.push(p1 + n1 * delta);
+                }
+            }
+        }
+    }
+    res
+}
+
+#[derive(Copy, Clone)]
+struct Edge {
+    a: Point,
+    b: Point,
+}
+
+// Returns the boundary of the area with positive winding number
+// of the given (possibly self-intersecting) contours.
+fn positive_contours(contours: &[Vec<Point>]) -> Vec<Vec<Point>> {
+    let mut edges = Vec::new();
+    for contour in contours {
+        for i in 0..contour.len() {
+            let a = contour[i];
+            let b = contour[(i + 1) % contour.len()];
+            if !a.equals(b, 1e-5) {
+                edges.push(Edge { a, b });
+            }
+        }
+    }
+
+    let split = split_edges(&edges);
+    let bands = Bands::new(&split);
+
+    let eps = 1e-3;
+    let mut kept = Vec::new();
+    for e in &split {
+        let d = e.b - e.a;
+        let len = d.length();
+        if len < 1e-5 {
+            continue;
+        }
+        let d = d / len;
+        let m = e.a.lerp(e.b, 0.5);
+        let left = Point::new(m.x - d.y * eps, m.y + d.x * eps);
+        let right = Point::new(m.x + d.y * eps, m.y - d.x * eps);
+        let wl = winding_number(&split, &bands, left) > 0;
+        let wr = winding_number(&split, &bands, right) > 0;
+        if wl && !wr {
+            kept.push(*e);
+        } else if !wl && wr {
+            kept.push(Edge { a: e.b, b: e.a });
+        }
+    }
+
+    link_edges(&kept)
+}
+
+// Splits the edges at all their mutual intersections, so that edges
+// only meet at their end points.
+fn split_edges(edges: &[Edge]) -> Vec<Edge> {
+    let eps = 1e-5;
+    let bands = Bands::new(edges);
+    let mut cuts: Vec<Vec<(f32, Point)>> = vec![Vec::new(); edges.len()];
+    for (band, indices) in bands.bands.iter().enumerate() {
+        for (k, &i) in indices.iter().enumerate() {
+            for &j in &indices[k + 1..] {
+                // test every pair only in the first band both edges overlap
+                if bands.first_band(&edges[i]).max(bands.first_band(&edges[j])) == band {
+                    intersect_edges(edges, i, j, eps, &mut cuts);
+                }
+            }
+        }
+    }
+
+    let mut res = Vec::with_capacity(edges.len());
+    for (e, mut cuts) in edges.iter().zip(cuts) {
+        cuts.sort_by(|c1, c2| c1.0.total_cmp(&c2.0));
+        let mut a = e.a;
+        for (_, pt) in cuts {
+            if !a.equals(pt, 1e-5) {
+                res.push(Edge { a, b: pt });
+                a = pt;
+            }
+        }
+        if !a.equals(e.b, 1e-5) {
+            res.push(Edge { a, b: e.b });
+        }
+    }
+    res
+}
+
+// Finds the intersection of two edges and records where it cuts them.
+fn intersect_edges(edges: &[Edge], i: usize, j: usize, eps: f32, cuts: &mut [Vec<(f32, Point)>]) {
+    let (e1, e2) = (edges[i], edges[j]);
+    if e1.a.x.max(e1.b.x) < e2.a.x.min(e2.b.x)
+        || e2.a.x.max(e2.b.x) < e1.a.x.min(e1.b.x)
+        || e1.a.y.max(e1.b.y) < e2.a.y.min(e2.b.y)
+        || e2.a.y.max(e2.b.y) < e1.a.y.min(e1.b.y)
+    {
+        return;
+    }
+    let r = e1.b - e1.a;
+    let s = e2.b - e2.a;
+    let denom = r.x * s.y - r.y * s.x;
+    if denom.abs() < 1e-12 {
+        return;
+    }
+    let q = e2.a - e1.a;
+    let t = (q.x * s.y - q.y * s.x) / denom;
+    let u = (q.x * r.y - q.y * r.x) / denom;
+    if !(-eps..=1.0 + eps).contains(&t) || !(-eps..=1.0 + eps).contains(&u) {
+        return;
+    }
+    let t_inner = t > eps && t < 1.0 - eps;
+    let u_inner = u > eps && u < 1.0 - eps;
+    // snap to an existing end point, so both edges share exactly the same point
+    let pt = if !t_inner {
+        if t <= eps {
+            e1.a
+        } else {
+            e1.b
+        }
+    } else if !u_inner {
+        if u <= eps {
+            e2.a
+        } else {
+            e2.b
+        }
+    } else {
+        e1.a + r * t
+    };
+    if t_inner {
+        cuts[i].push((t, pt));
+    }
+    if u_inner {
+        cuts[j].push((u, pt));
+    }
+}
+
+// Edges sorted into horizontal bands by their vertical extent. Edges can only
+// intersect or cross a horizontal line when they overlap the same band,
+// so the tests don't need to go through all the edges.
+struct Bands {
+    min_y: f32,
+    height: f32,
+    bands: Vec<Vec<usize>>,
+}
+
+impl Bands {
+    fn new(edges: &[Edge]) -> Self {
+        let mut min_y = f32::MAX;
+        let mut max_y = f32::MIN;
+        for e in edges {
+            min_y = min_y.min(e.a.y.min(e.b.y));
+            max_y = max_y.max(e.a.y.max(e.b.y));
+        }
+        if edges.is_empty() {
+            min_y = 0.0;
+            max_y = 0.0;
+        }
+
+        let count = ((edges.len() as f32).sqrt().ceil() as usize).max(1);
+        let mut res = Bands {
+            min_y,
+            height: ((max_y - min_y) / count as f32).max(1e-3),
+            bands: vec![Vec::new(); count],
+        };
+        for (i, e) in edges.iter().enumerate() {
+            for band in res.first_band(e)..=res.band(e.a.y.max(e.b.y)) {
+                res.bands[band].push(i);
+            }
+        }
+        res
+    }
+
+    fn band(&self, y: f32) -> usize {
+        let band = ((y - self.min_y) / self.height).max(0.0) as usize;
+        band.min(self.bands.len() - 1)
+    }
+
+    fn first_band(&self, e: &Edge) -> usize {
+        self.band(e.a.y.min(e.b.y))
+    }
+}
+
+fn winding_number(edges: &[Edge], bands: &Bands, pt: Point) -> i32 {
+    let mut wn = 0;
+    for e in bands.bands[bands.band(pt.y)].iter().map(|&i| &edges[i]) {
+        let side = (e.b.x - e.a.x) * (pt.y - e.a.y) - (pt.x - e.a.x) * (e.b.y - e.a.y);
+        if e.a.y <= pt.y {
+            if e.b.y > pt.y && side > 0.0 {
+                wn += 1;
+            }
+        } else if e.b.y <= pt.y && side < 0.0 {
+            wn -= 1;
+        }
+    }
+    wn
+}
+
+fn point_key(pt: Point) -> (i32, i32) {
+    (
+        (pt.x * 1024.0).round() as i32,
+        (pt.y * 1024.0).round() as i32,
+    )
+}
+
+// Joins the edges into closed contours. Edges that can not be closed are dropped.
+fn link_edges(edges: &[Edge]) -> Vec<Vec<Point>> {
+    let mut starts: HashMap<(i32, i32), Vec<usize>> = HashMap::new();
+    for (i, e) in edges.iter().enumerate() {
+        starts.entry(point_key(e.a)).or_default().push(i);
+    }
+
+    let mut used = vec![false; edges.len()];
+    let mut res = Vec::new();
+    for first in 0..edges.len() {
+        if used[first] {
+            continue;
+        }
+        used[first] = true;
+        let start = point_key(edges[first].a);
+        let mut contour = vec![edges[first].a];
+        let mut current = first;
+        let mut closed = false;
+        loop {
+            let end = edges[current].b;
+            if point_key(end) == start {
+                closed = true;
+                break;
+            }
+            contour.push(end);
+
+            // at touching vertices prefer the rightmost turn, it keeps the contours simple
+            let d = edges[current].b - edges[current].a;
+            let next = starts.get(&point_key(end)).and_then(|candidates| {
+                candidates
+                    .iter()
+                    .copied()
+                    .filter(|&i| !used[i])
+                    .min_by(|&i1, &i2| {
+                        turn_angle(d, edges[i1].b - edges[i1].a)
+                            .total_cmp(&turn_angle(d, edges[i2].b - edges[i2].a))
+                    })
+            });
+            match next {
+                Some(next) => {
+                    used[next] = true;
+                    current = next;
+                }
+                None => break,
+            }
+        }
+        if closed && contour.len() >= 3 {
+            res.push(contour);
+        }
+    }
+    res
+}
+
+fn turn_angle(d0: Vector, d1: Vector) -> f32 {
+    let cross = d0.x * d1.y - d0.y * d1.x;
+    let dot = d0.x * d1.x + d0.y * d1.y;
+    cross.atan2(dot)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::units::PixelPoint;
+
+    fn polygon(res: &mut Vec<PathElement>, points: &[(f32, f32)], solidity: Solidity) {
+        res.push(PathElement::MoveTo(PixelPoint::new(
+            points[0].0,
+            points[0].1,
+        )));
+        for &(x, y) in &points[1..] {
+            res.push(PathElement::LineTo(PixelPoint::new(x, y)));
+        }
+        res.push(PathElement::ClosePath);
+        res.push(PathElement::Solidity(solidity));
+    }
+
+    fn rect(res: &mut Vec<PathElement>, x0: f32, y0: f32, x1: f32, y1: f32, solidity: Solidity) {
+        polygon(res, &[(x0, y0), (x1, y0), (x1, y1), (x0, y1)], solidity);
+    }
+
+    fn offset(path: &[PathElement], delta: f32) -> Vec<(Vec<Point>, Solidity)> {
+        let fp = FlattenedPath::new(path, 0.01, 0.25);
+        let mut res: Vec<(Vec<Point>, Solidity)> = Vec::new();
+        for el in fp.offset_contours(delta, LineJoin::Miter, 10.0, 0.25) {
+            match el {
+                PathElement::MoveTo(p) => res.push((vec![p.to_untyped()], Solidity::Solid)),
+                PathElement::LineTo(p) => res.last_mut().unwrap().0.push(p.to_untyped()),
+                PathElement::Solidity(solidity) => res.last_mut().unwrap().1 = solidity,
+                _ => (),
+            }
+        }
+        res
+    }
+
+    fn assert_area(contour: &[Point], area: f32) {
+        let a = contour_area(contour).abs();
+        assert!((a - area).abs() < 1e-2, "area {} != {}", a, area);
+    }
+
+    fn assert_simple(contours: &[(Vec<Point>, Solidity)]) {
+        let mut edges = Vec::new();
+        for (pts, _) in contours {
+            for i in 0..pts.len() {
+                edges.push(Edge {
+                    a: pts[i],
+                    b: pts[(i + 1) % pts.len()],
+                });
+            }
+        }
+        let split = split_edges(&edges);
+        assert_eq!(split.len(), edges.len(), "contours intersect");
+    }
+
+    #[test]
+    fn positive_delta_grows_solid_contours() {
+        let mut path = Vec::new();
+        rect(&mut path, 0.0, 0.0, 10.0, 10.0, Solidity::Solid);
+
+        let res = offset(&path, 1.0);
+        assert_eq!(res.len(), 1);
+        assert!(matches!(res[0].1, Solidity::Solid));
+        assert_area(&res[0].0, 144.0);
+        for p in &res[0].0 {
+            assert!((p.x + 1.0).abs() < 1e-4 || (p.x - 11.0).abs() < 1e-4);
+            assert!((p.y + 1.0).abs() < 1e-4 || (p.y - 11.0).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn negative_delta_shrinks_solid_contours() {
+        let mut path = Vec::new();
+        rect(&mut path, 0.0, 0.0, 10.0, 10.0, Solidity::Solid);
+
+        let res = offset(&path, -2.0);
+        assert_eq!(res.len(), 1);
+        assert!(matches!(res[0].1, Solidity::Solid));
+        assert_area(&res[0].0, 36.0);
+
+        assert!(offset(&path, -6.0).is_empty());
+    }
+
+    #[test]
+    fn positive_delta_shrinks_holes() {
+        let mut path = Vec::new();
+        rect(&mut path, 0.0, 0.0, 10.0, 10.0, Solidity::Solid);
+        rect(&mut path, 3.0, 3.0, 7.0, 7.0, Solidity::Hole);
+
+        let res = offset(&path, 1.0);
+        assert_eq!(res.len(), 2);
+        let (solid, hole): (Vec<_>, Vec<_>) = res
+            .iter()
+            .partition(|(_, solidity)| matches!(solidity, Solidity::Solid));
+        assert_area(&solid[0].0, 144.0);
+        assert_area(&hole[0].0, 4.0);
+
+        let res = offset(&path, 2.5);
+        assert_eq!(res.len(), 1);
+        assert!(matches!(res[0].1, Solidity::Solid));
+    }
+
+    #[test]
+    fn overlapping_contours_are_merged() {
+        let mut path = Vec::new();
+        rect(&mut path, 0.0, 0.0, 10.0, 10.0, Solidity::Solid);
+        rect(&mut path, 12.0, 0.0, 22.0, 10.0, Solidity::Solid);
+
+        assert_eq!(offset(&path, 0.5).len(), 2);
+
+        let res = offset(&path, 1.5);
+        assert_eq!(res.len(), 1);
+        assert_area(&res[0].0, 25.0 * 13.0);
+        assert_simple(&res);
+    }
+
+    #[test]
+    fn concave_corners_dont_self_intersect() {
+        let mut path = Vec::new();
+        polygon(
+            &mut path,
+            &[
+                (0.0, 0.0),
+                (10.0, 0.0),
+                (10.0, 4.0),
+                (4.0, 4.0),
+                (4.0, 10.0),
+                (0.0, 10.0),
+            ],
+            Solidity::Solid,
+        );
+
+        let res = offset(&path, 1.0);
+        assert_eq!(res.len(), 1);
+        assert_area(&res[0].0, 12.0 * 12.0 - 6.0 * 6.0);
+        assert_simple(&res);
+
+        let res = offset(&path, -1.5);
+        assert_eq!(res.len(), 1);
+        assert_area(&res[0].0, 7.0 * 7.0 - 6.0 * 6.0);
+        assert_simple(&res);
+    }
+
+    #[test]
+    fn split_edges_cuts_at_all_intersections() {
+        // pentagram, every edge crosses two others
+        let pts: Vec<Point> = (0..5)
+            .map(|i| {
+                let a = i as f32 * std::f32::consts::PI * 4.0 / 5.0;
+                Point::new(a.cos() * 10.0, a.sin() * 10.0)
+            })
+            .collect();
+        let edges: Vec<Edge> = (0..5)
+            .map(|i| Edge {
+                a: pts[i],
+                b: pts[(i + 1) % 5],
+            })
+            .collect();
+        assert_eq!(split_edges(&edges).len(), 15);
+
+        // only the outline of the star has positive winding
+        let contours = positive_contours(&[pts]);
+        assert_eq!(contours.len(), 1);
+        assert_eq!(contours[0].len(), 10);
+    }
+}
@@ -7,10 +7,11 @@ use crate::primitive::LineCap;
 use crate::primitive::LineJoin;
 use crate::primitive::PathElement;
 use crate::primitive::Solidity;
+use crate::units::PixelPoint;
 use crate::units::Point;
 use clamped::Clamp;
-use rawpointer::ptrdistance;
 use std::f32::consts::PI;
+use std::ops::Range;
 
 bitflags! {
     struct PointFlags: u32 {
@@ -23,44 +24,74 @@ bitflags! {
 
 #[derive(Copy, Clone)]
 pub struct VPoint {
-    xy: Point,
+    pub(super) xy: Point,
     d: Point,
     len: f32,
     dm: Point,
     flags: PointFlags,
 }
 
+/// Side of the stroke the generated vertex belongs to.
+#[derive(Copy, Clone, PartialEq)]
+enum Side {
+    Left,
+    Right,
+    Center,
+}
+
+/// Receives vertices generated by the cap and join functions.
+///
+/// The vertices come in pairs forming a triangle strip.
+trait VertexSink {
+    fn vertex(&mut self, side: Side, x: f32, y: f32, u: f32, v: f32);
+}
+
+/// Appends triangle strip vertices to the vertex buffer.
+impl VertexSink for Vec<TexturedVertex> {
+    #[inline]
+    fn vertex(&mut self, _side: Side, x: f32, y: f32, u: f32, v: f32) {
+        self.push(TexturedVertex::new([x, y], [u, v], [1.0, 1.0, 1.0, 1.0]));
+    }
+}
+
+/// Collects the left and the right side of the stroke to build its outline.
+#[derive(Default)]
+struct OutlineSink {
+    left: Vec<Point>,
+    right: Vec<Point>,
+}
+
+impl VertexSink for OutlineSink {
+    fn vertex(&mut self, side: Side, x: f32, y: f32, _u: f32, _v: f32) {
+        let points = match side {
+            Side::Left => &mut self.left,
+            Side::Right => &mut self.right,
+            Side::Center => return,
+        };
+        let pt = Point::new(x, y);
+        if let Some(last) = points.last() {
+            if last.equals(pt, 1e-4) {
+                return;
+            }
+        }
+        points.push(pt);
+    }
+}
+
 pub struct Path {
     pub first: usize,
     pub count: usize,
     pub closed: bool,
     pub num_bevel: usize,
     pub solidity: Solidity,
-    pub fill: *mut TexturedVertex,
-    pub num_fill: usize,
-    pub stroke: *mut TexturedVertex,
-    pub num_stroke: usize,
+    /// Range of fill vertices in `FlattenedPath::vertexes`.
+    pub fill: Range<usize>,
+    /// Range of stroke (or fill fringe) vertices in `FlattenedPath::vertexes`.
+    pub stroke: Range<usize>,
     pub convex: bool,
 }
 
-impl Path {
-    pub fn get_fill(&self) -> &[TexturedVertex] {
-        if self.fill.is_null() {
-            &[]
-        } else {
-            unsafe { std::slice::from_raw_parts_mut(self.fill, self.num_fill) }
-        }
-    }
-
-    pub fn get_stroke(&self) -> &[TexturedVertex] {
-        if self.stroke.is_null() {
-            &[]
-        } else {
-            unsafe { std::slice::from_raw_parts_mut(self.stroke, self.num_stroke) }
-        }
-    }
-}
-
+#[derive(Copy, Clone)]
 pub struct Bounds {
     pub min: Point,
     pub max: Point,
@@ -73,7 +104,7 @@ pub struct FlattenedPath {
     pub bounds: Bounds,
 }
 
-trait PointExt {
+pub(super) trait PointExt {
     fn equals(self, pt: Point, tol: f32) -> bool;
     fn normalize(&mut self) -> f32;
 }
@@ -97,6 +128,14 @@ impl PointExt for Point {
 }
 
 impl FlattenedPath {
+    pub fn get_fill(&self, path: &Path) -> &[TexturedVertex] {
+        &self.vertexes[path.fill.clone()]
+    }
+
+    pub fn get_stroke(&self, path: &Path) -> &[TexturedVertex] {
+        &self.vertexes[path.stroke.clone()]
+    }
+
     pub fn clear(&mut self) {
         self.points.clear();
         self.paths.clear();
@@ -145,54 +184,52 @@ impl FlattenedPath {
             }
         }
 
-        unsafe {
-            // Calculate the direction and length of line segments.
-            for j in 0..flattened_path.paths.len() {
-                let path = &mut flattened_path.paths[j];
-                let pts = &mut flattened_path.points[path.first] as *mut VPoint;
-
-                // If the first and last points are the same, remove the last, mark as closed path.
-                let mut p0 = pts.offset(path.count as isize - 1);
-                let mut p1 = pts;
-                if (*p0).xy.equals((*p1).xy, dist_tol) {
-                    path.count -= 1;
-                    p0 = pts.offset(path.count as isize - 1);
-                    path.closed = true;
-                }
+        // Calculate the direction and length of line segments.
+        for path in flattened_path.paths.iter_mut() {
+            let pts = &mut flattened_path.points[path.first..path.first + path.count];
 
-                // Enforce winding.
-                if path.count > 2 {
-                    let area = poly_area(std::slice::from_raw_parts(pts, path.count));
-                    match path.solidity {
-                        Solidity::Solid => {
-                            if area < 0.0 {
-                                poly_reverse(std::slice::from_raw_parts_mut(pts, path.count));
-                            }
+            // If the first and last points are the same, remove the last, mark as closed path.
+            if path.count > 0 && pts[path.count - 1].xy.equals(pts[0].xy, dist_tol) {
+                path.count -= 1;
+                path.closed = true;
+            }
+            let pts = &mut pts[..path.count];
+
+            // Enforce winding.
+            if path.count > 2 {
+                let area = poly_area(pts);
+                match path.solidity {
+                    Solidity::Solid => {
+                        if area < 0.0 {
+                            poly_reverse(pts);
                         }
-                        Solidity::Hole => {
-                            if area > 0.0 {
-                                poly_reverse(std::slice::from_raw_parts_mut(pts, path.count));
-                            }
+                    }
+                    Solidity::Hole => {
+                        if area > 0.0 {
+                            poly_reverse(pts);
                         }
                     }
                 }
+            }
 
-                for _ in 0..path.count {
-                    // Calculate segment direction and length
-                    (*p0).d.x = (*p1).xy.x - (*p0).xy.x;
-                    (*p0).d.y = (*p1).xy.y - (*p0).xy.y;
-                    (*p0).len = (*p0).d.normalize();
+            let mut i0 = pts.len().saturating_sub(1);
+            for i1 in 0..pts.len() {
+                let next = pts[i1].xy;
+                let p0 = &mut pts[i0];
 
-                    // Update bounds
-                    flattened_path.bounds.min.x = flattened_path.bounds.min.x.min((*p0).xy.x);
-                    flattened_path.bounds.min.y = flattened_path.bounds.min.y.min((*p0).xy.y);
-                    flattened_path.bounds.max.x = flattened_path.bounds.max.x.max((*p0).xy.x);
-                    flattened_path.bounds.max.y = flattened_path.bounds.max.y.max((*p0).xy.y);
+                // Calculate segment direction and length
+                p0.d.x = next.x - p0.xy.x;
+                p0.d.y = next.y - p0.xy.y;
+                p0.len = p0.d.normalize();
 
-                    // Advance
-                    p0 = p1;
-                    p1 = p1.add(1);
-                }
+                // Update bounds
+                flattened_path.bounds.min.x = flattened_path.bounds.min.x.min(p0.xy.x);
+                flattened_path.bounds.min.y = flattened_path.bounds.min.y.min(p0.xy.y);
+                flattened_path.bounds.max.x = flattened_path.bounds.max.x.max(p0.xy.x);
+                flattened_path.bounds.max.y = flattened_path.bounds.max.y.max(p0.xy.y);
+
+                // Advance
+                i0 = i1;
             }
         }
 
@@ -235,209 +272,146 @@ impl FlattenedPath {
                 LineJoin::Bevel | LineJoin::Miter => {
                     // plus one for loop
                     cverts += (path.count + path.num_bevel * 5 + 1) * 2;
-                    if !loop_ {
-                        // space for caps
-                        cverts += match line_cap {
-                            LineCap::Round => (ncap * 2 + 2) * 2,
-                            LineCap::Butt | LineCap::Square => (3 + 3) * 2,
-                        };
-                    }
                 }
             }
+            if !loop_ {
+                // space for caps
+                cverts += match line_cap {
+                    LineCap::Round => (ncap * 2 + 2) * 2,
+                    LineCap::Butt | LineCap::Square => (3 + 3) * 2,
+                };
+            }
         }
 
-        unsafe {
-            let mut vertexes = self.alloc_temp_vertexes(cverts);
-            if vertexes.is_null() {
-                return;
+        self.vertexes.clear();
+        self.vertexes.reserve(cverts);
+        let dst = &mut self.vertexes;
+        let params = StrokeParams {
+            w,
+            ncap,
+            aa,
+            u0,
+            u1,
+        };
+
+        for path in self.paths.iter_mut() {
+            let pts = &self.points[path.first..path.first + path.count];
+            let start = dst.len();
+
+            path.fill = 0..0;
+            if pts.len() < 2 {
+                path.stroke = start..start;
+                continue;
             }
 
-            for i in 0..self.paths.len() {
-                let path = &mut self.paths[i];
-                let pts = &mut self.points[path.first] as *mut VPoint;
+            // Calculate fringe or stroke
+            if path.closed {
+                // Looping
+                let mut p0 = &pts[pts.len() - 1];
+                for p1 in pts {
+                    stroke_point(dst, line_join, p0, p1, params);
+                    p0 = p1;
+                }
 
-                path.fill = std::ptr::null_mut();
-                path.num_fill = 0;
+                // Loop it
+                let v0 = dst[start];
+                let v1 = dst[start + 1];
+                dst.vertex(Side::Left, v0.pos[0], v0.pos[1], u0, 1.0);
+                dst.vertex(Side::Right, v1.pos[0], v1.pos[1], u1, 1.0);
+            } else {
+                // Add cap
+                let (p0, p1) = (&pts[0], &pts[1]);
+                let mut d = Point::new(p1.xy.x - p0.xy.x, p1.xy.y - p0.xy.y);
+                d.normalize();
+                cap_start(dst, line_cap, p0, d.x, d.y, params);
+
+                for i in 1..pts.len() - 1 {
+                    stroke_point(dst, line_join, &pts[i - 1], &pts[i], params);
+                }
 
-                // Calculate fringe or stroke
-                let loop_ = path.closed;
-                let mut dst = vertexes;
-                path.stroke = dst;
+                // Add cap
+                let (p0, p1) = (&pts[pts.len() - 2], &pts[pts.len() - 1]);
+                let mut d = Point::new(p1.xy.x - p0.xy.x, p1.xy.y - p0.xy.y);
+                d.normalize();
+                cap_end(dst, line_cap, p1, d.x, d.y, params);
+            }
 
-                let (mut p0, mut p1, s, e) = if loop_ {
-                    // Looping
-                    (pts.offset(path.count as isize - 1), pts, 0, path.count)
-                } else {
-                    // Add cap
-                    (pts, pts.add(1), 1, path.count - 1)
-                };
+            path.stroke = start..dst.len();
+        }
+    }
 
-                if !loop_ {
-                    // Add cap
-                    let mut d = Point::new((*p1).xy.x - (*p0).xy.x, (*p1).xy.y - (*p0).xy.y);
-                    d.normalize();
-                    match line_cap {
-                        LineCap::Butt => {
-                            dst = butt_cap_start(
-                                dst,
-                                p0.as_mut().unwrap(),
-                                d.x,
-                                d.y,
-                                w,
-                                -aa * 0.5,
-                                aa,
-                                u0,
-                                u1,
-                            )
-                        }
-                        LineCap::Square => {
-                            dst = butt_cap_start(
-                                dst,
-                                p0.as_mut().unwrap(),
-                                d.x,
-                                d.y,
-                                w,
-                                w - aa,
-                                aa,
-                                u0,
-                                u1,
-                            )
-                        }
-                        LineCap::Round => {
-                            dst = round_cap_start(
-                                dst,
-                                p0.as_mut().unwrap(),
-                                d.x,
-                                d.y,
-                                w,
-                                ncap,
-                                aa,
-                                u0,
-                                u1,
-                            )
-                        }
-                    }
-                }
+    /// Generates the outline of the stroke as closed contours that can be filled.
+    ///
+    /// Uses the same caps and joins as `expand_stroke()` but doesn't generate
+    /// the antialiasing fringe. Closed paths produce two contours, the inner one
+    /// is marked as a hole.
+    pub fn expand_stroke_outline(
+        &mut self,
+        w: f32,
+        line_cap: LineCap,
+        line_join: LineJoin,
+        miter_limit: f32,
+        tess_tol: f32,
+    ) -> Vec<PathElement> {
+        let ncap = curve_divs(w, PI, tess_tol); // Calculate divisions per half circle
 
-                for _ in s..e {
-                    if (*p1).flags.contains(PointFlags::PT_BEVEL)
-                        || (*p1).flags.contains(PointFlags::PR_INNERBEVEL)
-                    {
-                        match line_join {
-                            LineJoin::Round => {
-                                dst = round_join(
-                                    dst,
-                                    p0.as_mut().unwrap(),
-                                    p1.as_mut().unwrap(),
-                                    w,
-                                    w,
-                                    u0,
-                                    u1,
-                                    ncap,
-                                    aa,
-                                );
-                            }
-                            _ => {
-                                dst = bevel_join(
-                                    dst,
-                                    p0.as_mut().unwrap(),
-                                    p1.as_mut().unwrap(),
-                                    w,
-                                    w,
-                                    u0,
-                                    u1,
-                                    aa,
-                                );
-                            }
-                        }
-                    } else {
-                        *dst = TexturedVertex::new(
-                            [(*p1).xy.x + ((*p1).dm.x * w), (*p1).xy.y + ((*p1).dm.y * w)],
-                            [u0, 1.0],
-                            [1.0, 1.0, 1.0, 1.0],
-                        );
-                        dst = dst.add(1);
+        self.calculate_joins(w, line_join, miter_limit);
+        // the outline has no antialiasing fringe
+        let params = StrokeParams {
+            w,
+            ncap,
+            aa: 0.0,
+            u0: 0.0,
+            u1: 1.0,
+        };
 
-                        *dst = TexturedVertex::new(
-                            [(*p1).xy.x - ((*p1).dm.x * w), (*p1).xy.y - ((*p1).dm.y * w)],
-                            [u1, 1.0],
-                            [1.0, 1.0, 1.0, 1.0],
-                        );
-                        dst = dst.add(1);
-                    }
+        let mut res = Vec::new();
+        for path in &self.paths {
+            let pts = &self.points[path.first..path.first + path.count];
+            if pts.len() < 2 {
+                continue;
+            }
+
+            let mut sink = OutlineSink::default();
+
+            if path.closed {
+                let mut p0 = &pts[pts.len() - 1];
+                for p1 in pts {
+                    stroke_point(&mut sink, line_join, p0, p1, params);
                     p0 = p1;
-                    p1 = p1.add(1);
                 }
 
-                if loop_ {
-                    // Loop it
-                    let v0 = vertexes;
-                    let v1 = vertexes.add(1);
-
-                    *dst = TexturedVertex::new(
-                        [(*v0).pos[0], (*v0).pos[1]],
-                        [u0, 1.0],
-                        [1.0, 1.0, 1.0, 1.0],
-                    );
-                    dst = dst.add(1);
-
-                    *dst = TexturedVertex::new(
-                        [(*v1).pos[0], (*v1).pos[1]],
-                        [u1, 1.0],
-                        [1.0, 1.0, 1.0, 1.0],
-                    );
-                    dst = dst.add(1);
-                } else {
-                    // Add cap
-                    let mut d = Point::new((*p1).xy.x - (*p0).xy.x, (*p1).xy.y - (*p0).xy.y);
-                    d.normalize();
-                    match line_cap {
-                        LineCap::Butt => {
-                            dst = butt_cap_end(
-                                dst,
-                                p1.as_mut().unwrap(),
-                                d.x,
-                                d.y,
-                                w,
-                                -aa * 0.5,
-                                aa,
-                                u0,
-                                u1,
-                            );
-                        }
-                        LineCap::Round => {
-                            dst = butt_cap_end(
-                                dst,
-                                p1.as_mut().unwrap(),
-                                d.x,
-                                d.y,
-                                w,
-                                w - aa,
-                                aa,
-                                u0,
-                                u1,
-                            );
-                        }
-                        LineCap::Square => {
-                            dst = round_cap_end(
-                                dst,
-                                p1.as_mut().unwrap(),
-                                d.x,
-                                d.y,
-                                w,
-                                ncap,
-                                aa,
-                                u0,
-                                u1,
-                            );
-                        }
-                    }
+                sink.right.reverse();
+                let (outer, inner) =
+                    if contour_area(&sink.left).abs() >= contour_area(&sink.right).abs() {
+                        (sink.left, sink.right)
+                    } else {
+                        (sink.right, sink.left)
+                    };
+                add_contour(&mut res, &outer, Solidity::Solid);
+                add_contour(&mut res, &inner, Solidity::Hole);
+            } else {
+                let (p0, p1) = (&pts[0], &pts[1]);
+                let mut d = Point::new(p1.xy.x - p0.xy.x, p1.xy.y - p0.xy.y);
+                d.normalize();
+                cap_start(&mut sink, line_cap, p0, d.x, d.y, params);
+
+                for i in 1..pts.len() - 1 {
+                    stroke_point(&mut sink, line_join, &pts[i - 1], &pts[i], params);
                 }
 
-                path.num_stroke = ptrdistance(vertexes, dst);
-                vertexes = dst;
+                let (p0, p1) = (&pts[pts.len() - 2], &pts[pts.len() - 1]);
+                let mut d = Point::new(p1.xy.x - p0.xy.x, p1.xy.y - p0.xy.y);
+                d.normalize();
+                cap_end(&mut sink, line_cap, p1, d.x, d.y, params);
+
+                let mut contour = sink.left;
+                contour.extend(sink.right.into_iter().rev());
+                add_contour(&mut res, &contour, Solidity::Solid);
             }
         }
+
+        res
     }
 
     pub fn expand_fill(
@@ -462,172 +436,113 @@ impl FlattenedPath {
             }
         }
 
-        unsafe {
-            let mut vertexes = self.alloc_temp_vertexes(cverts);
-            if vertexes.is_null() {
-                return;
+        self.vertexes.clear();
+        self.vertexes.reserve(cverts);
+        let dst = &mut self.vertexes;
+
+        let convex = self.paths.len() == 1 && self.paths[0].convex;
+
+        for path in self.paths.iter_mut() {
+            let pts = &self.points[path.first..path.first + path.count];
+            let woff = 0.5 * aa;
+
+            if pts.is_empty() {
+                path.fill = dst.len()..dst.len();
+                path.stroke = dst.len()..dst.len();
+                continue;
             }
 
-            let convex = self.paths.len() == 1 && self.paths[0].convex;
-
-            for i in 0..self.paths.len() {
-                // Calculate shape vertices.
-                let path = &mut self.paths[i];
-                let pts = &mut self.points[path.first] as *mut VPoint;
-                let woff = 0.5 * aa;
-                let mut dst = vertexes;
-
-                path.fill = dst;
-
-                if fringe {
-                    // Looping
-                    let mut p0 = pts.offset(path.count as isize - 1);
-                    let mut p1 = pts;
-                    for _ in 0..path.count {
-                        if (*p1).flags.contains(PointFlags::PT_BEVEL) {
-                            let dlx0 = (*p0).d.y;
-                            let dly0 = -(*p0).d.x;
-                            let dlx1 = (*p1).d.y;
-                            let dly1 = -(*p1).d.x;
-                            if (*p1).flags.contains(PointFlags::PT_LEFT) {
-                                let lx = (*p1).xy.x + (*p1).dm.x * woff;
-                                let ly = (*p1).xy.y + (*p1).dm.y * woff;
-                                *dst =
-                                    TexturedVertex::new([lx, ly], [0.5, 1.0], [1.0, 1.0, 1.0, 1.0]);
-                                dst = dst.add(1);
-                            } else {
-                                let lx0 = (*p1).xy.x + dlx0 * woff;
-                                let ly0 = (*p1).xy.y + dly0 * woff;
-                                let lx1 = (*p1).xy.x + dlx1 * woff;
-                                let ly1 = (*p1).xy.y + dly1 * woff;
-
-                                *dst = TexturedVertex::new(
-                                    [lx0, ly0],
-                                    [0.5, 1.0],
-                                    [1.0, 1.0, 1.0, 1.0],
-                                );
-                                dst = dst.add(1);
-
-                                *dst = TexturedVertex::new(
-                                    [lx1, ly1],
-                                    [0.5, 1.0],
-                                    [1.0, 1.0, 1.0, 1.0],
-                                );
-                                dst = dst.add(1);
-                            }
+            // Calculate shape vertices.
+            let start = dst.len();
+            if fringe {
+                // Looping
+                let mut p0 = &pts[pts.len() - 1];
+                for p1 in pts {
+                    if p1.flags.contains(PointFlags::PT_BEVEL) {
+                        let dlx0 = p0.d.y;
+                        let dly0 = -p0.d.x;
+                        let dlx1 = p1.d.y;
+                        let dly1 = -p1.d.x;
+                        if p1.flags.contains(PointFlags::PT_LEFT) {
+                            let lx = p1.xy.x + p1.dm.x * woff;
+                            let ly = p1.xy.y + p1.dm.y * woff;
+                            dst.vertex(Side::Center, lx, ly, 0.5, 1.0);
                         } else {
-                            *dst = TexturedVertex::new(
-                                [
-                                    (*p1).xy.x + ((*p1).dm.x * woff),
-                                    (*p1).xy.y + ((*p1).dm.y * woff),
-                                ],
-                                [0.5, 1.0],
-                                [1.0, 1.0, 1.0, 1.0],
-                            );
-                            dst = dst.add(1);
+                            let lx0 = p1.xy.x + dlx0 * woff;
+                            let ly0 = p1.xy.y + dly0 * woff;
+                            let lx1 = p1.xy.x + dlx1 * woff;
+                            let ly1 = p1.xy.y + dly1 * woff;
+                            dst.vertex(Side::Center, lx0, ly0, 0.5, 1.0);
+                            dst.vertex(Side::Center, lx1, ly1, 0.5, 1.0);
                         }
-
-                        p0 = p1;
-                        p1 = p1.add(1);
-                    }
-                } else {
-                    for j in 0..path.count {
-                        let pt = pts.add(j);
-                        *dst = TexturedVertex::new(
-                            [(*pt).xy.x, (*pt).xy.y],
-                            [0.5, 1.0],
-                            [1.0, 1.0, 1.0, 1.0],
+                    } else {
+                        dst.vertex(
+                            Side::Center,
+                            p1.xy.x + (p1.dm.x * woff),
+                            p1.xy.y + (p1.dm.y * woff),
+                            0.5,
+                            1.0,
                         );
-                        dst = dst.add(1);
                     }
+                    p0 = p1;
                 }
+            } else {
+                for pt in pts {
+                    dst.vertex(Side::Center, pt.xy.x, pt.xy.y, 0.5, 1.0);
+                }
+            }
+            path.fill = start..dst.len();
 
-                path.num_fill = ptrdistance(vertexes, dst);
-                vertexes = dst;
-
-                // Calculate fringe
-                if fringe {
-                    let mut lw = w + woff;
-                    let rw = w - woff;
-                    let mut lu = 0.0;
-                    let ru = 1.0;
-                    let mut dst = vertexes;
-                    path.stroke = dst;
-
-                    // Create only half a fringe for convex shapes so that
-                    // the shape can be rendered without stenciling.
-                    if convex {
-                        lw = woff; // This should generate the same vertex as fill inset above.
-                        lu = 0.5; // Set outline fade at middle.
-                    }
+            // Calculate fringe
+            if fringe {
+                let mut lw = w + woff;
+                let rw = w - woff;
+                let mut lu = 0.0;
+                let ru = 1.0;
+                let start = dst.len();
+
+                // Create only half a fringe for convex shapes so that
+                // the shape can be rendered without stenciling.
+                if convex {
+                    lw = woff; // This should generate the same vertex as fill inset above.
+                    lu = 0.5; // Set outline fade at middle.
+                }
 
-                    // Looping
-                    let mut p0 = pts.offset(path.count as isize - 1);
-                    let mut p1 = pts;
-
-                    for _ in 0..path.count {
-                        if (*p1).flags.contains(PointFlags::PT_BEVEL)
-                            || (*p1).flags.contains(PointFlags::PR_INNERBEVEL)
-                        {
-                            dst = bevel_join(
-                                dst,
-                                p0.as_mut().unwrap(),
-                                p1.as_mut().unwrap(),
-                                lw,
-                                rw,
-                                lu,
-                                ru,
-                                fringe_width,
-                            );
-                        } else {
-                            *dst = TexturedVertex::new(
-                                [
-                                    (*p1).xy.x + ((*p1).dm.x * lw),
-                                    (*p1).xy.y + ((*p1).dm.y * lw),
-                                ],
-                                [lu, 1.0],
-                                [1.0, 1.0, 1.0, 1.0],
-                            );
-                            dst = dst.add(1);
-
-                            *dst = TexturedVertex::new(
-                                [
-                                    (*p1).xy.x - ((*p1).dm.x * rw),
-                                    (*p1).xy.y - ((*p1).dm.y * rw),
-                                ],
-                                [ru, 1.0],
-                                [1.0, 1.0, 1.0, 1.0],
-                            );
-                            dst = dst.add(1);
-                        }
-                        p0 = p1;
-                        p1 = p1.add(1);
+                // Looping
+                let mut p0 = &pts[pts.len() - 1];
+                for p1 in pts {
+                    if p1.flags.contains(PointFlags::PT_BEVEL)
+                        || p1.flags.contains(PointFlags::PR_INNERBEVEL)
+                    {
+                        bevel_join(dst, p0, p1, lw, rw, lu, ru, fringe_width);
+                    } else {
+                        dst.vertex(
+                            Side::Left,
+                            p1.xy.x + (p1.dm.x * lw),
+                            p1.xy.y + (p1.dm.y * lw),
+                            lu,
+                            1.0,
+                        );
+                        dst.vertex(
+                            Side::Right,
+                            p1.xy.x - (p1.dm.x * rw),
+                            p1.xy.y - (p1.dm.y * rw),
+                            ru,
+                            1.0,
+                        );
                     }
-
-                    // Loop it
-                    let v0 = vertexes;
-                    let v1 = vertexes.add(1);
-
-                    *dst = TexturedVertex::new(
-                        [(*v0).pos[0], (*v0).pos[1]],
-                        [lu, 1.0],
-                        [1.0, 1.0, 1.0, 1.0],
-                    );
-                    dst = dst.add(1);
-
-                    *dst = TexturedVertex::new(
-                        [(*v1).pos[0], (*v1).pos[1]],
-                        [ru, 1.0],
-                        [1.0, 1.0, 1.0, 1.0],
-                    );
-                    dst = dst.add(1);
-
-                    path.num_stroke = ptrdistance(vertexes, dst);
-                    vertexes = dst;
-                } else {
-                    path.stroke = std::ptr::null_mut();
-                    path.num_stroke = 0;
+                    p0 = p1;
                 }
+
+                // Loop it
+                let v0 = dst[start];
+                let v1 = dst[start + 1];
+                dst.vertex(Side::Left, v0.pos[0], v0.pos[1], lu, 1.0);
+                dst.vertex(Side::Right, v1.pos[0], v1.pos[1], ru, 1.0);
+
+                path.stroke = start..dst.len();
+            } else {
+                path.stroke = dst.len()..dst.len();
             }
         }
     }
@@ -639,10 +554,8 @@ impl FlattenedPath {
             closed: false,
             num_bevel: 0,
             solidity: Solidity::Solid,
-            fill: std::ptr::null_mut(),
-            num_fill: 0,
-            stroke: std::ptr::null_mut(),
-            num_stroke: 0,
+            fill: 0..0,
+            stroke: 0..0,
             convex: false,
         });
         self.paths.last_mut().unwrap()
@@ -680,17 +593,6 @@ impl FlattenedPath {
         }
     }
 
-    unsafe fn alloc_temp_vertexes(&mut self, count: usize) -> *mut TexturedVertex {
-        self.vertexes.resize(
-            count,
-            TexturedVertex::new([0.0, 0.0], [0.0, 0.0], [1.0, 1.0, 1.0, 1.0]),
-        );
-        if self.vertexes.is_empty() {
-            return std::ptr::null_mut();
-        }
-        &mut self.vertexes[0] as *mut TexturedVertex
-    }
-
     fn tesselate_bezier(
         &mut self,
         pt1: Point,
@@ -760,77 +662,75 @@ impl FlattenedPath {
             iw = 1.0 / w;
         }
 
-        unsafe {
-            // Calculate which joins needs extra vertices to append, and gather vertex count.
-            for i in 0..self.paths.len() {
-                let path = &mut self.paths[i];
-                let pts = &mut self.points[path.first] as *mut VPoint;
-                let mut p0 = pts.offset(path.count as isize - 1);
-                let mut p1 = pts;
-                let mut nleft = 0;
-
-                path.num_bevel = 0;
-
-                for _ in 0..path.count {
-                    let dlx0 = (*p0).d.y;
-                    let dly0 = -(*p0).d.x;
-                    let dlx1 = (*p1).d.y;
-                    let dly1 = -(*p1).d.x;
-
-                    // Calculate extrusions
-                    (*p1).dm.x = (dlx0 + dlx1) * 0.5;
-                    (*p1).dm.y = (dly0 + dly1) * 0.5;
-                    let dmr2 = (*p1).dm.x * (*p1).dm.x + (*p1).dm.y * (*p1).dm.y;
-
-                    if dmr2 > 0.000001 {
-                        let mut scale = 1.0 / dmr2;
-                        if scale > 600.0 {
-                            scale = 600.0;
-                        }
-                        (*p1).dm.x *= scale;
-                        (*p1).dm.y *= scale;
-                    }
+        // Calculate which joins needs extra vertices to append, and gather vertex count.
+        for path in self.paths.iter_mut() {
+            let pts = &mut self.points[path.first..path.first + path.count];
+            let mut nleft = 0;
 
-                    // Clear flags, but keep the corner.
-                    (*p1).flags &= PointFlags::PT_CORNER;
+            path.num_bevel = 0;
 
-                    // Keep track of left turns.
-                    let cross = (*p1).d.x * (*p0).d.y - (*p0).d.x * (*p1).d.y;
-                    if cross > 0.0 {
-                        nleft += 1;
-                        (*p1).flags |= PointFlags::PT_LEFT;
-                    }
+            let mut i0 = pts.len().saturating_sub(1);
+            for i1 in 0..pts.len() {
+                let p0 = pts[i0];
+                let p1 = &mut pts[i1];
 
-                    // Calculate if we should use bevel or miter for inner join.
-                    let limit = (((*p0).len.min((*p1).len) as f32) * iw).max(1.01);
-                    if (dmr2 * limit * limit) < 1.0 {
-                        (*p1).flags |= PointFlags::PR_INNERBEVEL;
-                    }
+                let dlx0 = p0.d.y;
+                let dly0 = -p0.d.x;
+                let dlx1 = p1.d.y;
+                let dly1 = -p1.d.x;
 
-                    // Check to see if the corner needs to be beveled.
-                    if (*p1).flags.contains(PointFlags::PT_CORNER) {
-                        let line_join_bevel = match line_join {
-                            LineJoin::Bevel => true,
-                            LineJoin::Round => true,
-                            LineJoin::Miter => false,
-                        };
-                        if (dmr2 * miter_limit * miter_limit) < 1.0 || line_join_bevel {
-                            (*p1).flags |= PointFlags::PT_BEVEL;
-                        }
+                // Calculate extrusions
+                p1.dm.x = (dlx0 + dlx1) * 0.5;
+                p1.dm.y = (dly0 + dly1) * 0.5;
+                let dmr2 = p1.dm.x * p1.dm.x + p1.dm.y * p1.dm.y;
+
+                if dmr2 > 0.000001 {
+                    let mut scale = 1.0 / dmr2;
+                    if scale > 600.0 {
+                        scale = 600.0;
                     }
+                    p1.dm.x *= scale;
+                    p1.dm.y *= scale;
+                }
 
-                    if (*p1).flags.contains(PointFlags::PT_BEVEL)
-                        || (*p1).flags.contains(PointFlags::PR_INNERBEVEL)
-                    {
-                        path.num_bevel += 1;
+                // Clear flags, but keep the corner.
+                p1.flags &= PointFlags::PT_CORNER;
+
+                // Keep track of left turns.
+                let cross = p1.d.x * p0.d.y - p0.d.x * p1.d.y;
+                if cross > 0.0 {
+                    nleft += 1;
+                    p1.flags |= PointFlags::PT_LEFT;
+                }
+
+                // Calculate if we should use bevel or miter for inner join.
+                let limit = ((p0.len.min(p1.len) as f32) * iw).max(1.01);
+                if (dmr2 * limit * limit) < 1.0 {
+                    p1.flags |= PointFlags::PR_INNERBEVEL;
+                }
+
+                // Check to see if the corner needs to be beveled.
+                if p1.flags.contains(PointFlags::PT_CORNER) {
+                    let line_join_bevel = match line_join {
+                        LineJoin::Bevel => true,
+                        LineJoin::Round => true,
+                        LineJoin::Miter => false,
+                    };
+                    if (dmr2 * miter_limit * miter_limit) < 1.0 || line_join_bevel {
+                        p1.flags |= PointFlags::PT_BEVEL;
                     }
+                }
 
-                    p0 = p1;
-                    p1 = p1.add(1);
+                if p1.flags.contains(PointFlags::PT_BEVEL)
+                    || p1.flags.contains(PointFlags::PR_INNERBEVEL)
+                {
+                    path.num_bevel += 1;
                 }
 
-                path.convex = nleft == path.count;
+                i0 = i1;
             }
+
+            path.convex = nleft == path.count;
         }
     }
 }
@@ -867,12 +767,48 @@ fn poly_reverse(pts: &mut [VPoint]) {
     }
 }
 
-fn curve_divs(r: f32, arc: f32, tess_tol: f32) -> usize {
+pub(super) fn contour_area(pts: &[Point]) -> f32 {
+    let mut area = 0.0;
+    for i in 2..pts.len() {
+        let a = pts[0];
+        let b = pts[i - 1];
+        let c = pts[i];
+        area += (c.x - a.x) * (b.y - a.y) - (b.x - a.x) * (c.y - a.y);
+    }
+    area * 0.5
+}
+
+pub(super) fn add_contour(res: &mut Vec<PathElement>, pts: &[Point], solidity: Solidity) {
+    let mut contour: Vec<Point> = Vec::with_capacity(pts.len());
+    for pt in pts {
+        if let Some(last) = contour.last() {
+            if last.equals(*pt, 1e-4) {
+                continue;
+            }
+        }
+        contour.push(*pt);
+    }
+    while contour.len() > 1 && contour[0].equals(contour[contour.len() - 1], 1e-4) {
+        contour.pop();
+    }
+    if contour.len() < 3 {
+        return;
+    }
+
+    res.push(PathElement::MoveTo(PixelPoint::from_untyped(contour[0])));
+    for pt in &contour[1..] {
+        res.push(PathElement::LineTo(PixelPoint::from_untyped(*pt)));
+    }
+    res.push(PathElement::ClosePath);
+    res.push(PathElement::Solidity(solidity));
+}
+
+pub(super) fn curve_divs(r: f32, arc: f32, tess_tol: f32) -> usize {
     let da = (r / (r + tess_tol)).acos() * 2.0;
     ((arc / da).ceil() as i32).max(2) as usize
 }
 
-fn choose_bevel(bevel: bool, p0: &mut VPoint, p1: &mut VPoint, w: f32) -> (f32, f32, f32, f32) {
+fn choose_bevel(bevel: bool, p0: &VPoint, p1: &VPoint, w: f32) -> (f32, f32, f32, f32) {
     if bevel {
         let x0 = p1.xy.x + p0.d.y * w;
         let y0 = p1.xy.y - p0.d.x * w;
@@ -888,17 +824,112 @@ fn choose_bevel(bevel: bool, p0: &mut VPoint, p1: &mut VPoint, w: f32) -> (f32,
     }
 }
 
-unsafe fn round_join(
-    mut dst: *mut TexturedVertex,
-    p0: &mut VPoint,
-    p1: &mut VPoint,
+// Width of the stroke and the tessellation of its caps and joins.
+#[derive(Copy, Clone)]
+struct StrokeParams {
+    // half of the stroke width
+    w: f32,
+    // divisions of round caps and joins per half circle
+    ncap: usize,
+    // width of the antialiasing fringe
+    aa: f32,
+    // texture coordinates of the left and right edges
+    u0: f32,
+    u1: f32,
+}
+
+fn cap_start<S: VertexSink>(
+    dst: &mut S,
+    line_cap: LineCap,
+    p: &VPoint,
+    dx: f32,
+    dy: f32,
+    params: StrokeParams,
+) {
+    let StrokeParams {
+        w,
+        ncap,
+        aa,
+        u0,
+        u1,
+    } = params;
+    match line_cap {
+        LineCap::Butt => butt_cap_start(dst, p, dx, dy, w, -aa * 0.5, aa, u0, u1),
+        LineCap::Square => butt_cap_start(dst, p, dx, dy, w, w - aa, aa, u0, u1),
+        LineCap::Round => round_cap_start(dst, p, dx, dy, w, ncap, aa, u0, u1),
+    }
+}
+
+fn cap_end<S: VertexSink>(
+    dst: &mut S,
+    line_cap: LineCap,
+    p: &VPoint,
+    dx: f32,
+    dy: f32,
+    params: StrokeParams,
+) {
+    let StrokeParams {
+        w,
+        ncap,
+        aa,
+        u0,
+        u1,
+    } = params;
+    match line_cap {
+        LineCap::Butt => butt_cap_end(dst, p, dx, dy, w, -aa * 0.5, aa, u0, u1),
+        LineCap::Square => butt_cap_end(dst, p, dx, dy, w, w - aa, aa, u0, u1),
+        LineCap::Round => round_cap_end(dst, p, dx, dy, w, ncap, aa, u0, u1),
+    }
+}
+
+fn stroke_point<S: VertexSink>(
+    dst: &mut S,
+    line_join: LineJoin,
+    p0: &VPoint,
+    p1: &VPoint,
+    params: StrokeParams,
+) {
+    let StrokeParams {
+        w,
+        ncap,
+        aa,
+        u0,
+        u1,
+    } = params;
+    if p1.flags.contains(PointFlags::PT_BEVEL) || p1.flags.contains(PointFlags::PR_INNERBEVEL) {
+        match line_join {
+            LineJoin::Round => round_join(dst, p0, p1, w, w, u0, u1, ncap, aa),
+            _ => bevel_join(dst, p0, p1, w, w, u0, u1, aa),
+        }
+    } else {
+        dst.vertex(
+            Side::Left,
+            p1.xy.x + (p1.dm.x * w),
+            p1.xy.y + (p1.dm.y * w),
+            u0,
+            1.0,
+        );
+        dst.vertex(
+            Side::Right,
+            p1.xy.x - (p1.dm.x * w),
+            p1.xy.y - (p1.dm.y * w),
+            u1,
+            1.0,
+        );
+    }
+}
+
+fn round_join<S: VertexSink>(
+    dst: &mut S,
+    p0: &VPoint,
+    p1: &VPoint,
     lw: f32,
     rw: f32,
     lu: f32,
     ru: f32,
     ncap: usize,
     _fringe: f32,
-) -> *mut TexturedVertex {
+) {
     let dlx0 = p0.d.y;
     let dly0 = -p0.d.x;
     let dlx1 = p1.d.y;
@@ -913,15 +944,14 @@ unsafe fn round_join(
             a1 -= PI * 2.0;
         }
 
-        *dst = TexturedVertex::new([lx0, ly0], [lu, 1.0], [1.0, 1.0, 1.0, 1.0]);
-        dst = dst.add(1);
-
-        *dst = TexturedVertex::new(
-            [p1.xy.x - dlx0 * rw, p1.xy.y - dly0 * rw],
-            [ru, 1.0],
-            [1.0, 1.0, 1.0, 1.0],
+        dst.vertex(Side::Left, lx0, ly0, lu, 1.0);
+        dst.vertex(
+            Side::Right,
+            p1.xy.x - dlx0 * rw,
+            p1.xy.y - dly0 * rw,
+            ru,
+            1.0,
         );
-        dst = dst.add(1);
 
         let n = ((((a0 - a1) / PI) * (ncap as f32)).ceil() as i32).clamped(2, ncap as i32);
         for i in 0..n {
@@ -930,22 +960,18 @@ unsafe fn round_join(
             let rx = p1.xy.x + a.cos() * rw;
             let ry = p1.xy.y + a.sin() * rw;
 
-            *dst = TexturedVertex::new([p1.xy.x, p1.xy.y], [0.5, 1.0], [1.0, 1.0, 1.0, 1.0]);
-            dst = dst.add(1);
-
-            *dst = TexturedVertex::new([rx, ry], [ru, 1.0], [1.0, 1.0, 1.0, 1.0]);
-            dst = dst.add(1);
+            dst.vertex(Side::Center, p1.xy.x, p1.xy.y, 0.5, 1.0);
+            dst.vertex(Side::Right, rx, ry, ru, 1.0);
         }
 
-        *dst = TexturedVertex::new([lx1, ly1], [lu, 1.0], [1.0, 1.0, 1.0, 1.0]);
-        dst = dst.add(1);
-
-        *dst = TexturedVertex::new(
-            [p1.xy.x - dlx1 * rw, p1.xy.y - dly1 * rw],
-            [ru, 1.0],
-            [1.0, 1.0, 1.0, 1.0],
+        dst.vertex(Side::Left, lx1, ly1, lu, 1.0);
+        dst.vertex(
+            Side::Right,
+            p1.xy.x - dlx1 * rw,
+            p1.xy.y - dly1 * rw,
+            ru,
+            1.0,
         );
-        dst = dst.add(1);
     } else {
         let (rx0, ry0, rx1, ry1) =
             choose_bevel(p1.flags.contains(PointFlags::PR_INNERBEVEL), p0, p1, -rw);
@@ -955,54 +981,47 @@ unsafe fn round_join(
             a1 += PI * 2.0;
         }
 
-        *dst = TexturedVertex::new(
-            [p1.xy.x + dlx0 * rw, p1.xy.y + dly0 * rw],
-            [lu, 1.0],
-            [1.0, 1.0, 1.0, 1.0],
+        dst.vertex(
+            Side::Left,
+            p1.xy.x + dlx0 * rw,
+            p1.xy.y + dly0 * rw,
+            lu,
+            1.0,
         );
-        dst = dst.add(1);
-
-        *dst = TexturedVertex::new([rx0, ry0], [ru, 1.0], [1.0, 1.0, 1.0, 1.0]);
-        dst = dst.add(1);
+        dst.vertex(Side::Right, rx0, ry0, ru, 1.0);
 
-        let n = ((((a0 - a1) / PI) * (ncap as f32)).ceil() as i32).clamped(2, ncap as i32);
+        let n = ((((a1 - a0) / PI) * (ncap as f32)).ceil() as i32).clamped(2, ncap as i32);
         for i in 0..n {
             let u = (i as f32) / ((n - 1) as f32);
             let a = a0 + u * (a1 - a0);
             let lx = p1.xy.x + a.cos() * lw;
-            let ly = p1.xy.y + a.cos() * lw;
-
-            *dst = TexturedVertex::new([lx, ly], [lu, 1.0], [1.0, 1.0, 1.0, 1.0]);
-            dst = dst.add(1);
+            let ly = p1.xy.y + a.sin() * lw;
 
-            *dst = TexturedVertex::new([p1.xy.x, p1.xy.y], [0.5, 1.0], [1.0, 1.0, 1.0, 1.0]);
-            dst = dst.add(1);
+            dst.vertex(Side::Left, lx, ly, lu, 1.0);
+            dst.vertex(Side::Center, p1.xy.x, p1.xy.y, 0.5, 1.0);
         }
 
-        *dst = TexturedVertex::new(
-            [p1.xy.x + dlx1 * rw, p1.xy.y + dly1 * rw],
-            [lu, 1.0],
-            [1.0, 1.0, 1.0, 1.0],
+        dst.vertex(
+            Side::Left,
+            p1.xy.x + dlx1 * rw,
+            p1.xy.y + dly1 * rw,
+            lu,
+            1.0,
         );
-        dst = dst.add(1);
-
-        *dst = TexturedVertex::new([rx1, ry1], [ru, 1.0], [1.0, 1.0, 1.0, 1.0]);
-        dst = dst.add(1);
+        dst.vertex(Side::Right, rx1, ry1, ru, 1.0);
     }
-
-    dst
 }
 
-unsafe fn bevel_join(
-    mut dst: *mut TexturedVertex,
-    p0: &mut VPoint,
-    p1: &mut VPoint,
+fn bevel_join<S: VertexSink>(
+    dst: &mut S,
+    p0: &VPoint,
+    p1: &VPoint,
     lw: f32,
     rw: f32,
     lu: f32,
     ru: f32,
     _fringe: f32,
-) -> *mut TexturedVertex {
+) {
     let dlx0 = p0.d.y;
     let dly0 = -p0.d.x;
     let dlx1 = p1.d.y;
@@ -1012,155 +1031,138 @@ unsafe fn bevel_join(
         let (lx0, ly0, lx1, ly1) =
             choose_bevel(p1.flags.contains(PointFlags::PR_INNERBEVEL), p0, p1, lw);
 
-        *dst = TexturedVertex::new([lx0, ly0], [lu, 1.0], [1.0, 1.0, 1.0, 1.0]);
-        dst = dst.add(1);
-
-        *dst = TexturedVertex::new(
-            [p1.xy.x - dlx0 * rw, p1.xy.y - dly0 * rw],
-            [ru, 1.0],
-            [1.0, 1.0, 1.0, 1.0],
+        dst.vertex(Side::Left, lx0, ly0, lu, 1.0);
+        dst.vertex(
+            Side::Right,
+            p1.xy.x - dlx0 * rw,
+            p1.xy.y - dly0 * rw,
+            ru,
+            1.0,
         );
-        dst = dst.add(1);
 
         if p1.flags.contains(PointFlags::PT_BEVEL) {
-            *dst = TexturedVertex::new([lx0, ly0], [lu, 1.0], [1.0, 1.0, 1.0, 1.0]);
-            dst = dst.add(1);
-
-            *dst = TexturedVertex::new(
-                [p1.xy.x - dlx0 * rw, p1.xy.y - dly0 * rw],
-                [ru, 1.0],
-                [1.0, 1.0, 1.0, 1.0],
+            dst.vertex(Side::Left, lx0, ly0, lu, 1.0);
+            dst.vertex(
+                Side::Right,
+                p1.xy.x - dlx0 * rw,
+                p1.xy.y - dly0 * rw,
+                ru,
+                1.0,
             );
-            dst = dst.add(1);
 
-            *dst = TexturedVertex::new([lx1, ly1], [lu, 1.0], [1.0, 1.0, 1.0, 1.0]);
-            dst = dst.add(1);
-
-            *dst = TexturedVertex::new(
-                [p1.xy.x - dlx1 * rw, p1.xy.y - dly1 * rw],
-                [ru, 1.0],
-                [1.0, 1.0, 1.0, 1.0],
+            dst.vertex(Side::Left, lx1, ly1, lu, 1.0);
+            dst.vertex(
+                Side::Right,
+                p1.xy.x - dlx1 * rw,
+                p1.xy.y - dly1 * rw,
+                ru,
+                1.0,
             );
-            dst = dst.add(1);
         } else {
             let rx0 = p1.xy.x - p1.dm.x * rw;
             let ry0 = p1.xy.y - p1.dm.y * rw;
 
-            *dst = TexturedVertex::new([p1.xy.x, p1.xy.y], [0.5, 1.0], [1.0, 1.0, 1.0, 1.0]);
-            dst = dst.add(1);
-
-            *dst = TexturedVertex::new(
-                [p1.xy.x - dlx0 * rw, p1.xy.y - dly0 * rw],
-                [ru, 1.0],
-                [1.0, 1.0, 1.0, 1.0],
+            dst.vertex(Side::Center, p1.xy.x, p1.xy.y, 0.5, 1.0);
+            dst.vertex(
+                Side::Right,
+                p1.xy.x - dlx0 * rw,
+                p1.xy.y - dly0 * rw,
+                ru,
+                1.0,
             );
-            dst = dst.add(1);
 
-            *dst = TexturedVertex::new([rx0, ry0], [ru, 1.0], [1.0, 1.0, 1.0, 1.0]);
-            dst = dst.add(1);
+            dst.vertex(Side::Right, rx0, ry0, ru, 1.0);
+            dst.vertex(Side::Right, rx0, ry0, ru, 1.0);
 
-            *dst = TexturedVertex::new([rx0, ry0], [ru, 1.0], [1.0, 1.0, 1.0, 1.0]);
-            dst = dst.add(1);
-
-            *dst = TexturedVertex::new([p1.xy.x, p1.xy.y], [0.5, 1.0], [1.0, 1.0, 1.0, 1.0]);
-            dst = dst.add(1);
-
-            *dst = TexturedVertex::new(
-                [p1.xy.x - dlx1 * rw, p1.xy.y - dly1 * rw],
-                [ru, 1.0],
-                [1.0, 1.0, 1.0, 1.0],
+            dst.vertex(Side::Center, p1.xy.x, p1.xy.y, 0.5, 1.0);
+            dst.vertex(
+                Side::Right,
+                p1.xy.x - dlx1 * rw,
+                p1.xy.y - dly1 * rw,
+                ru,
+                1.0,
             );
-            dst = dst.add(1);
         }
 
-        *dst = TexturedVertex::new([lx1, ly1], [lu, 1.0], [1.0, 1.0, 1.0, 1.0]);
-        dst = dst.add(1);
-
-        *dst = TexturedVertex::new(
-            [p1.xy.x - dlx1 * rw, p1.xy.y - dly1 * rw],
-            [ru, 1.0],
-            [1.0, 1.0, 1.0, 1.0],
+        dst.vertex(Side::Left, lx1, ly1, lu, 1.0);
+        dst.vertex(
+            Side::Right,
+            p1.xy.x - dlx1 * rw,
+            p1.xy.y - dly1 * rw,
+            ru,
+            1.0,
         );
-        dst = dst.add(1);
     } else {
         let (rx0, ry0, rx1, ry1) =
             choose_bevel(p1.flags.contains(PointFlags::PR_INNERBEVEL), p0, p1, -rw);
 
-        *dst = TexturedVertex::new(
-            [p1.xy.x + dlx0 * lw, p1.xy.y + dly0 * lw],
-            [lu, 1.0],
-            [1.0, 1.0, 1.0, 1.0],
+        dst.vertex(
+            Side::Left,
+            p1.xy.x + dlx0 * lw,
+            p1.xy.y + dly0 * lw,
+            lu,
+            1.0,
         );
-        dst = dst.add(1);
+        dst.vertex(Side::Right, rx0, ry0, ru, 1.0);
 
         if p1.flags.contains(PointFlags::PT_BEVEL) {
-            *dst = TexturedVertex::new(
-                [p1.xy.x + dlx0 * lw, p1.xy.y + dly0 * lw],
-                [lu, 1.0],
-                [1.0, 1.0, 1.0, 1.0],
+            dst.vertex(
+                Side::Left,
+                p1.xy.x + dlx0 * lw,
+                p1.xy.y + dly0 * lw,
+                lu,
+                1.0,
             );
-            dst = dst.add(1);
-
-            *dst = TexturedVertex::new([rx0, ry0], [ru, 1.0], [1.0, 1.0, 1.0, 1.0]);
-            dst = dst.add(1);
-
-            *dst = TexturedVertex::new(
-                [p1.xy.x + dlx1 * lw, p1.xy.y + dly1 * lw],
-                [lu, 1.0],
-                [1.0, 1.0, 1.0, 1.0],
+            dst.vertex(Side::Right, rx0, ry0, ru, 1.0);
+
+            dst.vertex(
+                Side::Left,
+                p1.xy.x + dlx1 * lw,
+                p1.xy.y + dly1 * lw,
+                lu,
+                1.0,
             );
-            dst = dst.add(1);
-
-            *dst = TexturedVertex::new([rx1, ry1], [ru, 1.0], [1.0, 1.0, 1.0, 1.0]);
-            dst = dst.add(1);
+            dst.vertex(Side::Right, rx1, ry1, ru, 1.0);
         } else {
             let lx0 = p1.xy.x + p1.dm.x * lw;
             let ly0 = p1.xy.y + p1.dm.y * lw;
 
-            *dst = TexturedVertex::new(
-                [p1.xy.x + dlx0 * lw, p1.xy.y + dly0 * lw],
-                [lu, 1.0],
-                [1.0, 1.0, 1.0, 1.0],
+            dst.vertex(
+                Side::Left,
+                p1.xy.x + dlx0 * lw,
+                p1.xy.y + dly0 * lw,
+                lu,
+                1.0,
             );
-            dst = dst.add(1);
-
-            *dst = TexturedVertex::new([p1.xy.x, p1.xy.y], [0.5, 1.0], [1.0, 1.0, 1.0, 1.0]);
-            dst = dst.add(1);
-
-            *dst = TexturedVertex::new([lx0, ly0], [lu, 1.0], [1.0, 1.0, 1.0, 1.0]);
-            dst = dst.add(1);
+            dst.vertex(Side::Center, p1.xy.x, p1.xy.y, 0.5, 1.0);
 
-            *dst = TexturedVertex::new([lx0, ly0], [lu, 1.0], [1.0, 1.0, 1.0, 1.0]);
-            dst = dst.add(1);
+            dst.vertex(Side::Left, lx0, ly0, lu, 1.0);
+            dst.vertex(Side::Left, lx0, ly0, lu, 1.0);
 
-            *dst = TexturedVertex::new(
-                [p1.xy.x + dlx1 * lw, p1.xy.y + dly1 * lw],
-                [lu, 1.0],
-                [1.0, 1.0, 1.0, 1.0],
+            dst.vertex(
+                Side::Left,
+                p1.xy.x + dlx1 * lw,
+                p1.xy.y + dly1 * lw,
+                lu,
+                1.0,
             );
-            dst = dst.add(1);
-
-            *dst = TexturedVertex::new([p1.xy.x, p1.xy.y], [0.5, 1.0], [1.0, 1.0, 1.0, 1.0]);
-            dst = dst.add(1);
+            dst.vertex(Side::Center, p1.xy.x, p1.xy.y, 0.5, 1.0);
         }
 
-        *dst = TexturedVertex::new(
-            [p1.xy.x + dlx1 * lw, p1.xy.y + dly1 * lw],
-            [lu, 1.0],
-            [1.0, 1.0, 1.0, 1.0],
+        dst.vertex(
+            Side::Left,
+            p1.xy.x + dlx1 * lw,
+            p1.xy.y + dly1 * lw,
+            lu,
+            1.0,
         );
-        dst = dst.add(1);
-
-        *dst = TexturedVertex::new([rx1, ry1], [ru, 1.0], [1.0, 1.0, 1.0, 1.0]);
-        dst = dst.add(1);
+        dst.vertex(Side::Right, rx1, ry1, ru, 1.0);
     }
-
-    dst
 }
 
-unsafe fn butt_cap_start(
-    mut dst: *mut TexturedVertex,
-    p: &mut VPoint,
+fn butt_cap_start<S: VertexSink>(
+    dst: &mut S,
+    p: &VPoint,
     dx: f32,
     dy: f32,
     w: f32,
@@ -1168,46 +1170,33 @@ unsafe fn butt_cap_start(
     aa: f32,
     u0: f32,
     u1: f32,
-) -> *mut TexturedVertex {
+) {
     let px = p.xy.x - dx * d;
     let py = p.xy.y - dy * d;
     let dlx = dy;
     let dly = -dx;
 
-    *dst = TexturedVertex::new(
-        [px + dlx * w - dx * aa, py + dly * w - dy * aa],
-        [u0, 0.0],
-        [1.0, 1.0, 1.0, 1.0],
-    );
-    dst = dst.add(1);
-
-    *dst = TexturedVertex::new(
-        [px - dlx * w - dx * aa, py - dly * w - dy * aa],
-        [u1, 0.0],
-        [1.0, 1.0, 1.0, 1.0],
-    );
-    dst = dst.add(1);
-
-    *dst = TexturedVertex::new(
-        [px + dlx * w, py + dly * w],
-        [u0, 1.0],
-        [1.0, 1.0, 1.0, 1.0],
+    dst.vertex(
+        Side::Left,
+        px + dlx * w - dx * aa,
+        py + dly * w - dy * aa,
+        u0,
+        0.0,
     );
-    dst = dst.add(1);
-
-    *dst = TexturedVertex::new(
-        [px - dlx * w, py - dly * w],
-        [u1, 1.0],
-        [1.0, 1.0, 1.0, 1.0],
+    dst.vertex(
+        Side::Right,
+        px - dlx * w - dx * aa,
+        py - dly * w - dy * aa,
+        u1,
+        0.0,
     );
-    dst = dst.add(1);
-
-    dst
+    dst.vertex(Side::Left, px + dlx * w, py + dly * w, u0, 1.0);
+    dst.vertex(Side::Right, px - dlx * w, py - dly * w, u1, 1.0);
 }
 
-unsafe fn butt_cap_end(
-    mut dst: *mut TexturedVertex,
-    p: &mut VPoint,
+fn butt_cap_end<S: VertexSink>(
+    dst: &mut S,
+    p: &VPoint,
     dx: f32,
     dy: f32,
     w: f32,
@@ -1215,46 +1204,33 @@ unsafe fn butt_cap_end(
     aa: f32,
     u0: f32,
     u1: f32,
-) -> *mut TexturedVertex {
-    let px = p.xy.x - dx * d;
-    let py = p.xy.y - dy * d;
+) {
+    let px = p.xy.x + dx * d;
+    let py = p.xy.y + dy * d;
     let dlx = dy;
     let dly = -dx;
 
-    *dst = TexturedVertex::new(
-        [px + dlx * w, py + dly * w],
-        [u0, 1.0],
-        [1.0, 1.0, 1.0, 1.0],
+    dst.vertex(Side::Left, px + dlx * w, py + dly * w, u0, 1.0);
+    dst.vertex(Side::Right, px - dlx * w, py - dly * w, u1, 1.0);
+    dst.vertex(
+        Side::Left,
+        px + dlx * w + dx * aa,
+        py + dly * w + dy * aa,
+        u0,
+        0.0,
     );
-    dst = dst.add(1);
-
-    *dst = TexturedVertex::new(
-        [px - dlx * w, py - dly * w],
-        [u1, 1.0],
-        [1.0, 1.0, 1.0, 1.0],
+    dst.vertex(
+        Side::Right,
+        px - dlx * w + dx * aa,
+        py - dly * w + dy * aa,
+        u1,
+        0.0,
     );
-    dst = dst.add(1);
-
-    *dst = TexturedVertex::new(
-        [px + dlx * w + dx * aa, py + dly * w + dy * aa],
-        [u0, 0.0],
-        [1.0, 1.0, 1.0, 1.0],
-    );
-    dst = dst.add(1);
-
-    *dst = TexturedVertex::new(
-        [px - dlx * w + dx * aa, py - dly * w + dy * aa],
-        [u1, 0.0],
-        [1.0, 1.0, 1.0, 1.0],
-    );
-    dst = dst.add(1);
-
-    dst
 }
 
-unsafe fn round_cap_start(
-    mut dst: *mut TexturedVertex,
-    p: &mut VPoint,
+fn round_cap_start<S: VertexSink>(
+    dst: &mut S,
+    p: &VPoint,
     dx: f32,
     dy: f32,
     w: f32,
@@ -1262,48 +1238,36 @@ unsafe fn round_cap_start(
     _aa: f32,
     u0: f32,
     u1: f32,
-) -> *mut TexturedVertex {
+) {
     let px = p.xy.x;
     let py = p.xy.y;
     let dlx = dy;
     let dly = -dx;
 
+    // The cap goes from the right side around the start point to the left side,
+    // so it is a part of the left side of the outline.
     for i in 0..ncap {
         let a = (i as f32) / ((ncap - 1) as f32) * PI;
         let ax = a.cos() * w;
         let ay = a.sin() * w;
 
-        *dst = TexturedVertex::new(
-            [px - dlx * ax - dx * ay, py - dly * ax - dy * ay],
-            [u0, 1.0],
-            [1.0, 1.0, 1.0, 1.0],
+        dst.vertex(
+            Side::Left,
+            px - dlx * ax - dx * ay,
+            py - dly * ax - dy * ay,
+            u0,
+            1.0,
         );
-        dst = dst.add(1);
-
-        *dst = TexturedVertex::new([px, py], [0.5, 1.0], [1.0, 1.0, 1.0, 1.0]);
-        dst = dst.add(1);
+        dst.vertex(Side::Center, px, py, 0.5, 1.0);
     }
 
-    *dst = TexturedVertex::new(
-        [px + dlx * w, py + dly * w],
-        [u0, 1.0],
-        [1.0, 1.0, 1.0, 1.0],
-    );
-    dst = dst.add(1);
-
-    *dst = TexturedVertex::new(
-        [px - dlx * w, py - dly * w],
-        [u1, 1.0],
-        [1.0, 1.0, 1.0, 1.0],
-    );
-    dst = dst.add(1);
-
-    dst
+    dst.vertex(Side::Left, px + dlx * w, py + dly * w, u0, 1.0);
+    dst.vertex(Side::Right, px - dlx * w, py - dly * w, u1, 1.0);
 }
 
-unsafe fn round_cap_end(
-    mut dst: *mut TexturedVertex,
-    p: &mut VPoint,
+fn round_cap_end<S: VertexSink>(
+    dst: &mut S,
+    p: &VPoint,
     dx: f32,
     dy: f32,
     w: f32,
@@ -1311,41 +1275,300 @@ unsafe fn round_cap_end(
     _aa: f32,
     u0: f32,
     u1: f32,
-) -> *mut TexturedVertex {
+) {
     let px = p.xy.x;
     let py = p.xy.y;
     let dlx = dy;
     let dly = -dx;
 
-    *dst = TexturedVertex::new(
-        [px + dlx * w, py + dly * w],
-        [u0, 1.0],
-        [1.0, 1.0, 1.0, 1.0],
-    );
-    dst = dst.add(1);
-
-    *dst = TexturedVertex::new(
-        [px - dlx * w, py - dly * w],
-        [u1, 1.0],
-        [1.0, 1.0, 1.0, 1.0],
-    );
-    dst = dst.add(1);
+    dst.vertex(Side::Left, px + dlx * w, py + dly * w, u0, 1.0);
+    dst.vertex(Side::Right, px - dlx * w, py - dly * w, u1, 1.0);
 
+    // The cap goes from the right side around the end point to the left side,
+    // so it is a part of the (reversed) right side of the outline.
     for i in 0..ncap {
         let a = (i as f32) / ((ncap - 1) as f32) * PI;
         let ax = a.cos() * w;
         let ay = a.sin() * w;
 
-        *dst = TexturedVertex::new([px, py], [0.5, 1.0], [1.0, 1.0, 1.0, 1.0]);
-        dst = dst.add(1);
+        dst.vertex(Side::Center, px, py, 0.5, 1.0);
+        dst.vertex(
+            Side::Right,
+            px - dlx * ax + dx * ay,
+            py - dly * ax + dy * ay,
+            u0,
+            1.0,
+        );
+    }
+}
 
-        *dst = TexturedVertex::new(
-            [px - dlx * ax + dx * ay, py - dly * ax + dy * ay],
-            [u0, 1.0],
-            [1.0, 1.0, 1.0, 1.0],
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::primitive::StrokeStyle;
+    use crate::primitive_extensions::stroke_outline_path;
+    use crate::units::{PixelThickness, Rect};
+
+    const W: f32 = 2.0;
+
+    fn polyline(points: &[(f32, f32)]) -> Vec<PathElement> {
+        let mut res = Vec::new();
+        for (i, &(x, y)) in points.iter().enumerate() {
+            let pt = PixelPoint::new(x, y);
+            res.push(if i == 0 {
+                PathElement::MoveTo(pt)
+            } else {
+                PathElement::LineTo(pt)
+            });
+        }
+        res
+    }
+
+    fn stroke(path: &[PathElement], line_cap: LineCap, line_join: LineJoin) -> Vec<TexturedVertex> {
+        let mut fp = FlattenedPath::new(path, 0.01, 0.25);
+        fp.expand_stroke(W, 1.0, line_cap, line_join, 10.0, 0.25);
+        fp.paths
+            .iter()
+            .flat_map(|p| fp.get_stroke(p).to_vec())
+            .collect()
+    }
+
+    fn outline_points(path: &[PathElement], line_join: LineJoin) -> Vec<Point> {
+        let mut fp = FlattenedPath::new(path, 0.01, 0.25);
+        fp.expand_stroke_outline(W, LineCap::Round, line_join, 10.0, 0.25)
+            .iter()
+            .filter_map(|el| match el {
+                PathElement::MoveTo(p) | PathElement::LineTo(p) => Some(p.to_untyped()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn distance_to_segment(p: Point, a: Point, b: Point) -> f32 {
+        let ab = b - a;
+        let t = ((p - a).dot(ab) / ab.square_length()).clamped(0.0, 1.0);
+        (a + ab * t - p).length()
+    }
+
+    fn distance_to_polyline(p: Point, points: &[(f32, f32)]) -> f32 {
+        points
+            .windows(2)
+            .map(|s| {
+                let a = Point::new(s[0].0, s[0].1);
+                let b = Point::new(s[1].0, s[1].1);
+                distance_to_segment(p, a, b)
+            })
+            .fold(f32::MAX, f32::min)
+    }
+
+    #[test]
+    fn butt_and_square_caps_are_symmetric() {
+        // half width with the half of the fringe added
+        let w = W + 0.5;
+        let path = polyline(&[(0.0, 0.0), (10.0, 0.0)]);
+
+        // butt caps only extend by the fringe, square caps by the half width
+        for &(line_cap, ext) in &[(LineCap::Butt, 0.5), (LineCap::Square, w)] {
+            let verts = stroke(&path, line_cap, LineJoin::Miter);
+            let min_x = verts.iter().map(|v| v.pos[0]).fold(f32::MAX, f32::min);
+            let max_x = verts.iter().map(|v| v.pos[0]).fold(f32::MIN, f32::max);
+            let max_y = verts.iter().map(|v| v.pos[1].abs()).fold(0.0, f32::max);
+
+            assert!((min_x + ext).abs() < 1e-3, "{:?} start {}", line_cap, min_x);
+            assert!(
+                (max_x - 10.0 - ext).abs() < 1e-3,
+                "{:?} end {}",
+                line_cap,
+                max_x
+            );
+            assert!((max_y - w).abs() < 1e-3, "{:?} side {}", line_cap, max_y);
+        }
+    }
+
+    #[test]
+    fn round_caps_are_arcs_around_end_points() {
+        let w = W + 0.5;
+        let path = polyline(&[(0.0, 0.0), (10.0, 0.0)]);
+        let verts = stroke(&path, LineCap::Round, LineJoin::Miter);
+
+        let start: Vec<_> = verts.iter().filter(|v| v.pos[0] < -1e-3).collect();
+        let end: Vec<_> = verts.iter().filter(|v| v.pos[0] > 10.0 + 1e-3).collect();
+        assert_eq!(start.len(), end.len());
+        assert!(!start.is_empty());
+        for v in start {
+            assert!((Point::new(v.pos[0], v.pos[1]).to_vector().length() - w).abs() < 1e-3);
+        }
+        for v in end {
+            let d = Point::new(v.pos[0] - 10.0, v.pos[1]).to_vector().length();
+            assert!((d - w).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn round_joins_keep_stroke_width_for_both_turns() {
+        // open paths are reversed to enforce winding, so each path turns both ways
+        for points in &[
+            [(0.0, 0.0), (10.0, 0.0), (10.0, 10.0), (20.0, 10.0)],
+            [(0.0, 0.0), (10.0, 0.0), (3.0, 5.0), (13.0, 5.0)],
+        ] {
+            let outline = outline_points(&polyline(points), LineJoin::Round);
+            assert!(!outline.is_empty());
+            for p in outline {
+                let d = distance_to_polyline(p, points);
+                assert!((d - W).abs() < 1e-3, "{:?}: {:?} at {}", points, p, d);
+            }
+        }
+    }
+
+    #[test]
+    fn closed_outline_has_outer_solid_and_inner_hole_contours() {
+        let mut path = polyline(&[(0.0, 0.0), (10.0, 0.0), (10.0, 10.0), (0.0, 10.0)]);
+        path.push(PathElement::ClosePath);
+        let outline =
+            stroke_outline_path(&path, PixelThickness::new(W * 2.0), &StrokeStyle::default());
+
+        // bounds and solidity of every contour
+        let mut contours = Vec::new();
+        let mut points = Vec::new();
+        for el in &outline {
+            match el {
+                PathElement::MoveTo(p) | PathElement::LineTo(p) => points.push(p.to_untyped()),
+                PathElement::Solidity(solidity) => {
+                    contours.push((Rect::from_points(points.drain(..)), *solidity))
+                }
+                _ => (),
+            }
+        }
+        assert_eq!(contours.len(), 2);
+
+        let (outer, solidity) = contours[0];
+        assert!(matches!(solidity, Solidity::Solid));
+        assert!(outer.min().equals(Point::new(-W, -W), 1e-3), "{:?}", outer);
+        assert!(
+            outer.max().equals(Point::new(10.0 + W, 10.0 + W), 1e-3),
+            "{:?}",
+            outer
+        );
+
+        let (inner, solidity) = contours[1];
+        assert!(matches!(solidity, Solidity::Hole));
+        assert!(inner.min().equals(Point::new(W, W), 1e-3), "{:?}", inner);
+        assert!(
+            inner.max().equals(Point::new(10.0 - W, 10.0 - W), 1e-3),
+            "{:?}",
+            inner
         );
-        dst = dst.add(1);
     }
 
-    dst
+    #[test]
+    fn bevel_joins_pair_left_and_right_vertices() {
+        for points in &[
+            [(0.0, 0.0), (10.0, 0.0), (10.0, 10.0), (20.0, 10.0)],
+            [(0.0, 0.0), (10.0, 0.0), (3.0, 5.0), (13.0, 5.0)],
+        ] {
+            let verts = stroke(&polyline(points), LineCap::Butt, LineJoin::Bevel);
+            assert_eq!(verts.len() % 2, 0);
+            for (i, v) in verts.iter().enumerate() {
+                // u is 0 on the left side of the strip and 1 on the right side
+                let u = v.tex_coords[0];
+                assert_eq!(u, (i % 2) as f32, "{:?}: vertex {}", points, i);
+            }
+        }
+    }
+
+    fn zig_zag(closed: bool) -> Vec<PathElement> {
+        let mut path = polyline(&[
+            (10.0, 10.0),
+            (60.0, 20.0),
+            (30.0, 70.0),
+            (90.0, 80.0),
+            (95.0, 20.0),
+        ]);
+        if closed {
+            path.push(PathElement::ClosePath);
+        }
+        path
+    }
+
+    fn golden_vertices(name: &str) -> Vec<[f32; 4]> {
+        let golden = include_str!("testdata/tessellation.txt");
+        let line = golden
+            .lines()
+            .find(|line| line.split(": ").next() == Some(name))
+            .unwrap_or_else(|| panic!("missing golden data for {}", name));
+        line.split(": ")
+            .nth(1)
+            .unwrap()
+            .split(&[';', '|'][..])
+            .filter(|v| !v.is_empty())
+            .map(|v| {
+                let mut res = [0.0; 4];
+                for (dst, src) in res.iter_mut().zip(v.split(',')) {
+                    *dst = src.parse().unwrap();
+                }
+                res
+            })
+            .collect()
+    }
+
+    fn assert_golden(name: &str, vertices: &[TexturedVertex]) {
+        let golden = golden_vertices(name);
+        assert_eq!(vertices.len(), golden.len(), "{}: vertex count", name);
+        for (i, (v, g)) in vertices.iter().zip(golden.iter()).enumerate() {
+            let (pos, tex_coords) = (v.pos, v.tex_coords);
+            let v = [pos[0], pos[1], tex_coords[0], tex_coords[1]];
+            for (a, b) in v.iter().zip(g.iter()) {
+                assert!(
+                    (a - b).abs() < 2e-3,
+                    "{}: vertex {} {:?} != {:?}",
+                    name,
+                    i,
+                    v,
+                    g
+                );
+            }
+        }
+    }
+
+    // The golden data was generated by the tessellation that wrote the vertices
+    // through raw pointers, before it was replaced by the vector based one.
+    #[test]
+    fn tessellation_matches_golden_vertices() {
+        for &closed in &[false, true] {
+            let kind = if closed { "closed" } else { "open" };
+            let path = zig_zag(closed);
+            let caps: &[LineCap] = if closed {
+                &[LineCap::Butt]
+            } else {
+                &[LineCap::Butt, LineCap::Round, LineCap::Square]
+            };
+
+            for &line_cap in caps {
+                for &line_join in &[LineJoin::Miter, LineJoin::Round, LineJoin::Bevel] {
+                    let mut fp = FlattenedPath::new(&path, 0.01, 0.25);
+                    fp.expand_stroke(4.0, 1.0, line_cap, line_join, 10.0, 0.25);
+                    let vertices: Vec<_> = fp
+                        .paths
+                        .iter()
+                        .flat_map(|p| fp.get_stroke(p).to_vec())
+                        .collect();
+                    let name = if closed {
+                        format!("{} {:?}", kind, line_join)
+                    } else {
+                        format!("{} {:?} {:?}", kind, line_cap, line_join)
+                    };
+                    assert_golden(&name, &vertices);
+                }
+            }
+
+            let mut fp = FlattenedPath::new(&path, 0.01, 0.25);
+            fp.expand_fill(1.0, LineJoin::Miter, 2.4, 1.0);
+            let vertices: Vec<_> = fp
+                .paths
+                .iter()
+                .flat_map(|p| fp.get_fill(p).iter().chain(fp.get_stroke(p)).copied())
+                .collect();
+            assert_golden(&format!("{} fill", kind), &vertices);
+        }
+    }
 }
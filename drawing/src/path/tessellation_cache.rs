@@ -0,0 +1,314 @@
+use super::path::*;
+use crate::backend::TexturedVertex;
+use crate::primitive::*;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::hash::Hasher;
+use std::mem::size_of;
+
+/// Identifies the result of tessellation.
+///
+/// Contains the whole path content together with all parameters
+/// that have an influence on the generated vertices.
+/// The hash of the content is calculated once, when the key is created.
+#[derive(Clone, PartialEq, Eq)]
+pub struct TessellationKey {
+    hash: u64,
+    data: Vec<u32>,
+}
+
+impl Hash for TessellationKey {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_u64(self.hash);
+    }
+}
+
+impl TessellationKey {
+    pub fn fill(
+        path: &[PathElement],
+        tess_tol: f32,
+        fringe_width: f32,
+        antialiasing: bool,
+    ) -> Self {
+        let mut data = Vec::with_capacity(4 + path.len() * 3);
+        data.push(0);
+        data.push(antialiasing as u32);
+        data.push(tess_tol.to_bits());
+        data.push(fringe_width.to_bits());
+        Self::push_path(&mut data, path);
+        Self::new(data)
+    }
+
+    pub fn stroke(
+        path: &[PathElement],
+        stroke_width: f32,
+        style: &StrokeStyle,
+        tess_tol: f32,
+        fringe_width: f32,
+        antialiasing: bool,
+    ) -> Self {
+        let mut data = Vec::with_capacity(8 + path.len() * 3);
+        data.push(1);
+        data.push(antialiasing as u32);
+        data.push(tess_tol.to_bits());
+        data.push(fringe_width.to_bits());
+        data.push(stroke_width.to_bits());
+        data.push(style.line_cap as u32);
+        data.push(style.line_join as u32);
+        data.push(style.miter_limit.to_bits());
+        Self::push_path(&mut data, path);
+        Self::new(data)
+    }
+
+    fn new(data: Vec<u32>) -> Self {
+        let mut hasher = DefaultHasher::new();
+        data.hash(&mut hasher);
+        TessellationKey {
+            hash: hasher.finish(),
+            data,
+        }
+    }
+
+    fn push_path(data: &mut Vec<u32>, path: &[PathElement]) {
+        for element in path {
+            match *element {
+                PathElement::MoveTo(p) => {
+                    data.extend_from_slice(&[0, p.x.to_bits(), p.y.to_bits()]);
+                }
+                PathElement::LineTo(p) => {
+                    data.extend_from_slice(&[1, p.x.to_bits(), p.y.to_bits()]);
+                }
+                PathElement::BezierTo(c1, c2, p) => {
+                    data.extend_from_slice(&[
+                        2,
+                        c1.x.to_bits(),
+                        c1.y.to_bits(),
+                        c2.x.to_bits(),
+                        c2.y.to_bits(),
+                        p.x.to_bits(),
+                        p.y.to_bits(),
+                    ]);
+                }
+                PathElement::ClosePath => data.push(3),
+                PathElement::Solidity(solidity) => data.extend_from_slice(&[4, solidity as u32]),
+            }
+        }
+    }
+
+    fn memory_usage(&self) -> usize {
+        self.data.len() * size_of::<u32>()
+    }
+}
+
+struct CacheEntry {
+    key: TessellationKey,
+    flattened_path: FlattenedPath,
+    memory_usage: usize,
+    last_used: u64,
+}
+
+/// Keeps tessellated paths between frames.
+///
+/// Entries are looked up by the content of the path and tessellation parameters,
+/// so a changed path never reuses stale vertices. When the memory budget is exceeded
+/// the least recently used entries are removed.
+pub struct TessellationCache {
+    // entries by the hash of their key, a collision replaces the entry
+    entries: HashMap<u64, CacheEntry>,
+    // hashes of the entry keys by the time of the last use
+    lru: BTreeMap<u64, u64>,
+    memory_budget: usize,
+    memory_usage: usize,
+    counter: u64,
+}
+
+impl TessellationCache {
+    /// Creates an empty cache.
+    ///
+    /// # Arguments
+    ///
+    /// * `memory_budget` - max number of bytes used by the cached paths
+    pub fn new(memory_budget: usize) -> Self {
+        TessellationCache {
+            entries: HashMap::new(),
+            lru: BTreeMap::new(),
+            memory_budget,
+            memory_usage: 0,
+            counter: 0,
+        }
+    }
+
+    /// Returns the cached path or tessellates and stores a new one.
+    ///
+    /// A path that alone exceeds the memory budget is kept only until the next insertion.
+    pub fn get_or_insert_with<F: FnOnce() -> FlattenedPath>(
+        &mut self,
+        key: TessellationKey,
+        tessellate: F,
+    ) -> &FlattenedPath {
+        self.counter += 1;
+        let hash = key.hash;
+
+        match self.entries.get_mut(&hash) {
+            Some(entry) if entry.key == key => {
+                self.lru.remove(&entry.last_used);
+                entry.last_used = self.counter;
+            }
+            _ => {
+                self.remove(hash);
+                let flattened_path = tessellate();
+                let memory_usage = key.memory_usage() + Self::path_memory_usage(&flattened_path);
+                self.evict(memory_usage);
+                self.memory_usage += memory_usage;
+                self.entries.insert(
+                    hash,
+                    CacheEntry {
+                        key,
+                        flattened_path,
+                        memory_usage,
+                        last_used: self.counter,
+                    },
+                );
+            }
+        }
+        self.lru.insert(self.counter, hash);
+
+        &self.entries[&hash].flattened_path
+    }
+
+    pub fn contains(&self, key: &TessellationKey) -> bool {
+        matches!(self.entries.get(&key.hash), Some(entry) if entry.key == *key)
+    }
+
+    /// Removes all cached paths.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.lru.clear();
+        self.memory_usage = 0;
+    }
+
+    pub fn memory_usage(&self) -> usize {
+        self.memory_usage
+    }
+
+    pub fn memory_budget(&self) -> usize {
+        self.memory_budget
+    }
+
+    pub fn set_memory_budget(&mut self, memory_budget: usize) {
+        self.memory_budget = memory_budget;
+        self.evict(0);
+    }
+
+    // removes least recently used entries until there is enough room for new data
+    fn evict(&mut self, required: usize) {
+        while self.memory_usage + required > self.memory_budget {
+            let hash = match self.lru.values().next() {
+                Some(&hash) => hash,
+                None => break,
+            };
+            self.remove(hash);
+        }
+    }
+
+    fn remove(&mut self, hash: u64) {
+        if let Some(entry) = self.entries.remove(&hash) {
+            self.lru.remove(&entry.last_used);
+            self.memory_usage -= entry.memory_usage;
+        }
+    }
+
+    fn path_memory_usage(flattened_path: &FlattenedPath) -> usize {
+        flattened_path.points.capacity() * size_of::<VPoint>()
+            + flattened_path.paths.capacity() * size_of::<Path>()
+            + flattened_path.vertexes.capacity() * size_of::<TexturedVertex>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::units::PixelPoint;
+
+    fn rect(x: f32) -> Vec<PathElement> {
+        vec![
+            PathElement::MoveTo(PixelPoint::new(x, 0.0)),
+            PathElement::LineTo(PixelPoint::new(x + 10.0, 0.0)),
+            PathElement::LineTo(PixelPoint::new(x + 10.0, 10.0)),
+            PathElement::LineTo(PixelPoint::new(x, 10.0)),
+            PathElement::ClosePath,
+        ]
+    }
+
+    fn key(path: &[PathElement]) -> TessellationKey {
+        TessellationKey::fill(path, 0.25, 1.0, true)
+    }
+
+    fn insert(cache: &mut TessellationCache, path: &[PathElement]) -> bool {
+        let mut tessellated = false;
+        cache.get_or_insert_with(key(path), || {
+            tessellated = true;
+            let mut fp = FlattenedPath::new(path, 0.01, 0.25);
+            fp.expand_fill(1.0, LineJoin::Miter, 2.4, 1.0);
+            fp
+        });
+        tessellated
+    }
+
+    #[test]
+    fn keys_differ_by_path_and_parameters() {
+        let path = rect(0.0);
+        assert!(key(&path) == key(&rect(0.0)));
+        assert!(key(&path) != key(&rect(1.0)));
+        assert!(key(&path) != TessellationKey::fill(&path, 0.25, 1.0, false));
+        assert!(key(&path) != TessellationKey::fill(&path, 0.5, 1.0, true));
+    }
+
+    #[test]
+    fn cached_path_is_reused() {
+        let mut cache = TessellationCache::new(usize::MAX);
+        assert!(insert(&mut cache, &rect(0.0)));
+        assert!(!insert(&mut cache, &rect(0.0)));
+        assert!(insert(&mut cache, &rect(1.0)));
+        assert!(cache.contains(&key(&rect(0.0))));
+        assert!(cache.contains(&key(&rect(1.0))));
+    }
+
+    #[test]
+    fn least_recently_used_path_is_evicted() {
+        let mut cache = TessellationCache::new(usize::MAX);
+        insert(&mut cache, &rect(0.0));
+        let entry_size = cache.memory_usage();
+        cache.clear();
+        assert_eq!(cache.memory_usage(), 0);
+
+        cache.set_memory_budget(entry_size * 2);
+        insert(&mut cache, &rect(0.0));
+        insert(&mut cache, &rect(1.0));
+        insert(&mut cache, &rect(0.0));
+        insert(&mut cache, &rect(2.0));
+
+        assert!(cache.contains(&key(&rect(0.0))));
+        assert!(!cache.contains(&key(&rect(1.0))));
+        assert!(cache.contains(&key(&rect(2.0))));
+        assert_eq!(cache.memory_usage(), entry_size * 2);
+
+        cache.set_memory_budget(entry_size);
+        assert!(!cache.contains(&key(&rect(0.0))));
+        assert!(cache.contains(&key(&rect(2.0))));
+        assert_eq!(cache.memory_usage(), entry_size);
+    }
+
+    #[test]
+    fn path_over_budget_is_kept_until_next_insertion() {
+        let mut cache = TessellationCache::new(1);
+        insert(&mut cache, &rect(0.0));
+        assert!(cache.contains(&key(&rect(0.0))));
+
+        insert(&mut cache, &rect(1.0));
+        assert!(!cache.contains(&key(&rect(0.0))));
+        assert!(cache.contains(&key(&rect(1.0))));
+    }
+}
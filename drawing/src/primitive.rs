@@ -1,5 +1,11 @@
+use crate::bidi::TextDirection;
 use crate::color::*;
+use crate::font::TextEffects;
+use crate::text_layout::TextLayout;
+use crate::text_path::TextPathParams;
 use crate::units::*;
+use crate::vertical::WritingMode;
+use std::sync::Arc;
 
 #[derive(Debug)]
 pub enum Primitive {
@@ -22,12 +28,35 @@ pub enum Primitive {
     },
 
     Text {
+        /// Key of the font, the font fallback or the font query
+        /// (like `"bold 'Open Sans', sans-serif"`, see `FontQuery::parse()`).
         resource_key: String,
         size: PixelThickness,
         color: Color,
+        /// Top left corner of the text.
         position: PixelPoint,
         clipping_rect: PixelRect,
         text: String,
+        style: TextStyle,
+    },
+
+    /// Text laid out with `TextLayout::new()`.
+    TextLayout {
+        layout: Arc<TextLayout>,
+        color: Color,
+        position: PixelPoint,
+        clipping_rect: PixelRect,
+    },
+
+    /// Spans of text of different fonts, sizes and styles drawn on one line
+    /// with a shared baseline.
+    RichText {
+        spans: Vec<TextSpan>,
+        /// Base direction of the line, right-to-left lines place the spans from right to left.
+        direction: TextDirection,
+        /// Top left corner of the line.
+        position: PixelPoint,
+        clipping_rect: PixelRect,
     },
 
     Stroke {
@@ -118,6 +147,74 @@ pub enum Brush {
     },
 }
 
+/// Optional styles of `Primitive::Text`. The default draws horizontal text
+/// filled with its color.
+#[derive(Debug, Default)]
+pub struct TextStyle {
+    /// Fills the text instead of its color (a gradient or an image pattern).
+    pub brush: Option<Brush>,
+    /// Outline, shadow or glow drawn behind the text.
+    pub effects: TextEffects,
+    /// Base direction of the text. `TextDirection::Auto` takes it
+    /// from the first strong character.
+    pub direction: TextDirection,
+    /// Lines or columns (see the `vertical` module), `WritingMode::VerticalRl`
+    /// for Japanese and Chinese text.
+    pub writing_mode: WritingMode,
+    /// Draws the first line of the text along the path instead of at the position
+    /// (see the `text_path` module).
+    pub path: Option<TextPath>,
+}
+
+/// Path the text is drawn along.
+#[derive(Debug)]
+pub struct TextPath {
+    /// Path relative to the position of the text.
+    pub path: Vec<PathElement>,
+    pub params: TextPathParams,
+}
+
+/// Lines drawn along the text.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub struct TextDecoration {
+    pub underline: bool,
+    pub strikethrough: bool,
+    pub overline: bool,
+}
+
+/// Part of `Primitive::RichText` with its own font and style.
+#[derive(Debug)]
+pub struct TextSpan {
+    /// Same as the resource key of `Primitive::Text`.
+    pub resource_key: String,
+    pub size: PixelThickness,
+    pub brush: Brush,
+    pub text: String,
+    pub decoration: TextDecoration,
+    /// Color of the decoration lines. By default it's the color of the text
+    /// (the inner color of gradients and images).
+    pub decoration_color: Option<Color>,
+    /// Color of the background of the span (the whole height of the line).
+    pub background: Option<Color>,
+    pub effects: TextEffects,
+}
+
+impl TextSpan {
+    /// Creates the span of the colored text without decorations, background and effects.
+    pub fn new(resource_key: &str, size: f32, color: Color, text: &str) -> Self {
+        TextSpan {
+            resource_key: resource_key.to_string(),
+            size: PixelThickness::new(size),
+            brush: Brush::Color { color },
+            text: text.to_string(),
+            decoration: TextDecoration::default(),
+            decoration_color: None,
+            background: None,
+            effects: TextEffects::default(),
+        }
+    }
+}
+
 #[derive(Debug, Copy, Clone)]
 pub struct StrokeStyle {
     pub line_cap: LineCap,
@@ -1,3 +1,4 @@
+use crate::path::FlattenedPath;
 use crate::primitive::*;
 use crate::units::*;
 
@@ -365,3 +366,53 @@ pub fn pixel_vertical_line_path<P: Into<PixelPoint>, T: Into<PixelThickness>>(
         )),
     ]
 }
+
+/// Creates a path that represents the outline of the stroke.
+///
+/// Filling the result gives the same shape as stroking the `path`,
+/// so it can be used for hit testing, boolean operations or exporting
+/// to formats without stroke support.
+///
+/// # Arguments
+///
+/// * `path` - path to be stroked
+/// * `thickness` - thickness of the stroke
+/// * `style` - caps, joins and miter limit of the stroke
+pub fn stroke_outline_path<T: Into<PixelThickness>>(
+    path: &[PathElement],
+    thickness: T,
+    style: &StrokeStyle,
+) -> Vec<PathElement> {
+    let thickness = thickness.into().get();
+
+    let mut flattened_path = FlattenedPath::new(path, 0.01f32, 0.25f32);
+    flattened_path.expand_stroke_outline(
+        thickness * 0.5f32,
+        style.line_cap,
+        style.line_join,
+        style.miter_limit,
+        0.25f32,
+    )
+}
+
+/// Creates a path with contours moved outwards or inwards by the given distance.
+///
+/// Positive `offset` grows the shape, negative one shrinks it.
+/// Overlapping parts are merged and parts that vanish are removed,
+/// so the result has no self-intersections. Open contours are treated as closed.
+///
+/// # Arguments
+///
+/// * `path` - closed contours to be offset
+/// * `offset` - distance in pixels
+/// * `line_join` - how the moved edges are joined at the corners
+/// * `miter_limit` - max ratio of the miter length to the offset before the miter is beveled
+pub fn offset_path(
+    path: &[PathElement],
+    offset: f32,
+    line_join: LineJoin,
+    miter_limit: f32,
+) -> Vec<PathElement> {
+    let flattened_path = FlattenedPath::new(path, 0.01f32, 0.25f32);
+    flattened_path.offset_contours(offset, line_join, miter_limit, 0.25f32)
+}
@@ -3,8 +3,11 @@ use crate::backend::RenderTarget;
 use crate::clipping::Scissor;
 use crate::font::Font;
 use crate::font::FontParams;
+use crate::font::TextDrawParams;
 use crate::paint::Paint;
 use crate::path::FlattenedPath;
+use crate::path::TessellationCache;
+use crate::path::TessellationKey;
 use crate::primitive::*;
 use crate::resources::*;
 use crate::units::*;
@@ -12,7 +15,12 @@ use anyhow::Result;
 
 use std::convert::Into;
 
-pub struct Renderer;
+// Width and style of a stroke, `None` for fills.
+type PathStroke = Option<(PixelThickness, StrokeStyle)>;
+
+pub struct Renderer {
+    tessellation_cache: Option<TessellationCache>,
+}
 
 impl Default for Renderer {
     fn default() -> Self {
@@ -22,7 +30,35 @@ impl Default for Renderer {
 
 impl Renderer {
     pub fn new() -> Self {
-        Renderer {}
+        Renderer {
+            tessellation_cache: None,
+        }
+    }
+
+    /// Creates a renderer that reuses tessellated fills and strokes between frames.
+    ///
+    /// # Arguments
+    ///
+    /// * `memory_budget` - max number of bytes used by the cached paths
+    pub fn with_tessellation_cache(memory_budget: usize) -> Self {
+        Renderer {
+            tessellation_cache: Some(TessellationCache::new(memory_budget)),
+        }
+    }
+
+    pub fn tessellation_cache(&self) -> Option<&TessellationCache> {
+        self.tessellation_cache.as_ref()
+    }
+
+    pub fn tessellation_cache_mut(&mut self) -> Option<&mut TessellationCache> {
+        self.tessellation_cache.as_mut()
+    }
+
+    /// Removes all cached tessellation results.
+    pub fn invalidate_tessellation_cache(&mut self) {
+        if let Some(ref mut cache) = self.tessellation_cache {
+            cache.clear();
+        }
     }
 
     pub fn draw<D: Device, F: Font<D>>(
@@ -58,7 +94,10 @@ impl Renderer {
         let unknown_to_device_transform =
             UnknownToDeviceTransform::from_array(pixel_to_device_transform.to_array());
 
-        for primitive in primitives {
+        let aspect_ratio = render_target.get_aspect_ratio();
+        let mut tessellated = self.tessellate_all(primitives, aspect_ratio, antialiasing);
+
+        for (i, primitive) in primitives.iter().enumerate() {
             match *primitive {
                 Primitive::Line {
                     ref color,
@@ -112,21 +151,87 @@ impl Renderer {
                     clipping_rect,
                     size,
                     ref text,
+                    ref style,
                 } => {
-                    if let Some(font) = resources.fonts_mut().get_mut(resource_key) {
-                        font.draw(
-                            device,
-                            render_target,
-                            color,
-                            text,
+                    let font_params = FontParams::new(size.get())
+                        .with_direction(style.direction)
+                        .with_writing_mode(style.writing_mode)
+                        .with_effects(style.effects);
+                    let params = TextDrawParams::new(
+                        position.to_untyped(),
+                        clipping_rect.to_untyped(),
+                        font_params,
+                        unknown_to_device_transform,
+                    )
+                    .with_path(style.path.as_ref());
+                    let paint = style
+                        .brush
+                        .as_ref()
+                        .map(|brush| Paint::from_brush(brush, resources).0);
+                    match paint {
+                        Some(paint) => {
+                            resources.draw_text_paint(
+                                device,
+                                render_target,
+                                &paint,
+                                resource_key,
+                                text,
+                                params.with_scissor(scissor),
+                            )?;
+                        }
+                        None => {
+                            resources.draw_text(
+                                device,
+                                render_target,
+                                color,
+                                resource_key,
+                                text,
+                                params,
+                            )?;
+                        }
+                    }
+                }
+
+                Primitive::TextLayout {
+                    ref layout,
+                    ref color,
+                    position,
+                    clipping_rect,
+                } => {
+                    resources.draw_text_layout(
+                        device,
+                        render_target,
+                        color,
+                        layout,
+                        TextDrawParams::new(
                             position.to_untyped(),
                             clipping_rect.to_untyped(),
-                            FontParams {
-                                size: size.get() as u8,
-                            },
+                            layout.font_params,
                             unknown_to_device_transform,
-                        )?;
-                    }
+                        ),
+                    )?;
+                }
+
+                Primitive::RichText {
+                    ref spans,
+                    direction,
+                    position,
+                    clipping_rect,
+                } => {
+                    // sizes and effects are taken from the spans
+                    let font_params = FontParams::new(0.0).with_direction(direction);
+                    resources.draw_rich_text(
+                        device,
+                        render_target,
+                        spans,
+                        TextDrawParams::new(
+                            position.to_untyped(),
+                            clipping_rect.to_untyped(),
+                            font_params,
+                            unknown_to_device_transform,
+                        )
+                        .with_scissor(scissor),
+                    )?;
                 }
 
                 Primitive::Stroke {
@@ -136,11 +241,12 @@ impl Renderer {
                 } => {
                     let scale = 1.0f32; // TODO: take from transform? xform.average_scale()?
                     let stroke_width = *thickness * scale; //.clamped(0.0, 200.0);
-                    let aspect_ratio = render_target.get_aspect_ratio();
-                    let flattened_path = Self::get_stroke_path(
+                    let mut tessellated_path = tessellated.get_mut(i).and_then(Option::take);
+                    let flattened_path = Self::get_flattened_path(
+                        &mut self.tessellation_cache,
+                        &mut tessellated_path,
                         path,
-                        stroke_width,
-                        &Default::default(),
+                        Some((stroke_width, Default::default())),
                         aspect_ratio,
                         antialiasing,
                     );
@@ -152,7 +258,7 @@ impl Renderer {
                         &paint,
                         texture,
                         true,
-                        &flattened_path.paths,
+                        flattened_path,
                         stroke_width.get(),
                         1.0f32 / aspect_ratio,
                         antialiasing,
@@ -170,11 +276,12 @@ impl Renderer {
                 } => {
                     let scale = 1.0f32; // TODO: take from transform? xform.average_scale()?
                     let stroke_width = *thickness * scale; //.clamped(0.0, 200.0);
-                    let aspect_ratio = render_target.get_aspect_ratio();
-                    let flattened_path = Self::get_stroke_path(
+                    let mut tessellated_path = tessellated.get_mut(i).and_then(Option::take);
+                    let flattened_path = Self::get_flattened_path(
+                        &mut self.tessellation_cache,
+                        &mut tessellated_path,
                         path,
-                        stroke_width,
-                        style,
+                        Some((stroke_width, *style)),
                         aspect_ratio,
                         antialiasing,
                     );
@@ -186,7 +293,7 @@ impl Renderer {
                         &paint,
                         texture,
                         true,
-                        &flattened_path.paths,
+                        flattened_path,
                         stroke_width.get(),
                         1.0f32 / aspect_ratio,
                         antialiasing,
@@ -200,8 +307,15 @@ impl Renderer {
                     ref path,
                     ref brush,
                 } => {
-                    let aspect_ratio = render_target.get_aspect_ratio();
-                    let flattened_path = Self::get_fill_path(path, aspect_ratio, antialiasing);
+                    let mut tessellated_path = tessellated.get_mut(i).and_then(Option::take);
+                    let flattened_path = Self::get_flattened_path(
+                        &mut self.tessellation_cache,
+                        &mut tessellated_path,
+                        path,
+                        None,
+                        aspect_ratio,
+                        antialiasing,
+                    );
 
                     let (paint, texture) = Paint::from_brush(brush, resources);
 
@@ -210,8 +324,7 @@ impl Renderer {
                         &paint,
                         texture,
                         true,
-                        &flattened_path.paths,
-                        flattened_path.bounds,
+                        flattened_path,
                         1.0f32 / aspect_ratio,
                         antialiasing,
                         scissor,
@@ -290,6 +403,127 @@ impl Renderer {
         Ok(())
     }
 
+    // Tessellates all fills and strokes of one level on the thread pool.
+    // Paths that are already in the tessellation cache are skipped.
+    #[cfg(feature = "rayon")]
+    fn tessellate_all(
+        &self,
+        primitives: &[Primitive],
+        aspect_ratio: f32,
+        antialiasing: bool,
+    ) -> Vec<Option<FlattenedPath>> {
+        use rayon::prelude::*;
+
+        primitives
+            .par_iter()
+            .map(|primitive| {
+                let (path, stroke) = Self::get_tessellation_params(primitive)?;
+                if let Some(ref cache) = self.tessellation_cache {
+                    let key = Self::get_tessellation_key(path, stroke, aspect_ratio, antialiasing);
+                    if cache.contains(&key) {
+                        return None;
+                    }
+                }
+                Some(Self::tessellate(path, stroke, aspect_ratio, antialiasing))
+            })
+            .collect()
+    }
+
+    // Without the thread pool paths are tessellated on demand.
+    #[cfg(not(feature = "rayon"))]
+    fn tessellate_all(
+        &self,
+        _primitives: &[Primitive],
+        _aspect_ratio: f32,
+        _antialiasing: bool,
+    ) -> Vec<Option<FlattenedPath>> {
+        Vec::new()
+    }
+
+    #[cfg(feature = "rayon")]
+    fn get_tessellation_params(primitive: &Primitive) -> Option<(&[PathElement], PathStroke)> {
+        match *primitive {
+            Primitive::Stroke {
+                ref path,
+                thickness,
+                ..
+            } => Some((path, Some((thickness, Default::default())))),
+            Primitive::StrokeStyled {
+                ref path,
+                thickness,
+                style,
+                ..
+            } => Some((path, Some((thickness, style)))),
+            Primitive::Fill { ref path, .. } => Some((path, None)),
+            _ => None,
+        }
+    }
+
+    // Returns the path tessellated in advance, cached or tessellates it now.
+    fn get_flattened_path<'a>(
+        tessellation_cache: &'a mut Option<TessellationCache>,
+        tessellated_path: &'a mut Option<FlattenedPath>,
+        path: &[PathElement],
+        stroke: PathStroke,
+        aspect_ratio: f32,
+        antialiasing: bool,
+    ) -> &'a FlattenedPath {
+        match tessellation_cache {
+            Some(cache) => cache.get_or_insert_with(
+                Self::get_tessellation_key(path, stroke, aspect_ratio, antialiasing),
+                || {
+                    tessellated_path.take().unwrap_or_else(|| {
+                        Self::tessellate(path, stroke, aspect_ratio, antialiasing)
+                    })
+                },
+            ),
+            None => tessellated_path
+                .get_or_insert_with(|| Self::tessellate(path, stroke, aspect_ratio, antialiasing)),
+        }
+    }
+
+    fn get_tessellation_key(
+        path: &[PathElement],
+        stroke: PathStroke,
+        aspect_ratio: f32,
+        antialiasing: bool,
+    ) -> TessellationKey {
+        match stroke {
+            Some((stroke_width, stroke_style)) => TessellationKey::stroke(
+                path,
+                stroke_width.get(),
+                &stroke_style,
+                0.25f32 / aspect_ratio,
+                1.0f32 / aspect_ratio,
+                antialiasing,
+            ),
+            None => TessellationKey::fill(
+                path,
+                0.25f32 / aspect_ratio,
+                1.0f32 / aspect_ratio,
+                antialiasing,
+            ),
+        }
+    }
+
+    fn tessellate(
+        path: &[PathElement],
+        stroke: PathStroke,
+        aspect_ratio: f32,
+        antialiasing: bool,
+    ) -> FlattenedPath {
+        match stroke {
+            Some((stroke_width, stroke_style)) => Self::get_stroke_path(
+                path,
+                stroke_width,
+                &stroke_style,
+                aspect_ratio,
+                antialiasing,
+            ),
+            None => Self::get_fill_path(path, aspect_ratio, antialiasing),
+        }
+    }
+
     fn get_stroke_path(
         path: &[PathElement],
         stroke_width: PixelThickness,
@@ -334,3 +568,89 @@ impl Renderer {
         flattened_path
     }
 }
+
+#[cfg(all(test, feature = "rayon"))]
+mod tests {
+    use super::*;
+
+    fn primitives() -> Vec<Primitive> {
+        let path = || {
+            vec![
+                PathElement::MoveTo(PixelPoint::new(10.0, 10.0)),
+                PathElement::LineTo(PixelPoint::new(60.0, 20.0)),
+                PathElement::LineTo(PixelPoint::new(30.0, 70.0)),
+                PathElement::ClosePath,
+            ]
+        };
+        let brush = || Brush::Color {
+            color: [1.0, 1.0, 1.0, 1.0],
+        };
+        vec![
+            Primitive::Fill {
+                path: path(),
+                brush: brush(),
+            },
+            Primitive::Rectangle {
+                color: [1.0, 1.0, 1.0, 1.0],
+                rect: PixelRect::new(PixelPoint::new(0.0, 0.0), PixelSize::new(10.0, 10.0)),
+            },
+            Primitive::StrokeStyled {
+                path: path(),
+                thickness: PixelThickness::new(3.0),
+                brush: brush(),
+                style: StrokeStyle {
+                    line_join: LineJoin::Round,
+                    ..Default::default()
+                },
+            },
+        ]
+    }
+
+    fn vertices(flattened_path: &FlattenedPath) -> Vec<[f32; 4]> {
+        flattened_path
+            .vertexes
+            .iter()
+            .map(|v| [v.pos[0], v.pos[1], v.tex_coords[0], v.tex_coords[1]])
+            .collect()
+    }
+
+    #[test]
+    fn thread_pool_tessellation_matches_serial_tessellation() {
+        let primitives = primitives();
+        let tessellated = Renderer::new().tessellate_all(&primitives, 1.0, true);
+        assert_eq!(tessellated.len(), primitives.len());
+        assert!(tessellated[1].is_none());
+
+        for (primitive, tessellated) in primitives.iter().zip(&tessellated) {
+            if let Some((path, stroke)) = Renderer::get_tessellation_params(primitive) {
+                let serial = Renderer::tessellate(path, stroke, 1.0, true);
+                let parallel = tessellated.as_ref().unwrap();
+                assert_eq!(vertices(parallel), vertices(&serial));
+                let ranges = |fp: &FlattenedPath| {
+                    fp.paths
+                        .iter()
+                        .map(|p| (p.fill.clone(), p.stroke.clone()))
+                        .collect::<Vec<_>>()
+                };
+                assert_eq!(ranges(parallel), ranges(&serial));
+            }
+        }
+    }
+
+    #[test]
+    fn cached_paths_are_not_tessellated_again() {
+        let primitives = primitives();
+        let mut renderer = Renderer::with_tessellation_cache(1 << 20);
+        let (path, stroke) = Renderer::get_tessellation_params(&primitives[2]).unwrap();
+        let key = Renderer::get_tessellation_key(path, stroke, 1.0, true);
+        renderer
+            .tessellation_cache_mut()
+            .unwrap()
+            .get_or_insert_with(key, || Renderer::tessellate(path, stroke, 1.0, true));
+
+        let tessellated = renderer.tessellate_all(&primitives, 1.0, true);
+        assert!(tessellated[0].is_some());
+        assert!(tessellated[1].is_none());
+        assert!(tessellated[2].is_none());
+    }
+}
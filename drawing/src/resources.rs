@@ -1,9 +1,64 @@
 use crate::backend::Device;
-use crate::font::Font;
+use crate::bidi::{visual_order, BidiParagraph, TextDirection};
+use crate::color::Color;
+use crate::font::{Font, FontFace, FontMetrics, FontParams, GlyphFill, TextDrawParams};
+use crate::font_registry::{FontQuery, FontRegistry};
+use crate::paint::Paint;
+use crate::primitive::{Brush, PathElement, TextPath, TextSpan};
+use crate::text_layout::TextLayout;
+use crate::text_path::{PathMeasure, TextPathAlign, TextPathSide};
+use crate::units::{PixelPoint, PixelTransform, Point, Rect, Size};
+use crate::vertical::WritingMode;
+use anyhow::Result;
 use std::collections::HashMap;
+use std::ops::Range;
+use std::path::Path;
+
+// How the glyphs of the text are colored.
+#[derive(Copy, Clone)]
+enum TextFill<'a> {
+    Color(&'a Color),
+    Paint(&'a Paint),
+}
+
+// Run of characters of the same embedding level rendered by the same font face.
+struct FontRun {
+    font_key: String,
+    face: FontFace,
+    range: Range<usize>,
+    level: u8,
+}
+
+impl FontRun {
+    fn params(&self, params: FontParams) -> FontParams {
+        FontParams {
+            face: self.face,
+            ..params.with_direction(TextDirection::from_level(self.level))
+        }
+    }
+}
+
+// Run of the text with its position relative to the top left corner of the text.
+struct PlacedRun {
+    run: FontRun,
+    // byte range in the text
+    range: Range<usize>,
+    pos: Point,
+}
+
+// Position of the span of the rich text relative to the top left corner of the line.
+struct SpanBox {
+    x: f32,
+    width: f32,
+    metrics: FontMetrics,
+}
 
 pub struct Resources<D: Device, F: Font<D>> {
     fonts: HashMap<String, F>,
+    font_fallbacks: HashMap<String, Vec<String>>,
+    font_registry: FontRegistry,
+    // fonts the font queries used as resource keys resolve to
+    font_queries: HashMap<String, Vec<(String, FontFace)>>,
     textures: HashMap<i32, D::Texture>,
     last_texture_id: i32,
 }
@@ -18,6 +73,9 @@ impl<D: Device, F: Font<D>> Resources<D, F> {
     pub fn new() -> Resources<D, F> {
         Resources {
             fonts: HashMap::new(),
+            font_fallbacks: HashMap::new(),
+            font_registry: FontRegistry::new(),
+            font_queries: HashMap::new(),
             textures: HashMap::new(),
             last_texture_id: 0,
         }
@@ -27,6 +85,395 @@ impl<D: Device, F: Font<D>> Resources<D, F> {
         &mut self.fonts
     }
 
+    /// Loads the font and adds its faces to the font registry.
+    pub fn add_font(&mut self, resource_key: &str, bytes: Vec<u8>) -> Result<()> {
+        self.font_registry.remove_font(resource_key);
+        self.font_registry.add_font_data(resource_key, &bytes);
+        self.fonts
+            .insert(resource_key.to_string(), F::create(bytes)?);
+        self.font_queries.clear();
+        Ok(())
+    }
+
+    pub fn font_registry(&self) -> &FontRegistry {
+        &self.font_registry
+    }
+
+    /// Returns the font registry to add fonts or scan font directories.
+    /// Font queries are resolved again after the change.
+    pub fn font_registry_mut(&mut self) -> &mut FontRegistry {
+        self.font_queries.clear();
+        &mut self.font_registry
+    }
+
+    /// Returns the key of the font and the face that best match the query.
+    ///
+    /// Font files found by scanning directories are loaded on first use.
+    pub fn find_font(&mut self, query: &FontQuery) -> Result<Option<(String, FontFace)>> {
+        let found = self.font_registry.find(query).map(|m| {
+            (
+                m.descriptor.resource_key.clone(),
+                m.descriptor.path.clone(),
+                m.face,
+            )
+        });
+        match found {
+            Some((font_key, path, face)) => {
+                self.load_registered_font(&font_key, path.as_deref())?;
+                Ok(Some((font_key, face)))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Makes the resource key resolve to an ordered list of fonts.
+    ///
+    /// Every run of characters is rendered by the first font of the list that has
+    /// glyphs for it. The key may be the same as the key of the first font.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// resources.set_font_fallback("UI", &["OpenSans", "NotoSansCJK", "NotoEmoji"]);
+    /// ```
+    pub fn set_font_fallback(&mut self, resource_key: &str, font_keys: &[&str]) {
+        self.font_fallbacks.insert(
+            resource_key.to_string(),
+            font_keys.iter().map(|key| key.to_string()).collect(),
+        );
+    }
+
+    pub fn remove_font_fallback(&mut self, resource_key: &str) {
+        self.font_fallbacks.remove(resource_key);
+    }
+
+    /// Returns the keys of fonts the resource key resolves to.
+    ///
+    /// Resource keys that are neither font keys nor fallbacks are font queries
+    /// (see `FontQuery::parse()`). They resolve to the best face of every family
+    /// of the query, but only after they were used to draw or measure the text.
+    pub fn get_font_chain(&self, resource_key: &str) -> Vec<&str> {
+        self.font_chain(resource_key, FontFace::default())
+            .into_iter()
+            .map(|(key, _)| key)
+            .collect()
+    }
+
+    /// Returns the metrics of the first font of the chain of the resource key
+    /// (default metrics if there is no font).
+    pub fn get_font_metrics(
+        &mut self,
+        resource_key: &str,
+        params: FontParams,
+    ) -> Result<FontMetrics> {
+        self.load_font_query(resource_key)?;
+        let (first_key, face) = match self.font_chain(resource_key, params.face).first() {
+            Some((key, face)) => (key.to_string(), *face),
+            None => return Ok(FontMetrics::default()),
+        };
+        self.fonts
+            .get_mut(&first_key)
+            .unwrap()
+            .get_metrics(FontParams { face, ..params })
+    }
+
+    /// Returns the size of the text rendered with the font chain of the resource key.
+    pub fn get_text_dimensions(
+        &mut self,
+        resource_key: &str,
+        params: FontParams,
+        text: &str,
+    ) -> Result<(u16, u16)> {
+        self.load_font_query(resource_key)?;
+        let mut length = 0;
+        let mut height = 0;
+        for line in text.split('\n') {
+            let (line_length, line_height) = self.measure_line(resource_key, params, line)?;
+            length = length.max(line_length);
+            height += line_height;
+        }
+        if params.writing_mode.is_vertical() {
+            // columns next to each other
+            return Ok((height, length));
+        }
+        Ok((length, height))
+    }
+
+    /// Returns the width of every character of the text rendered with the font chain
+    /// of the resource key (new line characters have zero width) and the height of the text.
+    ///
+    /// For vertical text these are the advances of the characters down the columns
+    /// and the width of the text.
+    pub fn get_text_dimensions_each_char(
+        &mut self,
+        resource_key: &str,
+        params: FontParams,
+        text: &str,
+    ) -> Result<(Vec<i16>, u16)> {
+        self.load_font_query(resource_key)?;
+        let mut widths = Vec::with_capacity(text.len());
+        let mut height = 0;
+        for (i, line) in text.split('\n').enumerate() {
+            if i > 0 {
+                // new line character
+                widths.push(0);
+            }
+            let runs = self.split_runs(resource_key, line, params);
+            let mut line_height = 0;
+            for run in &runs {
+                let font = self.fonts.get_mut(&run.font_key).unwrap();
+                let (run_widths, run_height) =
+                    font.get_dimensions_each_char(run.params(params), &line[run.range.clone()])?;
+                widths.extend(run_widths);
+                line_height = line_height.max(run_height);
+            }
+            height += self.get_line_height(&runs, resource_key, params, line_height)?;
+        }
+        Ok((widths, height))
+    }
+
+    /// Draws the text using the font chain of the resource key.
+    ///
+    /// Runs of different fonts are aligned to the top of the line
+    /// and lines are placed below each other using the highest font of the line.
+    /// Runs of different directions are drawn in visual order.
+    ///
+    /// Columns of vertical text (see `FontParams::writing_mode`) are placed next
+    /// to each other using the widest font of the column, runs of different fonts
+    /// are centered in the column.
+    ///
+    /// With `params.path` only the first line of the text is drawn, along the path
+    /// relative to `params.pos` (see the `text_path` module). Runs of different fonts
+    /// are placed next to each other with their baselines on the path and the text
+    /// is always horizontal (the writing mode is ignored).
+    pub fn draw_text(
+        &mut self,
+        device: &mut D,
+        target: &D::RenderTarget,
+        color: &Color,
+        resource_key: &str,
+        text: &str,
+        params: TextDrawParams,
+    ) -> Result<()> {
+        self.draw_text_fill(
+            device,
+            target,
+            TextFill::Color(color),
+            resource_key,
+            text,
+            params,
+        )
+    }
+
+    /// Same as `draw_text()` but the text is filled with the paint
+    /// (see `Paint::from_brush()`). The image of the paint is taken from the textures.
+    pub fn draw_text_paint(
+        &mut self,
+        device: &mut D,
+        target: &D::RenderTarget,
+        paint: &Paint,
+        resource_key: &str,
+        text: &str,
+        params: TextDrawParams,
+    ) -> Result<()> {
+        self.draw_text_fill(
+            device,
+            target,
+            TextFill::Paint(paint),
+            resource_key,
+            text,
+            params,
+        )
+    }
+
+    /// Returns the outlines of the text as if it was drawn with `draw_text()`.
+    ///
+    /// The path can be used with `Primitive::Fill` and `Primitive::StrokeStyled`.
+    pub fn get_text_outline(
+        &mut self,
+        resource_key: &str,
+        text: &str,
+        pos: PixelPoint,
+        params: FontParams,
+    ) -> Result<Vec<PathElement>> {
+        self.load_font_query(resource_key)?;
+        let mut path = Vec::new();
+        for placed in self.place_runs(resource_key, text, params)? {
+            let font = self.fonts.get_mut(&placed.run.font_key).unwrap();
+            path.extend(font.get_outline(
+                placed.run.params(params),
+                &text[placed.range],
+                pos + PixelPoint::from_untyped(placed.pos).to_vector(),
+            )?);
+        }
+        Ok(path)
+    }
+
+    /// Draws the text laid out with `TextLayout::new()`.
+    ///
+    /// The text is drawn with the font parameters of the layout
+    /// (`params.font_params` is not used).
+    pub fn draw_text_layout(
+        &mut self,
+        device: &mut D,
+        target: &D::RenderTarget,
+        color: &Color,
+        layout: &TextLayout,
+        params: TextDrawParams,
+    ) -> Result<()> {
+        let pos = params.pos;
+        let clipping_rect = match layout.clip {
+            Some(clip) => {
+                match params
+                    .clipping_rect
+                    .intersection(&clip.translate(pos.to_vector()))
+                {
+                    Some(rect) => rect,
+                    None => return Ok(()),
+                }
+            }
+            None => params.clipping_rect,
+        };
+        for line in &layout.lines {
+            for run in &line.runs {
+                if run.text.trim().is_empty() {
+                    continue;
+                }
+                self.draw_text(
+                    device,
+                    target,
+                    color,
+                    &layout.resource_key,
+                    &run.text,
+                    params
+                        .with_pos(Point::new(pos.x + run.x, pos.y + run.y))
+                        .with_clipping_rect(clipping_rect)
+                        .with_font_params(layout.font_params.with_direction(run.direction)),
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the width and height of the spans drawn with `draw_rich_text()`.
+    pub fn get_rich_text_dimensions(
+        &mut self,
+        spans: &[TextSpan],
+        direction: TextDirection,
+    ) -> Result<(f32, f32)> {
+        let direction = spans_direction(spans, direction);
+        let (boxes, _, height) = self.layout_spans(spans, direction)?;
+        Ok((boxes.iter().map(|span| span.width).sum(), height))
+    }
+
+    /// Draws the spans next to each other on one line with their baselines aligned
+    /// (the baseline of the span with the biggest font is used).
+    ///
+    /// Backgrounds of all spans are drawn first, then the text and then the decorations.
+    /// Decorations are placed using the metrics of the font of the span.
+    ///
+    /// Spans are placed from right to left if the base direction (the direction
+    /// of `params.font_params`) is right-to-left (`TextDirection::Auto` takes it
+    /// from the first strong character of the spans). Other font parameters
+    /// are taken from the spans.
+    pub fn draw_rich_text(
+        &mut self,
+        device: &mut D,
+        target: &D::RenderTarget,
+        spans: &[TextSpan],
+        params: TextDrawParams,
+    ) -> Result<()> {
+        let TextDrawParams {
+            pos,
+            clipping_rect,
+            transform,
+            ..
+        } = params;
+        let direction = spans_direction(spans, params.font_params.direction);
+        let (boxes, baseline, height) = self.layout_spans(spans, direction)?;
+        let fill_rect = |device: &mut D, color: &Color, x: f32, y: f32, w: f32, h: f32| {
+            let rect = Rect::new(Point::new(pos.x + x, y), Size::new(w, h));
+            if let Some(rect) = rect.intersection(&clipping_rect) {
+                device.rect_colored(target, color, rect, transform);
+            }
+        };
+
+        for (span, span_box) in spans.iter().zip(&boxes) {
+            if let Some(ref color) = span.background {
+                fill_rect(device, color, span_box.x, pos.y, span_box.width, height);
+            }
+        }
+
+        for (span, span_box) in spans.iter().zip(&boxes) {
+            let text_pos = Point::new(
+                pos.x + span_box.x,
+                pos.y + baseline - span_box.metrics.baseline,
+            );
+            let font_params = FontParams::new(span.size.get())
+                .with_direction(direction)
+                .with_effects(span.effects);
+            let params = params.with_pos(text_pos).with_font_params(font_params);
+            match span.brush {
+                Brush::Color { ref color } => self.draw_text(
+                    device,
+                    target,
+                    color,
+                    &span.resource_key,
+                    &span.text,
+                    params,
+                )?,
+                ref brush => {
+                    let paint = Paint::from_brush(brush, self).0;
+                    self.draw_text_paint(
+                        device,
+                        target,
+                        &paint,
+                        &span.resource_key,
+                        &span.text,
+                        params,
+                    )?
+                }
+            }
+        }
+
+        for (span, span_box) in spans.iter().zip(&boxes) {
+            let decoration = span.decoration;
+            if !(decoration.underline || decoration.strikethrough || decoration.overline) {
+                continue;
+            }
+            let color = match (span.decoration_color, &span.brush) {
+                (Some(color), _) => color,
+                (None, Brush::Color { color }) => *color,
+                (None, brush) => Paint::from_brush(brush, self).0.inner_color,
+            };
+            let metrics = &span_box.metrics;
+            let thickness = metrics.underline_thickness.round().max(1.0);
+            let baseline_y = pos.y + baseline;
+            // centers of the lines
+            let mut lines = Vec::new();
+            if decoration.underline {
+                lines.push(baseline_y + metrics.underline_position);
+            }
+            if decoration.strikethrough {
+                let center = if metrics.x_height > 0.0 {
+                    metrics.x_height * 0.5
+                } else {
+                    metrics.ascent / 3.0
+                };
+                lines.push(baseline_y - center);
+            }
+            if decoration.overline {
+                lines.push(baseline_y - metrics.ascent + thickness * 0.5);
+            }
+            for center in lines {
+                // whole pixels keep the lines sharp
+                let y = (center - thickness * 0.5).round();
+                fill_rect(device, &color, span_box.x, y, span_box.width, thickness);
+            }
+        }
+        Ok(())
+    }
+
     pub fn get_next_texture_id(&mut self) -> i32 {
         self.last_texture_id += 1;
         self.last_texture_id
@@ -39,4 +486,402 @@ impl<D: Device, F: Font<D>> Resources<D, F> {
     pub fn textures_mut(&mut self) -> &mut HashMap<i32, D::Texture> {
         &mut self.textures
     }
+
+    fn draw_text_fill(
+        &mut self,
+        device: &mut D,
+        target: &D::RenderTarget,
+        fill: TextFill,
+        resource_key: &str,
+        text: &str,
+        params: TextDrawParams,
+    ) -> Result<()> {
+        self.load_font_query(resource_key)?;
+        if let Some(path) = params.path {
+            let line = text.split('\n').next().unwrap_or("");
+            let font_params = params
+                .font_params
+                .with_writing_mode(WritingMode::HorizontalTb);
+            for (run, glyph_transforms) in
+                self.place_runs_on_path(resource_key, line, path, font_params)?
+            {
+                let font = self.fonts.get_mut(&run.font_key).unwrap();
+                let fill = match fill {
+                    TextFill::Color(color) => GlyphFill::Color(color),
+                    TextFill::Paint(paint) => {
+                        GlyphFill::Paint(paint, paint.image.and_then(|key| self.textures.get(&key)))
+                    }
+                };
+                font.draw_glyphs_transformed(
+                    device,
+                    target,
+                    fill,
+                    &line[run.range.clone()],
+                    &glyph_transforms,
+                    params.with_font_params(run.params(font_params)),
+                )?;
+            }
+            return Ok(());
+        }
+        for placed in self.place_runs(resource_key, text, params.font_params)? {
+            let font = self.fonts.get_mut(&placed.run.font_key).unwrap();
+            let run_text = &text[placed.range];
+            let run_params = placed.run.params(params.font_params);
+            let run_pos = params.pos + placed.pos.to_vector();
+            match fill {
+                TextFill::Color(color) => font.draw(
+                    device,
+                    target,
+                    color,
+                    run_text,
+                    run_pos,
+                    params.clipping_rect,
+                    run_params,
+                    params.transform,
+                )?,
+                TextFill::Paint(paint) => font.draw_paint(
+                    device,
+                    target,
+                    paint,
+                    paint.image.and_then(|key| self.textures.get(&key)),
+                    run_text,
+                    params.with_pos(run_pos).with_font_params(run_params),
+                )?,
+            }
+        }
+        Ok(())
+    }
+
+    // Returns the boxes of the spans, the distance from the top of the line
+    // to the shared baseline and the height of the line.
+    // `direction` must be already resolved (not `Auto`).
+    fn layout_spans(
+        &mut self,
+        spans: &[TextSpan],
+        direction: TextDirection,
+    ) -> Result<(Vec<SpanBox>, f32, f32)> {
+        let mut boxes = Vec::with_capacity(spans.len());
+        let mut baseline = 0.0f32;
+        let mut below_baseline = 0.0f32;
+        for span in spans {
+            let params = FontParams::new(span.size.get()).with_direction(direction);
+            let metrics = self.get_font_metrics(&span.resource_key, params)?;
+            let width = self
+                .get_text_dimensions(&span.resource_key, params, &span.text)?
+                .0 as f32;
+            baseline = baseline.max(metrics.baseline);
+            below_baseline = below_baseline.max(metrics.line_height - metrics.baseline);
+            boxes.push(SpanBox {
+                x: 0.0,
+                width,
+                metrics,
+            });
+        }
+
+        let mut x = 0.0;
+        if direction == TextDirection::RightToLeft {
+            for span_box in boxes.iter_mut().rev() {
+                span_box.x = x;
+                x += span_box.width;
+            }
+        } else {
+            for span_box in boxes.iter_mut() {
+                span_box.x = x;
+                x += span_box.width;
+            }
+        }
+        Ok((boxes, baseline, baseline + below_baseline))
+    }
+
+    // Returns the length of the line and its height (the width of the column
+    // for vertical text).
+    fn measure_line(
+        &mut self,
+        resource_key: &str,
+        params: FontParams,
+        line: &str,
+    ) -> Result<(u16, u16)> {
+        let runs = self.split_runs(resource_key, line, params);
+        let mut length = 0;
+        let mut line_height = 0;
+        for run in &runs {
+            let font = self.fonts.get_mut(&run.font_key).unwrap();
+            let (run_length, run_height) = along_line(
+                params,
+                font.get_dimensions(run.params(params), &line[run.range.clone()])?,
+            );
+            length += run_length;
+            line_height = line_height.max(run_height);
+        }
+        let line_height = self.get_line_height(&runs, resource_key, params, line_height)?;
+        Ok((length, line_height))
+    }
+
+    // Returns the runs of the line (in logical order) with the transforms of their glyphs
+    // placed along the path (see `Font::draw_glyphs_transformed()`).
+    fn place_runs_on_path(
+        &mut self,
+        resource_key: &str,
+        line: &str,
+        path: &TextPath,
+        params: FontParams,
+    ) -> Result<Vec<(FontRun, Vec<Option<PixelTransform>>)>> {
+        let path_params = &path.params;
+        let measure = match path_params.side {
+            TextPathSide::Left => PathMeasure::new(&path.path),
+            TextPathSide::Right => PathMeasure::new(&path.path).reversed(),
+        };
+        let runs = self.split_runs(resource_key, line, params);
+        let levels: Vec<u8> = runs.iter().map(|run| run.level).collect();
+
+        // horizontal positions of the runs placed in visual order
+        let mut offsets = vec![0.0; runs.len()];
+        let mut width = 0.0;
+        for i in visual_order(&levels) {
+            let run = &runs[i];
+            let font = self.fonts.get_mut(&run.font_key).unwrap();
+            let run_width = font
+                .get_dimensions(run.params(params), &line[run.range.clone()])?
+                .0 as f32;
+            offsets[i] = width;
+            width += run_width;
+        }
+        let start = match path_params.align {
+            TextPathAlign::Start => path_params.start_offset,
+            TextPathAlign::Center => path_params.start_offset - width * 0.5,
+            TextPathAlign::End => path_params.start_offset - width,
+        };
+
+        let mut res = Vec::with_capacity(runs.len());
+        for (run, x) in runs.into_iter().zip(offsets) {
+            let font = self.fonts.get_mut(&run.font_key).unwrap();
+            let run_text = &line[run.range.clone()];
+            let run_params = run.params(params);
+            let bounds = font.get_glyph_bounds(run_params, run_text)?;
+            let baseline = font.get_metrics(run_params)?.baseline;
+            let glyph_transforms = measure.glyph_transforms(&bounds, start + x, baseline);
+            res.push((run, glyph_transforms));
+        }
+        Ok(res)
+    }
+
+    // Returns the runs of the lines of the text (in logical order) with their positions.
+    // Runs of vertical text are placed from top to bottom regardless of their direction.
+    fn place_runs(
+        &mut self,
+        resource_key: &str,
+        text: &str,
+        params: FontParams,
+    ) -> Result<Vec<PlacedRun>> {
+        // runs with their offsets along the line, their heights and line indices
+        let mut runs = Vec::new();
+        // offset of every line across the lines and its height
+        let mut lines = Vec::new();
+        let mut offset = 0.0;
+        let mut line_start = 0;
+        for (index, line) in text.split('\n').enumerate() {
+            let line_runs = self.split_runs(resource_key, line, params);
+            let order = if params.writing_mode.is_vertical() {
+                (0..line_runs.len()).collect()
+            } else {
+                let levels: Vec<u8> = line_runs.iter().map(|run| run.level).collect();
+                visual_order(&levels)
+            };
+            let mut run_offsets = vec![(0.0, 0); line_runs.len()];
+            let mut position = 0.0;
+            let mut line_height = 0;
+            for i in order {
+                let run = &line_runs[i];
+                let font = self.fonts.get_mut(&run.font_key).unwrap();
+                let (run_length, run_height) = along_line(
+                    params,
+                    font.get_dimensions(run.params(params), &line[run.range.clone()])?,
+                );
+                run_offsets[i] = (position, run_height);
+                position += run_length as f32;
+                line_height = line_height.max(run_height);
+            }
+            let line_height =
+                self.get_line_height(&line_runs, resource_key, params, line_height)?;
+            lines.push((offset, line_height as f32));
+            offset += line_height as f32;
+            for (run, (position, run_height)) in line_runs.into_iter().zip(run_offsets) {
+                let range = line_start + run.range.start..line_start + run.range.end;
+                runs.push((run, range, position, run_height as f32, index));
+            }
+            line_start += line.len() + 1;
+        }
+
+        let total = offset;
+        Ok(runs
+            .into_iter()
+            .map(|(run, range, position, run_height, index)| {
+                let (line_offset, line_height) = lines[index];
+                let pos = match params.writing_mode {
+                    WritingMode::HorizontalTb => Point::new(position, line_offset),
+                    WritingMode::VerticalRl => Point::new(
+                        total - line_offset - (line_height + run_height) * 0.5,
+                        position,
+                    ),
+                    WritingMode::VerticalLr => {
+                        Point::new(line_offset + (line_height - run_height) * 0.5, position)
+                    }
+                };
+                PlacedRun { run, range, pos }
+            })
+            .collect())
+    }
+
+    // empty lines have the height of the first font of the chain
+    fn get_line_height(
+        &mut self,
+        runs: &[FontRun],
+        resource_key: &str,
+        params: FontParams,
+        line_height: u16,
+    ) -> Result<u16> {
+        if !runs.is_empty() {
+            return Ok(line_height);
+        }
+        let (first_key, face) = match self.font_chain(resource_key, params.face).first() {
+            Some((key, face)) => (key.to_string(), *face),
+            None => return Ok(0),
+        };
+        let font = self.fonts.get_mut(&first_key).unwrap();
+        Ok(along_line(
+            params,
+            font.get_dimensions(FontParams { face, ..params }, "")?,
+        )
+        .1)
+    }
+
+    // Returns the keys and faces of fonts the resource key resolves to.
+    // Fonts of font keys and fallbacks are drawn with the face of the parameters.
+    fn font_chain(&self, resource_key: &str, face: FontFace) -> Vec<(&str, FontFace)> {
+        let loaded = |key: &str| self.fonts.get_key_value(key).map(|(key, _)| key.as_str());
+        if let Some(font_keys) = self.font_fallbacks.get(resource_key) {
+            font_keys
+                .iter()
+                .filter_map(|key| loaded(key).map(|key| (key, face)))
+                .collect()
+        } else if let Some(key) = loaded(resource_key) {
+            vec![(key, face)]
+        } else if let Some(fonts) = self.font_queries.get(resource_key) {
+            fonts
+                .iter()
+                .filter_map(|(key, face)| loaded(key).map(|key| (key, *face)))
+                .collect()
+        } else {
+            Vec::new()
+        }
+    }
+
+    // Resolves the resource key with the font registry
+    // if it's not the key of a font or a fallback.
+    fn load_font_query(&mut self, resource_key: &str) -> Result<()> {
+        if self.fonts.contains_key(resource_key)
+            || self.font_fallbacks.contains_key(resource_key)
+            || self.font_queries.contains_key(resource_key)
+        {
+            return Ok(());
+        }
+
+        let query = FontQuery::parse(resource_key);
+        let found: Vec<_> = self
+            .font_registry
+            .find_all(&query)
+            .into_iter()
+            .map(|m| {
+                (
+                    m.descriptor.resource_key.clone(),
+                    m.descriptor.path.clone(),
+                    m.face,
+                )
+            })
+            .collect();
+        let mut fonts = Vec::with_capacity(found.len());
+        for (font_key, path, face) in found {
+            self.load_registered_font(&font_key, path.as_deref())?;
+            fonts.push((font_key, face));
+        }
+        self.font_queries.insert(resource_key.to_string(), fonts);
+        Ok(())
+    }
+
+    // Loads the font file found by scanning the font directories.
+    fn load_registered_font(&mut self, font_key: &str, path: Option<&Path>) -> Result<()> {
+        if let (false, Some(path)) = (self.fonts.contains_key(font_key), path) {
+            let font = F::create(std::fs::read(path)?)?;
+            self.fonts.insert(font_key.to_string(), font);
+        }
+        Ok(())
+    }
+
+    // Splits the line into runs of characters of the same embedding level
+    // rendered by the same font face (in logical order).
+    // Characters not available in any font stay in the current run.
+    fn split_runs(&mut self, resource_key: &str, line: &str, params: FontParams) -> Vec<FontRun> {
+        let chain: Vec<(String, FontFace)> = self
+            .font_chain(resource_key, params.face)
+            .into_iter()
+            .map(|(key, face)| (key.to_string(), face))
+            .collect();
+        if chain.is_empty() {
+            return Vec::new();
+        }
+
+        let mut runs: Vec<FontRun> = Vec::new();
+        for bidi_run in BidiParagraph::new(line, params.direction).line_runs(0..line.len()) {
+            let first = runs.len();
+            for (pos, ch) in line[bidi_run.range.clone()].char_indices() {
+                let pos = bidi_run.range.start + pos;
+                let end = pos + ch.len_utf8();
+                let fonts = &mut self.fonts;
+                let font = chain.iter().find(|(key, face)| {
+                    fonts
+                        .get_mut(key)
+                        .is_some_and(|font| font.has_char(*face, ch))
+                });
+                match (runs[first..].last_mut(), font) {
+                    (Some(run), Some((key, face))) if run.font_key == *key && run.face == *face => {
+                        run.range.end = end
+                    }
+                    (Some(run), None) => run.range.end = end,
+                    (_, font) => {
+                        let (key, face) = font.unwrap_or(&chain[0]);
+                        runs.push(FontRun {
+                            font_key: key.clone(),
+                            face: *face,
+                            range: pos..end,
+                            level: bidi_run.level,
+                        })
+                    }
+                }
+            }
+        }
+        runs
+    }
+}
+
+// Returns the length and the height of the text measured by the font
+// (the width of the columns of vertical text).
+fn along_line(params: FontParams, (width, height): (u16, u16)) -> (u16, u16) {
+    if params.writing_mode.is_vertical() {
+        (height, width)
+    } else {
+        (width, height)
+    }
+}
+
+// Resolves `TextDirection::Auto` from the text of all the spans.
+fn spans_direction(spans: &[TextSpan], direction: TextDirection) -> TextDirection {
+    if direction != TextDirection::Auto {
+        return direction;
+    }
+    let text: String = spans.iter().map(|span| span.text.as_str()).collect();
+    if BidiParagraph::new(&text, direction).is_rtl() {
+        TextDirection::RightToLeft
+    } else {
+        TextDirection::LeftToRight
+    }
 }
@@ -0,0 +1,765 @@
+//! Converts text into positioned glyphs.
+//!
+//! With the `shaping` feature the text is shaped with rustybuzz, which applies
+//! kerning, ligatures and the rules of complex scripts (like Arabic or Devanagari).
+//! Without it every character is mapped to a single glyph using the character map
+//! of the font.
+//!
+//! Lines are split into runs of the same direction (see the `bidi` module)
+//! and the glyphs are returned in visual order. Lines of vertical text are split
+//! into runs of upright and rotated characters (see the `vertical` module).
+
+use crate::bidi::*;
+use crate::font::FontFace;
+use crate::units::*;
+use crate::vertical::{vertical_orientation, VerticalOrientation};
+#[cfg(not(feature = "shaping"))]
+use std::marker::PhantomData;
+use std::ops::Range;
+use unicode_bidi::{bidi_class, BidiClass};
+
+/// Glyph positioned by the shaper.
+///
+/// Characters missing in the font are represented by glyph zero with no advance,
+/// so every character belongs to some glyph cluster.
+#[derive(Debug, Copy, Clone)]
+pub struct ShapedGlyph {
+    /// Glyph index in the font. Zero means the character is missing in the font.
+    pub glyph_id: u32,
+    /// Byte offset in the text of the first character that produced this glyph.
+    pub cluster: usize,
+    /// How much the pen moves after drawing this glyph (in pixels, down the column
+    /// in vertical text).
+    pub x_advance: f32,
+    /// Offset of the glyph from the pen position (in pixels, y goes down).
+    pub x_offset: f32,
+    pub y_offset: f32,
+    /// True for glyphs drawn upright in vertical text, their offsets are the position
+    /// of the horizontal origin of the glyph relative to the pen on the center line
+    /// of the column. Other glyphs of vertical text are rotated 90° clockwise.
+    pub upright: bool,
+}
+
+/// Metrics of the glyph drawn upright in vertical text (in pixels, y goes down).
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub struct VerticalGlyphMetrics {
+    /// How much the pen moves down after drawing the glyph.
+    pub advance: f32,
+    /// Offset of the horizontal origin of the glyph from the pen on the center line
+    /// of the column.
+    pub x_offset: f32,
+    pub y_offset: f32,
+}
+
+/// Position and size of a glyph of the shaped text (in pixels, y goes down).
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct GlyphBounds {
+    /// Glyph index in the font. Zero means the character is missing in the font.
+    pub glyph_id: u32,
+    /// Byte offset in the text of the first character that produced this glyph.
+    pub cluster: usize,
+    /// True if the glyph belongs to a right-to-left run.
+    pub rtl: bool,
+    /// Pen position and advance of the glyph spanning the whole line height.
+    pub advance_rect: Rect,
+    /// Bounding box of the glyph image (empty for glyphs without image like spaces).
+    pub ink_rect: Rect,
+}
+
+/// Font information used to shape text without the shaping engine.
+pub trait GlyphProvider {
+    fn glyph_index(&self, ch: char) -> Option<u32>;
+
+    /// Returns the advance of the glyph in pixels.
+    fn glyph_advance(&self, glyph_id: u32) -> f32;
+
+    /// Returns the metrics of the glyph drawn upright in vertical text.
+    fn glyph_vertical_metrics(&self, glyph_id: u32) -> VerticalGlyphMetrics;
+}
+
+/// Face of the font file parsed for the shaping engine.
+///
+/// Parsing the tables of the font takes a while, so the face should be created
+/// once and used for all lines of the text. Fonts that can't be parsed (or all fonts
+/// without the `shaping` feature) are shaped using the glyph provider only.
+pub struct ShapingFace<'a> {
+    #[cfg(feature = "shaping")]
+    face: Option<rustybuzz::Face<'a>>,
+    // the synthesized bold widens the advances
+    #[cfg(feature = "shaping")]
+    bold: bool,
+    #[cfg(not(feature = "shaping"))]
+    face: PhantomData<&'a [u8]>,
+}
+
+impl<'a> ShapingFace<'a> {
+    /// # Arguments
+    ///
+    /// * `font_data` - content of the font file
+    /// * `font_face` - face of the font collection and its variation
+    #[cfg(feature = "shaping")]
+    pub fn new(font_data: &'a [u8], font_face: &FontFace) -> Self {
+        let face = rustybuzz::Face::from_slice(font_data, font_face.index).map(|mut face| {
+            if !font_face.variations.is_empty() {
+                let variations: Vec<rustybuzz::Variation> = font_face
+                    .variations
+                    .iter()
+                    .map(|(tag, value)| rustybuzz::Variation {
+                        tag: rustybuzz::ttf_parser::Tag::from_bytes(&tag),
+                        value,
+                    })
+                    .collect();
+                face.set_variations(&variations);
+            }
+            face
+        });
+        ShapingFace {
+            face,
+            bold: font_face.synthesis.bold,
+        }
+    }
+
+    #[cfg(not(feature = "shaping"))]
+    pub fn new(_font_data: &'a [u8], _font_face: &FontFace) -> Self {
+        ShapingFace { face: PhantomData }
+    }
+
+    // width the synthesized bold adds to the advances of the glyphs of the font size
+    // (the same strength as `ft_face::synthesize` uses)
+    #[cfg(feature = "shaping")]
+    fn bold_strength(&self, font_size: f32) -> f32 {
+        if self.bold {
+            font_size / 24.0
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Shapes a single line of text.
+///
+/// Glyphs are returned in visual order (left to right).
+/// Tab characters advance the pen by four spaces.
+///
+/// # Arguments
+///
+/// * `face` - font file parsed for the shaping engine
+/// * `font_size` - font size in pixels
+/// * `provider` - glyph indices and advances for unshaped text
+/// * `text` - line of text (without new line characters)
+/// * `direction` - base direction of the line
+pub fn shape_line<P: GlyphProvider>(
+    face: &ShapingFace,
+    font_size: f32,
+    provider: &P,
+    text: &str,
+    direction: TextDirection,
+) -> Vec<ShapedGlyph> {
+    let mut res = Vec::with_capacity(text.len());
+    let runs = BidiParagraph::new(text, direction).line_runs(0..text.len());
+    let levels: Vec<u8> = runs.iter().map(|run| run.level).collect();
+    for i in visual_order(&levels) {
+        let run = &runs[i];
+        shape_direction_run(
+            face,
+            font_size,
+            provider,
+            text,
+            run.range.clone(),
+            run.is_rtl(),
+            &mut res,
+        );
+    }
+    res
+}
+
+/// Shapes a single line of vertical text (a column).
+///
+/// Glyphs are returned from top to bottom. Upright characters (see `vertical_orientation()`)
+/// are shaped with the vertical metrics and the vertical alternates of the font,
+/// runs of the other characters are shaped like horizontal text with `shape_line()`
+/// and rotated.
+///
+/// # Arguments
+///
+/// * `face` - font file parsed for the shaping engine
+/// * `font_size` - font size in pixels
+/// * `provider` - glyph indices and metrics for unshaped text
+/// * `text` - line of text (without new line characters)
+/// * `direction` - base direction of the rotated runs
+pub fn shape_vertical_line<P: GlyphProvider>(
+    face: &ShapingFace,
+    font_size: f32,
+    provider: &P,
+    text: &str,
+    direction: TextDirection,
+) -> Vec<ShapedGlyph> {
+    let mut res = Vec::with_capacity(text.len());
+    for (range, upright) in orientation_runs(face, provider, text) {
+        if upright {
+            shape_upright_run(face, font_size, provider, text, range, &mut res);
+        } else {
+            let first = res.len();
+            res.extend(shape_line(
+                face,
+                font_size,
+                provider,
+                &text[range.clone()],
+                direction,
+            ));
+            for glyph in &mut res[first..] {
+                glyph.cluster += range.start;
+            }
+        }
+    }
+    res
+}
+
+/// Returns the advance of every character of the line.
+///
+/// When a glyph is made of several characters (a ligature)
+/// its advance is divided equally between them.
+pub fn char_advances(text: &str, glyphs: &[ShapedGlyph]) -> Vec<f32> {
+    let offsets: Vec<usize> = text.char_indices().map(|(pos, _)| pos).collect();
+    let mut advances = vec![0.0f32; offsets.len()];
+
+    let mut clusters: Vec<(usize, f32)> = glyphs
+        .iter()
+        .map(|glyph| (glyph.cluster, glyph.x_advance))
+        .collect();
+    clusters.sort_by_key(|cluster| cluster.0);
+    let mut merged: Vec<(usize, f32)> = Vec::with_capacity(clusters.len());
+    for (cluster, advance) in clusters {
+        match merged.last_mut() {
+            Some(last) if last.0 == cluster => last.1 += advance,
+            _ => merged.push((cluster, advance)),
+        }
+    }
+
+    for (i, &(cluster, advance)) in merged.iter().enumerate() {
+        let cluster_end = merged.get(i + 1).map_or(text.len(), |next| next.0);
+        let first = offsets.partition_point(|&pos| pos < cluster);
+        let last = offsets.partition_point(|&pos| pos < cluster_end);
+        if last > first {
+            let char_advance = advance / (last - first) as f32;
+            for advance in &mut advances[first..last] {
+                *advance = char_advance;
+            }
+        }
+    }
+
+    advances
+}
+
+/// Returns sorted glyph ids without duplicates.
+pub fn unique_glyphs(glyph_ids: impl Iterator<Item = u32>) -> Vec<u32> {
+    let mut glyph_ids: Vec<u32> = glyph_ids.collect();
+    glyph_ids.sort_unstable();
+    glyph_ids.dedup();
+    glyph_ids
+}
+
+/// Returns the width of the widest line.
+pub fn text_width(lines: &[Vec<ShapedGlyph>]) -> f32 {
+    lines
+        .iter()
+        .map(|line| line.iter().map(|glyph| glyph.x_advance).sum::<f32>())
+        .fold(0.0f32, f32::max)
+}
+
+/// Returns the width of every character of the text shaped line by line
+/// (new line characters have zero width).
+///
+/// Widths are differences between rounded positions of the characters,
+/// so they sum up to the rounded width of the line.
+pub fn char_widths(text: &str, lines: &[Vec<ShapedGlyph>]) -> Vec<i16> {
+    let mut widths = Vec::with_capacity(text.len());
+    for (i, (line, glyphs)) in text.split('\n').zip(lines).enumerate() {
+        if i > 0 {
+            // new line character
+            widths.push(0);
+        }
+        let mut pos = 0.0f32;
+        for advance in char_advances(line, glyphs) {
+            let next_pos = pos + advance;
+            widths.push((next_pos.round() - pos.round()) as i16);
+            pos = next_pos;
+        }
+    }
+    widths
+}
+
+/// Returns the bounds of the glyphs of the text shaped line by line.
+///
+/// # Arguments
+///
+/// * `text` - text with new line characters
+/// * `lines` - shaped lines of the text
+/// * `direction` - base direction the lines were shaped with
+/// * `line_height` - distance between the tops of two consecutive lines
+/// * `ink_rect` - bounding box of the glyph image relative to the pen at the top of the line
+pub fn glyph_bounds(
+    text: &str,
+    lines: &[Vec<ShapedGlyph>],
+    direction: TextDirection,
+    line_height: f32,
+    ink_rect: impl Fn(u32) -> Option<Rect>,
+) -> Vec<GlyphBounds> {
+    let mut res = Vec::with_capacity(text.len());
+    let mut line_start = 0;
+    let mut y = 0.0f32;
+    for (line, glyphs) in text.split('\n').zip(lines) {
+        let offsets: Vec<usize> = line.char_indices().map(|(pos, _)| pos).collect();
+        let levels = BidiParagraph::new(line, direction).line_levels(0..line.len());
+        let mut x = 0.0f32;
+        for glyph in glyphs {
+            let char_index = offsets.partition_point(|&pos| pos < glyph.cluster);
+            let ink_rect = match ink_rect(glyph.glyph_id) {
+                Some(rect) if glyph.glyph_id != 0 => {
+                    rect.translate(euclid::vec2(x + glyph.x_offset, y + glyph.y_offset))
+                }
+                _ => Rect::new(Point::new(x, y), Size::zero()),
+            };
+            res.push(GlyphBounds {
+                glyph_id: glyph.glyph_id,
+                cluster: line_start + glyph.cluster,
+                rtl: levels.get(char_index).is_some_and(|level| level % 2 == 1),
+                advance_rect: Rect::new(Point::new(x, y), Size::new(glyph.x_advance, line_height)),
+                ink_rect,
+            });
+            x += glyph.x_advance;
+        }
+        line_start += line.len() + 1;
+        y += line_height;
+    }
+    res
+}
+
+// Shapes characters of the same direction.
+fn shape_direction_run<P: GlyphProvider>(
+    face: &ShapingFace,
+    font_size: f32,
+    provider: &P,
+    text: &str,
+    range: Range<usize>,
+    rtl: bool,
+    res: &mut Vec<ShapedGlyph>,
+) {
+    let mut segments = Vec::new();
+    let mut start = range.start;
+    for (tab_pos, _) in text[range.clone()].match_indices('\t') {
+        let tab_pos = range.start + tab_pos;
+        segments.push(start..tab_pos);
+        segments.push(tab_pos..tab_pos + 1);
+        start = tab_pos + 1;
+    }
+    segments.push(start..range.end);
+    if rtl {
+        segments.reverse();
+    }
+
+    for segment in segments {
+        if &text[segment.clone()] == "\t" {
+            let space = provider.glyph_index(' ').unwrap_or(0);
+            res.push(ShapedGlyph {
+                glyph_id: 0,
+                cluster: segment.start,
+                x_advance: if space != 0 {
+                    provider.glyph_advance(space) * 4.0
+                } else {
+                    0.0
+                },
+                x_offset: 0.0,
+                y_offset: 0.0,
+                upright: false,
+            });
+        } else {
+            shape_run(face, font_size, provider, text, segment, rtl, res);
+        }
+    }
+}
+
+#[cfg(feature = "shaping")]
+fn shape_run<P: GlyphProvider>(
+    face: &ShapingFace,
+    font_size: f32,
+    provider: &P,
+    text: &str,
+    range: Range<usize>,
+    rtl: bool,
+    res: &mut Vec<ShapedGlyph>,
+) {
+    if range.is_empty() {
+        return;
+    }
+
+    let bold = face.bold_strength(font_size);
+    let face = match face.face {
+        Some(ref face) => face,
+        None => return shape_run_simple(provider, text, range, rtl, res),
+    };
+    let scale = font_size / face.units_per_em() as f32;
+    // glyphs without advances (like combining marks) are not widened
+    let advance = |advance: i32| match advance {
+        0 => 0.0,
+        advance => advance as f32 * scale + bold,
+    };
+
+    let mut buffer = rustybuzz::UnicodeBuffer::new();
+    buffer.push_str(&text[range.clone()]);
+    buffer.guess_segment_properties();
+    buffer.set_direction(if rtl {
+        rustybuzz::Direction::RightToLeft
+    } else {
+        rustybuzz::Direction::LeftToRight
+    });
+    let output = rustybuzz::shape(face, &[], buffer);
+
+    for (info, pos) in output.glyph_infos().iter().zip(output.glyph_positions()) {
+        res.push(ShapedGlyph {
+            glyph_id: info.glyph_id,
+            cluster: range.start + info.cluster as usize,
+            x_advance: if info.glyph_id != 0 {
+                advance(pos.x_advance)
+            } else {
+                0.0
+            },
+            x_offset: pos.x_offset as f32 * scale,
+            y_offset: -pos.y_offset as f32 * scale,
+            upright: false,
+        });
+    }
+}
+
+#[cfg(not(feature = "shaping"))]
+fn shape_run<P: GlyphProvider>(
+    _face: &ShapingFace,
+    _font_size: f32,
+    provider: &P,
+    text: &str,
+    range: Range<usize>,
+    rtl: bool,
+    res: &mut Vec<ShapedGlyph>,
+) {
+    shape_run_simple(provider, text, range, rtl, res)
+}
+
+fn shape_run_simple<P: GlyphProvider>(
+    provider: &P,
+    text: &str,
+    range: Range<usize>,
+    rtl: bool,
+    res: &mut Vec<ShapedGlyph>,
+) {
+    let first = res.len();
+    for (pos, ch) in text[range.clone()].char_indices() {
+        let glyph_id = provider.glyph_index(ch).unwrap_or(0);
+        res.push(ShapedGlyph {
+            glyph_id,
+            cluster: range.start + pos,
+            x_advance: if glyph_id != 0 {
+                provider.glyph_advance(glyph_id)
+            } else {
+                0.0
+            },
+            x_offset: 0.0,
+            y_offset: 0.0,
+            upright: false,
+        });
+    }
+    if rtl {
+        res[first..].reverse();
+    }
+}
+
+// Splits the line of vertical text into runs of upright and rotated characters.
+fn orientation_runs<P: GlyphProvider>(
+    face: &ShapingFace,
+    provider: &P,
+    text: &str,
+) -> Vec<(Range<usize>, bool)> {
+    let has_vertical_alternate = vertical_alternates(face);
+    let mut runs: Vec<(Range<usize>, bool)> = Vec::new();
+    for (pos, ch) in text.char_indices() {
+        let upright = match runs.last() {
+            // marks keep the orientation of their base characters
+            Some(&(_, upright)) if bidi_class(ch) == BidiClass::NSM => upright,
+            _ => match vertical_orientation(ch) {
+                VerticalOrientation::Upright | VerticalOrientation::TransformedUpright => true,
+                VerticalOrientation::Rotated => false,
+                VerticalOrientation::TransformedRotated => matches!(
+                    provider.glyph_index(ch),
+                    Some(glyph_id) if has_vertical_alternate(glyph_id)
+                ),
+            },
+        };
+        let end = pos + ch.len_utf8();
+        match runs.last_mut() {
+            Some(run) if run.1 == upright => run.0.end = end,
+            _ => runs.push((pos..end, upright)),
+        }
+    }
+    runs
+}
+
+// Returns the function telling if the vertical shaping replaces the glyph
+// (like brackets turned for vertical text).
+//
+// Glyphs replaced by the single substitutions of the 'vert' and 'vrt2' features
+// are looked up in the tables of the font once for the whole line.
+#[cfg(feature = "shaping")]
+fn vertical_alternates<'a>(face: &ShapingFace<'a>) -> impl Fn(u32) -> bool + 'a {
+    use rustybuzz::ttf_parser::gsub::{SingleSubstitution, SubstitutionSubtable};
+    use rustybuzz::ttf_parser::{GlyphId, Tag};
+
+    let mut coverages = Vec::new();
+    if let Some(gsub) = face.face.as_ref().and_then(|face| face.tables().gsub) {
+        let tags = [Tag::from_bytes(b"vert"), Tag::from_bytes(b"vrt2")];
+        // features are listed for every script and language
+        let mut lookup_indices: Vec<u16> = gsub
+            .features
+            .into_iter()
+            .filter(|feature| tags.contains(&feature.tag))
+            .flat_map(|feature| feature.lookup_indices)
+            .collect();
+        lookup_indices.sort_unstable();
+        lookup_indices.dedup();
+
+        for lookup in lookup_indices
+            .into_iter()
+            .filter_map(|index| gsub.lookups.get(index))
+        {
+            for subtable in lookup.subtables.into_iter::<SubstitutionSubtable>() {
+                match subtable {
+                    SubstitutionSubtable::Single(SingleSubstitution::Format1 {
+                        coverage, ..
+                    })
+                    | SubstitutionSubtable::Single(SingleSubstitution::Format2 {
+                        coverage, ..
+                    }) => coverages.push(coverage),
+                    _ => (),
+                }
+            }
+        }
+    }
+
+    move |glyph_id| {
+        coverages
+            .iter()
+            .any(|coverage| coverage.contains(GlyphId(glyph_id as u16)))
+    }
+}
+
+// Without the shaping engine there are no vertical alternates.
+#[cfg(not(feature = "shaping"))]
+fn vertical_alternates<'a>(_face: &ShapingFace<'a>) -> impl Fn(u32) -> bool + 'a {
+    |_| false
+}
+
+#[cfg(feature = "shaping")]
+fn shape_upright_run<P: GlyphProvider>(
+    face: &ShapingFace,
+    font_size: f32,
+    provider: &P,
+    text: &str,
+    range: Range<usize>,
+    res: &mut Vec<ShapedGlyph>,
+) {
+    let bold = face.bold_strength(font_size);
+    let face = match face.face {
+        Some(ref face) => face,
+        None => return shape_upright_run_simple(provider, text, range, res),
+    };
+    let scale = font_size / face.units_per_em() as f32;
+    let advance = |advance: i32| match advance {
+        0 => 0.0,
+        advance => advance as f32 * scale + bold,
+    };
+
+    let mut buffer = rustybuzz::UnicodeBuffer::new();
+    buffer.push_str(&text[range.clone()]);
+    buffer.guess_segment_properties();
+    buffer.set_direction(rustybuzz::Direction::TopToBottom);
+    let output = rustybuzz::shape(face, &[], buffer);
+
+    // advances go up and offsets are relative to the vertical origin of the glyph
+    for (info, pos) in output.glyph_infos().iter().zip(output.glyph_positions()) {
+        res.push(ShapedGlyph {
+            glyph_id: info.glyph_id,
+            cluster: range.start + info.cluster as usize,
+            x_advance: if info.glyph_id != 0 {
+                advance(-pos.y_advance)
+            } else {
+                0.0
+            },
+            x_offset: pos.x_offset as f32 * scale,
+            y_offset: -pos.y_offset as f32 * scale,
+            upright: true,
+        });
+    }
+}
+
+#[cfg(not(feature = "shaping"))]
+fn shape_upright_run<P: GlyphProvider>(
+    _face: &ShapingFace,
+    _font_size: f32,
+    provider: &P,
+    text: &str,
+    range: Range<usize>,
+    res: &mut Vec<ShapedGlyph>,
+) {
+    shape_upright_run_simple(provider, text, range, res)
+}
+
+fn shape_upright_run_simple<P: GlyphProvider>(
+    provider: &P,
+    text: &str,
+    range: Range<usize>,
+    res: &mut Vec<ShapedGlyph>,
+) {
+    for (pos, ch) in text[range.clone()].char_indices() {
+        let glyph_id = provider.glyph_index(ch).unwrap_or(0);
+        let metrics = if glyph_id != 0 {
+            provider.glyph_vertical_metrics(glyph_id)
+        } else {
+            VerticalGlyphMetrics::default()
+        };
+        res.push(ShapedGlyph {
+            glyph_id,
+            cluster: range.start + pos,
+            x_advance: metrics.advance,
+            x_offset: metrics.x_offset,
+            y_offset: metrics.y_offset,
+            upright: true,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Every character except 'x' is its own glyph 10 pixels wide.
+    struct Provider;
+
+    impl GlyphProvider for Provider {
+        fn glyph_index(&self, ch: char) -> Option<u32> {
+            if ch == 'x' {
+                None
+            } else {
+                Some(ch as u32)
+            }
+        }
+
+        fn glyph_advance(&self, _glyph_id: u32) -> f32 {
+            10.0
+        }
+
+        fn glyph_vertical_metrics(&self, _glyph_id: u32) -> VerticalGlyphMetrics {
+            VerticalGlyphMetrics {
+                advance: 12.0,
+                x_offset: -5.0,
+                y_offset: 1.0,
+            }
+        }
+    }
+
+    fn glyph(ch: char, cluster: usize, x_advance: f32) -> ShapedGlyph {
+        ShapedGlyph {
+            glyph_id: ch as u32,
+            cluster,
+            x_advance,
+            x_offset: 0.0,
+            y_offset: 0.0,
+            upright: false,
+        }
+    }
+
+    fn shape(text: &str, direction: TextDirection) -> Vec<ShapedGlyph> {
+        // the empty font is shaped with the provider only
+        shape_line(
+            &ShapingFace::new(&[], &FontFace::default()),
+            10.0,
+            &Provider,
+            text,
+            direction,
+        )
+    }
+
+    fn glyph_ids(glyphs: &[ShapedGlyph]) -> Vec<u32> {
+        glyphs.iter().map(|glyph| glyph.glyph_id).collect()
+    }
+
+    fn clusters(glyphs: &[ShapedGlyph]) -> Vec<usize> {
+        glyphs.iter().map(|glyph| glyph.cluster).collect()
+    }
+
+    #[test]
+    fn missing_characters_have_no_advance() {
+        let glyphs = shape("axb", TextDirection::Auto);
+        assert_eq!(glyph_ids(&glyphs), vec!['a' as u32, 0, 'b' as u32]);
+        assert_eq!(clusters(&glyphs), vec![0, 1, 2]);
+        assert_eq!(text_width(&[glyphs]), 20.0);
+    }
+
+    #[test]
+    fn tabs_advance_by_four_spaces() {
+        let glyphs = shape("a\tb", TextDirection::Auto);
+        assert_eq!(glyph_ids(&glyphs), vec!['a' as u32, 0, 'b' as u32]);
+        assert_eq!(glyphs[1].x_advance, 40.0);
+        assert_eq!(text_width(&[glyphs]), 60.0);
+    }
+
+    #[test]
+    fn rtl_runs_are_in_visual_order() {
+        let glyphs = shape("ab אב", TextDirection::Auto);
+        assert_eq!(
+            glyph_ids(&glyphs),
+            vec!['a' as u32, 'b' as u32, ' ' as u32, 'ב' as u32, 'א' as u32]
+        );
+        assert_eq!(clusters(&glyphs), vec![0, 1, 2, 5, 3]);
+
+        let glyphs = shape("ab אב", TextDirection::RightToLeft);
+        assert_eq!(
+            glyph_ids(&glyphs),
+            vec!['ב' as u32, 'א' as u32, ' ' as u32, 'a' as u32, 'b' as u32]
+        );
+    }
+
+    #[test]
+    fn upright_characters_of_vertical_text_use_vertical_metrics() {
+        let face = ShapingFace::new(&[], &FontFace::default());
+        let glyphs = shape_vertical_line(&face, 10.0, &Provider, "a中b", TextDirection::Auto);
+        assert_eq!(clusters(&glyphs), vec![0, 1, 4]);
+        let upright: Vec<bool> = glyphs.iter().map(|glyph| glyph.upright).collect();
+        assert_eq!(upright, vec![false, true, false]);
+        assert_eq!(glyphs[1].x_advance, 12.0);
+        assert_eq!((glyphs[1].x_offset, glyphs[1].y_offset), (-5.0, 1.0));
+    }
+
+    #[test]
+    fn ligature_advance_is_divided_between_characters() {
+        // "fi" ligature followed by 'x'
+        let glyphs = [glyph('f', 0, 20.0), glyph('x', 2, 7.0)];
+        assert_eq!(char_advances("fix", &glyphs), vec![10.0, 10.0, 7.0]);
+
+        // clusters of right-to-left glyphs are in reverse order
+        let glyphs = [glyph('b', 1, 4.0), glyph('a', 0, 6.0)];
+        assert_eq!(char_advances("ab", &glyphs), vec![6.0, 4.0]);
+    }
+
+    #[test]
+    fn char_widths_sum_up_to_rounded_line_width() {
+        let lines = [
+            vec![glyph('a', 0, 3.4), glyph('a', 1, 3.4), glyph('a', 2, 3.4)],
+            vec![glyph('b', 0, 5.5)],
+        ];
+        assert_eq!(char_widths("aaa\nb", &lines), vec![3, 4, 3, 0, 6]);
+        assert_eq!(text_width(&lines), 3.4 * 3.0);
+    }
+
+    #[test]
+    fn unique_glyphs_are_sorted() {
+        assert_eq!(unique_glyphs([3, 1, 3, 2, 1].into_iter()), vec![1, 2, 3]);
+    }
+}
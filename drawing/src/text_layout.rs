@@ -0,0 +1,910 @@
+//! Text layout: line wrapping (Unicode line breaking rules), alignment,
+//! line spacing, overflow handling and bidirectional text.
+//!
+//! # Examples
+//!
+//! ```ignore
+//! let layout = Arc::new(TextLayout::new(
+//!     &mut resources,
+//!     "OpenSans",
+//!     FontParams::new(16.0),
+//!     "The quick brown fox jumps over the lazy dog",
+//!     &TextLayoutParams {
+//!         max_width: Some(120.0),
+//!         align: TextAlign::Justify,
+//!         ..Default::default()
+//!     },
+//! )?);
+//!
+//! // In render loop:
+//! primitives.push(Primitive::TextLayout {
+//!     layout: layout.clone(),
+//!     color: [1.0, 1.0, 1.0, 1.0],
+//!     position: PixelPoint::new(10.0, 10.0),
+//!     clipping_rect: PixelRect::new(PixelPoint::new(0.0, 0.0), PixelSize::new(800.0, 600.0)),
+//! });
+//! ```
+
+use crate::backend::Device;
+use crate::bidi::{visual_order, BidiParagraph, TextDirection};
+use crate::font::{Font, FontParams};
+use crate::resources::Resources;
+use crate::units::{Point, Rect, Size};
+use crate::vertical::WritingMode;
+use anyhow::Result;
+use std::ops::Range;
+use unicode_linebreak::{linebreaks, BreakOpportunity};
+
+const ELLIPSIS: char = '…';
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum TextAlign {
+    /// Left for left-to-right paragraphs, right for right-to-left paragraphs.
+    Start,
+    /// Right for left-to-right paragraphs, left for right-to-left paragraphs.
+    End,
+    Left,
+    Center,
+    Right,
+    /// Lines (except the last line of the paragraph) are stretched to the width
+    /// of the layout by widening the spaces.
+    Justify,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum LineHeight {
+    /// Height of the line reported by the font.
+    Normal,
+    /// Multiple of the height reported by the font.
+    Relative(f32),
+    /// Height in pixels.
+    Absolute(f32),
+}
+
+/// What happens with the text that doesn't fit into `max_width` (when not wrapped)
+/// or `max_height`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum TextOverflow {
+    /// The text is clipped at the edges of the layout.
+    Clip,
+    /// Lines that don't fit are removed and the last visible line ends with an ellipsis.
+    Ellipsis,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct TextLayoutParams {
+    pub max_width: Option<f32>,
+    pub max_height: Option<f32>,
+    /// Breaks lines longer than `max_width`.
+    pub wrap: bool,
+    pub align: TextAlign,
+    pub line_height: LineHeight,
+    pub overflow: TextOverflow,
+}
+
+impl Default for TextLayoutParams {
+    fn default() -> Self {
+        TextLayoutParams {
+            max_width: None,
+            max_height: None,
+            wrap: true,
+            align: TextAlign::Start,
+            line_height: LineHeight::Normal,
+            overflow: TextOverflow::Clip,
+        }
+    }
+}
+
+/// Position of a character in the layout.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct LayoutGlyph {
+    /// Byte index of the character in the source text
+    /// (the ellipsis has the index of the end of the line).
+    pub index: usize,
+    /// Length of the character in bytes (zero for the ellipsis).
+    pub len: usize,
+    pub x: f32,
+    pub width: f32,
+    pub rtl: bool,
+}
+
+/// Characters of the same direction drawn at once.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LayoutRun {
+    /// Text of the run in logical order.
+    pub text: String,
+    pub direction: TextDirection,
+    pub x: f32,
+    pub y: f32,
+    /// Glyphs in visual order (left to right).
+    pub glyphs: Vec<LayoutGlyph>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LayoutLine {
+    /// Byte range of the line in the source text (including trailing white spaces).
+    pub range: Range<usize>,
+    /// Direction of the paragraph.
+    pub rtl: bool,
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+    /// Runs in visual order (left to right).
+    pub runs: Vec<LayoutRun>,
+}
+
+/// Text broken into positioned lines. All positions are relative to the top left
+/// corner of the layout.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextLayout {
+    pub resource_key: String,
+    pub font_params: FontParams,
+    pub size: Size,
+    /// Rectangle the text is clipped to (if any part of the text overflows).
+    pub clip: Option<Rect>,
+    pub lines: Vec<LayoutLine>,
+}
+
+// Character of a line before alignment.
+struct RawGlyph {
+    index: usize,
+    len: usize,
+    ch: char,
+    width: f32,
+    level: u8,
+}
+
+// Line before alignment (characters in logical order).
+struct RawLine {
+    range: Range<usize>,
+    glyphs: Vec<RawGlyph>,
+    rtl: bool,
+    y: f32,
+    height: f32,
+    // distance from the top of the line to the top of the text
+    leading: f32,
+    last_in_paragraph: bool,
+    truncated: bool,
+}
+
+impl RawLine {
+    fn width(&self) -> f32 {
+        self.glyphs.iter().map(|glyph| glyph.width).sum()
+    }
+
+    // Removes characters from the end until the ellipsis fits and appends it.
+    fn truncate(&mut self, max_width: Option<f32>, ellipsis_width: f32) {
+        let mut width = self.width();
+        while let Some(glyph) = self.glyphs.last() {
+            if fits(max_width, width + ellipsis_width) && !glyph.ch.is_whitespace() {
+                break;
+            }
+            width -= glyph.width;
+            self.glyphs.pop();
+        }
+        self.glyphs.push(RawGlyph {
+            index: self.range.end,
+            len: 0,
+            ch: ELLIPSIS,
+            width: ellipsis_width,
+            level: self.rtl as u8,
+        });
+        self.truncated = true;
+    }
+}
+
+impl TextLayout {
+    /// Lays out the text rendered with the font chain of the resource key.
+    ///
+    /// The base direction of the paragraphs is taken from `font_params.direction`.
+    /// The text is always laid out in horizontal lines (the writing mode is ignored).
+    pub fn new<D: Device, F: Font<D>>(
+        resources: &mut Resources<D, F>,
+        resource_key: &str,
+        font_params: FontParams,
+        text: &str,
+        params: &TextLayoutParams,
+    ) -> Result<Self> {
+        let font_params = font_params.with_writing_mode(WritingMode::HorizontalTb);
+        let ellipsis_width = resources
+            .get_text_dimensions(resource_key, font_params, &ELLIPSIS.to_string())?
+            .0 as f32;
+        Self::with_measure(
+            resource_key,
+            font_params,
+            text,
+            params,
+            ellipsis_width,
+            |paragraph| {
+                resources.get_text_dimensions_each_char(resource_key, font_params, paragraph)
+            },
+        )
+    }
+
+    // Lays out the text with the widths of the characters of every paragraph
+    // and the height of its lines returned by `measure`.
+    fn with_measure(
+        resource_key: &str,
+        font_params: FontParams,
+        text: &str,
+        params: &TextLayoutParams,
+        ellipsis_width: f32,
+        mut measure: impl FnMut(&str) -> Result<(Vec<i16>, u16)>,
+    ) -> Result<Self> {
+        let max_width = params.max_width;
+        let mut lines = Vec::new();
+        let mut y = 0.0;
+        let mut paragraph_start = 0;
+        for paragraph in text.split('\n') {
+            let (widths, font_height) = measure(paragraph)?;
+            let font_height = font_height as f32;
+            let height = match params.line_height {
+                LineHeight::Normal => font_height,
+                LineHeight::Relative(scale) => font_height * scale,
+                LineHeight::Absolute(height) => height,
+            };
+            let bidi = BidiParagraph::new(paragraph, font_params.direction);
+
+            // (byte index in the paragraph, character, width)
+            let chars: Vec<(usize, char, f32)> = paragraph
+                .char_indices()
+                .enumerate()
+                .map(|(i, (pos, ch))| (pos, ch, widths.get(i).copied().unwrap_or(0) as f32))
+                .collect();
+
+            let wrap_width = if params.wrap { max_width } else { None };
+            let line_ranges = break_lines(paragraph, &chars, wrap_width);
+            let line_count = line_ranges.len();
+            for (i, range) in line_ranges.into_iter().enumerate() {
+                let byte_range = byte_range(paragraph.len(), &chars, &range);
+                let levels = bidi.line_levels(byte_range.clone());
+                let mut end = range.end;
+                while end > range.start && chars[end - 1].1.is_whitespace() {
+                    end -= 1;
+                }
+                let mut line = RawLine {
+                    range: paragraph_start + byte_range.start..paragraph_start + byte_range.end,
+                    glyphs: chars[range.start..end]
+                        .iter()
+                        .zip(levels)
+                        .map(|(&(pos, ch, width), level)| RawGlyph {
+                            index: paragraph_start + pos,
+                            len: ch.len_utf8(),
+                            ch,
+                            width,
+                            level,
+                        })
+                        .collect(),
+                    rtl: bidi.is_rtl(),
+                    y,
+                    height,
+                    leading: (height - font_height) * 0.5,
+                    last_in_paragraph: i + 1 == line_count,
+                    truncated: false,
+                };
+                if params.overflow == TextOverflow::Ellipsis && !fits(max_width, line.width()) {
+                    line.truncate(max_width, ellipsis_width);
+                }
+                lines.push(line);
+                y += height;
+            }
+
+            paragraph_start += paragraph.len() + 1;
+        }
+
+        let text_height = y;
+        let text_width = lines.iter().map(RawLine::width).fold(0.0f32, f32::max);
+        let mut overflows = !fits(max_width, text_width);
+
+        if let Some(max_height) = params.max_height {
+            if text_height > max_height {
+                overflows = true;
+                match params.overflow {
+                    TextOverflow::Clip => lines.retain(|line| line.y < max_height),
+                    TextOverflow::Ellipsis => {
+                        let visible = lines
+                            .iter()
+                            .filter(|line| line.y + line.height <= max_height)
+                            .count()
+                            .max(1);
+                        lines.truncate(visible);
+                        if let Some(line) = lines.last_mut() {
+                            line.truncate(max_width, ellipsis_width);
+                        }
+                    }
+                }
+            }
+        }
+
+        let width = max_width.unwrap_or(text_width);
+        let height = match params.max_height {
+            Some(max_height) => max_height.min(text_height),
+            None => text_height,
+        };
+        let clip = if overflows && params.overflow == TextOverflow::Clip {
+            Some(Rect::new(
+                Point::new(0.0, 0.0),
+                Size::new(width, params.max_height.unwrap_or(text_height)),
+            ))
+        } else {
+            None
+        };
+
+        let lines = lines
+            .into_iter()
+            .map(|line| align_line(line, width, params.align))
+            .collect();
+
+        Ok(TextLayout {
+            resource_key: resource_key.to_string(),
+            font_params,
+            size: Size::new(width, height),
+            clip,
+            lines,
+        })
+    }
+
+    /// Returns the top of the caret placed before the character at the byte index
+    /// of the source text (or at the end of the text). The caret has the height of the line.
+    pub fn caret_position(&self, index: usize) -> Point {
+        let line = match self.find_line(index) {
+            Some(line) => line,
+            None => return Point::new(0.0, 0.0),
+        };
+        let glyphs = || line.runs.iter().flat_map(|run| run.glyphs.iter());
+
+        // leading edge of the character or trailing edge of the previous one
+        let x = if let Some(glyph) = glyphs().find(|glyph| glyph.len > 0 && glyph.index == index) {
+            if glyph.rtl {
+                glyph.x + glyph.width
+            } else {
+                glyph.x
+            }
+        } else if let Some(glyph) =
+            glyphs().find(|glyph| glyph.len > 0 && glyph.index + glyph.len == index)
+        {
+            if glyph.rtl {
+                glyph.x
+            } else {
+                glyph.x + glyph.width
+            }
+        } else if line.rtl == (index > line.range.start) {
+            line.x
+        } else {
+            line.x + line.width
+        };
+
+        Point::new(x, line.y)
+    }
+
+    /// Returns the byte index of the caret position closest to the point.
+    pub fn hit_test(&self, point: Point) -> usize {
+        let line = match self
+            .lines
+            .iter()
+            .rev()
+            .find(|line| line.y <= point.y)
+            .or_else(|| self.lines.first())
+        {
+            Some(line) => line,
+            None => return 0,
+        };
+
+        let glyphs: Vec<&LayoutGlyph> = line.runs.iter().flat_map(|run| &run.glyphs).collect();
+        let glyph = match glyphs
+            .iter()
+            .find(|glyph| point.x < glyph.x + glyph.width)
+            .or_else(|| glyphs.last())
+        {
+            Some(glyph) => glyph,
+            None => return line.range.start,
+        };
+
+        // the caret goes before the character if the point is on its leading half
+        let left_half = point.x < glyph.x + glyph.width * 0.5;
+        if left_half != glyph.rtl {
+            glyph.index
+        } else {
+            glyph.index + glyph.len
+        }
+    }
+
+    /// Returns rectangles covering the characters of the byte range of the source text.
+    pub fn selection_rects(&self, range: Range<usize>) -> Vec<Rect> {
+        let mut res: Vec<Rect> = Vec::new();
+        for line in &self.lines {
+            let first = res.len();
+            for glyph in line.runs.iter().flat_map(|run| &run.glyphs) {
+                if glyph.len == 0 || glyph.index < range.start || glyph.index >= range.end {
+                    continue;
+                }
+                match res[first..].last_mut() {
+                    Some(rect) if (rect.max_x() - glyph.x).abs() < 0.01 => {
+                        rect.size.width += glyph.width;
+                    }
+                    _ => res.push(Rect::new(
+                        Point::new(glyph.x, line.y),
+                        Size::new(glyph.width, line.height),
+                    )),
+                }
+            }
+        }
+        res
+    }
+
+    // Returns the line of the caret at the byte index.
+    fn find_line(&self, index: usize) -> Option<&LayoutLine> {
+        self.lines
+            .iter()
+            .enumerate()
+            .find(|(i, line)| {
+                // the end of the line belongs to the next line unless it is a new paragraph
+                index < line.range.end
+                    || index == line.range.end
+                        && !matches!(self.lines.get(i + 1), Some(next) if next.range.start <= index)
+            })
+            .map(|(_, line)| line)
+            .or_else(|| self.lines.last())
+    }
+}
+
+fn fits(max_width: Option<f32>, width: f32) -> bool {
+    match max_width {
+        Some(max_width) => width <= max_width,
+        None => true,
+    }
+}
+
+// Returns ranges of characters (indices to `chars`) of every line
+// of the paragraph. Lines are broken at break opportunities,
+// words wider than `max_width` are broken between characters.
+fn break_lines(
+    paragraph: &str,
+    chars: &[(usize, char, f32)],
+    max_width: Option<f32>,
+) -> Vec<Range<usize>> {
+    let char_count = chars.len();
+    // width of the line without trailing white spaces
+    let line_width = |start: usize, mut end: usize| {
+        while end > start && chars[end - 1].1.is_whitespace() {
+            end -= 1;
+        }
+        chars[start..end].iter().map(|ch| ch.2).sum::<f32>()
+    };
+
+    let mut char_index = 0;
+    let mut res = Vec::new();
+    let mut start = 0;
+    let mut last_fit = None;
+    for (pos, opportunity) in linebreaks(paragraph) {
+        // convert byte position to character index
+        while char_index < char_count && chars[char_index].0 < pos {
+            char_index += 1;
+        }
+        let end = char_index;
+
+        if let Some(max_width) = max_width {
+            while line_width(start, end) > max_width {
+                match last_fit.take() {
+                    Some(fit) => {
+                        res.push(start..fit);
+                        start = fit;
+                    }
+                    None => {
+                        // the word is too long, break it after the last character that fits
+                        let mut fit = start + 1;
+                        while fit < end && line_width(start, fit + 1) <= max_width {
+                            fit += 1;
+                        }
+                        res.push(start..fit);
+                        start = fit;
+                    }
+                }
+            }
+        }
+
+        if opportunity == BreakOpportunity::Mandatory && end < char_count {
+            res.push(start..end);
+            start = end;
+            last_fit = None;
+        } else {
+            last_fit = Some(end);
+        }
+    }
+    res.push(start..char_count);
+    res
+}
+
+// Converts range of characters to range of bytes in the paragraph.
+fn byte_range(
+    paragraph_len: usize,
+    chars: &[(usize, char, f32)],
+    range: &Range<usize>,
+) -> Range<usize> {
+    let byte_index = |i: usize| chars.get(i).map_or(paragraph_len, |ch| ch.0);
+    byte_index(range.start)..byte_index(range.end)
+}
+
+fn align_line(line: RawLine, width: f32, align: TextAlign) -> LayoutLine {
+    let line_width = line.width();
+    let free_space = width - line_width;
+    let spaces = line
+        .glyphs
+        .iter()
+        .filter(|glyph| glyph.ch.is_whitespace())
+        .count();
+    let justify = align == TextAlign::Justify
+        && !line.last_in_paragraph
+        && !line.truncated
+        && spaces > 0
+        && free_space > 0.0;
+
+    let align_right = match align {
+        TextAlign::Start | TextAlign::Justify => line.rtl,
+        TextAlign::End => !line.rtl,
+        TextAlign::Left | TextAlign::Center => false,
+        TextAlign::Right => true,
+    };
+    let offset = if justify {
+        0.0
+    } else if align == TextAlign::Center {
+        free_space * 0.5
+    } else if align_right {
+        free_space
+    } else {
+        0.0
+    };
+    let space_extra = if justify {
+        free_space / spaces as f32
+    } else {
+        0.0
+    };
+
+    // glyphs are placed in visual order, a new run starts when the direction changes
+    // (with justification every word is a separate run)
+    let levels: Vec<u8> = line.glyphs.iter().map(|glyph| glyph.level).collect();
+    let mut runs: Vec<LayoutRun> = Vec::new();
+    let mut x = offset;
+    let mut previous: Option<(u8, bool)> = None;
+    for i in visual_order(&levels) {
+        let raw_glyph = &line.glyphs[i];
+        let is_space = raw_glyph.ch.is_whitespace();
+        let glyph = LayoutGlyph {
+            index: raw_glyph.index,
+            len: raw_glyph.len,
+            x,
+            width: raw_glyph.width + if is_space { space_extra } else { 0.0 },
+            rtl: raw_glyph.level % 2 == 1,
+        };
+        x += glyph.width;
+
+        let same_run = previous.is_some_and(|(level, space)| {
+            level == raw_glyph.level && (!justify || space == is_space)
+        });
+        match runs.last_mut() {
+            Some(run) if same_run => {
+                run.text.push(raw_glyph.ch);
+                run.glyphs.push(glyph);
+            }
+            _ => runs.push(LayoutRun {
+                text: raw_glyph.ch.to_string(),
+                direction: TextDirection::from_level(raw_glyph.level),
+                x: glyph.x,
+                y: line.y + line.leading,
+                glyphs: vec![glyph],
+            }),
+        }
+        previous = Some((raw_glyph.level, is_space));
+    }
+
+    // characters of right-to-left runs were added in visual order
+    for run in &mut runs {
+        if run.direction == TextDirection::RightToLeft {
+            run.text = run.text.chars().rev().collect();
+        }
+    }
+
+    LayoutLine {
+        range: line.range,
+        rtl: line.rtl,
+        x: offset,
+        y: line.y,
+        width: if justify { width } else { line_width },
+        height: line.height,
+        runs,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Every character is 10 pixels wide and lines are 20 pixels high.
+    fn layout_with(text: &str, direction: TextDirection, params: TextLayoutParams) -> TextLayout {
+        TextLayout::with_measure(
+            "F",
+            FontParams::new(16.0).with_direction(direction),
+            text,
+            &params,
+            10.0,
+            |paragraph| Ok((vec![10; paragraph.chars().count()], 20)),
+        )
+        .unwrap()
+    }
+
+    fn layout_text(text: &str, params: TextLayoutParams) -> TextLayout {
+        layout_with(text, TextDirection::Auto, params)
+    }
+
+    fn max_width(max_width: f32) -> TextLayoutParams {
+        TextLayoutParams {
+            max_width: Some(max_width),
+            ..Default::default()
+        }
+    }
+
+    fn ranges(layout: &TextLayout) -> Vec<Range<usize>> {
+        layout.lines.iter().map(|line| line.range.clone()).collect()
+    }
+
+    fn xs(layout: &TextLayout) -> Vec<f32> {
+        layout.lines.iter().map(|line| line.x).collect()
+    }
+
+    // (index, x, width) of the glyphs of the line in visual order
+    fn glyphs(line: &LayoutLine) -> Vec<(usize, f32, f32)> {
+        line.runs
+            .iter()
+            .flat_map(|run| &run.glyphs)
+            .map(|glyph| (glyph.index, glyph.x, glyph.width))
+            .collect()
+    }
+
+    #[test]
+    fn lines_break_at_break_opportunities() {
+        let layout = layout_text("aaa bbb ccc", max_width(75.0));
+        assert_eq!(ranges(&layout), vec![0..8, 8..11]);
+        // trailing white spaces don't count into the width
+        assert_eq!(layout.lines[0].width, 70.0);
+        assert_eq!(layout.lines[1].y, 20.0);
+        assert_eq!(layout.size, Size::new(75.0, 40.0));
+        assert_eq!(layout.clip, None);
+    }
+
+    #[test]
+    fn long_words_break_between_characters() {
+        let layout = layout_text("abcdefgh ij", max_width(35.0));
+        assert_eq!(ranges(&layout), vec![0..3, 3..6, 6..9, 9..11]);
+    }
+
+    #[test]
+    fn new_lines_start_paragraphs() {
+        let layout = layout_text("ab\n\ncd", TextLayoutParams::default());
+        assert_eq!(ranges(&layout), vec![0..2, 3..3, 4..6]);
+        let ys: Vec<f32> = layout.lines.iter().map(|line| line.y).collect();
+        assert_eq!(ys, vec![0.0, 20.0, 40.0]);
+        assert_eq!(layout.size, Size::new(20.0, 60.0));
+    }
+
+    #[test]
+    fn unwrapped_text_is_clipped() {
+        let params = TextLayoutParams {
+            wrap: false,
+            ..max_width(35.0)
+        };
+        let layout = layout_text("aaa bbb", params);
+        assert_eq!(ranges(&layout), vec![0..7]);
+        assert_eq!(
+            layout.clip,
+            Some(Rect::new(Point::new(0.0, 0.0), Size::new(35.0, 20.0)))
+        );
+    }
+
+    #[test]
+    fn lines_are_aligned() {
+        let align = |text: &str, align: TextAlign| {
+            let params = TextLayoutParams {
+                align,
+                ..max_width(100.0)
+            };
+            xs(&layout_text(text, params))[0]
+        };
+        assert_eq!(align("ab", TextAlign::Start), 0.0);
+        assert_eq!(align("ab", TextAlign::End), 80.0);
+        assert_eq!(align("ab", TextAlign::Left), 0.0);
+        assert_eq!(align("ab", TextAlign::Center), 40.0);
+        assert_eq!(align("ab", TextAlign::Right), 80.0);
+        // start and end of right-to-left paragraphs are swapped
+        assert_eq!(align("אב", TextAlign::Start), 80.0);
+        assert_eq!(align("אב", TextAlign::End), 0.0);
+        assert_eq!(align("אב", TextAlign::Left), 0.0);
+    }
+
+    #[test]
+    fn justified_lines_widen_spaces() {
+        let params = TextLayoutParams {
+            align: TextAlign::Justify,
+            ..max_width(75.0)
+        };
+        let layout = layout_text("aa bb cc\nd e", params);
+        assert_eq!(ranges(&layout), vec![0..6, 6..8, 9..12]);
+        assert_eq!(
+            glyphs(&layout.lines[0]),
+            vec![
+                (0, 0.0, 10.0),
+                (1, 10.0, 10.0),
+                (2, 20.0, 35.0),
+                (3, 55.0, 10.0),
+                (4, 65.0, 10.0)
+            ]
+        );
+        assert_eq!(layout.lines[0].width, 75.0);
+        // every word is a separate run
+        let runs: Vec<&str> = layout.lines[0]
+            .runs
+            .iter()
+            .map(|run| run.text.as_str())
+            .collect();
+        assert_eq!(runs, vec!["aa", " ", "bb"]);
+        // last lines of the paragraphs are not justified
+        assert_eq!(layout.lines[1].width, 20.0);
+        assert_eq!(layout.lines[2].width, 30.0);
+    }
+
+    #[test]
+    fn line_height() {
+        let params = TextLayoutParams {
+            line_height: LineHeight::Relative(1.5),
+            ..Default::default()
+        };
+        let layout = layout_text("a\nb", params);
+        assert_eq!((layout.lines[1].y, layout.lines[1].height), (30.0, 30.0));
+        // the text is centered in the line
+        assert_eq!(layout.lines[1].runs[0].y, 35.0);
+
+        let params = TextLayoutParams {
+            line_height: LineHeight::Absolute(16.0),
+            ..Default::default()
+        };
+        let layout = layout_text("a\nb", params);
+        assert_eq!(layout.lines[1].runs[0].y, 14.0);
+        assert_eq!(layout.size.height, 32.0);
+    }
+
+    #[test]
+    fn long_lines_end_with_ellipsis() {
+        let params = TextLayoutParams {
+            wrap: false,
+            overflow: TextOverflow::Ellipsis,
+            ..max_width(45.0)
+        };
+        let layout = layout_text("abc defgh", params);
+        // the space before the ellipsis is removed
+        assert_eq!(
+            glyphs(&layout.lines[0]),
+            vec![
+                (0, 0.0, 10.0),
+                (1, 10.0, 10.0),
+                (2, 20.0, 10.0),
+                (9, 30.0, 10.0)
+            ]
+        );
+        assert_eq!(layout.lines[0].runs[0].text, "abc…");
+        assert_eq!(layout.clip, None);
+    }
+
+    #[test]
+    fn lines_over_max_height() {
+        let params = TextLayoutParams {
+            max_height: Some(45.0),
+            ..Default::default()
+        };
+        let clipped = layout_text("a\nb\nc\nd", params);
+        // partially visible lines are kept
+        assert_eq!(ranges(&clipped), vec![0..1, 2..3, 4..5]);
+        assert_eq!(clipped.size, Size::new(10.0, 45.0));
+        assert_eq!(
+            clipped.clip,
+            Some(Rect::new(Point::new(0.0, 0.0), Size::new(10.0, 45.0)))
+        );
+
+        let params = TextLayoutParams {
+            overflow: TextOverflow::Ellipsis,
+            ..params
+        };
+        let truncated = layout_text("a\nb\nc\nd", params);
+        assert_eq!(ranges(&truncated), vec![0..1, 2..3]);
+        assert_eq!(truncated.lines[1].runs[0].text, "b…");
+        assert_eq!(truncated.clip, None);
+    }
+
+    #[test]
+    fn carets_and_hit_testing() {
+        let layout = layout_text("ab cd\nef", max_width(40.0));
+        assert_eq!(ranges(&layout), vec![0..3, 3..5, 6..8]);
+
+        assert_eq!(layout.caret_position(0), Point::new(0.0, 0.0));
+        assert_eq!(layout.caret_position(2), Point::new(20.0, 0.0));
+        // the end of a wrapped line is the start of the next line
+        assert_eq!(layout.caret_position(3), Point::new(0.0, 20.0));
+        // the end of a paragraph stays on its line
+        assert_eq!(layout.caret_position(5), Point::new(20.0, 20.0));
+        assert_eq!(layout.caret_position(6), Point::new(0.0, 40.0));
+        assert_eq!(layout.caret_position(8), Point::new(20.0, 40.0));
+
+        assert_eq!(layout.hit_test(Point::new(4.0, 5.0)), 0);
+        assert_eq!(layout.hit_test(Point::new(16.0, 5.0)), 2);
+        assert_eq!(layout.hit_test(Point::new(100.0, 25.0)), 5);
+        assert_eq!(layout.hit_test(Point::new(12.0, 100.0)), 7);
+        assert_eq!(layout.hit_test(Point::new(-5.0, -5.0)), 0);
+    }
+
+    #[test]
+    fn bidirectional_lines() {
+        // "ab " is followed by alef and bet, that are drawn from right to left
+        let layout = layout_with(
+            "ab אב",
+            TextDirection::LeftToRight,
+            TextLayoutParams::default(),
+        );
+        let line = &layout.lines[0];
+        assert_eq!(
+            glyphs(line),
+            vec![
+                (0, 0.0, 10.0),
+                (1, 10.0, 10.0),
+                (2, 20.0, 10.0),
+                (5, 30.0, 10.0),
+                (3, 40.0, 10.0)
+            ]
+        );
+        let runs: Vec<(&str, TextDirection)> = line
+            .runs
+            .iter()
+            .map(|run| (run.text.as_str(), run.direction))
+            .collect();
+        assert_eq!(
+            runs,
+            vec![
+                ("ab ", TextDirection::LeftToRight),
+                ("אב", TextDirection::RightToLeft)
+            ]
+        );
+
+        // carets of right-to-left characters are on their right edges
+        assert_eq!(layout.caret_position(3), Point::new(50.0, 0.0));
+        assert_eq!(layout.caret_position(5), Point::new(40.0, 0.0));
+        assert_eq!(layout.caret_position(7), Point::new(30.0, 0.0));
+        assert_eq!(layout.hit_test(Point::new(48.0, 0.0)), 3);
+        assert_eq!(layout.hit_test(Point::new(42.0, 0.0)), 5);
+
+        assert_eq!(
+            layout.selection_rects(3..7),
+            vec![Rect::new(Point::new(30.0, 0.0), Size::new(20.0, 20.0))]
+        );
+        assert_eq!(
+            layout.selection_rects(1..5),
+            vec![
+                Rect::new(Point::new(10.0, 0.0), Size::new(20.0, 20.0)),
+                Rect::new(Point::new(40.0, 0.0), Size::new(10.0, 20.0))
+            ]
+        );
+    }
+
+    #[test]
+    fn rtl_paragraph_direction() {
+        let layout = layout_text("אב ab", max_width(100.0));
+        let line = &layout.lines[0];
+        assert!(line.rtl);
+        assert_eq!(line.x, 50.0);
+        // the left-to-right run is on the left
+        assert_eq!(line.runs[0].text, "ab");
+        // texts of the runs are in logical order
+        assert_eq!(line.runs[1].text, "אב ");
+        // direction of the font parameters overrides the text
+        let layout = layout_with("אב ab", TextDirection::LeftToRight, max_width(100.0));
+        assert!(!layout.lines[0].rtl);
+        assert_eq!(layout.lines[0].x, 0.0);
+    }
+}
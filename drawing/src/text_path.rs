@@ -0,0 +1,476 @@
+//! Text drawn along a path (like labels along roads or curved captions).
+//!
+//! Glyphs are placed one by one: the center of every glyph is put on the path
+//! at its distance from the start of the text and the glyph is rotated
+//! to the direction of the path at that point. The baseline of the text lies
+//! on the path. Glyphs that would be placed beyond the ends of the path
+//! are not drawn.
+//!
+//! # Examples
+//!
+//! ```ignore
+//! // label centered on the road
+//! let length = PathMeasure::new(&road).length();
+//! primitives.push(Primitive::Text {
+//!     resource_key: "OpenSans".to_string(),
+//!     size: PixelThickness::new(14.0),
+//!     color: [0.0, 0.0, 0.0, 1.0],
+//!     position: PixelPoint::new(0.0, 0.0),
+//!     clipping_rect: PixelRect::new(PixelPoint::new(0.0, 0.0), PixelSize::new(800.0, 600.0)),
+//!     text: "Main Street".to_string(),
+//!     style: TextStyle {
+//!         path: Some(TextPath {
+//!             path: road,
+//!             params: TextPathParams {
+//!                 start_offset: length * 0.5,
+//!                 align: TextPathAlign::Center,
+//!                 ..Default::default()
+//!             },
+//!         }),
+//!         ..Default::default()
+//!     },
+//! });
+//! ```
+
+use crate::primitive::PathElement;
+use crate::shaping::GlyphBounds;
+use crate::units::{PixelPoint, PixelTransform};
+
+// max distance of the flattened curve from the real one (in pixels)
+const FLATTEN_TOLERANCE: f32 = 0.1;
+
+/// Which part of the text is placed at the start offset.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum TextPathAlign {
+    /// The beginning of the text.
+    #[default]
+    Start,
+    Center,
+    /// The end of the text.
+    End,
+}
+
+/// Side of the path the text is drawn on.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum TextPathSide {
+    /// Left of the direction of the path (above the path drawn from left to right).
+    #[default]
+    Left,
+    /// Right of the direction of the path. The text goes from the end of the path
+    /// to its start.
+    Right,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub struct TextPathParams {
+    /// Distance along the path (in pixels) from its start the text is aligned to.
+    /// With `TextPathSide::Right` it's measured from the end of the path.
+    pub start_offset: f32,
+    pub align: TextPathAlign,
+    pub side: TextPathSide,
+}
+
+/// Distances along the first subpath of the path.
+///
+/// Curves are flattened into line segments, so the distances are approximate.
+#[derive(Debug, Clone, Default)]
+pub struct PathMeasure {
+    points: Vec<PixelPoint>,
+    // distance of every point from the first one
+    distances: Vec<f32>,
+}
+
+impl PathMeasure {
+    pub fn new(path: &[PathElement]) -> Self {
+        let mut points: Vec<PixelPoint> = Vec::new();
+        for element in path {
+            match *element {
+                PathElement::MoveTo(point) => {
+                    if points.len() > 1 {
+                        break;
+                    }
+                    points.clear();
+                    points.push(point);
+                }
+                PathElement::LineTo(point) => points.push(point),
+                PathElement::BezierTo(c1, c2, point) => {
+                    let start = match points.last() {
+                        Some(&start) => start,
+                        None => c1,
+                    };
+                    flatten_cubic(start, c1, c2, point, &mut points);
+                }
+                PathElement::ClosePath => {
+                    if let Some(&first) = points.first() {
+                        points.push(first);
+                    }
+                    break;
+                }
+                PathElement::Solidity(_) => (),
+            }
+        }
+
+        let mut distances = Vec::with_capacity(points.len());
+        let mut distance = 0.0;
+        for (i, point) in points.iter().enumerate() {
+            if i > 0 {
+                distance += (*point - points[i - 1]).length();
+            }
+            distances.push(distance);
+        }
+        PathMeasure { points, distances }
+    }
+
+    /// Returns the measure of the same path going from its end to its start.
+    pub fn reversed(&self) -> Self {
+        let length = self.length();
+        PathMeasure {
+            points: self.points.iter().rev().copied().collect(),
+            distances: self.distances.iter().rev().map(|d| length - d).collect(),
+        }
+    }
+
+    /// Length of the path in pixels.
+    pub fn length(&self) -> f32 {
+        self.distances.last().copied().unwrap_or(0.0)
+    }
+
+    /// Returns the point at the distance from the start of the path and the direction
+    /// of the path there (angle in radians from the x axis, clockwise as y goes down).
+    ///
+    /// Returns `None` for distances beyond the ends of the path.
+    pub fn point_at(&self, distance: f32) -> Option<(PixelPoint, f32)> {
+        if self.points.len() < 2 || !(0.0..=self.length()).contains(&distance) {
+            return None;
+        }
+        // the segment containing the distance (skipping segments of zero length)
+        let end = self
+            .distances
+            .partition_point(|&d| d < distance)
+            .clamp(1, self.points.len() - 1);
+        let start = (0..end)
+            .rev()
+            .find(|&i| self.distances[i] < self.distances[end])
+            .unwrap_or(end - 1);
+        let (p1, p2) = (self.points[start], self.points[end]);
+        let segment_length = self.distances[end] - self.distances[start];
+        let t = if segment_length > 0.0 {
+            (distance - self.distances[start]) / segment_length
+        } else {
+            0.0
+        };
+        let direction = p2 - p1;
+        Some((p1.lerp(p2, t), direction.y.atan2(direction.x)))
+    }
+
+    /// Returns the transforms placing the glyphs of the first line of the text along
+    /// the path (in the order of the bounds, `None` for glyphs beyond the ends
+    /// of the path and glyphs of the other lines).
+    ///
+    /// # Arguments
+    ///
+    /// * `bounds` - bounds of the glyphs relative to the position the text would be drawn at
+    /// * `start` - distance along the path of that position
+    /// * `baseline` - distance from the top of the text to the baseline
+    pub fn glyph_transforms(
+        &self,
+        bounds: &[GlyphBounds],
+        start: f32,
+        baseline: f32,
+    ) -> Vec<Option<PixelTransform>> {
+        bounds
+            .iter()
+            .map(|glyph| {
+                let rect = glyph.advance_rect;
+                if rect.origin.y > 0.0 {
+                    return None;
+                }
+                let center = rect.center().x;
+                let (point, angle) = self.point_at(start + center)?;
+                Some(
+                    PixelTransform::translation(-center, -baseline)
+                        .then_rotate(euclid::Angle::radians(angle))
+                        .then_translate(point.to_vector()),
+                )
+            })
+            .collect()
+    }
+}
+
+// Appends the points of the cubic curve (except its start) divided into line segments.
+pub(crate) fn flatten_cubic(
+    p0: PixelPoint,
+    p1: PixelPoint,
+    p2: PixelPoint,
+    p3: PixelPoint,
+    points: &mut Vec<PixelPoint>,
+) {
+    // the distance of the curve from the chord is at most 3/4 of the largest
+    // second difference of the control points, segments reduce it quadratically
+    let dd1 = (p0.to_vector() - p1.to_vector() * 2.0 + p2.to_vector()).length();
+    let dd2 = (p1.to_vector() - p2.to_vector() * 2.0 + p3.to_vector()).length();
+    let segments = ((0.75 * dd1.max(dd2) / FLATTEN_TOLERANCE).sqrt().ceil() as usize).clamp(1, 100);
+    for i in 1..=segments {
+        let t = i as f32 / segments as f32;
+        let mt = 1.0 - t;
+        let point = p0.to_vector() * (mt * mt * mt)
+            + p1.to_vector() * (3.0 * mt * mt * t)
+            + p2.to_vector() * (3.0 * mt * t * t)
+            + p3.to_vector() * (t * t * t);
+        points.push(point.to_point());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::units::{Point, Rect, Size};
+    use std::f32::consts::{FRAC_PI_2, PI};
+
+    fn polyline(points: &[(f32, f32)]) -> Vec<PathElement> {
+        let mut res = vec![PathElement::MoveTo(PixelPoint::new(
+            points[0].0,
+            points[0].1,
+        ))];
+        for &(x, y) in &points[1..] {
+            res.push(PathElement::LineTo(PixelPoint::new(x, y)));
+        }
+        res
+    }
+
+    fn glyph(x: f32, y: f32, width: f32) -> GlyphBounds {
+        let rect = Rect::new(Point::new(x, y), Size::new(width, 20.0));
+        GlyphBounds {
+            glyph_id: 1,
+            cluster: 0,
+            rtl: false,
+            advance_rect: rect,
+            ink_rect: rect,
+        }
+    }
+
+    fn assert_point(point: PixelPoint, x: f32, y: f32) {
+        assert!(
+            (point.x - x).abs() < 1e-4 && (point.y - y).abs() < 1e-4,
+            "{:?} != ({}, {})",
+            point,
+            x,
+            y
+        );
+    }
+
+    fn assert_angle(angle: f32, expected: f32) {
+        assert!((angle - expected).abs() < 1e-4, "{} != {}", angle, expected);
+    }
+
+    #[test]
+    fn point_at_follows_segments() {
+        let measure = PathMeasure::new(&polyline(&[(0.0, 0.0), (30.0, 0.0), (30.0, 40.0)]));
+        assert_eq!(measure.length(), 70.0);
+
+        let (point, angle) = measure.point_at(15.0).unwrap();
+        assert_point(point, 15.0, 0.0);
+        assert_angle(angle, 0.0);
+        // y goes down, so the angle is clockwise
+        let (point, angle) = measure.point_at(50.0).unwrap();
+        assert_point(point, 30.0, 20.0);
+        assert_angle(angle, FRAC_PI_2);
+        // the corner belongs to the first segment
+        let (point, angle) = measure.point_at(30.0).unwrap();
+        assert_point(point, 30.0, 0.0);
+        assert_angle(angle, 0.0);
+    }
+
+    #[test]
+    fn point_at_out_of_range() {
+        let measure = PathMeasure::new(&polyline(&[(0.0, 0.0), (30.0, 0.0), (30.0, 40.0)]));
+        assert!(measure.point_at(-0.5).is_none());
+        assert!(measure.point_at(70.5).is_none());
+        assert_point(measure.point_at(0.0).unwrap().0, 0.0, 0.0);
+        assert_point(measure.point_at(70.0).unwrap().0, 30.0, 40.0);
+
+        let point = PathMeasure::new(&polyline(&[(10.0, 10.0)]));
+        assert_eq!(point.length(), 0.0);
+        assert!(point.point_at(0.0).is_none());
+        assert!(PathMeasure::new(&[]).point_at(0.0).is_none());
+    }
+
+    #[test]
+    fn segments_of_zero_length_are_skipped() {
+        let measure = PathMeasure::new(&polyline(&[
+            (0.0, 0.0),
+            (10.0, 0.0),
+            (10.0, 0.0),
+            (10.0, 10.0),
+        ]));
+        assert_eq!(measure.length(), 20.0);
+        let (point, angle) = measure.point_at(10.0).unwrap();
+        assert_point(point, 10.0, 0.0);
+        assert_angle(angle, 0.0);
+        let (_, angle) = measure.point_at(10.5).unwrap();
+        assert_angle(angle, FRAC_PI_2);
+    }
+
+    #[test]
+    fn only_first_subpath_is_measured() {
+        let mut path = polyline(&[(0.0, 0.0), (10.0, 0.0)]);
+        path.extend(polyline(&[(50.0, 50.0), (100.0, 50.0)]));
+        assert_eq!(PathMeasure::new(&path).length(), 10.0);
+
+        // leading moves start the subpath over
+        let mut path = vec![PathElement::MoveTo(PixelPoint::new(50.0, 50.0))];
+        path.extend(polyline(&[(0.0, 0.0), (10.0, 0.0)]));
+        assert_eq!(PathMeasure::new(&path).length(), 10.0);
+
+        // closing the path adds the segment back to the start
+        let mut path = polyline(&[(0.0, 0.0), (10.0, 0.0), (10.0, 10.0), (0.0, 10.0)]);
+        path.push(PathElement::ClosePath);
+        path.extend(polyline(&[(50.0, 50.0), (100.0, 50.0)]));
+        let measure = PathMeasure::new(&path);
+        assert_eq!(measure.length(), 40.0);
+        let (point, angle) = measure.point_at(35.0).unwrap();
+        assert_point(point, 0.0, 5.0);
+        assert_angle(angle, -FRAC_PI_2);
+    }
+
+    #[test]
+    fn reversed_measure_goes_from_the_end() {
+        let measure = PathMeasure::new(&polyline(&[(0.0, 0.0), (30.0, 0.0), (30.0, 40.0)]));
+        let reversed = measure.reversed();
+        assert_eq!(reversed.length(), measure.length());
+
+        let (point, angle) = reversed.point_at(10.0).unwrap();
+        assert_point(point, 30.0, 30.0);
+        assert_angle(angle, -FRAC_PI_2);
+        let (point, angle) = reversed.point_at(60.0).unwrap();
+        assert_point(point, 10.0, 0.0);
+        assert_angle(angle, PI);
+        assert_point(reversed.point_at(0.0).unwrap().0, 30.0, 40.0);
+        assert_point(reversed.point_at(70.0).unwrap().0, 0.0, 0.0);
+        assert!(reversed.point_at(70.5).is_none());
+
+        let twice = reversed.reversed();
+        for distance in [0.0, 15.0, 30.0, 50.0, 70.0] {
+            let (point, angle) = measure.point_at(distance).unwrap();
+            let (twice_point, twice_angle) = twice.point_at(distance).unwrap();
+            assert_point(twice_point, point.x, point.y);
+            assert_angle(twice_angle, angle);
+        }
+    }
+
+    #[test]
+    fn glyphs_are_centered_on_the_path() {
+        let measure = PathMeasure::new(&polyline(&[(0.0, 100.0), (200.0, 100.0)]));
+        let bounds = [glyph(0.0, 0.0, 10.0), glyph(10.0, 0.0, 10.0)];
+        let transforms = measure.glyph_transforms(&bounds, 50.0, 15.0);
+        assert_eq!(transforms.len(), 2);
+        // the center of the baseline of the glyph is on the path
+        let transform = transforms[0].unwrap();
+        assert_point(
+            transform.transform_point(PixelPoint::new(5.0, 15.0)),
+            55.0,
+            100.0,
+        );
+        assert_point(
+            transform.transform_point(PixelPoint::new(0.0, 0.0)),
+            50.0,
+            85.0,
+        );
+        let transform = transforms[1].unwrap();
+        assert_point(
+            transform.transform_point(PixelPoint::new(15.0, 15.0)),
+            65.0,
+            100.0,
+        );
+    }
+
+    #[test]
+    fn glyphs_are_rotated_to_the_path() {
+        // path going down, the text is on its left (to the right on the screen)
+        let measure = PathMeasure::new(&polyline(&[(100.0, 0.0), (100.0, 200.0)]));
+        let transforms = measure.glyph_transforms(&[glyph(0.0, 0.0, 10.0)], 50.0, 15.0);
+        let transform = transforms[0].unwrap();
+        assert_point(
+            transform.transform_point(PixelPoint::new(5.0, 15.0)),
+            100.0,
+            55.0,
+        );
+        assert_point(
+            transform.transform_point(PixelPoint::new(0.0, 0.0)),
+            115.0,
+            50.0,
+        );
+        assert_point(
+            transform.transform_point(PixelPoint::new(10.0, 0.0)),
+            115.0,
+            60.0,
+        );
+    }
+
+    #[test]
+    fn glyphs_beyond_the_path_are_not_placed() {
+        let measure = PathMeasure::new(&polyline(&[(0.0, 0.0), (100.0, 0.0)]));
+        let bounds = [
+            glyph(0.0, 0.0, 10.0),
+            glyph(10.0, 0.0, 10.0),
+            glyph(20.0, 0.0, 10.0),
+            // second line
+            glyph(0.0, 20.0, 10.0),
+        ];
+        let transforms = measure.glyph_transforms(&bounds, 80.0, 15.0);
+        assert!(transforms[0].is_some());
+        assert!(transforms[1].is_some());
+        // center at 105
+        assert!(transforms[2].is_none());
+        assert!(transforms[3].is_none());
+
+        let transforms = measure.glyph_transforms(&bounds, -10.0, 15.0);
+        assert!(transforms[0].is_none());
+        assert!(transforms[1].is_some());
+    }
+
+    #[test]
+    fn straight_cubic_is_one_segment() {
+        let mut points = Vec::new();
+        flatten_cubic(
+            PixelPoint::new(0.0, 0.0),
+            PixelPoint::new(10.0, 0.0),
+            PixelPoint::new(20.0, 0.0),
+            PixelPoint::new(30.0, 0.0),
+            &mut points,
+        );
+        assert_eq!(points, vec![PixelPoint::new(30.0, 0.0)]);
+    }
+
+    #[test]
+    fn flattened_cubic_stays_on_the_curve() {
+        // quarter of the circle of radius 100 around the origin
+        let k = 100.0 * 0.552_284_8;
+        let mut points = vec![PixelPoint::new(100.0, 0.0)];
+        flatten_cubic(
+            PixelPoint::new(100.0, 0.0),
+            PixelPoint::new(100.0, k),
+            PixelPoint::new(k, 100.0),
+            PixelPoint::new(0.0, 100.0),
+            &mut points,
+        );
+        assert!(points.len() > 2);
+        assert_eq!(*points.last().unwrap(), PixelPoint::new(0.0, 100.0));
+        for pair in points.windows(2) {
+            // points are on the circle and the segments don't leave it
+            // by more than the tolerance
+            assert!((pair[1].to_vector().length() - 100.0).abs() < 0.05);
+            let middle = pair[0].lerp(pair[1], 0.5).to_vector().length();
+            assert!(100.0 - middle < FLATTEN_TOLERANCE, "{}", middle);
+        }
+
+        let mut path = vec![PathElement::MoveTo(PixelPoint::new(100.0, 0.0))];
+        path.push(PathElement::BezierTo(
+            PixelPoint::new(100.0, k),
+            PixelPoint::new(k, 100.0),
+            PixelPoint::new(0.0, 100.0),
+        ));
+        let length = PathMeasure::new(&path).length();
+        assert!((length - 50.0 * PI).abs() < 0.1, "{}", length);
+    }
+}
@@ -1,22 +1,56 @@
 //! Construct bitmap font using FreeType library.
-//! Generates raw texture data for the given font and collects information
-//! about available font characters to map them into texture.
-
+//! Collects information about available font glyphs and rasterizes them
+//! into the glyph atlas on first use.
+//!
+//! Color glyphs (embedded BGRA bitmaps like CBDT/sbix and COLRv0 layers
+//! rendered by FreeType) are put into a separate RGBA atlas. COLRv1 glyphs
+//! are not rendered in color by FreeType, they are painted from the `COLR` table
+//! (see `color_glyph`).
+//!
+//! Glyphs are rasterized for every subpixel position they are drawn at
+//! (see `TextRendering`). The coverage of LCD subpixels is stored in RGBA atlas.
+//! Outlines are hinted as requested by the rendering and the synthesized
+//! bold and oblique styles of the face are applied before rasterization.
+//!
+//! Masks of the glyphs for the text effects (see `GlyphMask`) are computed
+//! from the grayscale coverage (or the alpha of color glyphs).
+
+use crate::backend::Device;
+use crate::font::{FontFace, FontHinting, FontMetrics, FontSize, TextAntialiasing, TextRendering};
+use crate::shaping::{GlyphProvider, VerticalGlyphMetrics};
+use crate::texture_font::color_glyph::{open_color_face, paint_color_glyph, ColorGlyphImage};
+use crate::texture_font::ft_face::{open_face, synthesize};
+use crate::texture_font::glyph_atlas::{GlyphAtlas, GlyphKey, GlyphMask};
+use crate::texture_font::glyph_mask::create_mask;
 use freetype as ft;
+use freetype::bitmap::PixelMode;
+use freetype::face::LoadFlag;
+use freetype::render_mode::RenderMode;
 use freetype::Error as FreetypeError;
-use freetype::Face;
+use freetype::{Bitmap, Face};
 use std::char::from_u32;
-use std::cmp::max;
-use std::collections::{HashMap, HashSet};
-use std::iter::{repeat, FromIterator};
+use std::collections::HashMap;
+use std::sync::Arc;
 
+/// Glyph metrics of the font of one size.
 #[derive(Debug)]
 pub struct BitmapFont {
-    width: u16,
-    height: u16,
-    chars: HashMap<char, BitmapChar>,
-    image: Vec<u8>,
+    font_face: FontFace,
+    font_size: FontSize,
+    rendering: TextRendering,
+    // coverage adjusted by the gamma and contrast
+    coverage_table: [u8; 256],
+    glyphs: HashMap<u32, BitmapChar>,
+    // glyphs rasterized at the other subpixel positions than zero
+    subpixel_glyphs: HashMap<(u32, u8), BitmapChar>,
+    // masks of the glyphs rasterized at whole pixels
+    masked_glyphs: HashMap<(u32, GlyphMask), BitmapChar>,
+    char_map: Arc<HashMap<char, u32>>,
     font_height: u16,
+    metrics: FontMetrics,
+    // scale of the bitmaps of the fixed size closest to the font size
+    // (1.0 for scalable fonts)
+    bitmap_scale: f32,
 }
 
 #[derive(Debug)]
@@ -24,15 +58,12 @@ pub struct BitmapChar {
     // Real glyph's coordinates in pixels.
     pub x_offset: i32,
     pub y_offset: i32,
-    pub x_advance: i32,
+    pub x_advance: f32,
     pub width: i32,
     pub height: i32,
-    // Precalculated scaled positions in texture.
-    pub tex: [f32; 2],
-    pub tex_width: f32,
-    pub tex_height: f32,
-    // This field is used only while building the texture.
-    data: Option<Vec<u8>>,
+    // the bitmap is in the color atlas
+    pub color: bool,
+    pub vertical: VerticalGlyphMetrics,
 }
 
 /// Represents possible errors which may occur during the font loading.
@@ -50,296 +81,607 @@ impl From<FreetypeError> for FontError {
     }
 }
 
-pub type FontResult = Result<BitmapFont, FontError>;
-
 impl BitmapFont {
-    pub fn from_bytes(data: &[u8], font_size: u8, chars: Option<&[char]>) -> FontResult {
-        use std::rc::Rc;
+    pub fn new(
+        data: &[u8],
+        font_face: FontFace,
+        font_size: FontSize,
+        rendering: TextRendering,
+        char_map: Arc<HashMap<char, u32>>,
+    ) -> Result<Self, FontError> {
+        let face = Self::load_face(data, &font_face, font_size)?;
+        let size_metrics = face.size_metrics().unwrap();
+        let bitmap_scale = if face.is_scalable() || size_metrics.y_ppem == 0 {
+            1.0
+        } else {
+            font_size.get() / size_metrics.y_ppem as f32
+        };
+        let font_height = (((size_metrics.height + 32) >> 6) as f32 * bitmap_scale).round() as u16;
+        // glyphs are placed so that the baseline is the font size below the top of the line
+        let baseline = font_size.get().round();
+        let mut metrics = Self::face_metrics(&face, &char_map, baseline).scale(bitmap_scale);
+        metrics.baseline = baseline;
+        metrics.line_height = font_height as f32;
+        Ok(BitmapFont {
+            font_face,
+            font_size,
+            rendering,
+            coverage_table: rendering.coverage_table(),
+            glyphs: HashMap::new(),
+            subpixel_glyphs: HashMap::new(),
+            masked_glyphs: HashMap::new(),
+            char_map,
+            font_height,
+            metrics,
+            bitmap_scale,
+        })
+    }
 
-        let library = ft::Library::init()?;
-        let face = library.new_memory_face(Rc::new(data.into()), 0)?;
-        Self::new(face, font_size, chars)
+    /// Returns vertical metrics of the face at its current size.
+    ///
+    /// `baseline` is the distance from the top of the line to the baseline.
+    pub fn face_metrics(face: &Face, char_map: &HashMap<char, u32>, baseline: f32) -> FontMetrics {
+        let size_metrics = face.size_metrics().unwrap();
+        // y_scale converts font units to 26.6 fixed point pixels (it's 16.16 fixed point)
+        let units_to_pixels = size_metrics.y_scale as f32 / 65536.0 / 64.0;
+        let ascent = size_metrics.ascender as f32 / 64.0;
+        let descent = -size_metrics.descender as f32 / 64.0;
+        let line_height = size_metrics.height as f32 / 64.0;
+
+        // height of the glyph above the baseline
+        let glyph_top = |ch: char| match char_map.get(&ch) {
+            Some(&glyph_id) if face.load_glyph(glyph_id, LoadFlag::NO_BITMAP).is_ok() => {
+                face.glyph().metrics().horiBearingY as f32 / 64.0
+            }
+            _ => 0.0,
+        };
+
+        FontMetrics {
+            baseline,
+            ascent,
+            descent,
+            line_gap: (line_height - ascent - descent).max(0.0),
+            line_height,
+            underline_position: -face.underline_position() as f32 * units_to_pixels,
+            underline_thickness: face.underline_thickness() as f32 * units_to_pixels,
+            x_height: glyph_top('x'),
+            cap_height: glyph_top('H'),
+        }
     }
 
-    fn get_all_face_chars(face: &mut Face) -> HashSet<char> {
-        let mut result = HashSet::new();
+    /// Returns glyph indices of all characters of the face of the font collection
+    /// (zero for fonts with one face).
+    pub fn char_map_from_bytes(
+        data: &[u8],
+        face_index: u32,
+    ) -> Result<HashMap<char, u32>, FontError> {
+        let mut face = open_face(
+            data,
+            &FontFace {
+                index: face_index,
+                ..Default::default()
+            },
+        )?;
+        let char_map = Self::get_char_map(&mut face);
+        if char_map.is_empty() {
+            return Err(FontError::EmptyFont);
+        }
+        Ok(char_map)
+    }
+
+    fn get_char_map(face: &mut Face) -> HashMap<char, u32> {
+        let mut result = HashMap::new();
         let mut index = 0;
         let face_ptr = face.raw_mut();
         unsafe {
             let mut code = ft::ffi::FT_Get_First_Char(face_ptr, &mut index);
             while index != 0 {
-                from_u32(code as u32).map(|ch| result.insert(ch));
+                from_u32(code as u32).map(|ch| result.insert(ch, index));
                 code = ft::ffi::FT_Get_Next_Char(face_ptr, code, &mut index);
             }
         }
         result
     }
 
-    // FIXME(Kagami): Profile and optimize this function!
-    // TODO(Kagami): Limit too huge textures? We should keep it less than
-    // 8k X 8k in general.
-    // TODO(Kagami): Add bunch of asserts to check for negative values and
-    // overflows.
-    /// Construct new BitMap font using provided parameters (this is general
-    /// method, called via `from_` helpers).
-    fn new(mut face: ft::Face, font_size: u8, chars: Option<&[char]>) -> FontResult {
-        let needed_chars = chars
-            .map(|sl| HashSet::from_iter(sl.iter().cloned()))
-            .unwrap_or_else(|| Self::get_all_face_chars(&mut face));
-        if needed_chars.is_empty() {
-            return Err(FontError::EmptyFont);
+    fn load_face(
+        data: &[u8],
+        font_face: &FontFace,
+        font_size: FontSize,
+    ) -> Result<Face, FontError> {
+        let mut face = open_face(data, font_face)?;
+        if !face.is_scalable() && face.has_fixed_sizes() {
+            // fonts with bitmaps only (like color emoji fonts) cannot be scaled by FreeType,
+            // the bitmaps of the closest size are scaled when drawn
+            Self::select_fixed_size(&mut face, font_size.get())?;
+            return Ok(face);
+        }
+        match font_size.to_pixels() {
+            Some(pixels) => face.set_pixel_sizes(0, pixels)?,
+            // 26.6 fixed point size in points (at 72 DPI points are equal to pixels)
+            None => face.set_char_size(0, (font_size.get() * 64.0).round() as isize, 72, 72)?,
         }
+        Ok(face)
+    }
 
-        face.set_pixel_sizes(0, font_size as u32)?;
-
-        // FreeType representation of rendered glyph 'j':
-        //
-        // b_left   w
-        // +-----+-----+-----+
-        // |     |     |     | font_size - bitmap_top()
-        // +-----+-----+-----+
-        // |     |  x  |     |
-        // |     |     |     |
-        // |     |  x  |     | bitmap_top()
-        // |     |  x  |     |
-        // |     |  x  |     |
-        // |     |  x  |     |
-        // +-----+--x--+-----+
-        // |     | x   |     | rows() - bitmap_top()
-        // |     |x    |     |
-        // +-----------+-----+
-        //      advance.x
-        //
-        // (Read <http://www.freetype.org/freetype2/docs/glyphs/glyphs-3.html>
-        // for more details.)
-        //
-        // Notes:
-        // * Width/height of the rendered glyph generally smaller than the the
-        //   specified font size
-        // * But if we add x/y offsets to the real glyph's dimensions it might
-        //   go beyound that limits (e.g. chars like 'j', 'q')
-        // * `bottom_left()` may be less than zero for some tight characters
-        //   (too push it to the previous one)
-        // * Theoretically `bitmap_top()` may be bigger than the `font_size`
-        //
-        // For simplicity we use fixed box height to store characters in the
-        // texture (extended with blank pixels downwards), but width may vary:
-        //
-        //         width()
-        //  +-----+-------+
-        //  |  x  |   x   |
-        //  |     |       |
-        //  |  x  |   x   |
-        //  |  x  |   x   | rows()
-        //  |  x  |   x   |
-        //  |  x  |   x   |
-        //  +-----+  x    |
-        //  |     | x     |
-        //  |     +-------+
-        //  |     |       | ch_box_height - rows()
-        //  +-----+-------+
-        //
-        // To construct the optimal texture (i.e. square enought and with box
-        // height of the highest character) we need to do several passes.
-
-        // In first pass we collect information about the chars and store their
-        // raw bitmap data. It gives us max character height and summary width
-        // of all characters.
-
-        let chars_len = needed_chars.len();
-        let mut chars_info = HashMap::with_capacity(chars_len);
-        let mut sum_image_width = 0;
-        let mut max_ch_width = 0;
-        let mut ch_box_height = 0;
-
-        // debug!("Start building the bitmap (chars: {})", chars_len);
-
-        for ch in needed_chars {
-            face.load_char(ch as usize, ft::face::LoadFlag::RENDER)?;
-            let glyph = face.glyph();
-            let bitmap = glyph.bitmap();
-            let ch_width = bitmap.width();
-            let ch_height = bitmap.rows();
-            let ch_x_offset = glyph.bitmap_left();
-            let ch_y_offset = font_size as i32 - glyph.bitmap_top();
-            let ch_x_advance = ((glyph.advance().x + 32) >> 6) as i32;
-            let buffer = bitmap.buffer();
-            let ch_data = Vec::from(buffer);
-
-            chars_info.insert(
-                ch,
-                BitmapChar {
-                    x_offset: ch_x_offset,
-                    y_offset: ch_y_offset,
-                    x_advance: ch_x_advance,
-                    width: ch_width,
-                    height: ch_height,
-                    // We'll need to fix that fields later:
-                    tex: [0.0, 0.0],
-                    tex_width: 0.0,
-                    tex_height: 0.0,
-                    data: Some(ch_data),
-                },
-            );
+    // selects the smallest fixed size not smaller than the font size (or the biggest one)
+    fn select_fixed_size(face: &mut Face, size: f32) -> Result<(), FontError> {
+        let raw = face.raw();
+        let sizes = unsafe {
+            std::slice::from_raw_parts(raw.available_sizes, raw.num_fixed_sizes as usize)
+        };
+        let mut best: Option<(usize, f32)> = None;
+        for (index, fixed_size) in sizes.iter().enumerate() {
+            // 26.6 fixed point
+            let ppem = fixed_size.y_ppem as f32 / 64.0;
+            let better = match best {
+                None => true,
+                Some((_, best_ppem)) if best_ppem < size => ppem > best_ppem,
+                Some((_, best_ppem)) => ppem >= size && ppem < best_ppem,
+            };
+            if better {
+                best = Some((index, ppem));
+            }
+        }
+        if let Some((index, _)) = best {
+            let error =
+                unsafe { ft::ffi::FT_Select_Size(face.raw_mut(), index as ft::ffi::FT_Int) };
+            if error != 0 {
+                return Err(FontError::FreetypeError(error.into()));
+            }
+        }
+        Ok(())
+    }
 
-            sum_image_width += ch_width;
-            max_ch_width = max(max_ch_width, ch_width);
-            ch_box_height = max(ch_box_height, ch_height);
+    // FreeType BGRA bitmaps have premultiplied alpha, the atlas expects straight alpha
+    fn color_bitmap_data(bitmap: &Bitmap) -> Vec<u8> {
+        let width = bitmap.width() as usize;
+        let rows = bitmap.rows() as usize;
+        let pitch = bitmap.pitch().unsigned_abs() as usize;
+        let mut data = Vec::with_capacity(width * rows * 4);
+        for row in bitmap.buffer().chunks(pitch).take(rows) {
+            for pixel in row[..width * 4].chunks_exact(4) {
+                let alpha = pixel[3] as u32;
+                let unpremultiply = |value: u8| match alpha {
+                    0 => 0,
+                    _ => ((value as u32 * 255 + alpha / 2) / alpha).min(255) as u8,
+                };
+                data.extend_from_slice(&[
+                    unpremultiply(pixel[0]),
+                    unpremultiply(pixel[1]),
+                    unpremultiply(pixel[2]),
+                    pixel[3],
+                ]);
+            }
         }
+        data
+    }
 
-        // In second pass we map character boxes with varying width onto the
-        // fixed quad texture image and build the final texture image.
-        //
-        // We start with optimist (square) assumption about texture dimensions
-        // and adjust the image's height and size while filling the rows.
-        //
-        // TODO(Kagami): We may try some cool CS algorithm to fit char boxes
-        // into the quad texture space with the best level of compression.
-        // Though current level of inefficiency is good enough.
-
-        let ideal_image_size = sum_image_width * ch_box_height;
-        let ideal_image_width = (ideal_image_size as f32).sqrt() as i32;
-        let image_width = max(max_ch_width, ideal_image_width);
-        let image_width = ((image_width + 3) / 4) * 4;
-        let assumed_size = ideal_image_size as f32 * 1.5;
-        let assumed_ch_in_row = image_width as f32 / max_ch_width as f32;
-        let mut image = Vec::with_capacity(assumed_size as usize);
-        let mut chars_row = Vec::with_capacity(assumed_ch_in_row as usize);
-        let mut cursor_x = 0;
-        let mut image_height = 0;
-
-        let dump_row = |image: &mut Vec<u8>, chars_row: &Vec<(i32, i32, Vec<u8>)>| {
-            // Copy character data into the image row by row:
-            //
-            //       image_width
-            // +-------+---------+---+
-            // |   x   |    x    |   |
-            // |       |         |   |
-            // |   x   |    x    |   | ch_box_height
-            // |   x   |    x    |   |
-            // |   x   |    x    |   |
-            // |   x   |   x     |   |
-            // |       |  x      |   |
-            // +-------+---------+---+
-            //                     ^--- image_width - width_ch_i - width_ch_j
-            for i in 0..ch_box_height {
-                let mut x = 0;
-                for &(width, height, ref data) in chars_row {
-                    if i >= height {
-                        image.extend(repeat(0).take(width as usize));
-                    } else {
-                        let skip = i * width;
-                        debug_assert!(data.len() >= (skip + width) as usize);
-                        let line = data.iter().skip(skip as usize).take(width as usize);
-                        image.extend(line.cloned());
-                    };
-                    x += width;
+    fn gray_bitmap_data(&self, bitmap: &Bitmap) -> Vec<u8> {
+        let width = bitmap.width() as usize;
+        let rows = bitmap.rows() as usize;
+        let pitch = bitmap.pitch().unsigned_abs() as usize;
+        let mut data = Vec::with_capacity(width * rows);
+        for row in bitmap.buffer().chunks(pitch).take(rows) {
+            data.extend(
+                row[..width]
+                    .iter()
+                    .map(|&value| self.coverage_table[value as usize]),
+            );
+        }
+        data
+    }
+
+    // LCD bitmaps have 3 bytes (RGB subpixels) per pixel, the atlas expects BGRA bytes
+    fn lcd_bitmap_data(&self, bitmap: &Bitmap) -> Vec<u8> {
+        let width = bitmap.width() as usize / 3;
+        let rows = bitmap.rows() as usize;
+        let pitch = bitmap.pitch().unsigned_abs() as usize;
+        let bgr = self.rendering.antialiasing == TextAntialiasing::LcdBgr;
+        let mut data = Vec::with_capacity(width * rows * 4);
+        for row in bitmap.buffer().chunks(pitch).take(rows) {
+            for pixel in row[..width * 3].chunks_exact(3) {
+                let [mut r, g, mut b] =
+                    [pixel[0], pixel[1], pixel[2]].map(|value| self.coverage_table[value as usize]);
+                if bgr {
+                    std::mem::swap(&mut r, &mut b);
                 }
-                let cols_to_fill = image_width - x;
-                image.extend(repeat(0).take(cols_to_fill as usize));
+                data.extend_from_slice(&[b, g, r, r.max(g).max(b)]);
             }
+        }
+        data
+    }
+
+    /// Makes sure the metrics of the glyphs are known and their bitmaps
+    /// are in the atlases (`atlas` for the grayscale or LCD glyphs, `color_atlas`
+    /// for the color ones). The font file is parsed only if some glyph is missing.
+    pub fn load_glyphs<D: Device>(
+        &mut self,
+        data: &[u8],
+        glyph_ids: &[u32],
+        atlas: &mut GlyphAtlas<D>,
+        color_atlas: &mut GlyphAtlas<D>,
+    ) -> Result<(), FontError> {
+        let glyphs: Vec<(u32, u8)> = glyph_ids.iter().map(|&glyph_id| (glyph_id, 0)).collect();
+        self.load_glyphs_at(data, &glyphs, atlas, color_atlas)
+    }
+
+    /// Same as `load_glyphs()` but the glyphs are rasterized at the subpixel positions
+    /// (glyph id and index of the subpixel position pairs).
+    pub fn load_glyphs_at<D: Device>(
+        &mut self,
+        data: &[u8],
+        glyphs: &[(u32, u8)],
+        atlas: &mut GlyphAtlas<D>,
+        color_atlas: &mut GlyphAtlas<D>,
+    ) -> Result<(), FontError> {
+        let mut face = None;
+        let mut color_face = None;
+        let lcd = self.rendering.antialiasing != TextAntialiasing::Grayscale;
+        let subpixel_positions = self.rendering.subpixel_positions.max(1) as ft::ffi::FT_Pos;
+        let load_flags = self.load_flags();
+        let render_mode = if lcd {
+            RenderMode::Lcd
+        } else {
+            RenderMode::Normal
         };
+        for &(glyph_id, subpixel) in glyphs {
+            let key = GlyphKey {
+                face: self.font_face,
+                font_size: self.font_size,
+                glyph_id,
+                subpixel,
+                mask: GlyphMask::Coverage,
+            };
+            let loaded = glyph_id == 0
+                || match self.find_glyph_at(glyph_id, subpixel) {
+                    Some(ch_info) => {
+                        let atlas = if ch_info.color {
+                            &mut *color_atlas
+                        } else {
+                            &mut *atlas
+                        };
+                        ch_info.width == 0 || ch_info.height == 0 || atlas.find(&key).is_some()
+                    }
+                    None => false,
+                };
+            if loaded {
+                continue;
+            }
 
-        // debug!("Placing chars onto a plane");
+            let face = match face {
+                Some(ref mut face) => face,
+                None => face.insert(Self::load_face(data, &self.font_face, self.font_size)?),
+            };
 
-        // Hashmap doesn't preserve the order but we don't need it anyway.
-        for (_, ch_info) in chars_info.iter_mut() {
-            if cursor_x + ch_info.width > image_width {
-                dump_row(&mut image, &chars_row);
-                chars_row.clear();
-                cursor_x = 0;
-                image_height += ch_box_height;
+            // FreeType representation of rendered glyph 'j':
+            //
+            // b_left   w
+            // +-----+-----+-----+
+            // |     |     |     | font_size - bitmap_top()
+            // +-----+-----+-----+
+            // |     |  x  |     |
+            // |     |     |     |
+            // |     |  x  |     | bitmap_top()
+            // |     |  x  |     |
+            // |     |  x  |     |
+            // |     |  x  |     |
+            // +-----+--x--+-----+
+            // |     | x   |     | rows() - bitmap_top()
+            // |     |x    |     |
+            // +-----------+-----+
+            //      advance.x
+            //
+            // (Read <http://www.freetype.org/freetype2/docs/glyphs/glyphs-3.html>
+            // for more details.)
+            //
+            // Notes:
+            // * Width/height of the rendered glyph generally smaller than the the
+            //   specified font size
+            // * But if we add x/y offsets to the real glyph's dimensions it might
+            //   go beyound that limits (e.g. chars like 'j', 'q')
+            // * `bottom_left()` may be less than zero for some tight characters
+            //   (too push it to the previous one)
+            // * Theoretically `bitmap_top()` may be bigger than the `font_size`
+            //
+            // * Bitmaps of fonts with fixed sizes only are scaled, so the width/height
+            //   of the drawn glyph may differ from the size of the bitmap
+            //
+            // the outline is moved right by the subpixel position (in 26.6 fixed point)
+            let shift = subpixel as ft::ffi::FT_Pos * 64 / subpixel_positions;
+            let mut delta = ft::ffi::FT_Vector { x: shift, y: 0 };
+            unsafe {
+                ft::ffi::FT_Set_Transform(face.raw_mut(), std::ptr::null_mut(), &mut delta);
+            }
+            face.load_glyph(glyph_id, load_flags)?;
+            synthesize(face, self.font_face.synthesis, false)?;
+            face.glyph().render_glyph(render_mode)?;
+            let glyph = face.glyph();
+            let bitmap = glyph.bitmap();
+            let pixel_mode = bitmap.pixel_mode()?;
+            let color_glyph = match pixel_mode {
+                PixelMode::Bgra => None,
+                _ => {
+                    let color_face =
+                        color_face.get_or_insert_with(|| open_color_face(data, &self.font_face));
+                    self.paint_color_glyph(face, color_face.as_ref(), glyph_id, shift as f32 / 64.0)
+                }
+            };
+            let color = pixel_mode == PixelMode::Bgra || color_glyph.is_some();
+            let bitmap_width = match pixel_mode {
+                PixelMode::Lcd => bitmap.width() / 3,
+                _ => bitmap.width(),
+            };
+            let scale = self.bitmap_scale;
+            let x_advance = if !face.is_scalable() {
+                glyph.advance().x as f32 / 64.0 * scale
+            } else if self.font_size.to_pixels().is_none()
+                || self.rendering.hinting == FontHinting::None
+            {
+                // hinted advances are rounded to whole pixels, so use the unhinted one
+                // (16.16 fixed point) to keep the proportions of fractional sizes
+                glyph.linear_hori_advance() as f32 / 65536.0
+            } else {
+                ((glyph.advance().x + 32) >> 6) as f32
+            };
+            // FreeType synthesizes the vertical metrics of fonts without them
+            let metrics = glyph.metrics();
+            let y_advance = if !face.is_scalable() {
+                metrics.vertAdvance as f32 / 64.0 * scale
+            } else if self.font_size.to_pixels().is_none()
+                || self.rendering.hinting == FontHinting::None
+            {
+                glyph.linear_vert_advance() as f32 / 65536.0
+            } else {
+                ((metrics.vertAdvance + 32) >> 6) as f32
+            };
+            let mut ch_info = BitmapChar {
+                x_offset: (glyph.bitmap_left() as f32 * scale).round() as i32,
+                y_offset: self.font_size.get().round() as i32
+                    - (glyph.bitmap_top() as f32 * scale).round() as i32,
+                x_advance,
+                width: (bitmap_width as f32 * scale).round() as i32,
+                height: (bitmap.rows() as f32 * scale).round() as i32,
+                color,
+                // the horizontal origin relative to the vertical one
+                vertical: VerticalGlyphMetrics {
+                    advance: y_advance,
+                    x_offset: (metrics.vertBearingX - metrics.horiBearingX) as f32 / 64.0 * scale,
+                    y_offset: (metrics.vertBearingY + metrics.horiBearingY) as f32 / 64.0 * scale,
+                },
+            };
+            if let Some(image) = &color_glyph {
+                ch_info.x_offset = image.left;
+                ch_info.y_offset = self.font_size.get().round() as i32 - image.top;
+                ch_info.width = image.width as i32;
+                ch_info.height = image.height as i32;
+            }
+            if ch_info.width > 0 && ch_info.height > 0 {
+                let (width, height) = (bitmap_width as u16, bitmap.rows() as u16);
+                let inserted = match (&color_glyph, pixel_mode) {
+                    (Some(image), _) => color_atlas.insert(
+                        key,
+                        image.width as u16,
+                        image.height as u16,
+                        &image.data,
+                    ),
+                    (None, PixelMode::Bgra) => {
+                        color_atlas.insert(key, width, height, &Self::color_bitmap_data(&bitmap))
+                    }
+                    (None, PixelMode::Lcd) => {
+                        atlas.insert(key, width, height, &self.lcd_bitmap_data(&bitmap))
+                    }
+                    _ => atlas.insert(key, width, height, &self.gray_bitmap_data(&bitmap)),
+                };
+                if !inserted {
+                    // bigger than the atlas page, the glyph is not drawn
+                    ch_info.width = 0;
+                    ch_info.height = 0;
+                }
+            }
+            if subpixel == 0 {
+                self.glyphs.insert(glyph_id, ch_info);
+            } else {
+                self.subpixel_glyphs.insert((glyph_id, subpixel), ch_info);
             }
-            let ch_data = ch_info.data.take().unwrap();
-            chars_row.push((ch_info.width, ch_info.height, ch_data));
-            ch_info.tex = [cursor_x as f32, image_height as f32];
-            cursor_x += ch_info.width;
         }
-        dump_row(&mut image, &chars_row);
-        image_height += ch_box_height;
+        Ok(())
+    }
 
-        // Finally, we just precalculate some fields to make it easier to use
-        // our font.
+    /// Makes sure the masks of the glyphs (rasterized at whole pixels) are in the atlas
+    /// (in `atlas` for all glyphs, it has to be grayscale).
+    pub fn load_masks<D: Device>(
+        &mut self,
+        data: &[u8],
+        glyph_ids: &[u32],
+        mask: GlyphMask,
+        atlas: &mut GlyphAtlas<D>,
+    ) -> Result<(), FontError> {
+        let mut face = None;
+        let mut color_face = None;
+        for &glyph_id in glyph_ids {
+            let key = GlyphKey {
+                face: self.font_face,
+                font_size: self.font_size,
+                glyph_id,
+                subpixel: 0,
+                mask,
+            };
+            let loaded = glyph_id == 0
+                || match self.masked_glyphs.get(&(glyph_id, mask)) {
+                    Some(ch_info) => {
+                        ch_info.width == 0 || ch_info.height == 0 || atlas.find(&key).is_some()
+                    }
+                    None => false,
+                };
+            if loaded {
+                continue;
+            }
 
-        for (_, ch_info) in chars_info.iter_mut() {
-            ch_info.tex[0] /= image_width as f32;
-            ch_info.tex[1] /= image_height as f32;
-            ch_info.tex_width = ch_info.width as f32 / image_width as f32;
-            ch_info.tex_height = ch_info.height as f32 / image_height as f32;
+            let face = match face {
+                Some(ref mut face) => face,
+                None => face.insert(Self::load_face(data, &self.font_face, self.font_size)?),
+            };
+            face.load_glyph(glyph_id, self.load_flags())?;
+            synthesize(face, self.font_face.synthesis, false)?;
+            face.glyph().render_glyph(RenderMode::Normal)?;
+            let glyph = face.glyph();
+            let bitmap = glyph.bitmap();
+            let scale = self.bitmap_scale;
+            let mut ch_info = BitmapChar {
+                x_offset: 0,
+                y_offset: 0,
+                // masks are placed at the positions of their glyphs
+                x_advance: 0.0,
+                width: 0,
+                height: 0,
+                color: false,
+                vertical: VerticalGlyphMetrics::default(),
+            };
+            let pixel_mode = bitmap.pixel_mode()?;
+            let color_glyph = match pixel_mode {
+                PixelMode::Bgra => None,
+                _ => {
+                    let color_face =
+                        color_face.get_or_insert_with(|| open_color_face(data, &self.font_face));
+                    self.paint_color_glyph(face, color_face.as_ref(), glyph_id, 0.0)
+                }
+            };
+            // the left and the top of the bitmap, its size and coverage
+            let image = match color_glyph {
+                Some(image) => Some((
+                    image.left,
+                    image.top,
+                    image.width,
+                    image.height,
+                    image.data.chunks_exact(4).map(|pixel| pixel[3]).collect(),
+                )),
+                None if bitmap.width() > 0 && bitmap.rows() > 0 => Some((
+                    glyph.bitmap_left(),
+                    glyph.bitmap_top(),
+                    bitmap.width() as usize,
+                    bitmap.rows() as usize,
+                    match pixel_mode {
+                        PixelMode::Bgra => Self::color_bitmap_data(&bitmap)
+                            .chunks_exact(4)
+                            .map(|pixel| pixel[3])
+                            .collect(),
+                        _ => self.gray_bitmap_data(&bitmap),
+                    },
+                )),
+                None => None,
+            };
+            if let Some((left, top, width, height, coverage)) = image {
+                let mask_image = create_mask(mask, &coverage, width, height, 1.0 / scale);
+                let padding = mask_image.padding as i32;
+                ch_info.x_offset = ((left - padding) as f32 * scale).round() as i32;
+                ch_info.y_offset = self.font_size.get().round() as i32
+                    - ((top + padding) as f32 * scale).round() as i32;
+                // masks bigger than the atlas page are not drawn
+                if atlas.insert(
+                    key,
+                    mask_image.width as u16,
+                    mask_image.height as u16,
+                    &mask_image.data,
+                ) {
+                    ch_info.width = (mask_image.width as f32 * scale).round() as i32;
+                    ch_info.height = (mask_image.height as f32 * scale).round() as i32;
+                }
+            }
+            self.masked_glyphs.insert((glyph_id, mask), ch_info);
         }
+        Ok(())
+    }
 
-        // info!("Image width: {}, image height: {}, total size: {}",
-        //     image_width, image_height, image.len());
+    // glyphs of the `COLR` table that FreeType did not render in color (COLRv1 ones)
+    fn paint_color_glyph(
+        &self,
+        face: &Face,
+        color_face: Option<&ttf_parser::Face>,
+        glyph_id: u32,
+        shift: f32,
+    ) -> Option<ColorGlyphImage> {
+        let color_face = color_face.filter(|_| face.is_scalable())?;
+        // font units to pixels (16.16 fixed point scale to 26.6 fixed point pixels)
+        let scale = face.size_metrics()?.x_scale as f32 / 65536.0 / 64.0;
+        paint_color_glyph(
+            color_face,
+            glyph_id,
+            scale,
+            shift,
+            self.font_face.synthesis.oblique,
+        )
+    }
 
-        Ok(BitmapFont {
-            width: image_width as u16,
-            height: image_height as u16,
-            chars: chars_info,
-            image,
-            font_height: ((face.size_metrics().unwrap().height + 32) >> 6) as u16,
-        })
+    fn load_flags(&self) -> LoadFlag {
+        let target = if self.rendering.antialiasing != TextAntialiasing::Grayscale {
+            LoadFlag::TARGET_LCD
+        } else {
+            LoadFlag::TARGET_NORMAL
+        };
+        LoadFlag::COLOR
+            | match self.rendering.hinting {
+                FontHinting::None => LoadFlag::NO_HINTING,
+                FontHinting::Light => LoadFlag::TARGET_LIGHT,
+                FontHinting::Full => target,
+                FontHinting::Auto => LoadFlag::FORCE_AUTOHINT | target,
+            }
     }
 
-    pub fn get_width(&self) -> u16 {
-        self.width
+    pub fn get_font_face(&self) -> &FontFace {
+        &self.font_face
     }
 
-    pub fn get_height(&self) -> u16 {
-        self.height
+    pub fn get_font_size(&self) -> FontSize {
+        self.font_size
     }
 
-    /// Return 8-bit texture raw data (grayscale).
-    pub fn get_image(&self) -> &[u8] {
-        &self.image
+    pub fn get_rendering(&self) -> &TextRendering {
+        &self.rendering
     }
 
     pub fn get_font_height(&self) -> u16 {
         self.font_height
     }
 
-    pub fn find_char(&self, ch: char) -> Option<&BitmapChar> {
-        self.chars.get(&ch)
+    pub fn get_metrics(&self) -> &FontMetrics {
+        &self.metrics
     }
 
-    /// Get the bounding box size of a string as rendered by this font.
-    pub fn measure(&self, text: &str) -> (i32, i32) {
-        let mut width = 0;
-        let mut max_width = 0;
-        let mut lines = 1;
-
-        for ch in text.chars() {
-            if ch == '\n' {
-                lines += 1;
-                max_width = max_width.max(width);
-                width = 0;
-            } else if ch == '\t' {
-                if let Some(ch_info) = self.find_char(' ') {
-                    width += ch_info.x_advance * 4;
-                }
-            } else {
-                let ch_info = match self.find_char(ch) {
-                    Some(info) => info,
-                    None => continue,
-                };
-                width += ch_info.x_advance;
-            }
+    pub fn find_glyph(&self, glyph_id: u32) -> Option<&BitmapChar> {
+        self.glyphs.get(&glyph_id)
+    }
+
+    /// Returns the glyph rasterized at the subpixel position.
+    pub fn find_glyph_at(&self, glyph_id: u32, subpixel: u8) -> Option<&BitmapChar> {
+        match subpixel {
+            0 => self.glyphs.get(&glyph_id),
+            _ => self.subpixel_glyphs.get(&(glyph_id, subpixel)),
         }
-        max_width = max_width.max(width);
+    }
 
-        (max_width, lines * (self.get_font_height() as i32))
+    /// Returns the mask of the glyph (`GlyphMask::Coverage` for the glyph itself).
+    pub fn find_glyph_mask(&self, glyph_id: u32, mask: GlyphMask) -> Option<&BitmapChar> {
+        match mask {
+            GlyphMask::Coverage => self.glyphs.get(&glyph_id),
+            _ => self.masked_glyphs.get(&(glyph_id, mask)),
+        }
     }
+}
 
-    pub fn measure_each_char(&self, text: &str) -> (Vec<i16>, i32) {
-        let mut pos_px = Vec::with_capacity(text.len());
+impl GlyphProvider for BitmapFont {
+    fn glyph_index(&self, ch: char) -> Option<u32> {
+        self.char_map.get(&ch).copied()
+    }
 
-        for ch in text.chars() {
-            let ch_info = match self.find_char(ch) {
-                Some(info) => info,
-                None => continue,
-            };
-            pos_px.push(ch_info.x_advance as i16);
-        }
+    fn glyph_advance(&self, glyph_id: u32) -> f32 {
+        self.glyphs
+            .get(&glyph_id)
+            .map_or(0.0, |ch_info| ch_info.x_advance)
+    }
 
-        (pos_px, self.get_font_height() as i32)
+    fn glyph_vertical_metrics(&self, glyph_id: u32) -> VerticalGlyphMetrics {
+        self.glyphs
+            .get(&glyph_id)
+            .map_or(VerticalGlyphMetrics::default(), |ch_info| ch_info.vertical)
     }
 }
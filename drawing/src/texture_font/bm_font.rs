@@ -0,0 +1,1317 @@
+//! Fonts pre-rendered into page images with the AngelCode BMFont tool (or compatible).
+//!
+//! The font descriptor (the `.fnt` file in the text or the binary format) contains
+//! the position of every character in the page images, its metrics and the kerning
+//! pairs. Page images are decoded by the application (see `BmFont::page_files()`)
+//! and set with `BmFont::set_page()`, so no rasterizer is needed at runtime.
+
+use anyhow::Result;
+
+use crate::backend::*;
+use crate::clipping::clip_image;
+use crate::color::*;
+use crate::font::*;
+use crate::paint::Paint;
+use crate::primitive::PathElement;
+use crate::shaping::*;
+use crate::texture_font::glyph_atlas::*;
+use crate::texture_font::glyph_mask::{create_mask, effect_layers};
+use crate::units::*;
+use crate::vertical::*;
+
+use std::collections::HashMap;
+use std::str::FromStr;
+use thiserror::Error;
+
+/// Error of the BMFont descriptor or its pages.
+#[derive(Error, Debug)]
+pub enum BmFontError {
+    #[error("Invalid BMFont descriptor: {0}")]
+    InvalidDescriptor(String),
+    #[error("Invalid image of the BMFont page {0}")]
+    InvalidPage(usize),
+    #[error("Image of the BMFont page {0} is not set")]
+    MissingPage(usize),
+    #[error("BMFont has no glyph outlines")]
+    NoOutlines,
+}
+
+// Character of the font (in pixels of the font size).
+#[derive(Debug, Copy, Clone, Default)]
+struct BmChar {
+    // rectangle of the character in the page image
+    x: u16,
+    y: u16,
+    width: u16,
+    height: u16,
+    // position of the image relative to the pen at the top of the line
+    x_offset: i16,
+    y_offset: i16,
+    x_advance: i16,
+    page: usize,
+    // channels of the page with the character (1 blue, 2 green, 4 red, 8 alpha, 15 all)
+    channels: u8,
+}
+
+// Content of the red, green, blue and alpha channels of the pages
+// (0 glyph, 1 outline, 2 glyph and outline, 3 zero, 4 one).
+const GLYPH_CONTENT: u8 = 0;
+const ZERO_CONTENT: u8 = 3;
+const ONE_CONTENT: u8 = 4;
+
+// Content of the font descriptor.
+#[derive(Debug, Default)]
+struct Descriptor {
+    // size the font was rendered at
+    size: f32,
+    line_height: f32,
+    base: f32,
+    channel_contents: [u8; 4],
+    page_files: Vec<String>,
+    chars: HashMap<u32, BmChar>,
+    kerning: HashMap<(u32, u32), i16>,
+}
+
+impl Descriptor {
+    fn parse(bytes: &[u8]) -> Result<Self, BmFontError> {
+        let descriptor = if bytes.starts_with(b"BMF") {
+            Self::parse_binary(bytes)?
+        } else {
+            let text = std::str::from_utf8(bytes)
+                .map_err(|_| BmFontError::InvalidDescriptor("not a text file".to_string()))?;
+            Self::parse_text(text)?
+        };
+        if descriptor.line_height <= 0.0 {
+            return Err(BmFontError::InvalidDescriptor(
+                "missing line height".to_string(),
+            ));
+        }
+        if let Some(ch) = descriptor
+            .chars
+            .values()
+            .find(|ch| ch.page >= descriptor.page_files.len())
+        {
+            return Err(BmFontError::InvalidDescriptor(format!(
+                "missing page {}",
+                ch.page
+            )));
+        }
+        Ok(descriptor)
+    }
+
+    fn parse_text(text: &str) -> Result<Self, BmFontError> {
+        let mut descriptor = Descriptor {
+            channel_contents: [ONE_CONTENT, ONE_CONTENT, ONE_CONTENT, GLYPH_CONTENT],
+            ..Descriptor::default()
+        };
+        for line in text.lines() {
+            let (tag, attributes) = text_line_attributes(line);
+            match tag {
+                "info" => {
+                    // negative size means the size matches the height of the characters
+                    descriptor.size = number::<f32>(&attributes, "size")?.abs();
+                }
+                "common" => {
+                    descriptor.line_height = number(&attributes, "lineHeight")?;
+                    descriptor.base = number(&attributes, "base")?;
+                    for (content, key) in descriptor.channel_contents.iter_mut().zip([
+                        "redChnl",
+                        "greenChnl",
+                        "blueChnl",
+                        "alphaChnl",
+                    ]) {
+                        if attributes.contains_key(key) {
+                            *content = number(&attributes, key)?;
+                        }
+                    }
+                }
+                "page" => {
+                    let id: usize = number(&attributes, "id")?;
+                    if id >= descriptor.page_files.len() {
+                        descriptor.page_files.resize(id + 1, String::new());
+                    }
+                    descriptor.page_files[id] = attributes
+                        .get("file")
+                        .map_or(String::new(), |file| file.to_string());
+                }
+                "char" => {
+                    // the character with id -1 is used for missing characters by some tools
+                    let id: i64 = number(&attributes, "id")?;
+                    if id >= 0 {
+                        descriptor.chars.insert(
+                            id as u32,
+                            BmChar {
+                                x: number(&attributes, "x")?,
+                                y: number(&attributes, "y")?,
+                                width: number(&attributes, "width")?,
+                                height: number(&attributes, "height")?,
+                                x_offset: number(&attributes, "xoffset")?,
+                                y_offset: number(&attributes, "yoffset")?,
+                                x_advance: number(&attributes, "xadvance")?,
+                                page: number(&attributes, "page")?,
+                                channels: if attributes.contains_key("chnl") {
+                                    number(&attributes, "chnl")?
+                                } else {
+                                    15
+                                },
+                            },
+                        );
+                    }
+                }
+                "kerning" => {
+                    descriptor.kerning.insert(
+                        (
+                            number(&attributes, "first")?,
+                            number(&attributes, "second")?,
+                        ),
+                        number(&attributes, "amount")?,
+                    );
+                }
+                _ => (),
+            }
+        }
+        if descriptor.size <= 0.0 {
+            descriptor.size = descriptor.line_height;
+        }
+        Ok(descriptor)
+    }
+
+    // Binary format version 3 (little endian blocks of the same content as the text format).
+    fn parse_binary(bytes: &[u8]) -> Result<Self, BmFontError> {
+        if bytes.get(3) != Some(&3) {
+            return Err(BmFontError::InvalidDescriptor(
+                "unsupported binary version".to_string(),
+            ));
+        }
+        let mut descriptor = Descriptor::default();
+        let mut blocks = BinaryReader { bytes, pos: 4 };
+        while blocks.pos < bytes.len() {
+            let block_type = blocks.u8()?;
+            let block_size = blocks.u32()? as usize;
+            let block = blocks.take(block_size)?;
+            let reader = &mut BinaryReader {
+                bytes: block,
+                pos: 0,
+            };
+            match block_type {
+                1 => {
+                    descriptor.size = (reader.u16()? as i16).unsigned_abs() as f32;
+                }
+                2 => {
+                    descriptor.line_height = reader.u16()? as f32;
+                    descriptor.base = reader.u16()? as f32;
+                    // scale, pages and packed flag
+                    reader.take(7)?;
+                    let alpha = reader.u8()?;
+                    descriptor.channel_contents = [reader.u8()?, reader.u8()?, reader.u8()?, alpha];
+                }
+                3 => {
+                    while reader.pos < block.len() {
+                        let file = reader.string()?;
+                        descriptor.page_files.push(file);
+                    }
+                }
+                4 => {
+                    while reader.pos < block.len() {
+                        let id = reader.u32()?;
+                        let ch = BmChar {
+                            x: reader.u16()?,
+                            y: reader.u16()?,
+                            width: reader.u16()?,
+                            height: reader.u16()?,
+                            x_offset: reader.u16()? as i16,
+                            y_offset: reader.u16()? as i16,
+                            x_advance: reader.u16()? as i16,
+                            page: reader.u8()? as usize,
+                            channels: reader.u8()?,
+                        };
+                        if id != u32::MAX {
+                            descriptor.chars.insert(id, ch);
+                        }
+                    }
+                }
+                5 => {
+                    while reader.pos < block.len() {
+                        let pair = (reader.u32()?, reader.u32()?);
+                        descriptor.kerning.insert(pair, reader.u16()? as i16);
+                    }
+                }
+                _ => (),
+            }
+        }
+        if descriptor.size <= 0.0 {
+            descriptor.size = descriptor.line_height;
+        }
+        Ok(descriptor)
+    }
+}
+
+// Splits the line of the text descriptor into the tag and its attributes
+// (values with spaces are quoted).
+fn text_line_attributes(line: &str) -> (&str, HashMap<&str, &str>) {
+    let line = line.trim();
+    let (tag, mut rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+    let mut attributes = HashMap::new();
+    while let Some((key, value)) = rest.split_once('=') {
+        let (value, next) = match value.strip_prefix('"') {
+            Some(quoted) => quoted.split_once('"').unwrap_or((quoted, "")),
+            None => value.split_once(char::is_whitespace).unwrap_or((value, "")),
+        };
+        attributes.insert(key.trim(), value);
+        rest = next;
+    }
+    (tag, attributes)
+}
+
+// Parses the number of the attribute (zero if the attribute is missing).
+fn number<T: FromStr + Default>(
+    attributes: &HashMap<&str, &str>,
+    key: &str,
+) -> Result<T, BmFontError> {
+    match attributes.get(key) {
+        Some(value) => value
+            .parse()
+            .map_err(|_| BmFontError::InvalidDescriptor(format!("invalid {}: {}", key, value))),
+        None => Ok(T::default()),
+    }
+}
+
+struct BinaryReader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> BinaryReader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], BmFontError> {
+        let bytes = self
+            .pos
+            .checked_add(len)
+            .and_then(|end| self.bytes.get(self.pos..end))
+            .ok_or_else(|| BmFontError::InvalidDescriptor("unexpected end".to_string()))?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, BmFontError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, BmFontError> {
+        let bytes = self.take(2)?;
+        Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn u32(&mut self) -> Result<u32, BmFontError> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    // null terminated string
+    fn string(&mut self) -> Result<String, BmFontError> {
+        let len = self.bytes[self.pos..]
+            .iter()
+            .position(|&byte| byte == 0)
+            .ok_or_else(|| BmFontError::InvalidDescriptor("unterminated string".to_string()))?;
+        let string = String::from_utf8_lossy(self.take(len)?).to_string();
+        self.pos += 1;
+        Ok(string)
+    }
+}
+
+// Decoded page image.
+struct Page {
+    width: usize,
+    height: usize,
+    format: ColorFormat,
+    data: Vec<u8>,
+}
+
+// Pixels of the character cut from the page.
+enum CharPixels {
+    Coverage(Vec<u8>),
+    // straight alpha RGBA
+    Color(Vec<u8>),
+}
+
+impl Page {
+    fn char_pixels(&self, ch: &BmChar, channel: Option<usize>) -> CharPixels {
+        let (bytes_per_pixel, pixel_size) = match (self.format, channel) {
+            (ColorFormat::RGBA, Some(_)) => (4, 1),
+            (ColorFormat::RGBA, None) => (4, 4),
+            (ColorFormat::Y8, _) => (1, 1),
+        };
+        let mut pixels = Vec::with_capacity(ch.width as usize * ch.height as usize * pixel_size);
+        for y in ch.y as usize..ch.y as usize + ch.height as usize {
+            for x in ch.x as usize..ch.x as usize + ch.width as usize {
+                // characters are clipped to the page
+                if x >= self.width || y >= self.height {
+                    pixels.resize(pixels.len() + pixel_size, 0);
+                    continue;
+                }
+                let index = (y * self.width + x) * bytes_per_pixel;
+                match channel {
+                    Some(channel) if bytes_per_pixel == 4 => {
+                        pixels.push(self.data[index + channel])
+                    }
+                    _ => pixels.extend_from_slice(&self.data[index..index + pixel_size]),
+                }
+            }
+        }
+        if pixel_size == 4 {
+            CharPixels::Color(pixels)
+        } else {
+            CharPixels::Coverage(pixels)
+        }
+    }
+}
+
+// Index of the RGBA channel with the coverage of the character (None for color characters,
+// like the ones with colored outlines).
+fn coverage_channel(channel_contents: [u8; 4], channels: u8) -> Option<usize> {
+    let constant = |content: u8| content == ZERO_CONTENT || content == ONE_CONTENT;
+    match channels {
+        1 => Some(2),
+        2 => Some(1),
+        4 => Some(0),
+        8 => Some(3),
+        _ => {
+            let [red, green, blue, alpha] = channel_contents;
+            if constant(alpha) {
+                Some(0)
+            } else if [red, green, blue]
+                .iter()
+                .all(|&content| content == GLYPH_CONTENT || constant(content))
+            {
+                Some(3)
+            } else {
+                None
+            }
+        }
+    }
+}
+
+// Image of the character (or its mask) in the atlas.
+struct GlyphImage {
+    page: usize,
+    // the image is in the color atlas
+    color: bool,
+    // position and size relative to the pen at the top of the line (in pixels of the drawn text)
+    rect: [f32; 4],
+    uv: [f32; 4],
+}
+
+struct BmGlyphProvider<'a> {
+    chars: &'a HashMap<u32, BmChar>,
+    line_height: f32,
+    base: f32,
+    scale: f32,
+}
+
+impl GlyphProvider for BmGlyphProvider<'_> {
+    // glyph indices are the character codes
+    fn glyph_index(&self, ch: char) -> Option<u32> {
+        let id = ch as u32;
+        if id != 0 && self.chars.contains_key(&id) {
+            Some(id)
+        } else {
+            None
+        }
+    }
+
+    fn glyph_advance(&self, glyph_id: u32) -> f32 {
+        self.chars
+            .get(&glyph_id)
+            .map_or(0.0, |ch| ch.x_advance as f32 * self.scale)
+    }
+
+    // upright characters are centered in the column cells of the line height
+    fn glyph_vertical_metrics(&self, glyph_id: u32) -> VerticalGlyphMetrics {
+        VerticalGlyphMetrics {
+            advance: self.line_height * self.scale,
+            x_offset: -self.glyph_advance(glyph_id) * 0.5,
+            y_offset: self.base * self.scale,
+        }
+    }
+}
+
+// Draws the glyphs added to the atlas. Glyphs of the font size are placed
+// at whole pixels, glyphs of other sizes are scaled with linear filtering.
+fn draw_atlas<D: Device>(
+    atlas: &mut GlyphAtlas<D>,
+    device: &mut D,
+    target: &D::RenderTarget,
+    scaled: bool,
+    transform: UnknownToDeviceTransform,
+) -> Result<()> {
+    if scaled {
+        atlas.draw_filtered_at(device, target, transform)
+    } else {
+        atlas.draw_at(device, target, transform)
+    }
+}
+
+/// Font drawn from the characters pre-rendered by the AngelCode BMFont tool
+/// (or other tools writing the BMFont format).
+///
+/// `Font::create()` parses the font descriptor (the `.fnt` file in the text
+/// or the binary format), the page images have to be set with `set_page()` before
+/// the text is drawn. Text is measured and shaped without the pages, the kerning
+/// pairs of the descriptor are applied (the shaping engine is not used).
+///
+/// Text drawn at the size of the font is placed at whole pixels, other sizes
+/// are scaled. Characters of the pages with colors (like colored outlines) are drawn
+/// untinted, the outline, glow and shadow of `TextEffects` are computed from
+/// the coverage of the characters. Face index, variations and synthesis
+/// of `FontFace` are ignored, the font has no glyph outlines (`get_outline()`
+/// returns an error).
+pub struct BmFont<D: Device> {
+    size: f32,
+    line_height: f32,
+    base: f32,
+    channel_contents: [u8; 4],
+    page_files: Vec<String>,
+    pages: Vec<Option<Page>>,
+    chars: HashMap<u32, BmChar>,
+    kerning: HashMap<(u32, u32), i16>,
+    // margins of the masks in the atlas
+    mask_paddings: HashMap<GlyphKey, u16>,
+    atlas: GlyphAtlas<D>,
+    color_atlas: GlyphAtlas<D>,
+}
+
+impl<D: Device> BmFont<D> {
+    /// Size (in pixels) the characters were rendered at.
+    pub fn font_size(&self) -> f32 {
+        self.size
+    }
+
+    /// File names of the page images as written in the descriptor (indexed by page).
+    pub fn page_files(&self) -> &[String] {
+        &self.page_files
+    }
+
+    /// Sets the decoded image of the page (8-bit grayscale or RGBA with straight alpha).
+    ///
+    /// Characters of the replaced page are removed from the atlases.
+    pub fn set_page(
+        &mut self,
+        page: usize,
+        width: u16,
+        height: u16,
+        format: ColorFormat,
+        data: Vec<u8>,
+    ) -> Result<(), BmFontError> {
+        let bytes_per_pixel = match format {
+            ColorFormat::RGBA => 4,
+            ColorFormat::Y8 => 1,
+        };
+        if page >= self.pages.len()
+            || data.len() != width as usize * height as usize * bytes_per_pixel
+        {
+            return Err(BmFontError::InvalidPage(page));
+        }
+        if self.pages[page].is_some() {
+            self.atlas.clear();
+            self.color_atlas.clear();
+            self.mask_paddings.clear();
+        }
+        self.pages[page] = Some(Page {
+            width: width as usize,
+            height: height as usize,
+            format,
+            data,
+        });
+        Ok(())
+    }
+
+    pub fn glyph_atlas(&self) -> &GlyphAtlas<D> {
+        &self.atlas
+    }
+
+    pub fn glyph_atlas_mut(&mut self) -> &mut GlyphAtlas<D> {
+        &mut self.atlas
+    }
+
+    /// Characters of the pages with colors are copied into the separate RGBA atlas.
+    pub fn color_glyph_atlas(&self) -> &GlyphAtlas<D> {
+        &self.color_atlas
+    }
+
+    pub fn color_glyph_atlas_mut(&mut self) -> &mut GlyphAtlas<D> {
+        &mut self.color_atlas
+    }
+
+    fn metrics(&self) -> FontMetrics {
+        // the descriptor has no other metrics than the line height and the baseline
+        let top = |ch: char| {
+            self.chars
+                .get(&(ch as u32))
+                .map_or(0.0, |ch| self.base - ch.y_offset as f32)
+        };
+        FontMetrics {
+            baseline: self.base,
+            ascent: self.base,
+            descent: self.line_height - self.base,
+            line_gap: 0.0,
+            line_height: self.line_height,
+            underline_position: (self.size / 10.0).round(),
+            underline_thickness: (self.size / 16.0).round().max(1.0),
+            x_height: top('x'),
+            cap_height: top('H'),
+        }
+    }
+
+    fn shape(&self, params: FontParams, text: &str) -> Vec<Vec<ShapedGlyph>> {
+        let size = params.font_size().get();
+        let scale = size / self.size;
+        let provider = BmGlyphProvider {
+            chars: &self.chars,
+            line_height: self.line_height,
+            base: self.base,
+            scale,
+        };
+        let shape = if params.writing_mode.is_vertical() {
+            shape_vertical_line
+        } else {
+            shape_line
+        };
+        // without the font file the characters are mapped by the provider
+        let face = ShapingFace::new(&[], &params.face);
+        text.split('\n')
+            .map(|line| {
+                let mut glyphs = shape(&face, size, &provider, line, params.direction);
+                self.kern(line, &mut glyphs, scale);
+                glyphs
+            })
+            .collect()
+    }
+
+    // Applies the kerning to the pairs of characters next to each other
+    // (except upright characters of vertical text).
+    fn kern(&self, text: &str, glyphs: &mut [ShapedGlyph], scale: f32) {
+        if self.kerning.is_empty() {
+            return;
+        }
+        for i in 1..glyphs.len() {
+            let (left, right) = (glyphs[i - 1], glyphs[i]);
+            if left.upright || right.upright {
+                continue;
+            }
+            // glyphs of right-to-left runs are in reverse order
+            let (first, second) = if left.cluster < right.cluster {
+                (left, right)
+            } else {
+                (right, left)
+            };
+            let first_len = text[first.cluster..]
+                .chars()
+                .next()
+                .map_or(0, char::len_utf8);
+            if first.cluster + first_len != second.cluster {
+                continue;
+            }
+            if let Some(&amount) = self.kerning.get(&(first.glyph_id, second.glyph_id)) {
+                glyphs[i - 1].x_advance += amount as f32 * scale;
+            }
+        }
+    }
+
+    fn glyph_key(&self, glyph_id: u32, mask: GlyphMask, size: FontSize) -> GlyphKey {
+        GlyphKey {
+            face: FontFace::default(),
+            // characters are stored once, masks for every size they are drawn at
+            font_size: match mask {
+                GlyphMask::Coverage => FontSize::new(self.size),
+                _ => size,
+            },
+            glyph_id,
+            subpixel: 0,
+            mask,
+        }
+    }
+
+    // Makes sure the characters (or their masks) are in the atlases.
+    fn load_glyphs(
+        &mut self,
+        glyph_ids: &[u32],
+        mask: GlyphMask,
+        size: FontSize,
+    ) -> Result<(), BmFontError> {
+        for &glyph_id in glyph_ids {
+            let ch = match self.chars.get(&glyph_id) {
+                Some(ch) if glyph_id != 0 && ch.width > 0 && ch.height > 0 => *ch,
+                _ => continue,
+            };
+            let key = self.glyph_key(glyph_id, mask, size);
+            if self.atlas.find(&key).is_some() || self.color_atlas.find(&key).is_some() {
+                continue;
+            }
+            let page = self.pages[ch.page]
+                .as_ref()
+                .ok_or(BmFontError::MissingPage(ch.page))?;
+            let pixels =
+                page.char_pixels(&ch, coverage_channel(self.channel_contents, ch.channels));
+
+            // glyphs that don't fit now are inserted again with the next batch
+            let (width, height) = (ch.width, ch.height);
+            if mask == GlyphMask::Coverage {
+                match pixels {
+                    CharPixels::Coverage(data) => self.atlas.insert(key, width, height, &data),
+                    CharPixels::Color(data) => self.color_atlas.insert(key, width, height, &data),
+                };
+                continue;
+            }
+            let coverage = match pixels {
+                CharPixels::Coverage(data) => data,
+                CharPixels::Color(data) => data.chunks_exact(4).map(|pixel| pixel[3]).collect(),
+            };
+            let mask_image = create_mask(
+                mask,
+                &coverage,
+                width as usize,
+                height as usize,
+                self.size / size.get(),
+            );
+            if self.atlas.insert(
+                key,
+                mask_image.width as u16,
+                mask_image.height as u16,
+                &mask_image.data,
+            ) {
+                self.mask_paddings.insert(key, mask_image.padding as u16);
+            }
+        }
+        Ok(())
+    }
+
+    fn glyph_image(
+        &mut self,
+        glyph_id: u32,
+        mask: GlyphMask,
+        size: FontSize,
+    ) -> Option<GlyphImage> {
+        let ch = *self.chars.get(&glyph_id)?;
+        let key = self.glyph_key(glyph_id, mask, size);
+        let (color, atlas_glyph) = match self.atlas.find(&key) {
+            Some(atlas_glyph) => (false, atlas_glyph),
+            None => (true, self.color_atlas.find(&key)?),
+        };
+        let padding = match mask {
+            GlyphMask::Coverage => 0.0,
+            _ => *self.mask_paddings.get(&key)? as f32,
+        };
+        let scale = size.get() / self.size;
+        Some(GlyphImage {
+            page: atlas_glyph.page,
+            color,
+            rect: [
+                (ch.x_offset as f32 - padding) * scale,
+                (ch.y_offset as f32 - padding) * scale,
+                (ch.width as f32 + 2.0 * padding) * scale,
+                (ch.height as f32 + 2.0 * padding) * scale,
+            ],
+            uv: atlas_glyph.uv,
+        })
+    }
+
+    // Adds the characters (or their masks) of the shaped text to the atlases.
+    fn add_glyphs(
+        &mut self,
+        lines: &[Vec<ShapedGlyph>],
+        pos: [f32; 2],
+        clipping_rect: Rect,
+        (mask, size): (GlyphMask, FontSize),
+        color: Color,
+    ) {
+        let line_height = self.line_height * size.get() / self.size;
+        let untinted = [1.0, 1.0, 1.0, color[3]];
+        let mut y = pos[1];
+        for line in lines {
+            let mut x = pos[0];
+            for glyph in line {
+                let image = if glyph.glyph_id != 0 {
+                    self.glyph_image(glyph.glyph_id, mask, size)
+                } else {
+                    None
+                };
+                if let Some(image) = image {
+                    if let Some(clipped) = clip_image(
+                        x + glyph.x_offset + image.rect[0],
+                        y + glyph.y_offset + image.rect[1],
+                        image.rect[2],
+                        image.rect[3],
+                        clipping_rect.origin.x,
+                        clipping_rect.origin.y,
+                        clipping_rect.size.width,
+                        clipping_rect.size.height,
+                        &image.uv,
+                    ) {
+                        let (atlas, color) = if image.color {
+                            (&mut self.color_atlas, untinted)
+                        } else {
+                            (&mut self.atlas, color)
+                        };
+                        atlas.add_image(image.page, clipped, color);
+                    }
+                }
+                x += glyph.x_advance;
+            }
+            y += line_height;
+        }
+    }
+
+    // Adds the transformed characters (or their masks) of the shaped text to the atlases.
+    // Glyphs with the center of the advance outside of the clipping rectangle are skipped.
+    fn add_transformed(
+        &mut self,
+        lines: &[Vec<ShapedGlyph>],
+        glyph_transforms: &[Option<PixelTransform>],
+        clipping_rect: Rect,
+        (mask, size): (GlyphMask, FontSize),
+        color: Color,
+    ) {
+        let line_height = self.line_height * size.get() / self.size;
+        let untinted = [1.0, 1.0, 1.0, color[3]];
+        let mut glyph_transforms = glyph_transforms.iter();
+        let mut y = 0.0;
+        for line in lines {
+            let mut x = 0.0;
+            for glyph in line {
+                let glyph_x = x + glyph.x_offset;
+                let advance_center =
+                    PixelPoint::new(x + glyph.x_advance * 0.5, y + line_height * 0.5);
+                x += glyph.x_advance;
+                let glyph_transform = match glyph_transforms.next() {
+                    Some(Some(glyph_transform)) if glyph.glyph_id != 0 => glyph_transform,
+                    _ => continue,
+                };
+                let center = glyph_transform.transform_point(advance_center);
+                if !clipping_rect.contains(center.to_untyped()) {
+                    continue;
+                }
+                if let Some(image) = self.glyph_image(glyph.glyph_id, mask, size) {
+                    let left = glyph_x + image.rect[0];
+                    let top = y + glyph.y_offset + image.rect[1];
+                    let right = left + image.rect[2];
+                    let bottom = top + image.rect[3];
+                    let corners = [[left, top], [left, bottom], [right, bottom], [right, top]].map(
+                        |[x, y]| {
+                            glyph_transform
+                                .transform_point(PixelPoint::new(x, y))
+                                .to_array()
+                        },
+                    );
+                    let (atlas, color) = if image.color {
+                        (&mut self.color_atlas, untinted)
+                    } else {
+                        (&mut self.atlas, color)
+                    };
+                    atlas.add_quad(image.page, corners, image.uv, color);
+                }
+            }
+            y += line_height;
+        }
+    }
+
+    // Draws the effects of the shaped text (the text has to be drawn after them).
+    fn draw_effects(
+        &mut self,
+        device: &mut D,
+        target: &D::RenderTarget,
+        lines: &[Vec<ShapedGlyph>],
+        params: &TextDrawParams,
+    ) -> Result<()> {
+        let TextDrawParams {
+            pos,
+            clipping_rect,
+            font_params,
+            transform,
+            ..
+        } = *params;
+        let size = font_params.font_size();
+        let glyph_ids = unique_glyphs(lines.iter().flatten().map(|glyph| glyph.glyph_id));
+        for (mask, offset, color) in effect_layers(&font_params.effects) {
+            self.load_glyphs(&glyph_ids, mask, size)?;
+            self.add_glyphs(
+                lines,
+                [pos.x + offset[0], pos.y + offset[1]],
+                clipping_rect,
+                (mask, size),
+                color,
+            );
+            draw_atlas(
+                &mut self.atlas,
+                device,
+                target,
+                size.get() != self.size,
+                transform,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl<D: Device> Font<D> for BmFont<D> {
+    fn create(bytes: Vec<u8>) -> Result<Self> {
+        let descriptor = Descriptor::parse(&bytes)?;
+        Ok(BmFont {
+            size: descriptor.size,
+            line_height: descriptor.line_height,
+            base: descriptor.base,
+            channel_contents: descriptor.channel_contents,
+            pages: descriptor.page_files.iter().map(|_| None).collect(),
+            page_files: descriptor.page_files,
+            chars: descriptor.chars,
+            kerning: descriptor.kerning,
+            mask_paddings: HashMap::new(),
+            atlas: GlyphAtlas::new(DEFAULT_PAGE_SIZE, DEFAULT_MEMORY_BUDGET),
+            color_atlas: GlyphAtlas::with_format(
+                DEFAULT_PAGE_SIZE,
+                DEFAULT_MEMORY_BUDGET,
+                ColorFormat::RGBA,
+            ),
+        })
+    }
+
+    // bitmap fonts have one face
+    fn has_char(&mut self, _face: FontFace, ch: char) -> bool {
+        ch != '\0' && self.chars.contains_key(&(ch as u32))
+    }
+
+    fn draw(
+        &mut self,
+        device: &mut D,
+        target: &D::RenderTarget,
+        color: &Color,
+        text: &str,
+        pos: Point,
+        clipping_rect: Rect,
+        font_params: FontParams,
+        transform: UnknownToDeviceTransform,
+    ) -> Result<()> {
+        let params = TextDrawParams::new(pos, clipping_rect, font_params, transform);
+        if font_params.writing_mode.is_vertical() {
+            return draw_vertical(self, device, target, GlyphFill::Color(color), text, params);
+        }
+        let size = font_params.font_size();
+        let scaled = size.get() != self.size;
+        let pos = if scaled { pos } else { pos.round() };
+        let lines = self.shape(font_params, text);
+        self.atlas.next_batch();
+        self.color_atlas.next_batch();
+        self.draw_effects(device, target, &lines, &params.with_pos(pos))?;
+        let glyph_ids = unique_glyphs(lines.iter().flatten().map(|glyph| glyph.glyph_id));
+        self.load_glyphs(&glyph_ids, GlyphMask::Coverage, size)?;
+        self.add_glyphs(
+            &lines,
+            [pos.x, pos.y],
+            clipping_rect,
+            (GlyphMask::Coverage, size),
+            *color,
+        );
+        draw_atlas(&mut self.atlas, device, target, scaled, transform)?;
+        self.color_atlas.draw_at(device, target, transform)
+    }
+
+    fn draw_paint(
+        &mut self,
+        device: &mut D,
+        target: &D::RenderTarget,
+        paint: &Paint,
+        paint_texture: Option<&D::Texture>,
+        text: &str,
+        params: TextDrawParams,
+    ) -> Result<()> {
+        let TextDrawParams {
+            pos,
+            clipping_rect,
+            scissor,
+            font_params,
+            transform,
+            ..
+        } = params;
+        if font_params.writing_mode.is_vertical() {
+            let fill = GlyphFill::Paint(paint, paint_texture);
+            return draw_vertical(self, device, target, fill, text, params);
+        }
+        let size = font_params.font_size();
+        let pos = if size.get() != self.size {
+            pos
+        } else {
+            pos.round()
+        };
+        let lines = self.shape(font_params, text);
+        self.atlas.next_batch();
+        self.color_atlas.next_batch();
+        self.draw_effects(device, target, &lines, &params.with_pos(pos))?;
+        let glyph_ids = unique_glyphs(lines.iter().flatten().map(|glyph| glyph.glyph_id));
+        self.load_glyphs(&glyph_ids, GlyphMask::Coverage, size)?;
+        self.add_glyphs(
+            &lines,
+            [pos.x, pos.y],
+            clipping_rect,
+            (GlyphMask::Coverage, size),
+            [1.0, 1.0, 1.0, 1.0],
+        );
+        let paint = GlyphPaint {
+            paint,
+            texture: paint_texture,
+            scissor,
+        };
+        self.atlas
+            .draw_paint_at(device, target, &paint, transform)?;
+        // color glyphs keep their own colors
+        self.color_atlas.draw_at(device, target, transform)
+    }
+
+    fn draw_glyphs_transformed(
+        &mut self,
+        device: &mut D,
+        target: &D::RenderTarget,
+        fill: GlyphFill<D::Texture>,
+        text: &str,
+        glyph_transforms: &[Option<PixelTransform>],
+        params: TextDrawParams,
+    ) -> Result<()> {
+        let TextDrawParams {
+            pos,
+            clipping_rect,
+            scissor,
+            font_params,
+            transform,
+            ..
+        } = params;
+        let size = font_params.font_size();
+        let lines = self.shape(font_params, text);
+        self.atlas.next_batch();
+        self.color_atlas.next_batch();
+        let glyph_transforms = &in_columns(
+            &lines,
+            font_params.writing_mode,
+            &self.metrics().scale(size.get() / self.size),
+            glyph_transforms,
+            pos,
+        );
+        let glyph_ids = unique_glyphs(lines.iter().flatten().map(|glyph| glyph.glyph_id));
+        for (mask, offset, color) in effect_layers(&font_params.effects) {
+            self.load_glyphs(&glyph_ids, mask, size)?;
+            // the effects are moved after the glyphs are transformed
+            let offset_transforms: Vec<Option<PixelTransform>> = glyph_transforms
+                .iter()
+                .map(|glyph_transform| {
+                    glyph_transform.map(|glyph_transform| {
+                        glyph_transform.then_translate(euclid::vec2(offset[0], offset[1]))
+                    })
+                })
+                .collect();
+            self.add_transformed(
+                &lines,
+                &offset_transforms,
+                clipping_rect,
+                (mask, size),
+                color,
+            );
+            self.atlas.draw_filtered_at(device, target, transform)?;
+        }
+        self.load_glyphs(&glyph_ids, GlyphMask::Coverage, size)?;
+        let color = match fill {
+            GlyphFill::Color(color) => *color,
+            GlyphFill::Paint(..) => [1.0, 1.0, 1.0, 1.0],
+        };
+        self.add_transformed(
+            &lines,
+            glyph_transforms,
+            clipping_rect,
+            (GlyphMask::Coverage, size),
+            color,
+        );
+        match fill {
+            GlyphFill::Color(_) => self.atlas.draw_filtered_at(device, target, transform)?,
+            GlyphFill::Paint(paint, texture) => {
+                let paint = GlyphPaint {
+                    paint,
+                    texture,
+                    scissor,
+                };
+                self.atlas
+                    .draw_paint_filtered_at(device, target, &paint, transform)?
+            }
+        }
+        // color glyphs keep their own colors
+        self.color_atlas.draw_at(device, target, transform)
+    }
+
+    fn get_dimensions(&mut self, params: FontParams, text: &str) -> Result<(u16, u16)> {
+        let lines = self.shape(params, text);
+        let line_height = self.line_height * params.font_size().get() / self.size;
+        let height = lines.len() as f32 * line_height;
+        if params.writing_mode.is_vertical() {
+            // columns of the line height
+            return Ok((height.round() as u16, text_width(&lines).round() as u16));
+        }
+        Ok((text_width(&lines).round() as u16, height.round() as u16))
+    }
+
+    fn get_metrics(&mut self, params: FontParams) -> Result<FontMetrics> {
+        Ok(self.metrics().scale(params.font_size().get() / self.size))
+    }
+
+    fn get_glyph_bounds(&mut self, params: FontParams, text: &str) -> Result<Vec<GlyphBounds>> {
+        let lines = self.shape(params, text);
+        let scale = params.font_size().get() / self.size;
+        let bounds = glyph_bounds(
+            text,
+            &lines,
+            params.direction,
+            self.line_height * scale,
+            |glyph_id| {
+                self.chars
+                    .get(&glyph_id)
+                    .filter(|ch| ch.width > 0 && ch.height > 0)
+                    .map(|ch| {
+                        Rect::new(
+                            Point::new(ch.x_offset as f32 * scale, ch.y_offset as f32 * scale),
+                            Size::new(ch.width as f32 * scale, ch.height as f32 * scale),
+                        )
+                    })
+            },
+        );
+        if params.writing_mode.is_vertical() {
+            return Ok(column_bounds(
+                bounds,
+                &lines,
+                params.writing_mode,
+                &self.metrics().scale(scale),
+            ));
+        }
+        Ok(bounds)
+    }
+
+    fn get_outline(
+        &mut self,
+        _params: FontParams,
+        _text: &str,
+        _pos: PixelPoint,
+    ) -> Result<Vec<PathElement>> {
+        Err(BmFontError::NoOutlines.into())
+    }
+
+    fn get_dimensions_each_char(
+        &mut self,
+        params: FontParams,
+        text: &str,
+    ) -> Result<(Vec<i16>, u16)> {
+        let lines = self.shape(params, text);
+        let widths = char_widths(text, &lines);
+        let line_height = self.line_height * params.font_size().get() / self.size;
+        Ok((widths, line_height.round() as u16))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEXT: &str = "info face=\"Test Font\" size=-32 bold=0
+common lineHeight=36 base=29 scaleW=256 scaleH=256 pages=2 packed=0 alphaChnl=1 redChnl=0 greenChnl=3 blueChnl=4
+page id=0 file=\"test 0.png\"
+page id=1 file=test_1.png
+chars count=3
+char id=-1   x=0   y=0   width=0  height=0  xoffset=0  yoffset=0  xadvance=5  page=0
+char id=65   x=10  y=20  width=18 height=22 xoffset=-1 yoffset=7  xadvance=17 page=0 chnl=15
+char id=66   x=30  y=40  width=16 height=22 xoffset=2  yoffset=7  xadvance=18 page=1
+kernings count=1
+kerning first=65 second=66 amount=-2
+";
+
+    // Binary descriptor with the same content as TEXT.
+    fn binary() -> Vec<u8> {
+        fn block(bytes: &mut Vec<u8>, block_type: u8, content: &[u8]) {
+            bytes.push(block_type);
+            bytes.extend_from_slice(&(content.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(content);
+        }
+        fn char_record(id: u32, values: [i16; 7], page: u8, channels: u8) -> Vec<u8> {
+            let mut record = id.to_le_bytes().to_vec();
+            for value in values {
+                record.extend_from_slice(&value.to_le_bytes());
+            }
+            record.extend_from_slice(&[page, channels]);
+            record
+        }
+
+        let mut bytes = b"BMF\x03".to_vec();
+        let mut info = (-32i16).to_le_bytes().to_vec();
+        info.extend_from_slice(&[0; 12]);
+        info.extend_from_slice(b"Test Font\0");
+        block(&mut bytes, 1, &info);
+
+        let mut common = Vec::new();
+        for value in [36u16, 29, 256, 256, 2] {
+            common.extend_from_slice(&value.to_le_bytes());
+        }
+        common.extend_from_slice(&[0, 1, 0, 3, 4]);
+        block(&mut bytes, 2, &common);
+
+        block(&mut bytes, 3, b"test 0.png\0test_1.png\0");
+
+        let mut chars = char_record(u32::MAX, [0, 0, 0, 0, 0, 0, 5], 0, 15);
+        chars.extend(char_record(65, [10, 20, 18, 22, -1, 7, 17], 0, 15));
+        chars.extend(char_record(66, [30, 40, 16, 22, 2, 7, 18], 1, 15));
+        block(&mut bytes, 4, &chars);
+
+        let mut kerning = 65u32.to_le_bytes().to_vec();
+        kerning.extend_from_slice(&66u32.to_le_bytes());
+        kerning.extend_from_slice(&(-2i16).to_le_bytes());
+        block(&mut bytes, 5, &kerning);
+        bytes
+    }
+
+    fn error(bytes: &[u8]) -> String {
+        match Descriptor::parse(bytes) {
+            Err(BmFontError::InvalidDescriptor(message)) => message,
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    fn assert_test_font(descriptor: &Descriptor) {
+        assert_eq!(descriptor.size, 32.0);
+        assert_eq!(descriptor.line_height, 36.0);
+        assert_eq!(descriptor.base, 29.0);
+        assert_eq!(
+            descriptor.channel_contents,
+            [0, 3, 4, 1],
+            "red, green, blue and alpha"
+        );
+        assert_eq!(descriptor.page_files, vec!["test 0.png", "test_1.png"]);
+
+        let mut ids: Vec<u32> = descriptor.chars.keys().copied().collect();
+        ids.sort_unstable();
+        assert_eq!(ids, vec![65, 66]);
+        let a = descriptor.chars[&65];
+        assert_eq!(
+            (
+                a.x,
+                a.y,
+                a.width,
+                a.height,
+                a.x_offset,
+                a.y_offset,
+                a.x_advance
+            ),
+            (10, 20, 18, 22, -1, 7, 17)
+        );
+        let b = descriptor.chars[&66];
+        assert_eq!((b.x, b.y, b.page, b.channels), (30, 40, 1, 15));
+
+        assert_eq!(descriptor.kerning.len(), 1);
+        assert_eq!(descriptor.kerning[&(65, 66)], -2);
+    }
+
+    #[test]
+    fn text_descriptor() {
+        assert_test_font(&Descriptor::parse(TEXT.as_bytes()).unwrap());
+    }
+
+    #[test]
+    fn binary_descriptor() {
+        assert_test_font(&Descriptor::parse(&binary()).unwrap());
+    }
+
+    #[test]
+    fn text_attributes() {
+        let (tag, attributes) =
+            text_line_attributes("  page id=0 file=\"a b=c.png\"  extra=\"unterminated");
+        assert_eq!(tag, "page");
+        assert_eq!(attributes.len(), 3);
+        assert_eq!(attributes["id"], "0");
+        assert_eq!(attributes["file"], "a b=c.png");
+        assert_eq!(attributes["extra"], "unterminated");
+
+        let (tag, attributes) = text_line_attributes("chars");
+        assert_eq!(tag, "chars");
+        assert!(attributes.is_empty());
+    }
+
+    #[test]
+    fn text_defaults() {
+        let descriptor =
+            Descriptor::parse(b"common lineHeight=20 base=16\npage id=0 file=p.png\nchar id=32")
+                .unwrap();
+        // the size is the line height without the info line
+        assert_eq!(descriptor.size, 20.0);
+        // glyphs are in the alpha channel of the pages
+        assert_eq!(descriptor.channel_contents, [4, 4, 4, 0]);
+        let space = descriptor.chars[&32];
+        assert_eq!((space.page, space.channels, space.x_advance), (0, 15, 0));
+    }
+
+    #[test]
+    fn invalid_text_descriptors() {
+        assert_eq!(error(b"info size=12"), "missing line height");
+        assert_eq!(
+            error(b"common lineHeight=10 base=8\nchar id=65 page=1"),
+            "missing page 1"
+        );
+        assert_eq!(error(b"common lineHeight=ten"), "invalid lineHeight: ten");
+        assert_eq!(
+            error(b"common lineHeight=10\npage id=0\nchar id=65 x=-3"),
+            "invalid x: -3"
+        );
+        assert_eq!(error(b"info size=\xff"), "not a text file");
+        assert_eq!(error(b""), "missing line height");
+    }
+
+    #[test]
+    fn invalid_binary_descriptors() {
+        assert_eq!(error(b"BMF\x02"), "unsupported binary version");
+        assert_eq!(error(b"BMF"), "unsupported binary version");
+
+        // page names have to be null terminated
+        assert_eq!(
+            error(b"BMF\x03\x03\x05\x00\x00\x00a.png"),
+            "unterminated string"
+        );
+        // the null character of the next block doesn't terminate the name
+        assert_eq!(
+            error(b"BMF\x03\x03\x05\x00\x00\x00a.png\x01\x00\x00\x00\x00"),
+            "unterminated string"
+        );
+
+        // blocks can't be longer than the descriptor
+        assert_eq!(
+            error(b"BMF\x03\x01\xff\xff\xff\xff\x20\x00"),
+            "unexpected end"
+        );
+    }
+
+    #[test]
+    fn truncated_binary_descriptors() {
+        let bytes = binary();
+        // ends of the blocks, the descriptor is valid if it ends with a block
+        // after the common block (with the line height)
+        let mut block_ends = Vec::new();
+        let mut pos = 4;
+        while pos < bytes.len() {
+            let size = u32::from_le_bytes([
+                bytes[pos + 1],
+                bytes[pos + 2],
+                bytes[pos + 3],
+                bytes[pos + 4],
+            ]);
+            pos += 5 + size as usize;
+            block_ends.push(pos);
+        }
+        assert_eq!(block_ends.len(), 5);
+        assert_eq!(*block_ends.last().unwrap(), bytes.len());
+
+        for len in 4..bytes.len() {
+            let result = Descriptor::parse(&bytes[..len]);
+            if len >= block_ends[1] && block_ends.contains(&len) {
+                assert!(result.is_ok(), "length {}", len);
+            } else {
+                assert!(result.is_err(), "length {}", len);
+            }
+        }
+    }
+}
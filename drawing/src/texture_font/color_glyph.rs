@@ -0,0 +1,1117 @@
+//! Paints color glyphs of the `COLR` table into BGRA bitmaps.
+//!
+//! FreeType renders only the layers of COLRv0 glyphs in color. The other glyphs
+//! of the table (COLRv1 gradients, clips, transforms and composite modes) are
+//! painted here from the outlines read by ttf-parser. Outlines are rasterized
+//! into coverage with the nonzero fill rule and the paints are computed for every
+//! pixel in premultiplied RGBA.
+//!
+//! The color of the text is not known when the glyph is put into the atlas,
+//! so the paints with the foreground color are black.
+
+use crate::font::FontFace;
+use crate::text_path::flatten_cubic;
+use crate::units::{PixelPoint, Point};
+use ttf_parser as ttf;
+use ttf_parser::colr::{self, CompositeMode, GradientExtend};
+
+// premultiplied RGBA
+type Rgba = [f32; 4];
+
+type Line = (PixelPoint, PixelPoint);
+
+type Vector = euclid::default::Vector2D<f32>;
+
+// slant of the synthesized oblique style (the same as FreeType uses)
+const OBLIQUE_SHEAR: f32 = 0x366A as f32 / 65536.0;
+
+// scanlines of every row of pixels (the vertical antialiasing)
+const SCANLINES: usize = 16;
+
+// bigger glyphs do not fit into the atlas anyway
+const MAX_SIZE: f32 = 2048.0;
+
+/// Color glyph painted at the pen position.
+pub struct ColorGlyphImage {
+    /// Distance of the left side of the bitmap from the pen position.
+    pub left: i32,
+    /// Distance of the top of the bitmap above the baseline.
+    pub top: i32,
+    pub width: usize,
+    pub height: usize,
+    /// BGRA bytes with straight alpha (as the color atlas expects).
+    pub data: Vec<u8>,
+}
+
+/// Opens the face with the variations set if it has the `COLR` table.
+pub fn open_color_face<'a>(data: &'a [u8], font_face: &FontFace) -> Option<ttf::Face<'a>> {
+    let mut face = ttf::Face::parse(data, font_face.index).ok()?;
+    face.tables().colr?;
+    for (tag, value) in font_face.variations.iter() {
+        face.set_variation(ttf::Tag::from_bytes(&tag), value);
+    }
+    Some(face)
+}
+
+/// Paints the glyph if it's in the `COLR` table.
+///
+/// `scale` converts font units to pixels, `shift` moves the glyph right
+/// (by the subpixel position).
+pub fn paint_color_glyph(
+    face: &ttf::Face,
+    glyph_id: u32,
+    scale: f32,
+    shift: f32,
+    oblique: bool,
+) -> Option<ColorGlyphImage> {
+    let glyph_id = ttf::GlyphId(u16::try_from(glyph_id).ok()?);
+    if !face.is_color_glyph(glyph_id) {
+        return None;
+    }
+    // font units to pixels with y going down from the baseline
+    let mut transform = ttf::Transform::new(scale, 0.0, 0.0, -scale, shift, 0.0);
+    if oblique {
+        transform = ttf::Transform::combine(
+            transform,
+            ttf::Transform::new(1.0, 0.0, OBLIQUE_SHEAR, 1.0, 0.0, 0.0),
+        );
+    }
+
+    let mut bounds = BoundsPainter {
+        face,
+        transforms: vec![transform],
+        bounds: None,
+    };
+    face.paint_color_glyph(glyph_id, 0, BLACK, &mut bounds)?;
+    let [x_min, y_min, x_max, y_max] = bounds.bounds?;
+    let (left, top) = (x_min.floor(), y_min.floor());
+    let (width, height) = (x_max.ceil() - left, y_max.ceil() - top);
+    if width < 1.0 || height < 1.0 || width > MAX_SIZE || height > MAX_SIZE {
+        return None;
+    }
+
+    let transform = ttf::Transform::combine(
+        ttf::Transform::new(1.0, 0.0, 0.0, 1.0, -left, -top),
+        transform,
+    );
+    let mut painter = ColorPainter {
+        face,
+        canvas: Canvas::new(width as usize, height as usize),
+        transforms: vec![transform],
+        outline: None,
+        outline_index: 0,
+    };
+    face.paint_color_glyph(glyph_id, 0, BLACK, &mut painter)?;
+    Some(ColorGlyphImage {
+        left: left as i32,
+        top: -top as i32,
+        width: width as usize,
+        height: height as usize,
+        data: painter.canvas.into_bgra(),
+    })
+}
+
+// the foreground color
+const BLACK: ttf::RgbaColor = ttf::RgbaColor {
+    red: 0,
+    green: 0,
+    blue: 0,
+    alpha: 255,
+};
+
+fn apply(transform: &ttf::Transform, x: f32, y: f32) -> PixelPoint {
+    PixelPoint::new(
+        transform.a * x + transform.c * y + transform.e,
+        transform.b * x + transform.d * y + transform.f,
+    )
+}
+
+fn invert(transform: &ttf::Transform) -> Option<ttf::Transform> {
+    let ttf::Transform { a, b, c, d, e, f } = *transform;
+    let det = a * d - b * c;
+    if det.abs() < f32::EPSILON {
+        return None;
+    }
+    Some(ttf::Transform::new(
+        d / det,
+        -b / det,
+        -c / det,
+        a / det,
+        (c * f - d * e) / det,
+        (b * e - a * f) / det,
+    ))
+}
+
+fn current(transforms: &[ttf::Transform]) -> ttf::Transform {
+    transforms.last().copied().unwrap_or_default()
+}
+
+// Finds the pixel bounds of all the outlines of the glyph.
+struct BoundsPainter<'f> {
+    face: &'f ttf::Face<'f>,
+    transforms: Vec<ttf::Transform>,
+    // x min, y min, x max, y max
+    bounds: Option<[f32; 4]>,
+}
+
+impl<'f> colr::Painter<'f> for BoundsPainter<'f> {
+    fn outline_glyph(&mut self, glyph_id: ttf::GlyphId) {
+        let rect = match self.face.glyph_bounding_box(glyph_id) {
+            Some(rect) => rect,
+            None => return,
+        };
+        let transform = current(&self.transforms);
+        for (x, y) in [
+            (rect.x_min, rect.y_min),
+            (rect.x_max, rect.y_min),
+            (rect.x_min, rect.y_max),
+            (rect.x_max, rect.y_max),
+        ] {
+            let point = apply(&transform, x as f32, y as f32);
+            let bounds = self
+                .bounds
+                .get_or_insert([point.x, point.y, point.x, point.y]);
+            *bounds = [
+                bounds[0].min(point.x),
+                bounds[1].min(point.y),
+                bounds[2].max(point.x),
+                bounds[3].max(point.y),
+            ];
+        }
+    }
+
+    fn paint(&mut self, _paint: colr::Paint<'f>) {}
+
+    fn push_clip(&mut self) {}
+
+    fn push_clip_box(&mut self, _clipbox: colr::ClipBox) {}
+
+    fn pop_clip(&mut self) {}
+
+    fn push_layer(&mut self, _mode: CompositeMode) {}
+
+    fn pop_layer(&mut self) {}
+
+    fn push_transform(&mut self, transform: ttf::Transform) {
+        let transform = ttf::Transform::combine(current(&self.transforms), transform);
+        self.transforms.push(transform);
+    }
+
+    fn pop_transform(&mut self) {
+        self.transforms.pop();
+    }
+}
+
+// Paints the glyph into the canvas.
+struct ColorPainter<'f> {
+    face: &'f ttf::Face<'f>,
+    canvas: Canvas,
+    transforms: Vec<ttf::Transform>,
+    // lines of the last outlined glyph (in pixels)
+    outline: Option<Vec<Line>>,
+    // increased for every outline, so the clips know which outline they are made from
+    outline_index: usize,
+}
+
+impl<'f> ColorPainter<'f> {
+    fn shader(&self, paint: colr::Paint<'f>) -> Option<Shader> {
+        let coords = self.face.variation_coordinates();
+        // paints are defined in font units, their colors are computed at the centers
+        // of the pixels mapped back by the current transform
+        let to_paint = invert(&current(&self.transforms))?;
+        let kind = match paint {
+            colr::Paint::Solid(color) => ShaderKind::Solid(premultiply(color)),
+            colr::Paint::LinearGradient(gradient) => {
+                let p0 = Point::new(gradient.x0, gradient.y0);
+                let p1 = Point::new(gradient.x1, gradient.y1);
+                let p2 = Point::new(gradient.x2, gradient.y2);
+                let color_line = ColorLine::new(gradient.stops(0, coords), gradient.extend)?;
+                ShaderKind::linear(p0, p1, p2, color_line)?
+            }
+            colr::Paint::RadialGradient(gradient) => ShaderKind::Radial {
+                c0: Point::new(gradient.x0, gradient.y0),
+                r0: gradient.r0,
+                c1: Point::new(gradient.x1, gradient.y1),
+                r1: gradient.r1,
+                color_line: ColorLine::new(gradient.stops(0, coords), gradient.extend)?,
+            },
+            colr::Paint::SweepGradient(gradient) => ShaderKind::Sweep {
+                center: Point::new(gradient.center_x, gradient.center_y),
+                // 180 degrees per unit
+                start_angle: gradient.start_angle * 180.0,
+                end_angle: gradient.end_angle * 180.0,
+                color_line: ColorLine::new(gradient.stops(0, coords), gradient.extend)?,
+            },
+        };
+        Some(Shader { to_paint, kind })
+    }
+}
+
+impl<'f> colr::Painter<'f> for ColorPainter<'f> {
+    fn outline_glyph(&mut self, glyph_id: ttf::GlyphId) {
+        let mut builder = OutlineBuilder {
+            transform: current(&self.transforms),
+            lines: Vec::new(),
+            start: PixelPoint::zero(),
+            last: PixelPoint::zero(),
+        };
+        self.face.outline_glyph(glyph_id, &mut builder);
+        ttf::OutlineBuilder::close(&mut builder);
+        self.outline = Some(builder.lines);
+        self.outline_index += 1;
+    }
+
+    fn paint(&mut self, paint: colr::Paint<'f>) {
+        let shader = match self.shader(paint) {
+            Some(shader) => shader,
+            None => return,
+        };
+        let coverage = match self.canvas.clips.last() {
+            // the glyph clips its own paint (`PaintGlyph`)
+            Some((clip, Some(index))) if *index == self.outline_index => clip.clone(),
+            _ => {
+                let coverage = match &self.outline {
+                    Some(lines) => rasterize(lines, self.canvas.width, self.canvas.height),
+                    None => vec![1.0; self.canvas.width * self.canvas.height],
+                };
+                self.canvas.clipped(coverage)
+            }
+        };
+        self.canvas.fill(&coverage, |point| shader.color(point));
+    }
+
+    fn push_clip(&mut self) {
+        let coverage = match &self.outline {
+            Some(lines) => rasterize(lines, self.canvas.width, self.canvas.height),
+            None => vec![0.0; self.canvas.width * self.canvas.height],
+        };
+        self.canvas.push_clip(coverage, Some(self.outline_index));
+    }
+
+    fn push_clip_box(&mut self, clipbox: colr::ClipBox) {
+        let transform = current(&self.transforms);
+        let corners = [
+            apply(&transform, clipbox.x_min, clipbox.y_min),
+            apply(&transform, clipbox.x_max, clipbox.y_min),
+            apply(&transform, clipbox.x_max, clipbox.y_max),
+            apply(&transform, clipbox.x_min, clipbox.y_max),
+        ];
+        let lines: Vec<Line> = (0..4).map(|i| (corners[i], corners[(i + 1) % 4])).collect();
+        let coverage = rasterize(&lines, self.canvas.width, self.canvas.height);
+        self.canvas.push_clip(coverage, None);
+    }
+
+    fn pop_clip(&mut self) {
+        self.canvas.clips.pop();
+    }
+
+    fn push_layer(&mut self, mode: CompositeMode) {
+        self.canvas.push_layer(mode);
+    }
+
+    fn pop_layer(&mut self) {
+        self.canvas.pop_layer();
+    }
+
+    fn push_transform(&mut self, transform: ttf::Transform) {
+        let transform = ttf::Transform::combine(current(&self.transforms), transform);
+        self.transforms.push(transform);
+    }
+
+    fn pop_transform(&mut self) {
+        self.transforms.pop();
+    }
+}
+
+// Flattens the outline into lines in pixels.
+struct OutlineBuilder {
+    transform: ttf::Transform,
+    lines: Vec<Line>,
+    start: PixelPoint,
+    last: PixelPoint,
+}
+
+impl OutlineBuilder {
+    fn line(&mut self, to: PixelPoint) {
+        self.lines.push((self.last, to));
+        self.last = to;
+    }
+
+    fn curve(&mut self, c1: PixelPoint, c2: PixelPoint, to: PixelPoint) {
+        let mut points = Vec::new();
+        flatten_cubic(self.last, c1, c2, to, &mut points);
+        for point in points {
+            self.line(point);
+        }
+    }
+}
+
+impl ttf::OutlineBuilder for OutlineBuilder {
+    fn move_to(&mut self, x: f32, y: f32) {
+        self.close();
+        self.start = apply(&self.transform, x, y);
+        self.last = self.start;
+    }
+
+    fn line_to(&mut self, x: f32, y: f32) {
+        self.line(apply(&self.transform, x, y));
+    }
+
+    fn quad_to(&mut self, x1: f32, y1: f32, x: f32, y: f32) {
+        let control = apply(&self.transform, x1, y1);
+        let to = apply(&self.transform, x, y);
+        // the same curve as cubic
+        let c1 = self.last + (control - self.last) * (2.0 / 3.0);
+        let c2 = to + (control - to) * (2.0 / 3.0);
+        self.curve(c1, c2, to);
+    }
+
+    fn curve_to(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, x: f32, y: f32) {
+        let c1 = apply(&self.transform, x1, y1);
+        let c2 = apply(&self.transform, x2, y2);
+        self.curve(c1, c2, apply(&self.transform, x, y));
+    }
+
+    fn close(&mut self) {
+        if self.last != self.start {
+            self.line(self.start);
+        }
+    }
+}
+
+// Coverage of the pixels by the closed polygons with the nonzero fill rule, row by row.
+//
+// The winding numbers are counted along several scanlines in every row and the spans
+// of nonzero winding are added with the covered fractions of the pixels at their ends
+// (so the contours that share an edge leave no gaps, as glyphs drawn in parts have).
+fn rasterize(lines: &[Line], width: usize, height: usize) -> Vec<f32> {
+    let mut coverage = vec![0.0f32; width * height];
+    let weight = 1.0 / SCANLINES as f32;
+    let mut crossings: Vec<(f32, i32)> = Vec::new();
+    for (y, row) in coverage.chunks_exact_mut(width).enumerate() {
+        let (top, bottom) = (y as f32, (y + 1) as f32);
+        let row_lines: Vec<&Line> = lines
+            .iter()
+            .filter(|(p0, p1)| p0.y.min(p1.y) < bottom && p0.y.max(p1.y) > top)
+            .collect();
+        for scanline in 0..SCANLINES {
+            let scan_y = top + (scanline as f32 + 0.5) * weight;
+            crossings.clear();
+            for (p0, p1) in &row_lines {
+                let (upper, lower, direction) = if p0.y < p1.y {
+                    (p0, p1, 1)
+                } else {
+                    (p1, p0, -1)
+                };
+                // the lower ends are not included, so the points shared by two lines
+                // are crossed once
+                if upper.y <= scan_y && scan_y < lower.y {
+                    let x =
+                        upper.x + (scan_y - upper.y) * (lower.x - upper.x) / (lower.y - upper.y);
+                    crossings.push((x, direction));
+                }
+            }
+            crossings.sort_by(|a, b| a.0.total_cmp(&b.0));
+            let mut winding = 0;
+            let mut span_start = 0.0;
+            for &(x, direction) in &crossings {
+                if winding == 0 {
+                    span_start = x;
+                }
+                winding += direction;
+                if winding == 0 {
+                    add_span(row, span_start, x, weight);
+                }
+            }
+        }
+    }
+    coverage
+}
+
+// Adds the weight to the pixels of the span (the fraction of it to the pixels at the ends).
+fn add_span(row: &mut [f32], start: f32, end: f32, weight: f32) {
+    let width = row.len() as f32;
+    let (start, end) = (start.clamp(0.0, width), end.clamp(0.0, width));
+    if end <= start {
+        return;
+    }
+    let (first, last) = (start.floor() as usize, end.floor() as usize);
+    if first == last {
+        row[first] += (end - start) * weight;
+        return;
+    }
+    row[first] += (first as f32 + 1.0 - start) * weight;
+    for value in &mut row[first + 1..last] {
+        *value += weight;
+    }
+    if last < row.len() {
+        row[last] += (end - last as f32) * weight;
+    }
+}
+
+// Premultiplied colors of the pixels with the layers and clips of the paints.
+struct Canvas {
+    width: usize,
+    height: usize,
+    // the first layer is the glyph, the others are composited into the layer below
+    layers: Vec<(Vec<Rgba>, CompositeMode)>,
+    // coverage of the clip (intersected with the clips below) and the index
+    // of the outline it's made from (`None` for clip boxes)
+    clips: Vec<(Vec<f32>, Option<usize>)>,
+}
+
+impl Canvas {
+    fn new(width: usize, height: usize) -> Self {
+        Canvas {
+            width,
+            height,
+            layers: vec![(vec![[0.0; 4]; width * height], CompositeMode::SourceOver)],
+            clips: Vec::new(),
+        }
+    }
+
+    // intersection of the coverage with the current clip
+    fn clipped(&self, mut coverage: Vec<f32>) -> Vec<f32> {
+        if let Some((clip, _)) = self.clips.last() {
+            for (value, clip) in coverage.iter_mut().zip(clip) {
+                *value *= clip;
+            }
+        }
+        coverage
+    }
+
+    fn push_clip(&mut self, coverage: Vec<f32>, outline_index: Option<usize>) {
+        let clip = self.clipped(coverage);
+        self.clips.push((clip, outline_index));
+    }
+
+    // draws the color of the pixel centers over the covered pixels of the top layer
+    fn fill(&mut self, coverage: &[f32], color: impl Fn(PixelPoint) -> Option<Rgba>) {
+        let width = self.width;
+        let pixels = match self.layers.last_mut() {
+            Some((pixels, _)) => pixels,
+            None => return,
+        };
+        for (index, (pixel, &coverage)) in pixels.iter_mut().zip(coverage).enumerate() {
+            if coverage <= 0.0 {
+                continue;
+            }
+            let center =
+                PixelPoint::new((index % width) as f32 + 0.5, (index / width) as f32 + 0.5);
+            if let Some(color) = color(center) {
+                let source = color.map(|value| value * coverage);
+                *pixel = composite(CompositeMode::SourceOver, source, *pixel);
+            }
+        }
+    }
+
+    fn push_layer(&mut self, mode: CompositeMode) {
+        self.layers
+            .push((vec![[0.0; 4]; self.width * self.height], mode));
+    }
+
+    // composites the top layer into the layer below (inside the current clip)
+    fn pop_layer(&mut self) {
+        // the glyph layer is never popped
+        if self.layers.len() < 2 {
+            return;
+        }
+        let (source, mode) = self.layers.pop().unwrap();
+        let clip = self.clips.last().map(|(clip, _)| clip);
+        let (pixels, _) = self.layers.last_mut().unwrap();
+        for (index, (pixel, source)) in pixels.iter_mut().zip(source).enumerate() {
+            let coverage = clip.map_or(1.0, |clip| clip[index]);
+            if coverage <= 0.0 {
+                continue;
+            }
+            let result = composite(mode, source, *pixel);
+            for channel in 0..4 {
+                pixel[channel] += (result[channel] - pixel[channel]) * coverage;
+            }
+        }
+    }
+
+    fn into_bgra(self) -> Vec<u8> {
+        let mut data = Vec::with_capacity(self.width * self.height * 4);
+        if let Some((pixels, _)) = self.layers.into_iter().next() {
+            for [r, g, b, a] in pixels {
+                let alpha = a.clamp(0.0, 1.0);
+                let unpremultiply = |value: f32| match alpha {
+                    0.0 => 0,
+                    _ => ((value / alpha).clamp(0.0, 1.0) * 255.0).round() as u8,
+                };
+                data.extend_from_slice(&[
+                    unpremultiply(b),
+                    unpremultiply(g),
+                    unpremultiply(r),
+                    (alpha * 255.0).round() as u8,
+                ]);
+            }
+        }
+        data
+    }
+}
+
+fn premultiply(color: ttf::RgbaColor) -> Rgba {
+    let alpha = color.alpha as f32 / 255.0;
+    [
+        color.red as f32 / 255.0 * alpha,
+        color.green as f32 / 255.0 * alpha,
+        color.blue as f32 / 255.0 * alpha,
+        alpha,
+    ]
+}
+
+// Colors of the gradient along its parameter.
+struct ColorLine {
+    // sorted by offset
+    stops: Vec<(f32, Rgba)>,
+    extend: GradientExtend,
+}
+
+impl ColorLine {
+    fn new(stops: impl Iterator<Item = colr::ColorStop>, extend: GradientExtend) -> Option<Self> {
+        let mut stops: Vec<(f32, Rgba)> = stops
+            .map(|stop| (stop.stop_offset, premultiply(stop.color)))
+            .collect();
+        if stops.is_empty() {
+            return None;
+        }
+        stops.sort_by(|a, b| a.0.total_cmp(&b.0));
+        Some(ColorLine { stops, extend })
+    }
+
+    fn color(&self, t: f32) -> Rgba {
+        let (first, last) = (self.stops[0], self.stops[self.stops.len() - 1]);
+        // the extend mode repeats the part between the first and the last stop
+        let range = last.0 - first.0;
+        let t = if range > 0.0 {
+            let u = (t - first.0) / range;
+            let u = match self.extend {
+                GradientExtend::Pad => u.clamp(0.0, 1.0),
+                GradientExtend::Repeat => u - u.floor(),
+                GradientExtend::Reflect => {
+                    let u = u.rem_euclid(2.0);
+                    if u > 1.0 {
+                        2.0 - u
+                    } else {
+                        u
+                    }
+                }
+            };
+            first.0 + u * range
+        } else {
+            t
+        };
+        let next = self.stops.partition_point(|stop| stop.0 <= t);
+        if next == 0 {
+            return first.1;
+        }
+        if next == self.stops.len() {
+            return last.1;
+        }
+        let ((offset0, color0), (offset1, color1)) = (self.stops[next - 1], self.stops[next]);
+        let f = (t - offset0) / (offset1 - offset0);
+        [0, 1, 2, 3].map(|channel| color0[channel] + (color1[channel] - color0[channel]) * f)
+    }
+}
+
+// Color of the paint at the points of the canvas.
+struct Shader {
+    // pixels to the paint space (font units)
+    to_paint: ttf::Transform,
+    kind: ShaderKind,
+}
+
+enum ShaderKind {
+    Solid(Rgba),
+    Linear {
+        start: Point,
+        // from the start to the point of the parameter 1 divided by its squared length
+        direction: Vector,
+        color_line: ColorLine,
+    },
+    Radial {
+        c0: Point,
+        r0: f32,
+        c1: Point,
+        r1: f32,
+        color_line: ColorLine,
+    },
+    Sweep {
+        center: Point,
+        // degrees counter-clockwise
+        start_angle: f32,
+        end_angle: f32,
+        color_line: ColorLine,
+    },
+}
+
+impl ShaderKind {
+    // the parameter grows from `p0` in the direction of `p1` rotated to be
+    // perpendicular to the line from `p0` to `p2` (as the COLR table defines)
+    fn linear(p0: Point, p1: Point, p2: Point, color_line: ColorLine) -> Option<Self> {
+        let (to_p1, to_p2) = (p1 - p0, p2 - p0);
+        let normal = Vector::new(to_p2.y, -to_p2.x);
+        let vector = match normal.square_length() {
+            length if length > 0.0 => normal * (to_p1.dot(normal) / length),
+            _ => to_p1,
+        };
+        let length = vector.square_length();
+        if length <= 0.0 {
+            return None;
+        }
+        Some(ShaderKind::Linear {
+            start: p0,
+            direction: vector / length,
+            color_line,
+        })
+    }
+}
+
+impl Shader {
+    fn color(&self, point: PixelPoint) -> Option<Rgba> {
+        let point = apply(&self.to_paint, point.x, point.y).to_untyped();
+        match &self.kind {
+            ShaderKind::Solid(color) => Some(*color),
+            ShaderKind::Linear {
+                start,
+                direction,
+                color_line,
+            } => Some(color_line.color((point - *start).dot(*direction))),
+            ShaderKind::Radial {
+                c0,
+                r0,
+                c1,
+                r1,
+                color_line,
+            } => radial_parameter(point, *c0, *r0, *c1, *r1).map(|t| color_line.color(t)),
+            ShaderKind::Sweep {
+                center,
+                start_angle,
+                end_angle,
+                color_line,
+            } => {
+                if start_angle == end_angle {
+                    return None;
+                }
+                let to_point = point - *center;
+                let angle = to_point.y.atan2(to_point.x).to_degrees().rem_euclid(360.0);
+                Some(color_line.color((angle - start_angle) / (end_angle - start_angle)))
+            }
+        }
+    }
+}
+
+// Parameter of the biggest circle interpolated between the two circles
+// that goes through the point (the circles with negative radius are not drawn).
+fn radial_parameter(point: Point, c0: Point, r0: f32, c1: Point, r1: f32) -> Option<f32> {
+    // |point - c(t)| = r(t) is the quadratic equation a t^2 - 2 b t + c = 0
+    let (to_c1, to_point, dr) = (c1 - c0, point - c0, r1 - r0);
+    let a = to_c1.square_length() - dr * dr;
+    let b = to_point.dot(to_c1) + r0 * dr;
+    let c = to_point.square_length() - r0 * r0;
+    let valid = |t: f32| r0 + t * dr >= 0.0;
+    if a.abs() < 1e-6 {
+        if b.abs() < 1e-6 {
+            return None;
+        }
+        let t = c / (2.0 * b);
+        return valid(t).then_some(t);
+    }
+    let discriminant = b * b - a * c;
+    if discriminant < 0.0 {
+        return None;
+    }
+    let root = discriminant.sqrt();
+    let (t0, t1) = ((b + root) / a, (b - root) / a);
+    let (t_max, t_min) = if t0 > t1 { (t0, t1) } else { (t1, t0) };
+    if valid(t_max) {
+        Some(t_max)
+    } else if valid(t_min) {
+        Some(t_min)
+    } else {
+        None
+    }
+}
+
+// Composites the source color over the destination (premultiplied colors).
+fn composite(mode: CompositeMode, source: Rgba, destination: Rgba) -> Rgba {
+    let (sa, da) = (source[3], destination[3]);
+    // Porter-Duff operators with the fractions of the source and the destination
+    let porter_duff = |fs: f32, fd: f32| -> Rgba {
+        [0, 1, 2, 3].map(|channel| source[channel] * fs + destination[channel] * fd)
+    };
+    match mode {
+        CompositeMode::Clear => [0.0; 4],
+        CompositeMode::Source => source,
+        CompositeMode::Destination => destination,
+        CompositeMode::SourceOver => porter_duff(1.0, 1.0 - sa),
+        CompositeMode::DestinationOver => porter_duff(1.0 - da, 1.0),
+        CompositeMode::SourceIn => porter_duff(da, 0.0),
+        CompositeMode::DestinationIn => porter_duff(0.0, sa),
+        CompositeMode::SourceOut => porter_duff(1.0 - da, 0.0),
+        CompositeMode::DestinationOut => porter_duff(0.0, 1.0 - sa),
+        CompositeMode::SourceAtop => porter_duff(da, 1.0 - sa),
+        CompositeMode::DestinationAtop => porter_duff(1.0 - da, sa),
+        CompositeMode::Xor => porter_duff(1.0 - da, 1.0 - sa),
+        CompositeMode::Plus => porter_duff(1.0, 1.0).map(|value| value.min(1.0)),
+        _ => {
+            // blend modes mix the colors where both are present
+            let unpremultiply = |color: Rgba| -> [f32; 3] {
+                match color[3] {
+                    alpha if alpha > 0.0 => [0, 1, 2].map(|channel| color[channel] / alpha),
+                    _ => [0.0; 3],
+                }
+            };
+            let blended = blend(mode, unpremultiply(destination), unpremultiply(source));
+            let mut result = porter_duff(1.0 - da, 1.0 - sa);
+            for channel in 0..3 {
+                result[channel] += sa * da * blended[channel];
+            }
+            result[3] = sa + da - sa * da;
+            result
+        }
+    }
+}
+
+// Blend functions of the backdrop and source colors (as defined by the W3C compositing spec).
+fn blend(mode: CompositeMode, backdrop: [f32; 3], source: [f32; 3]) -> [f32; 3] {
+    let separable = |function: fn(f32, f32) -> f32| {
+        [0, 1, 2].map(|channel| function(backdrop[channel], source[channel]))
+    };
+    match mode {
+        CompositeMode::Screen => separable(screen),
+        CompositeMode::Overlay => separable(|cb, cs| hard_light(cs, cb)),
+        CompositeMode::Darken => separable(f32::min),
+        CompositeMode::Lighten => separable(f32::max),
+        CompositeMode::ColorDodge => separable(|cb, cs| {
+            if cb <= 0.0 {
+                0.0
+            } else if cs >= 1.0 {
+                1.0
+            } else {
+                (cb / (1.0 - cs)).min(1.0)
+            }
+        }),
+        CompositeMode::ColorBurn => separable(|cb, cs| {
+            if cb >= 1.0 {
+                1.0
+            } else if cs <= 0.0 {
+                0.0
+            } else {
+                1.0 - ((1.0 - cb) / cs).min(1.0)
+            }
+        }),
+        CompositeMode::HardLight => separable(hard_light),
+        CompositeMode::SoftLight => separable(|cb, cs| {
+            if cs <= 0.5 {
+                cb - (1.0 - 2.0 * cs) * cb * (1.0 - cb)
+            } else {
+                let d = if cb <= 0.25 {
+                    ((16.0 * cb - 12.0) * cb + 4.0) * cb
+                } else {
+                    cb.sqrt()
+                };
+                cb + (2.0 * cs - 1.0) * (d - cb)
+            }
+        }),
+        CompositeMode::Difference => separable(|cb, cs| (cb - cs).abs()),
+        CompositeMode::Exclusion => separable(|cb, cs| cb + cs - 2.0 * cb * cs),
+        CompositeMode::Multiply => separable(|cb, cs| cb * cs),
+        CompositeMode::Hue => set_luminosity(
+            set_saturation(source, saturation(backdrop)),
+            luminosity(backdrop),
+        ),
+        CompositeMode::Saturation => set_luminosity(
+            set_saturation(backdrop, saturation(source)),
+            luminosity(backdrop),
+        ),
+        CompositeMode::Color => set_luminosity(source, luminosity(backdrop)),
+        CompositeMode::Luminosity => set_luminosity(backdrop, luminosity(source)),
+        // Porter-Duff operators are not blended
+        _ => source,
+    }
+}
+
+fn screen(cb: f32, cs: f32) -> f32 {
+    cb + cs - cb * cs
+}
+
+fn hard_light(cb: f32, cs: f32) -> f32 {
+    if cs <= 0.5 {
+        cb * 2.0 * cs
+    } else {
+        screen(cb, 2.0 * cs - 1.0)
+    }
+}
+
+fn luminosity(color: [f32; 3]) -> f32 {
+    0.3 * color[0] + 0.59 * color[1] + 0.11 * color[2]
+}
+
+fn set_luminosity(color: [f32; 3], value: f32) -> [f32; 3] {
+    let delta = value - luminosity(color);
+    let color = color.map(|channel| channel + delta);
+    // moves the channels back into the range keeping the luminosity
+    let l = luminosity(color);
+    let min = color[0].min(color[1]).min(color[2]);
+    let max = color[0].max(color[1]).max(color[2]);
+    color.map(|channel| {
+        let mut channel = channel;
+        if min < 0.0 {
+            channel = l + (channel - l) * l / (l - min);
+        }
+        if max > 1.0 {
+            channel = l + (channel - l) * (1.0 - l) / (max - l);
+        }
+        channel
+    })
+}
+
+fn saturation(color: [f32; 3]) -> f32 {
+    color[0].max(color[1]).max(color[2]) - color[0].min(color[1]).min(color[2])
+}
+
+fn set_saturation(color: [f32; 3], value: f32) -> [f32; 3] {
+    let min = color[0].min(color[1]).min(color[2]);
+    let max = color[0].max(color[1]).max(color[2]);
+    if max > min {
+        color.map(|channel| (channel - min) * value / (max - min))
+    } else {
+        [0.0; 3]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RED: Rgba = [1.0, 0.0, 0.0, 1.0];
+    const BLUE: Rgba = [0.0, 0.0, 1.0, 1.0];
+
+    fn polygon(points: &[(f32, f32)]) -> Vec<Line> {
+        let points: Vec<PixelPoint> = points.iter().map(|&(x, y)| PixelPoint::new(x, y)).collect();
+        (0..points.len())
+            .map(|i| (points[i], points[(i + 1) % points.len()]))
+            .collect()
+    }
+
+    fn color_line(extend: GradientExtend) -> ColorLine {
+        ColorLine {
+            stops: vec![(0.0, RED), (1.0, BLUE)],
+            extend,
+        }
+    }
+
+    fn shader(kind: ShaderKind) -> Shader {
+        Shader {
+            to_paint: ttf::Transform::default(),
+            kind,
+        }
+    }
+
+    fn assert_color(color: Rgba, expected: Rgba) {
+        for (value, expected) in color.iter().zip(expected) {
+            assert!(
+                (value - expected).abs() < 1e-3,
+                "{:?} != {:?}",
+                color,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn rectangle_covers_pixels_inside_and_fractions_at_edges() {
+        let coverage = rasterize(
+            &polygon(&[(1.0, 1.0), (3.5, 1.0), (3.5, 3.0), (1.0, 3.0)]),
+            5,
+            4,
+        );
+        let at = |x: usize, y: usize| coverage[y * 5 + x];
+        assert_eq!(at(0, 0), 0.0);
+        assert_eq!(at(0, 1), 0.0);
+        assert!((at(1, 1) - 1.0).abs() < 1e-5);
+        assert!((at(2, 2) - 1.0).abs() < 1e-5);
+        assert!((at(3, 1) - 0.5).abs() < 1e-5);
+        assert!(at(4, 1).abs() < 1e-5);
+        assert!(at(1, 3).abs() < 1e-5);
+    }
+
+    #[test]
+    fn overlapping_contours_are_covered_once_and_reversed_ones_are_holes() {
+        let mut lines = polygon(&[(0.0, 0.0), (6.0, 0.0), (6.0, 6.0), (0.0, 6.0)]);
+        lines.extend(polygon(&[(2.0, 2.0), (8.0, 2.0), (8.0, 4.0), (2.0, 4.0)]));
+        let coverage = rasterize(&lines, 8, 6);
+        assert!((coverage[3 * 8 + 3] - 1.0).abs() < 1e-5);
+        assert!((coverage[3 * 8 + 7] - 1.0).abs() < 1e-5);
+
+        let mut lines = polygon(&[(0.0, 0.0), (6.0, 0.0), (6.0, 6.0), (0.0, 6.0)]);
+        lines.extend(polygon(&[(2.0, 2.0), (2.0, 4.0), (4.0, 4.0), (4.0, 2.0)]));
+        let coverage = rasterize(&lines, 6, 6);
+        assert!(coverage[3 * 6 + 3].abs() < 1e-5);
+        assert!((coverage[3 * 6 + 1] - 1.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn contours_sharing_edge_leave_no_gap() {
+        // halves of a square with the opposite directions, the edge is inside the pixels
+        let mut lines = polygon(&[(0.0, 0.0), (4.0, 0.0), (4.0, 1.5), (0.0, 1.5)]);
+        lines.extend(polygon(&[(0.0, 1.5), (0.0, 3.0), (4.0, 3.0), (4.0, 1.5)]));
+        let coverage = rasterize(&lines, 4, 3);
+        for value in coverage {
+            assert!((value - 1.0).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn lines_outside_of_bitmap_cover_pixels_right_of_them() {
+        let coverage = rasterize(
+            &polygon(&[(-4.0, 0.0), (2.0, 0.0), (2.0, 2.0), (-4.0, 2.0)]),
+            4,
+            2,
+        );
+        assert_eq!(
+            coverage
+                .iter()
+                .map(|value| value.round())
+                .collect::<Vec<_>>(),
+            vec![1.0, 1.0, 0.0, 0.0, 1.0, 1.0, 0.0, 0.0]
+        );
+    }
+
+    #[test]
+    fn color_line_is_interpolated_and_extended() {
+        assert_color(
+            color_line(GradientExtend::Pad).color(0.25),
+            [0.75, 0.0, 0.25, 1.0],
+        );
+        assert_color(color_line(GradientExtend::Pad).color(1.25), BLUE);
+        assert_color(color_line(GradientExtend::Pad).color(-1.0), RED);
+        assert_color(
+            color_line(GradientExtend::Repeat).color(1.25),
+            [0.75, 0.0, 0.25, 1.0],
+        );
+        assert_color(
+            color_line(GradientExtend::Reflect).color(1.25),
+            [0.25, 0.0, 0.75, 1.0],
+        );
+    }
+
+    #[test]
+    fn linear_gradient_is_perpendicular_to_second_vector() {
+        // p1 is projected onto the normal of the line from p0 to p2 (the x axis)
+        let kind = ShaderKind::linear(
+            Point::new(0.0, 0.0),
+            Point::new(10.0, 5.0),
+            Point::new(0.0, 10.0),
+            color_line(GradientExtend::Pad),
+        );
+        let shader = shader(kind.unwrap());
+        assert_color(
+            shader.color(PixelPoint::new(5.0, 0.0)).unwrap(),
+            [0.5, 0.0, 0.5, 1.0],
+        );
+        assert_color(
+            shader.color(PixelPoint::new(5.0, 20.0)).unwrap(),
+            [0.5, 0.0, 0.5, 1.0],
+        );
+        assert_color(shader.color(PixelPoint::new(10.0, -3.0)).unwrap(), BLUE);
+    }
+
+    #[test]
+    fn radial_gradient_grows_from_first_circle() {
+        let shader = shader(ShaderKind::Radial {
+            c0: Point::new(0.0, 0.0),
+            r0: 0.0,
+            c1: Point::new(0.0, 0.0),
+            r1: 10.0,
+            color_line: color_line(GradientExtend::Pad),
+        });
+        assert_color(
+            shader.color(PixelPoint::new(0.0, 5.0)).unwrap(),
+            [0.5, 0.0, 0.5, 1.0],
+        );
+        assert_color(
+            shader.color(PixelPoint::new(-3.0, 4.0)).unwrap(),
+            [0.5, 0.0, 0.5, 1.0],
+        );
+        assert_color(shader.color(PixelPoint::new(20.0, 0.0)).unwrap(), BLUE);
+
+        // the circles of the cone do not reach the points far from its axis
+        let cone = super::radial_parameter;
+        let (c0, c1) = (Point::new(0.0, 0.0), Point::new(10.0, 0.0));
+        assert!(cone(Point::new(-5.0, 5.0), c0, 1.0, c1, 2.0).is_none());
+        // the point is on the circles 1 and 13/9, the bigger one is drawn over
+        assert!((cone(Point::new(12.0, 0.0), c0, 1.0, c1, 2.0).unwrap() - 13.0 / 9.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn sweep_gradient_goes_counter_clockwise() {
+        let shader = shader(ShaderKind::Sweep {
+            center: Point::new(0.0, 0.0),
+            start_angle: 0.0,
+            end_angle: 180.0,
+            color_line: color_line(GradientExtend::Pad),
+        });
+        // y goes up in font units
+        assert_color(
+            shader.color(PixelPoint::new(0.0, 1.0)).unwrap(),
+            [0.5, 0.0, 0.5, 1.0],
+        );
+        assert_color(
+            shader.color(PixelPoint::new(1.0, 1.0)).unwrap(),
+            [0.75, 0.0, 0.25, 1.0],
+        );
+        assert_color(shader.color(PixelPoint::new(0.0, -1.0)).unwrap(), BLUE);
+    }
+
+    #[test]
+    fn paint_is_clipped() {
+        let mut canvas = Canvas::new(4, 1);
+        canvas.push_clip(vec![1.0, 1.0, 0.5, 0.0], None);
+        canvas.push_clip(vec![0.0, 1.0, 1.0, 1.0], Some(1));
+        let coverage = canvas.clipped(vec![1.0; 4]);
+        assert_eq!(coverage, vec![0.0, 1.0, 0.5, 0.0]);
+        canvas.fill(&coverage, |_| Some(RED));
+        let pixels = &canvas.layers[0].0;
+        assert_color(pixels[0], [0.0; 4]);
+        assert_color(pixels[1], RED);
+        assert_color(pixels[2], [0.5, 0.0, 0.0, 0.5]);
+        assert_color(pixels[3], [0.0; 4]);
+    }
+
+    #[test]
+    fn layers_are_composited_with_their_modes() {
+        let mut canvas = Canvas::new(2, 1);
+        canvas.fill(&[1.0, 1.0], |_| Some(RED));
+        canvas.push_layer(CompositeMode::DestinationOut);
+        canvas.fill(&[1.0, 0.0], |_| Some(BLUE));
+        canvas.pop_layer();
+        assert_color(canvas.layers[0].0[0], [0.0; 4]);
+        assert_color(canvas.layers[0].0[1], RED);
+        assert_eq!(canvas.layers.len(), 1);
+
+        let half_gray = [0.25, 0.25, 0.25, 0.5];
+        assert_color(
+            composite(CompositeMode::Multiply, [0.5, 0.5, 0.5, 1.0], RED),
+            [0.5, 0.0, 0.0, 1.0],
+        );
+        assert_color(
+            composite(CompositeMode::Screen, half_gray, [0.0; 4]),
+            half_gray,
+        );
+        assert_color(
+            composite(CompositeMode::Luminosity, [1.0, 1.0, 1.0, 1.0], RED),
+            [1.0, 1.0, 1.0, 1.0],
+        );
+    }
+
+    #[test]
+    fn bitmap_has_bgra_bytes_with_straight_alpha() {
+        let mut canvas = Canvas::new(2, 1);
+        canvas.fill(&[0.5, 0.0], |_| Some(RED));
+        assert_eq!(canvas.into_bgra(), vec![0, 0, 255, 128, 0, 0, 0, 0]);
+    }
+}
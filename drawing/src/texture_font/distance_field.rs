@@ -0,0 +1,202 @@
+//! Signed distance field generation.
+//!
+//! The glyph is rendered a few times bigger than needed, the exact euclidean
+//! distance transform is computed for the inside and the outside of the glyph
+//! (Felzenszwalb & Huttenlocher) and the result is downsampled.
+
+const INF: f32 = 1e20;
+
+/// Distance field with margin around the glyph.
+pub struct DistanceField {
+    pub data: Vec<u8>,
+    pub width: usize,
+    pub height: usize,
+}
+
+/// Converts glyph coverage (8-bit grayscale, rendered `upscale` times bigger
+/// than the result) into a signed distance field.
+///
+/// The field has `spread` texels of margin on every side. Value 128 is the edge
+/// of the glyph, 255 is `spread` texels (or more) inside and 0 is `spread` texels
+/// (or more) outside.
+pub fn coverage_to_distance_field(
+    coverage: &[u8],
+    width: usize,
+    height: usize,
+    upscale: usize,
+    spread: usize,
+) -> DistanceField {
+    let padding = spread * upscale;
+    let field_width = (width + 2 * padding).div_ceil(upscale);
+    let field_height = (height + 2 * padding).div_ceil(upscale);
+    let hi_width = field_width * upscale;
+    let hi_height = field_height * upscale;
+
+    let mut inside = vec![false; hi_width * hi_height];
+    for y in 0..height {
+        for x in 0..width {
+            inside[(y + padding) * hi_width + x + padding] = coverage[y * width + x] >= 128;
+        }
+    }
+
+    // squared distances to the nearest inside and outside pixel
+    let to_inside = distance_transform(&inside, hi_width, hi_height, true);
+    let to_outside = distance_transform(&inside, hi_width, hi_height, false);
+
+    let mut data = vec![0u8; field_width * field_height];
+    let scale = 1.0 / (upscale * upscale) as f32;
+    for field_y in 0..field_height {
+        for field_x in 0..field_width {
+            let mut sum = 0.0;
+            for y in field_y * upscale..(field_y + 1) * upscale {
+                for x in field_x * upscale..(field_x + 1) * upscale {
+                    let i = y * hi_width + x;
+                    sum += if inside[i] {
+                        to_outside[i].sqrt() - 0.5
+                    } else {
+                        0.5 - to_inside[i].sqrt()
+                    };
+                }
+            }
+            let distance = sum * scale / upscale as f32;
+            let value = 0.5 + distance / (2.0 * spread as f32);
+            data[field_y * field_width + field_x] = (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+        }
+    }
+
+    DistanceField {
+        data,
+        width: field_width,
+        height: field_height,
+    }
+}
+
+// Returns squared distances to the nearest pixel with `inside` equal to `feature`.
+fn distance_transform(inside: &[bool], width: usize, height: usize, feature: bool) -> Vec<f32> {
+    let mut grid: Vec<f32> = inside
+        .iter()
+        .map(|&inside| if inside == feature { 0.0 } else { INF })
+        .collect();
+
+    let size = width.max(height);
+    let mut f = vec![0.0; size];
+    let mut d = vec![0.0; size];
+    let mut v = vec![0; size];
+    let mut z = vec![0.0; size + 1];
+
+    for x in 0..width {
+        for y in 0..height {
+            f[y] = grid[y * width + x];
+        }
+        distance_transform_1d(&f[..height], &mut d, &mut v, &mut z);
+        for y in 0..height {
+            grid[y * width + x] = d[y];
+        }
+    }
+
+    for y in 0..height {
+        f[..width].copy_from_slice(&grid[y * width..(y + 1) * width]);
+        distance_transform_1d(&f[..width], &mut d, &mut v, &mut z);
+        grid[y * width..(y + 1) * width].copy_from_slice(&d[..width]);
+    }
+
+    grid
+}
+
+// One dimensional squared distance transform of the sampled function `f`.
+fn distance_transform_1d(f: &[f32], d: &mut [f32], v: &mut [usize], z: &mut [f32]) {
+    let n = f.len();
+    let mut k = 0;
+    v[0] = 0;
+    z[0] = -INF;
+    z[1] = INF;
+
+    // intersection of the parabolas rooted at q and r
+    let intersection = |q: usize, r: usize| {
+        ((f[q] + (q * q) as f32) - (f[r] + (r * r) as f32)) / (2 * q - 2 * r) as f32
+    };
+
+    for q in 1..n {
+        let mut s = intersection(q, v[k]);
+        while s <= z[k] {
+            k -= 1;
+            s = intersection(q, v[k]);
+        }
+        k += 1;
+        v[k] = q;
+        z[k] = s;
+        z[k + 1] = INF;
+    }
+
+    k = 0;
+    for (q, distance) in d.iter_mut().enumerate().take(n) {
+        while z[k + 1] < q as f32 {
+            k += 1;
+        }
+        let r = v[k];
+        *distance = (q as f32 - r as f32) * (q as f32 - r as f32) + f[r];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const UPSCALE: usize = 4;
+    const SPREAD: usize = 2;
+
+    // 100x40 pixels with the left 40 columns covered
+    fn field() -> DistanceField {
+        let (width, height) = (100, 40);
+        let mut coverage = vec![0; width * height];
+        for row in coverage.chunks_exact_mut(width) {
+            row[..40].fill(255);
+        }
+        coverage_to_distance_field(&coverage, width, height, UPSCALE, SPREAD)
+    }
+
+    fn value(field: &DistanceField, x: usize, y: usize) -> u8 {
+        field.data[y * field.width + x]
+    }
+
+    #[test]
+    fn field_has_margin_of_spread_texels() {
+        let field = field();
+        assert_eq!(field.width, 100 / UPSCALE + 2 * SPREAD);
+        assert_eq!(field.height, 40 / UPSCALE + 2 * SPREAD);
+        assert_eq!(field.data.len(), field.width * field.height);
+
+        // sizes that are not multiples of the upscale are rounded up
+        let field = coverage_to_distance_field(&[255; 10 * 6], 10, 6, UPSCALE, 3);
+        assert_eq!((field.width, field.height), (9, 8));
+        assert_eq!(field.data.len(), 9 * 8);
+    }
+
+    #[test]
+    fn edge_is_in_the_middle_of_the_range() {
+        let field = field();
+        let y = field.height / 2;
+        // the left and the right edge of the covered area are between these texels
+        for x in [SPREAD - 1, 40 / UPSCALE + SPREAD - 1] {
+            let (a, b) = (value(&field, x, y), value(&field, x + 1, y));
+            assert!((a as i32 + b as i32 - 255).abs() <= 1, "{} {}", a, b);
+            assert_ne!(a, b);
+        }
+        assert!(value(&field, SPREAD - 1, y) < 128);
+        assert!(value(&field, 40 / UPSCALE + SPREAD - 1, y) > 128);
+    }
+
+    #[test]
+    fn distances_beyond_spread_saturate() {
+        let field = field();
+        let y = field.height / 2;
+        // middle of the covered area, 5 texels from its edges
+        assert_eq!(value(&field, 40 / UPSCALE / 2 + SPREAD, y), 255);
+        for x in 40 / UPSCALE + 2 * SPREAD + 1..field.width {
+            assert_eq!(value(&field, x, y), 0, "{}", x);
+        }
+        for x in 0..field.width {
+            assert!(value(&field, x, 0) < 128, "{}", x);
+        }
+    }
+}
@@ -1,5 +1,5 @@
 //! A library for drawing text for drawing graphics API.
-//! Uses freetype-rs underneath to former the font bitmap texture and collect
+//! Uses freetype-rs underneath to rasterize glyphs into the glyph atlas and collect
 //! information about face glyphs.
 //!
 //! # Examples
@@ -8,31 +8,63 @@
 //!
 //! ```ignore
 //! // Initialize text renderer.
-//! let mut text = FontSizeRenderer::new(&buffer, 25)?;
+//! let char_map = Arc::new(BitmapFont::char_map_from_bytes(&buffer, 0)?);
+//! let mut text = FontSizeRenderer::new(
+//!     Arc::from(buffer.as_slice()),
+//!     FontFace::default(),
+//!     FontSize::new(25.0),
+//!     TextRendering::default(),
+//!     char_map,
+//! )?;
+//! let mut atlas = GlyphAtlas::new(DEFAULT_PAGE_SIZE, DEFAULT_MEMORY_BUDGET);
+//! let mut color_atlas =
+//!     GlyphAtlas::with_format(DEFAULT_PAGE_SIZE, DEFAULT_MEMORY_BUDGET, ColorFormat::RGBA);
 //!
 //! // In render loop:
 //!
-//! // Add some text 10 pixels down and right from the top left screen corner.
+//! // Shape the text into glyphs (the face can be kept for the other texts).
+//! let face = ShapingFace::new(&buffer, &FontFace::default());
+//! let lines = vec![shape_line(
+//!     &face,
+//!     25.0,
+//!     text.get_bitmap_font(),
+//!     "The quick brown fox jumps over the lazy dog",
+//!     TextDirection::Auto,
+//! )];
+//!
+//! // Rasterize glyphs that are not in the atlas yet.
+//! let glyph_ids: Vec<u32> = lines[0].iter().map(|glyph| glyph.glyph_id).collect();
+//! text.load_glyphs(&glyph_ids, &mut atlas, &mut color_atlas)?;
+//!
+//! // Add shaped text 10 pixels down and right from the top left screen corner
+//! // (glyphs at other subpixel positions are rasterized now).
 //! text.add(
-//!     "The quick brown fox jumps over the lazy dog",  // Text to add
-//!     [10, 10],                                       // Position
+//!     &mut atlas,                                     // Glyph atlas
+//!     &mut color_atlas,                               // Color glyph atlas
+//!     &lines,                                         // Shaped lines to add
+//!     [10.0, 10.5],                                   // Position
+//!     [0.0, 0.0, 800.0, 600.0],                       // Clipping rect
 //!     [0.65, 0.16, 0.16, 1.0],                        // Text color
-//! );
+//! )?;
 //!
 //! // Draw text.
-//! text.draw_at(&mut device, &render_target, camera_projection).unwrap();
+//! atlas.draw_at(&mut device, &render_target, camera_projection).unwrap();
+//! color_atlas.draw_at(&mut device, &render_target, camera_projection).unwrap();
 //! ```
 
 #![deny(missing_docs)]
 
 use crate::backend::Device;
-use crate::backend::TexturedY8Vertex;
 use crate::clipping::clip_image;
-use crate::color::ColorFormat;
+use crate::font::{FontFace, FontSize, TextRendering};
+use crate::shaping::ShapedGlyph;
 use crate::texture_font::bitmap_font::BitmapFont;
 pub use crate::texture_font::bitmap_font::FontError;
-use crate::units::UnknownToDeviceTransform;
+use crate::texture_font::glyph_atlas::{GlyphAtlas, GlyphKey, GlyphMask};
+use crate::units::{PixelPoint, PixelTransform};
 
+use std::collections::HashMap;
+use std::sync::Arc;
 use thiserror::Error;
 
 /// General error type returned by the library. Wraps all other errors.
@@ -66,155 +98,335 @@ impl From<anyhow::Error> for Error {
 /// let mut buffer = Vec::new();
 /// file.read_to_end(&mut buffer);
 ///
-/// let mut text = FontSizeRenderer::new(&buffer, 25);
+/// let char_map = Arc::new(BitmapFont::char_map_from_bytes(&buffer, 0)?);
+/// let mut text = FontSizeRenderer::new(
+///     Arc::from(buffer.as_slice()),
+///     FontFace::default(),
+///     FontSize::new(25.0),
+///     TextRendering::default(),
+///     char_map,
+/// );
 /// ```
-pub struct FontSizeRenderer<D: Device> {
+pub struct FontSizeRenderer {
     bitmap_font: BitmapFont,
-    texture: Option<D::Texture>,
-    vertex_data: Vec<TexturedY8Vertex>,
+    // glyphs are rasterized on first use, the font file is shared by all sizes
+    font_data: Arc<[u8]>,
 }
 
-impl<D: Device> FontSizeRenderer<D> {
-    pub fn new(font_data: &[u8], font_size: u8) -> Result<Self, Error> {
-        let bitmap_font = BitmapFont::from_bytes(font_data, font_size, None)?;
+impl FontSizeRenderer {
+    pub fn new(
+        font_data: Arc<[u8]>,
+        font_face: FontFace,
+        font_size: FontSize,
+        rendering: TextRendering,
+        char_map: Arc<HashMap<char, u32>>,
+    ) -> Result<Self, Error> {
+        let bitmap_font = BitmapFont::new(&font_data, font_face, font_size, rendering, char_map)?;
 
         Ok(FontSizeRenderer {
             bitmap_font,
-            texture: None,
-            vertex_data: Vec::new(),
+            font_data,
         })
     }
 
-    /// Add some text to the current draw scene relative to the top left corner
+    /// Rasterizes the glyphs that are not in the atlases yet.
+    pub fn load_glyphs<D: Device>(
+        &mut self,
+        glyph_ids: &[u32],
+        atlas: &mut GlyphAtlas<D>,
+        color_atlas: &mut GlyphAtlas<D>,
+    ) -> Result<(), Error> {
+        Ok(self
+            .bitmap_font
+            .load_glyphs(&self.font_data, glyph_ids, atlas, color_atlas)?)
+    }
+
+    /// Computes the masks of the glyphs that are not in the grayscale atlas yet.
+    pub fn load_masks<D: Device>(
+        &mut self,
+        glyph_ids: &[u32],
+        mask: GlyphMask,
+        atlas: &mut GlyphAtlas<D>,
+    ) -> Result<(), Error> {
+        Ok(self
+            .bitmap_font
+            .load_masks(&self.font_data, glyph_ids, mask, atlas)?)
+    }
+
+    /// Add some shaped text to the current draw scene relative to the top left corner
     /// of the screen using pixel coordinates.
-    pub fn add(&mut self, text: &str, pos: [i32; 2], clipping_rect: [f32; 4], color: [f32; 4]) {
-        // `Result` is used here as an `Either` analogue.
-        let (mut x, mut y) = (pos[0] as f32, pos[1] as f32);
+    ///
+    /// The glyphs have to be loaded first with `load_glyphs()`, glyphs placed
+    /// at other subpixel positions are rasterized here. Color glyphs are added
+    /// to `color_atlas` untinted (only the alpha of the text color is applied)
+    /// and placed at whole pixels.
+    pub fn add<D: Device>(
+        &mut self,
+        atlas: &mut GlyphAtlas<D>,
+        color_atlas: &mut GlyphAtlas<D>,
+        lines: &[Vec<ShapedGlyph>],
+        pos: [f32; 2],
+        clipping_rect: [f32; 4],
+        color: [f32; 4],
+    ) -> Result<(), Error> {
         let line_height = self.bitmap_font.get_font_height() as f32;
-        for ch in text.chars() {
-            if ch == '\n' {
-                x = pos[0] as f32;
-                y = y + line_height;
-            } else if ch == '\t' {
-                if let Some(ch_info) = self.bitmap_font.find_char(' ') {
-                    x += (ch_info.x_advance * 4) as f32;
+        let subpixel_positions = self.bitmap_font.get_rendering().subpixel_positions.max(1);
+
+        // glyph id, subpixel position and whole pixel position of every glyph
+        let mut placed = Vec::new();
+        let mut y = pos[1];
+        for line in lines {
+            let mut x = pos[0];
+            for glyph in line {
+                // Glyph zero means the character is missing in the font. Probably it would be
+                // better to place some "?" mark instead but it may not exist
+                // in the font too.
+                if glyph.glyph_id != 0 {
+                    let is_color = self
+                        .bitmap_font
+                        .find_glyph(glyph.glyph_id)
+                        .is_some_and(|ch_info| ch_info.color);
+                    let glyph_x = x + glyph.x_offset;
+                    let (glyph_x, subpixel) = if is_color || subpixel_positions == 1 {
+                        (glyph_x.round(), 0)
+                    } else {
+                        let whole = glyph_x.floor();
+                        match ((glyph_x - whole) * subpixel_positions as f32).round() as u8 {
+                            subpixel if subpixel == subpixel_positions => (whole + 1.0, 0),
+                            subpixel => (whole, subpixel),
+                        }
+                    };
+                    placed.push((
+                        glyph.glyph_id,
+                        subpixel,
+                        glyph_x,
+                        (y + glyph.y_offset).round(),
+                    ));
                 }
+                x += glyph.x_advance;
+            }
+            y += line_height;
+        }
+
+        let glyphs: Vec<(u32, u8)> = placed
+            .iter()
+            .filter(|glyph| glyph.1 != 0)
+            .map(|glyph| (glyph.0, glyph.1))
+            .collect();
+        self.bitmap_font
+            .load_glyphs_at(&self.font_data, &glyphs, atlas, color_atlas)?;
+
+        let untinted = [1.0, 1.0, 1.0, color[3]];
+        for (glyph_id, subpixel, x, y) in placed {
+            let key = GlyphKey {
+                face: *self.bitmap_font.get_font_face(),
+                font_size: self.bitmap_font.get_font_size(),
+                glyph_id,
+                subpixel,
+                mask: GlyphMask::Coverage,
+            };
+            let ch_info = match self.bitmap_font.find_glyph_at(glyph_id, subpixel) {
+                Some(ch_info) => ch_info,
+                None => continue,
+            };
+            let (atlas, color) = if ch_info.color {
+                (&mut *color_atlas, untinted)
             } else {
-                let ch_info = match self.bitmap_font.find_char(ch) {
-                    Some(info) => info,
-                    // Skip unknown chars from text string. Probably it would be
-                    // better to place some "?" mark instead but it may not exist
-                    // in the font too.
+                (&mut *atlas, color)
+            };
+            let atlas_glyph = match atlas.find(&key) {
+                Some(atlas_glyph) => atlas_glyph,
+                None => continue,
+            };
+
+            if let Some(clipped) = clip_image(
+                x + ch_info.x_offset as f32,
+                y + ch_info.y_offset as f32,
+                ch_info.width as f32,
+                ch_info.height as f32,
+                clipping_rect[0],
+                clipping_rect[1],
+                clipping_rect[2],
+                clipping_rect[3],
+                &atlas_glyph.uv,
+            ) {
+                atlas.add_image(atlas_glyph.page, clipped, color);
+            }
+        }
+        Ok(())
+    }
+
+    /// Adds the masks of the glyphs of the shaped text (like the outline of the text,
+    /// see `GlyphMask`) to the grayscale atlas, masks of color glyphs too.
+    ///
+    /// Masks are computed here on first use, they are placed at whole pixels.
+    pub fn add_mask<D: Device>(
+        &mut self,
+        atlas: &mut GlyphAtlas<D>,
+        lines: &[Vec<ShapedGlyph>],
+        pos: [f32; 2],
+        clipping_rect: [f32; 4],
+        mask: GlyphMask,
+        color: [f32; 4],
+    ) -> Result<(), Error> {
+        let glyph_ids: Vec<u32> = lines.iter().flatten().map(|glyph| glyph.glyph_id).collect();
+        self.load_masks(&glyph_ids, mask, atlas)?;
+
+        let line_height = self.bitmap_font.get_font_height() as f32;
+        let mut y = pos[1];
+        for line in lines {
+            let mut x = pos[0];
+            for glyph in line {
+                let glyph_x = (x + glyph.x_offset).round();
+                let glyph_y = (y + glyph.y_offset).round();
+                x += glyph.x_advance;
+                let key = GlyphKey {
+                    face: *self.bitmap_font.get_font_face(),
+                    font_size: self.bitmap_font.get_font_size(),
+                    glyph_id: glyph.glyph_id,
+                    subpixel: 0,
+                    mask,
+                };
+                let ch_info = match self.bitmap_font.find_glyph_mask(glyph.glyph_id, mask) {
+                    Some(ch_info) if glyph.glyph_id != 0 => ch_info,
+                    _ => continue,
+                };
+                let atlas_glyph = match atlas.find(&key) {
+                    Some(atlas_glyph) => atlas_glyph,
                     None => continue,
                 };
-                let x_offset = x + ch_info.x_offset as f32;
-                let y_offset = y + ch_info.y_offset as f32;
-                let tex = ch_info.tex;
 
                 if let Some(clipped) = clip_image(
-                    x_offset,
-                    y_offset,
+                    glyph_x + ch_info.x_offset as f32,
+                    glyph_y + ch_info.y_offset as f32,
                     ch_info.width as f32,
                     ch_info.height as f32,
                     clipping_rect[0],
                     clipping_rect[1],
                     clipping_rect[2],
                     clipping_rect[3],
-                    &[
-                        tex[0],
-                        tex[1],
-                        tex[0] + ch_info.tex_width,
-                        tex[1] + ch_info.tex_height,
-                    ],
+                    &atlas_glyph.uv,
                 ) {
-                    Self::add_image(
-                        &mut self.vertex_data,
-                        clipped.0,
-                        clipped.1,
-                        clipped.2,
-                        clipped.3,
-                        clipped.4,
-                        color,
-                    );
+                    atlas.add_image(atlas_glyph.page, clipped, color);
                 }
-
-                x += ch_info.x_advance as f32;
             }
+            y += line_height;
         }
+        Ok(())
     }
 
-    /// Draw using provided projection matrix.
+    /// Same as `add()` but every glyph is moved by its own transform from the position
+    /// it would have if the text was added at the origin (see `Font::draw_glyphs_transformed()`).
     ///
-    /// # Examples
-    ///
-    /// ```ignore
-    /// text.add_at("Test1", [6.0, 0.0, 0.0], [1.0, 0.0, 0.0, 1.0]);
-    /// text.add_at("Test2", [0.0, 5.0, 0.0], [0.0, 1.0, 0.0, 1.0]);
-    /// text.draw_at(&mut device, &render_target, camera_projection).unwrap();
-    /// ```
-    pub fn draw_at(
+    /// `glyph_transforms` are in the order of the glyphs of the lines, glyphs without
+    /// a transform and glyphs with the center outside of the clipping rect are skipped.
+    /// Glyphs are placed at whole pixels before they are transformed.
+    pub fn add_transformed<D: Device>(
         &mut self,
-        device: &mut D,
-        target: &D::RenderTarget,
-        transform: UnknownToDeviceTransform,
-    ) -> Result<(), Error> {
-        if self.texture.is_none() {
-            self.texture = Some(device.create_texture(
-                Some(self.bitmap_font.get_image()),
-                self.bitmap_font.get_width(),
-                self.bitmap_font.get_height(),
-                ColorFormat::Y8,
-                false,
-            )?);
+        atlas: &mut GlyphAtlas<D>,
+        color_atlas: &mut GlyphAtlas<D>,
+        lines: &[Vec<ShapedGlyph>],
+        glyph_transforms: &[Option<PixelTransform>],
+        clipping_rect: [f32; 4],
+        color: [f32; 4],
+    ) {
+        let untinted = [1.0, 1.0, 1.0, color[3]];
+        let quads =
+            self.transformed_quads(lines, glyph_transforms, clipping_rect, GlyphMask::Coverage);
+        for (key, is_color, corners) in quads {
+            let (atlas, color) = if is_color {
+                (&mut *color_atlas, untinted)
+            } else {
+                (&mut *atlas, color)
+            };
+            if let Some(atlas_glyph) = atlas.find(&key) {
+                atlas.add_quad(atlas_glyph.page, corners, atlas_glyph.uv, color);
+            }
         }
-        device.triangles_textured_y8(
-            target,
-            self.texture.as_ref().unwrap(),
-            false,
-            &self.vertex_data,
-            transform,
-        );
-        self.vertex_data.clear();
-        Ok(())
-    }
-
-    pub fn get_bitmap_font(&self) -> &BitmapFont {
-        &self.bitmap_font
     }
 
-    fn add_image(
-        vertex_data: &mut Vec<TexturedY8Vertex>,
-        x1: f32,
-        y1: f32,
-        width: f32,
-        height: f32,
-        uv: [f32; 4],
+    /// Same as `add_mask()` but every glyph is moved by its own transform
+    /// (see `add_transformed()`). The masks have to be loaded first with `load_masks()`.
+    pub fn add_mask_transformed<D: Device>(
+        &mut self,
+        atlas: &mut GlyphAtlas<D>,
+        lines: &[Vec<ShapedGlyph>],
+        glyph_transforms: &[Option<PixelTransform>],
+        clipping_rect: [f32; 4],
+        mask: GlyphMask,
         color: [f32; 4],
     ) {
-        // Top-left point, index + 0.
-        let vert0 = TexturedY8Vertex::new([x1, y1], [uv[0], uv[1]], color);
-        // Bottom-left point, index + 1.
-        let vert1 = TexturedY8Vertex::new([x1, y1 + height], [uv[0], uv[3]], color);
-        // Bottom-right point, index + 2.
-        let vert2 = TexturedY8Vertex::new([x1 + width, y1 + height], [uv[2], uv[3]], color);
-        // Top-right point, index + 3.
-        let vert3 = TexturedY8Vertex::new([x1 + width, y1], [uv[2], uv[1]], color);
-
-        // Top-left triangle.
-        // 0--3
-        // | /
-        // |/
-        // 1
-        vertex_data.push(vert0);
-        vertex_data.push(vert1);
-        vertex_data.push(vert3);
-        // Bottom-right triangle.
-        //    3
-        //   /|
-        //  / |
-        // 1--2
-        vertex_data.push(vert3);
-        vertex_data.push(vert1);
-        vertex_data.push(vert2);
+        let quads = self.transformed_quads(lines, glyph_transforms, clipping_rect, mask);
+        for (key, _, corners) in quads {
+            if let Some(atlas_glyph) = atlas.find(&key) {
+                atlas.add_quad(atlas_glyph.page, corners, atlas_glyph.uv, color);
+            }
+        }
+    }
+
+    // Returns the atlas key, the color flag and the transformed corners of every glyph
+    // added by `add_transformed()` and `add_mask_transformed()`.
+    fn transformed_quads(
+        &self,
+        lines: &[Vec<ShapedGlyph>],
+        glyph_transforms: &[Option<PixelTransform>],
+        clipping_rect: [f32; 4],
+        mask: GlyphMask,
+    ) -> Vec<(GlyphKey, bool, [[f32; 2]; 4])> {
+        let line_height = self.bitmap_font.get_font_height() as f32;
+        let mut quads = Vec::new();
+        let mut glyph_transforms = glyph_transforms.iter();
+        let mut y = 0.0;
+        for line in lines {
+            let mut x = 0.0;
+            for glyph in line {
+                let glyph_x = x + glyph.x_offset;
+                let advance_center =
+                    PixelPoint::new(x + glyph.x_advance * 0.5, y + line_height * 0.5);
+                x += glyph.x_advance;
+                let glyph_transform = match glyph_transforms.next() {
+                    Some(Some(glyph_transform)) if glyph.glyph_id != 0 => glyph_transform,
+                    _ => continue,
+                };
+                let center = glyph_transform.transform_point(advance_center);
+                if center.x < clipping_rect[0]
+                    || center.y < clipping_rect[1]
+                    || center.x >= clipping_rect[0] + clipping_rect[2]
+                    || center.y >= clipping_rect[1] + clipping_rect[3]
+                {
+                    continue;
+                }
+
+                let key = GlyphKey {
+                    face: *self.bitmap_font.get_font_face(),
+                    font_size: self.bitmap_font.get_font_size(),
+                    glyph_id: glyph.glyph_id,
+                    subpixel: 0,
+                    mask,
+                };
+                let ch_info = match self.bitmap_font.find_glyph_mask(glyph.glyph_id, mask) {
+                    Some(ch_info) => ch_info,
+                    None => continue,
+                };
+
+                let left = glyph_x.round() + ch_info.x_offset as f32;
+                let top = (y + glyph.y_offset).round() + ch_info.y_offset as f32;
+                let right = left + ch_info.width as f32;
+                let bottom = top + ch_info.height as f32;
+                let corners =
+                    [[left, top], [left, bottom], [right, bottom], [right, top]].map(|[x, y]| {
+                        glyph_transform
+                            .transform_point(PixelPoint::new(x, y))
+                            .to_array()
+                    });
+                quads.push((key, ch_info.color, corners));
+            }
+            y += line_height;
+        }
+        quads
+    }
+
+    pub fn get_bitmap_font(&self) -> &BitmapFont {
+        &self.bitmap_font
     }
 }
@@ -0,0 +1,161 @@
+//! Opens the face of the font file with FreeType.
+//!
+//! The face is selected by its index in the font collection and its variation
+//! axes are set to the requested values (FreeType multiple masters API).
+//! Synthesized bold and oblique styles are applied to the outlines of loaded glyphs.
+
+use crate::font::{FontFace, FontSynthesis};
+use crate::texture_font::bitmap_font::FontError;
+
+use freetype as ft;
+use freetype::ffi::{FT_Error, FT_Face, FT_Fixed, FT_Library, FT_Long, FT_UInt, FT_ULong};
+use freetype::Face;
+use std::os::raw::{c_char, c_void};
+use std::rc::Rc;
+
+// FT_Var_Axis
+#[repr(C)]
+struct VarAxis {
+    _name: *mut c_char,
+    minimum: FT_Fixed,
+    default: FT_Fixed,
+    maximum: FT_Fixed,
+    tag: FT_ULong,
+    _strid: FT_UInt,
+}
+
+// FT_MM_Var
+#[repr(C)]
+struct MmVar {
+    num_axis: FT_UInt,
+    _num_designs: FT_UInt,
+    _num_namedstyles: FT_UInt,
+    axis: *mut VarAxis,
+    _namedstyle: *mut c_void,
+}
+
+extern "C" {
+    fn FT_Get_MM_Var(face: FT_Face, amaster: *mut *mut MmVar) -> FT_Error;
+    fn FT_Done_MM_Var(library: FT_Library, amaster: *mut MmVar) -> FT_Error;
+    fn FT_Set_Var_Design_Coordinates(
+        face: FT_Face,
+        num_coords: FT_UInt,
+        coords: *mut FT_Fixed,
+    ) -> FT_Error;
+}
+
+/// Loads the face of the font file.
+///
+/// Variation axes not present in the font are ignored, values are clamped
+/// to the ranges of the axes.
+pub fn open_face(data: &[u8], font_face: &FontFace) -> Result<Face, FontError> {
+    let library = ft::Library::init()?;
+    // reduces color fringes of glyphs rendered for LCD screens (FreeType built without
+    // ClearType-style filtering returns an error and uses its own LCD rendering)
+    unsafe {
+        ft::ffi::FT_Library_SetLcdFilter(library.raw(), ft::ffi::FT_LCD_FILTER_DEFAULT);
+    }
+    let mut face = library.new_memory_face(Rc::new(data.into()), font_face.index as isize)?;
+    if !font_face.variations.is_empty() {
+        set_variations(&library, &mut face, font_face)?;
+    }
+    Ok(face)
+}
+
+/// Applies the synthesized styles to the outline of the loaded glyph
+/// (glyphs loaded as bitmaps are not changed).
+///
+/// The synthesized bold widens the advances of the glyph by the strength
+/// it widens the outline by (as `FT_GlyphSlot_Embolden` does).
+///
+/// `unscaled` is true for glyphs loaded in font units (`LoadFlag::NO_SCALE`).
+/// Must be called before the glyph is rendered.
+pub fn synthesize(
+    face: &mut Face,
+    synthesis: FontSynthesis,
+    unscaled: bool,
+) -> Result<(), FontError> {
+    if synthesis == FontSynthesis::default() {
+        return Ok(());
+    }
+    let units_per_em = face.raw().units_per_EM as FT_Long;
+    let em_size = match face.size_metrics() {
+        Some(metrics) if !unscaled => unsafe { ft::ffi::FT_MulFix(units_per_em, metrics.y_scale) },
+        _ => units_per_em,
+    };
+    let slot = unsafe { &mut *face.raw_mut().glyph };
+    if slot.format != ft::ffi::FT_GLYPH_FORMAT_OUTLINE {
+        return Ok(());
+    }
+    if synthesis.bold {
+        // the same strength as FT_GlyphSlot_Embolden uses
+        let strength = em_size / 24;
+        let error =
+            unsafe { ft::ffi::FT_Outline_EmboldenXY(&mut slot.outline, strength, strength) };
+        if error != 0 {
+            return Err(FontError::FreetypeError(error.into()));
+        }
+        // glyphs without advances (like combining marks) keep them
+        if slot.advance.x != 0 {
+            slot.advance.x += strength;
+        }
+        // linear advances are in font units for unscaled glyphs, in 16.16 fixed point
+        // pixels otherwise (the strength is 26.6 fixed point then)
+        let linear_strength = if unscaled { strength } else { strength << 10 };
+        if slot.linearHoriAdvance != 0 {
+            slot.linearHoriAdvance += linear_strength;
+        }
+        if slot.linearVertAdvance != 0 {
+            slot.linearVertAdvance += linear_strength;
+        }
+        slot.metrics.horiAdvance += strength;
+        slot.metrics.vertAdvance += strength;
+    }
+    if synthesis.oblique {
+        // slant of about 12 degrees (as FT_GlyphSlot_Oblique), 16.16 fixed point
+        let shear = ft::ffi::FT_Matrix {
+            xx: 0x10000,
+            xy: 0x0366A,
+            yx: 0,
+            yy: 0x10000,
+        };
+        unsafe { ft::ffi::FT_Outline_Transform(&slot.outline, &shear) };
+    }
+    Ok(())
+}
+
+fn set_variations(
+    library: &ft::Library,
+    face: &mut Face,
+    font_face: &FontFace,
+) -> Result<(), FontError> {
+    let mut mm_var: *mut MmVar = std::ptr::null_mut();
+    if unsafe { FT_Get_MM_Var(face.raw_mut(), &mut mm_var) } != 0 {
+        // not a variable font
+        return Ok(());
+    }
+
+    let mut coords: Vec<FT_Fixed> = {
+        let mm_var = unsafe { &*mm_var };
+        let axes = unsafe { std::slice::from_raw_parts(mm_var.axis, mm_var.num_axis as usize) };
+        axes.iter()
+            .map(|axis| {
+                let tag = (axis.tag as u32).to_be_bytes();
+                match font_face.variations.get(tag) {
+                    Some(value) => {
+                        ((value * 65536.0).round() as FT_Fixed).clamp(axis.minimum, axis.maximum)
+                    }
+                    None => axis.default,
+                }
+            })
+            .collect()
+    };
+    let error = unsafe {
+        FT_Done_MM_Var(library.raw(), mm_var);
+        FT_Set_Var_Design_Coordinates(face.raw_mut(), coords.len() as FT_UInt, coords.as_mut_ptr())
+    };
+    if error != 0 {
+        return Err(FontError::FreetypeError(error.into()));
+    }
+    Ok(())
+}
@@ -0,0 +1,802 @@
+//! Texture atlas of rasterized glyphs.
+//!
+//! Glyphs are packed into rows (shelves) of square pages. Every page is
+//! a separate texture, only rows changed since the last draw are uploaded.
+//! When the memory budget is exceeded the least recently used shelves
+//! are cleared and reused for new glyphs (adjacent shelves are merged
+//! when a higher one is needed).
+//!
+//! Pages are either 8-bit grayscale (coverage of monochrome glyphs)
+//! or RGBA (color glyphs like emoji, drawn without tinting, or the coverage
+//! of LCD subpixels).
+
+#[cfg(feature = "freetype")]
+use crate::backend::DistanceFieldParams;
+use crate::backend::{Device, GlyphPaint, Texture, TexturedVertex, TexturedY8Vertex};
+use crate::color::ColorFormat;
+use crate::font::{FontFace, FontSize};
+use crate::units::UnknownToDeviceTransform;
+use anyhow::Result;
+use std::collections::HashMap;
+use std::ops::Range;
+
+/// Default size of the atlas page in pixels.
+pub const DEFAULT_PAGE_SIZE: u16 = 1024;
+
+/// Default memory budget of the atlas (four pages of the default size).
+pub const DEFAULT_MEMORY_BUDGET: usize = 4 * 1024 * 1024;
+
+// empty space left on the right and bottom side of every glyph
+const PADDING: u16 = 1;
+
+#[derive(Debug, PartialEq, Eq, Hash, Copy, Clone)]
+pub struct GlyphKey {
+    pub face: FontFace,
+    pub font_size: FontSize,
+    pub glyph_id: u32,
+    /// Horizontal position within the pixel the glyph is rasterized for
+    /// (index of the subpixel position).
+    pub subpixel: u8,
+    pub mask: GlyphMask,
+}
+
+/// Image of the glyph stored in the atlas.
+///
+/// Distances are in 1/64 of a pixel.
+#[derive(Debug, PartialEq, Eq, Hash, Copy, Clone, Default)]
+pub enum GlyphMask {
+    /// Coverage of the glyph as rasterized (or its distance field).
+    #[default]
+    Coverage,
+    /// Coverage grown by the radius and fading out over the softness
+    /// (the outline or the glow of the text).
+    Grown { radius: u16, softness: u16 },
+    /// Coverage grown by the radius and blurred (the shadow of the text).
+    Blurred { radius: u16, blur: u16 },
+}
+
+impl GlyphMask {
+    /// Mask of the coverage grown by the radius (in pixels) with the edge fading out
+    /// over the softness (at least one pixel).
+    pub fn grown(radius: f32, softness: f32) -> Self {
+        GlyphMask::Grown {
+            radius: to_fixed(radius),
+            softness: to_fixed(softness.max(1.0)),
+        }
+    }
+
+    /// Mask of the coverage grown by the radius and blurred by the blur radius (in pixels).
+    pub fn blurred(radius: f32, blur: f32) -> Self {
+        GlyphMask::Blurred {
+            radius: to_fixed(radius),
+            blur: to_fixed(blur),
+        }
+    }
+}
+
+fn to_fixed(pixels: f32) -> u16 {
+    (pixels.max(0.0) * 64.0).round().min(u16::MAX as f32) as u16
+}
+
+/// Location of the glyph in the atlas.
+#[derive(Debug, Copy, Clone)]
+pub struct AtlasGlyph {
+    pub page: usize,
+    /// Texture coordinates (left, top, right, bottom).
+    pub uv: [f32; 4],
+    // top of the shelf
+    y: u16,
+}
+
+struct Shelf {
+    y: u16,
+    height: u16,
+    width_used: u16,
+    glyphs: Vec<GlyphKey>,
+    last_used: u64,
+}
+
+impl Shelf {
+    fn new(y: u16, height: u16) -> Self {
+        Shelf {
+            y,
+            height,
+            width_used: 0,
+            glyphs: Vec::new(),
+            last_used: 0,
+        }
+    }
+}
+
+struct AtlasPage<D: Device> {
+    image: Vec<u8>,
+    shelves: Vec<Shelf>,
+    shelves_height: u16,
+    texture: Option<D::Texture>,
+    // rows modified since the last upload
+    dirty: Option<(u16, u16)>,
+    vertex_data: Vec<TexturedY8Vertex>,
+}
+
+impl<D: Device> AtlasPage<D> {
+    fn new(page_size: u16, format: ColorFormat) -> Self {
+        AtlasPage {
+            image: vec![0; page_size as usize * page_size as usize * bytes_per_pixel(format)],
+            shelves: Vec::new(),
+            shelves_height: 0,
+            texture: None,
+            dirty: None,
+            vertex_data: Vec::new(),
+        }
+    }
+
+    // creates the texture or updates its modified rows
+    fn upload(&mut self, device: &mut D, page_size: u16, format: ColorFormat) -> Result<()> {
+        match (&mut self.texture, self.dirty) {
+            (None, _) => {
+                self.texture = Some(device.create_texture(
+                    Some(&self.image),
+                    page_size,
+                    page_size,
+                    format,
+                    true,
+                )?);
+            }
+            (Some(texture), Some((y1, y2))) => {
+                let row_size = page_size as usize * bytes_per_pixel(format);
+                let rows = &self.image[y1 as usize * row_size..y2 as usize * row_size];
+                texture.update(rows, 0, y1, page_size, y2 - y1)?;
+            }
+            (Some(_), None) => (),
+        }
+        self.dirty = None;
+        Ok(())
+    }
+
+    fn mark_dirty(&mut self, y1: u16, y2: u16) {
+        self.dirty = Some(match self.dirty {
+            Some((dirty_y1, dirty_y2)) => (dirty_y1.min(y1), dirty_y2.max(y2)),
+            None => (y1, y2),
+        });
+    }
+}
+
+/// Glyph bitmaps of all sizes of one font.
+pub struct GlyphAtlas<D: Device> {
+    page_size: u16,
+    format: ColorFormat,
+    memory_budget: usize,
+    pages: Vec<AtlasPage<D>>,
+    glyphs: HashMap<GlyphKey, AtlasGlyph>,
+    counter: u64,
+}
+
+impl<D: Device> GlyphAtlas<D> {
+    /// Creates an empty atlas of 8-bit grayscale glyphs.
+    ///
+    /// # Arguments
+    ///
+    /// * `page_size` - width and height of every page (multiple of 4)
+    /// * `memory_budget` - max number of bytes used by the pages
+    pub fn new(page_size: u16, memory_budget: usize) -> Self {
+        Self::with_format(page_size, memory_budget, ColorFormat::Y8)
+    }
+
+    /// Creates an empty atlas with pages of the given format.
+    ///
+    /// Glyphs of `ColorFormat::RGBA` atlas are drawn with their own colors
+    /// (only the alpha of the vertex color is applied).
+    pub fn with_format(page_size: u16, memory_budget: usize, format: ColorFormat) -> Self {
+        GlyphAtlas {
+            page_size,
+            format,
+            memory_budget,
+            pages: Vec::new(),
+            glyphs: HashMap::new(),
+            counter: 0,
+        }
+    }
+
+    /// Starts the next batch of glyphs.
+    ///
+    /// Glyphs used in the current batch are never evicted to make room
+    /// for other glyphs of the same batch (the budget is exceeded instead
+    /// until the next batch).
+    pub(crate) fn next_batch(&mut self) {
+        self.counter += 1;
+        self.remove_pages_over_budget();
+    }
+
+    /// Returns the location of the glyph and marks it as used.
+    pub(crate) fn find(&mut self, key: &GlyphKey) -> Option<AtlasGlyph> {
+        let glyph = *self.glyphs.get(key)?;
+        let page = &mut self.pages[glyph.page];
+        if let Ok(shelf_index) = page.shelves.binary_search_by_key(&glyph.y, |shelf| shelf.y) {
+            page.shelves[shelf_index].last_used = self.counter;
+        }
+        Some(glyph)
+    }
+
+    /// Format of the pages.
+    pub fn format(&self) -> ColorFormat {
+        self.format
+    }
+
+    /// Copies the glyph bitmap into the atlas. The bitmap has the format
+    /// of the atlas (8-bit grayscale or BGRA bytes with straight alpha).
+    ///
+    /// Returns false if the glyph is bigger than the page.
+    pub(crate) fn insert(&mut self, key: GlyphKey, width: u16, height: u16, data: &[u8]) -> bool {
+        let (page_index, shelf_index) = match self.allocate(width + PADDING, height + PADDING) {
+            Some(location) => location,
+            None => return false,
+        };
+
+        let bytes_per_pixel = bytes_per_pixel(self.format);
+        let row_size = self.page_size as usize * bytes_per_pixel;
+        let page = &mut self.pages[page_index];
+        let shelf = &mut page.shelves[shelf_index];
+        let x = shelf.width_used;
+        let y = shelf.y;
+        shelf.width_used += width + PADDING;
+        shelf.glyphs.push(key);
+        shelf.last_used = self.counter;
+
+        let width_bytes = width as usize * bytes_per_pixel;
+        for row in 0..height as usize {
+            let src = &data[row * width_bytes..(row + 1) * width_bytes];
+            let dst = (y as usize + row) * row_size + x as usize * bytes_per_pixel;
+            page.image[dst..dst + width_bytes].copy_from_slice(src);
+        }
+        page.mark_dirty(y, y + height);
+
+        let size = self.page_size as f32;
+        self.glyphs.insert(
+            key,
+            AtlasGlyph {
+                page: page_index,
+                uv: [
+                    x as f32 / size,
+                    y as f32 / size,
+                    (x + width) as f32 / size,
+                    (y + height) as f32 / size,
+                ],
+                y,
+            },
+        );
+        true
+    }
+
+    /// Adds the image quad that will be drawn with the page texture.
+    ///
+    /// The image is the rectangle and the texture coordinates returned by `clip_image()`.
+    pub(crate) fn add_image(
+        &mut self,
+        page: usize,
+        (x1, y1, width, height, uv): (f32, f32, f32, f32, [f32; 4]),
+        color: [f32; 4],
+    ) {
+        let (x2, y2) = (x1 + width, y1 + height);
+        self.add_quad(page, [[x1, y1], [x1, y2], [x2, y2], [x2, y1]], uv, color);
+    }
+
+    /// Adds the image quad of any shape (like a rotated glyph) that will be drawn
+    /// with the page texture.
+    ///
+    /// Corners are top-left, bottom-left, bottom-right and top-right corners
+    /// of the image.
+    pub(crate) fn add_quad(
+        &mut self,
+        page: usize,
+        corners: [[f32; 2]; 4],
+        uv: [f32; 4],
+        color: [f32; 4],
+    ) {
+        // Top-left point, index + 0.
+        let vert0 = TexturedY8Vertex::new(corners[0], [uv[0], uv[1]], color);
+        // Bottom-left point, index + 1.
+        let vert1 = TexturedY8Vertex::new(corners[1], [uv[0], uv[3]], color);
+        // Bottom-right point, index + 2.
+        let vert2 = TexturedY8Vertex::new(corners[2], [uv[2], uv[3]], color);
+        // Top-right point, index + 3.
+        let vert3 = TexturedY8Vertex::new(corners[3], [uv[2], uv[1]], color);
+
+        let vertex_data = &mut self.pages[page].vertex_data;
+
+        // Top-left triangle.
+        // 0--3
+        // | /
+        // |/
+        // 1
+        vertex_data.push(vert0);
+        vertex_data.push(vert1);
+        vertex_data.push(vert3);
+        // Bottom-right triangle.
+        //    3
+        //   /|
+        //  / |
+        // 1--2
+        vertex_data.push(vert3);
+        vertex_data.push(vert1);
+        vertex_data.push(vert2);
+    }
+
+    /// Uploads the modified parts of the pages and draws the added vertices
+    /// using provided projection matrix.
+    pub(crate) fn draw_at(
+        &mut self,
+        device: &mut D,
+        target: &D::RenderTarget,
+        transform: UnknownToDeviceTransform,
+    ) -> Result<()> {
+        self.draw_pages(device, target, false, transform)
+    }
+
+    /// Same as `draw_at()` but grayscale pages are sampled with linear filtering
+    /// (for glyphs that are not aligned to the pixels, like rotated ones).
+    pub(crate) fn draw_filtered_at(
+        &mut self,
+        device: &mut D,
+        target: &D::RenderTarget,
+        transform: UnknownToDeviceTransform,
+    ) -> Result<()> {
+        self.draw_pages(device, target, true, transform)
+    }
+
+    fn draw_pages(
+        &mut self,
+        device: &mut D,
+        target: &D::RenderTarget,
+        filtering: bool,
+        transform: UnknownToDeviceTransform,
+    ) -> Result<()> {
+        let page_size = self.page_size;
+        let format = self.format;
+        for page in &mut self.pages {
+            if page.vertex_data.is_empty() {
+                continue;
+            }
+            page.upload(device, page_size, format)?;
+            match format {
+                ColorFormat::Y8 => device.triangles_textured_y8(
+                    target,
+                    page.texture.as_ref().unwrap(),
+                    filtering,
+                    &page.vertex_data,
+                    transform,
+                ),
+                ColorFormat::RGBA => {
+                    // bitmaps of color glyphs may be scaled (fonts with fixed sizes only)
+                    let vertices: Vec<TexturedVertex> = page
+                        .vertex_data
+                        .iter()
+                        .map(|v| TexturedVertex::new(v.pos, v.tex_coords, v.color))
+                        .collect();
+                    device.triangles_textured(
+                        target,
+                        page.texture.as_ref().unwrap(),
+                        true,
+                        &vertices,
+                        transform,
+                    );
+                }
+            }
+            page.vertex_data.clear();
+        }
+        Ok(())
+    }
+
+    /// Same as `draw_at()` but the glyphs are filled with the paint
+    /// (the color of the added vertices is ignored). Grayscale atlas only.
+    pub(crate) fn draw_paint_at(
+        &mut self,
+        device: &mut D,
+        target: &D::RenderTarget,
+        paint: &GlyphPaint<D::Texture>,
+        transform: UnknownToDeviceTransform,
+    ) -> Result<()> {
+        self.draw_paint_pages(device, target, false, paint, transform)
+    }
+
+    /// Same as `draw_paint_at()` but the pages are sampled with linear filtering
+    /// (for glyphs that are not aligned to the pixels, like rotated ones).
+    pub(crate) fn draw_paint_filtered_at(
+        &mut self,
+        device: &mut D,
+        target: &D::RenderTarget,
+        paint: &GlyphPaint<D::Texture>,
+        transform: UnknownToDeviceTransform,
+    ) -> Result<()> {
+        self.draw_paint_pages(device, target, true, paint, transform)
+    }
+
+    fn draw_paint_pages(
+        &mut self,
+        device: &mut D,
+        target: &D::RenderTarget,
+        filtering: bool,
+        paint: &GlyphPaint<D::Texture>,
+        transform: UnknownToDeviceTransform,
+    ) -> Result<()> {
+        let page_size = self.page_size;
+        let format = self.format;
+        for page in &mut self.pages {
+            if page.vertex_data.is_empty() {
+                continue;
+            }
+            page.upload(device, page_size, format)?;
+            device.triangles_textured_y8_paint(
+                target,
+                page.texture.as_ref().unwrap(),
+                filtering,
+                &page.vertex_data,
+                paint,
+                transform,
+            );
+            page.vertex_data.clear();
+        }
+        Ok(())
+    }
+
+    /// Same as `draw_at()` but the pages contain the coverage of LCD subpixels
+    /// (RGBA atlas only, the alpha is not used).
+    #[cfg(feature = "freetype")]
+    pub(crate) fn draw_lcd_at(
+        &mut self,
+        device: &mut D,
+        target: &D::RenderTarget,
+        transform: UnknownToDeviceTransform,
+    ) -> Result<()> {
+        let page_size = self.page_size;
+        let format = self.format;
+        for page in &mut self.pages {
+            if page.vertex_data.is_empty() {
+                continue;
+            }
+            page.upload(device, page_size, format)?;
+            device.triangles_textured_lcd(
+                target,
+                page.texture.as_ref().unwrap(),
+                &page.vertex_data,
+                transform,
+            );
+            page.vertex_data.clear();
+        }
+        Ok(())
+    }
+
+    /// Same as `draw_at()` but the pages contain signed distance fields
+    /// (grayscale atlas only).
+    #[cfg(feature = "freetype")]
+    pub(crate) fn draw_distance_field_at(
+        &mut self,
+        device: &mut D,
+        target: &D::RenderTarget,
+        params: &DistanceFieldParams,
+        transform: UnknownToDeviceTransform,
+    ) -> Result<()> {
+        let page_size = self.page_size;
+        let format = self.format;
+        for page in &mut self.pages {
+            if page.vertex_data.is_empty() {
+                continue;
+            }
+            page.upload(device, page_size, format)?;
+            device.triangles_distance_field(
+                target,
+                page.texture.as_ref().unwrap(),
+                &page.vertex_data,
+                params,
+                transform,
+            );
+            page.vertex_data.clear();
+        }
+        Ok(())
+    }
+
+    /// Removes all glyphs and pages.
+    pub fn clear(&mut self) {
+        self.pages.clear();
+        self.glyphs.clear();
+    }
+
+    pub fn memory_usage(&self) -> usize {
+        self.pages.len() * self.page_memory_usage()
+    }
+
+    pub fn memory_budget(&self) -> usize {
+        self.memory_budget
+    }
+
+    /// Sets the memory budget. Last pages are removed if the budget is exceeded.
+    pub fn set_memory_budget(&mut self, memory_budget: usize) {
+        self.memory_budget = memory_budget;
+        self.remove_pages_over_budget();
+    }
+
+    fn remove_pages_over_budget(&mut self) {
+        while self.pages.len() > 1 && self.memory_usage() > self.memory_budget {
+            let page_index = self.pages.len() - 1;
+            self.glyphs.retain(|_, glyph| glyph.page != page_index);
+            self.pages.pop();
+        }
+    }
+
+    fn page_memory_usage(&self) -> usize {
+        self.page_size as usize * self.page_size as usize * bytes_per_pixel(self.format)
+    }
+
+    // finds the shelf with enough room for the rectangle
+    fn allocate(&mut self, width: u16, height: u16) -> Option<(usize, usize)> {
+        if width > self.page_size || height > self.page_size {
+            return None;
+        }
+
+        // the lowest existing shelf that is not too high for the glyph
+        let max_height = height + height / 2 + 4;
+        let best = self
+            .shelves()
+            .filter(|(_, _, shelf)| {
+                shelf.height >= height
+                    && shelf.height <= max_height
+                    && self.page_size - shelf.width_used >= width
+            })
+            .min_by_key(|(_, _, shelf)| shelf.height);
+        if let Some((page_index, shelf_index, _)) = best {
+            return Some((page_index, shelf_index));
+        }
+
+        // new shelf on the existing page
+        for (page_index, page) in self.pages.iter_mut().enumerate() {
+            if self.page_size - page.shelves_height >= height {
+                return Some((page_index, Self::add_shelf(page, height)));
+            }
+        }
+
+        // new page
+        if self.pages.is_empty()
+            || self.memory_usage() + self.page_memory_usage() <= self.memory_budget
+        {
+            return Some(self.add_page(height));
+        }
+
+        // the least recently used shelves
+        if let Some(location) = self.evict(height) {
+            return Some(location);
+        }
+
+        // all glyphs are in use, exceed the budget
+        Some(self.add_page(height))
+    }
+
+    fn shelves(&self) -> impl Iterator<Item = (usize, usize, &Shelf)> {
+        self.pages
+            .iter()
+            .enumerate()
+            .flat_map(|(page_index, page)| {
+                page.shelves
+                    .iter()
+                    .enumerate()
+                    .map(move |(shelf_index, shelf)| (page_index, shelf_index, shelf))
+            })
+    }
+
+    fn add_page(&mut self, height: u16) -> (usize, usize) {
+        let mut page = AtlasPage::new(self.page_size, self.format);
+        let shelf_index = Self::add_shelf(&mut page, height);
+        self.pages.push(page);
+        (self.pages.len() - 1, shelf_index)
+    }
+
+    fn add_shelf(page: &mut AtlasPage<D>, height: u16) -> usize {
+        page.shelves.push(Shelf::new(page.shelves_height, height));
+        page.shelves_height += height;
+        page.shelves.len() - 1
+    }
+
+    // Clears adjacent shelves (not used in the current batch) that are high enough
+    // together for the glyph and were least recently used. They are replaced by the new
+    // shelf for the glyph and an empty shelf for the rest of the space.
+    fn evict(&mut self, height: u16) -> Option<(usize, usize)> {
+        let mut lru: Option<(usize, Range<usize>, u64)> = None;
+        for (page_index, page) in self.pages.iter().enumerate() {
+            let free_height = self.page_size - page.shelves_height;
+            for first in 0..page.shelves.len() {
+                let mut run_height = 0;
+                let mut run_last_used = 0;
+                for (last, shelf) in page.shelves.iter().enumerate().skip(first) {
+                    if shelf.last_used == self.counter {
+                        break;
+                    }
+                    run_height += shelf.height;
+                    run_last_used = run_last_used.max(shelf.last_used);
+                    if lru.as_ref().is_some_and(|lru| run_last_used >= lru.2) {
+                        break;
+                    }
+                    let is_last = last == page.shelves.len() - 1;
+                    if run_height >= height || is_last && run_height + free_height >= height {
+                        lru = Some((page_index, first..last + 1, run_last_used));
+                        break;
+                    }
+                }
+            }
+        }
+
+        let (page_index, range, _) = lru?;
+        let row_size = self.page_size as usize * bytes_per_pixel(self.format);
+        let page = &mut self.pages[page_index];
+        for shelf in &page.shelves[range.clone()] {
+            for key in &shelf.glyphs {
+                self.glyphs.remove(key);
+            }
+        }
+
+        let y = page.shelves[range.start].y;
+        let last = &page.shelves[range.end - 1];
+        let y2 = last.y + last.height;
+        page.image[y as usize * row_size..y2 as usize * row_size].fill(0);
+        page.mark_dirty(y, y2);
+
+        let mut new_shelves = vec![Shelf::new(y, height)];
+        if range.end == page.shelves.len() {
+            page.shelves_height = y + height;
+        } else {
+            let end = page.shelves[range.end].y;
+            if end > y + height {
+                new_shelves.push(Shelf::new(y + height, end - y - height));
+            }
+        }
+        page.shelves.splice(range.clone(), new_shelves);
+        Some((page_index, range.start))
+    }
+}
+
+fn bytes_per_pixel(format: ColorFormat) -> usize {
+    match format {
+        ColorFormat::RGBA => 4,
+        ColorFormat::Y8 => 1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::TestDevice;
+
+    const PAGE_SIZE: u16 = 32;
+    const PAGE_MEMORY: usize = PAGE_SIZE as usize * PAGE_SIZE as usize;
+
+    fn key(glyph_id: u32) -> GlyphKey {
+        GlyphKey {
+            face: FontFace::default(),
+            font_size: FontSize::new(12.0),
+            glyph_id,
+            subpixel: 0,
+            mask: GlyphMask::Coverage,
+        }
+    }
+
+    fn insert(atlas: &mut GlyphAtlas<TestDevice>, glyph_id: u32, width: u16, height: u16) {
+        let data = vec![255; width as usize * height as usize];
+        assert!(atlas.insert(key(glyph_id), width, height, &data));
+    }
+
+    // left and top of the glyph in pixels
+    fn position(atlas: &mut GlyphAtlas<TestDevice>, glyph_id: u32) -> (usize, u16, u16) {
+        let glyph = atlas.find(&key(glyph_id)).unwrap();
+        let size = PAGE_SIZE as f32;
+        (
+            glyph.page,
+            (glyph.uv[0] * size) as u16,
+            (glyph.uv[1] * size) as u16,
+        )
+    }
+
+    #[test]
+    fn glyphs_of_similar_height_share_a_shelf() {
+        let mut atlas = GlyphAtlas::<TestDevice>::new(PAGE_SIZE, PAGE_MEMORY);
+        insert(&mut atlas, 1, 8, 8);
+        insert(&mut atlas, 2, 8, 6);
+        insert(&mut atlas, 3, 8, 7);
+        // too high for the first shelf
+        insert(&mut atlas, 4, 8, 20);
+
+        assert_eq!(position(&mut atlas, 1), (0, 0, 0));
+        assert_eq!(position(&mut atlas, 2), (0, 8 + PADDING, 0));
+        assert_eq!(position(&mut atlas, 3), (0, 2 * (8 + PADDING), 0));
+        assert_eq!(position(&mut atlas, 4), (0, 0, 8 + PADDING));
+        assert_eq!(atlas.pages.len(), 1);
+        assert_eq!(atlas.pages[0].shelves.len(), 2);
+    }
+
+    #[test]
+    fn full_page_adds_a_new_page_within_budget() {
+        let mut atlas = GlyphAtlas::<TestDevice>::new(PAGE_SIZE, 2 * PAGE_MEMORY);
+        insert(&mut atlas, 1, 20, 20);
+        insert(&mut atlas, 2, 20, 20);
+
+        assert_eq!(position(&mut atlas, 1), (0, 0, 0));
+        assert_eq!(position(&mut atlas, 2), (1, 0, 0));
+        assert_eq!(atlas.memory_usage(), 2 * PAGE_MEMORY);
+    }
+
+    #[test]
+    fn glyph_bigger_than_page_is_not_inserted() {
+        let mut atlas = GlyphAtlas::<TestDevice>::new(PAGE_SIZE, PAGE_MEMORY);
+        let data = vec![255; PAGE_SIZE as usize * PAGE_SIZE as usize];
+        assert!(!atlas.insert(key(1), PAGE_SIZE, PAGE_SIZE, &data));
+        assert!(atlas.find(&key(1)).is_none());
+        assert!(atlas.pages.is_empty());
+    }
+
+    #[test]
+    fn least_recently_used_adjacent_shelves_are_merged_for_higher_glyph() {
+        let mut atlas = GlyphAtlas::<TestDevice>::new(PAGE_SIZE, PAGE_MEMORY);
+        // three full width shelves, each used in its own batch
+        for glyph_id in 1..=3 {
+            insert(&mut atlas, glyph_id, PAGE_SIZE - PADDING, 9);
+            atlas.next_batch();
+        }
+
+        // two shelves are needed for the glyph
+        insert(&mut atlas, 4, PAGE_SIZE - PADDING, 19);
+
+        assert!(atlas.find(&key(1)).is_none());
+        assert!(atlas.find(&key(2)).is_none());
+        assert_eq!(position(&mut atlas, 3), (0, 0, 20));
+        assert_eq!(position(&mut atlas, 4), (0, 0, 0));
+        assert_eq!(atlas.pages.len(), 1);
+        let shelves: Vec<(u16, u16)> = atlas.pages[0]
+            .shelves
+            .iter()
+            .map(|shelf| (shelf.y, shelf.height))
+            .collect();
+        assert_eq!(shelves, vec![(0, 20), (20, 10)]);
+    }
+
+    #[test]
+    fn find_marks_the_shelf_of_the_glyph_after_eviction() {
+        let mut atlas = GlyphAtlas::<TestDevice>::new(PAGE_SIZE, PAGE_MEMORY);
+        for glyph_id in 1..=3 {
+            insert(&mut atlas, glyph_id, PAGE_SIZE - PADDING, 9);
+            atlas.next_batch();
+        }
+        insert(&mut atlas, 4, PAGE_SIZE - PADDING, 19);
+
+        atlas.next_batch();
+        atlas.find(&key(3));
+        let last_used: Vec<u64> = atlas.pages[0]
+            .shelves
+            .iter()
+            .map(|shelf| shelf.last_used)
+            .collect();
+        assert_eq!(last_used, vec![atlas.counter - 1, atlas.counter]);
+
+        atlas.find(&key(4));
+        assert!(atlas.pages[0]
+            .shelves
+            .iter()
+            .all(|shelf| shelf.last_used == atlas.counter));
+    }
+
+    #[test]
+    fn shelves_used_in_current_batch_are_not_evicted() {
+        let mut atlas = GlyphAtlas::<TestDevice>::new(PAGE_SIZE, PAGE_MEMORY);
+        for glyph_id in 1..=3 {
+            insert(&mut atlas, glyph_id, PAGE_SIZE - PADDING, 9);
+        }
+        insert(&mut atlas, 4, PAGE_SIZE - PADDING, 19);
+
+        // the budget is exceeded until the next batch
+        for glyph_id in 1..=3 {
+            assert_eq!(position(&mut atlas, glyph_id).0, 0);
+        }
+        assert_eq!(position(&mut atlas, 4), (1, 0, 0));
+        assert_eq!(atlas.memory_usage(), 2 * PAGE_MEMORY);
+
+        atlas.next_batch();
+        assert_eq!(atlas.memory_usage(), PAGE_MEMORY);
+        assert!(atlas.find(&key(4)).is_none());
+        assert!(atlas.find(&key(1)).is_some());
+    }
+}
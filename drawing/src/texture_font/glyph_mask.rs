@@ -0,0 +1,345 @@
+//! Masks of the glyphs drawn behind the text (outline, glow and shadow).
+//!
+//! The coverage of the glyph is grown by the radius (the maximum of the coverage
+//! around every pixel weighted by the distance, so the antialiased edges are kept)
+//! and blurred with the separable gaussian filter for shadows. Masks are computed
+//! once and kept in the atlas like the glyphs, so the text is drawn only once
+//! for every effect.
+
+use crate::color::Color;
+use crate::font::TextEffects;
+use crate::texture_font::glyph_atlas::GlyphMask;
+
+/// Mask with margin around the glyph.
+pub struct Mask {
+    pub data: Vec<u8>,
+    pub width: usize,
+    pub height: usize,
+    /// Margin added on every side of the glyph.
+    pub padding: usize,
+}
+
+/// Computes the mask of the glyph coverage (8-bit grayscale).
+///
+/// `scale` converts the distances of the mask (in pixels of the drawn text)
+/// to the pixels of the coverage.
+pub fn create_mask(
+    mask: GlyphMask,
+    coverage: &[u8],
+    width: usize,
+    height: usize,
+    scale: f32,
+) -> Mask {
+    let pixels = |fixed: u16| fixed as f32 / 64.0 * scale;
+    match mask {
+        GlyphMask::Coverage => Mask {
+            data: coverage.to_vec(),
+            width,
+            height,
+            padding: 0,
+        },
+        GlyphMask::Grown { radius, softness } => grow(
+            coverage,
+            width,
+            height,
+            pixels(radius),
+            pixels(softness).max(1.0),
+        ),
+        GlyphMask::Blurred { radius, blur } => gaussian_blur(
+            grow(coverage, width, height, pixels(radius), 1.0),
+            pixels(blur),
+        ),
+    }
+}
+
+/// Returns the masks of the effects drawn behind the text (from the back),
+/// their offsets and colors.
+pub fn effect_layers(effects: &TextEffects) -> Vec<(GlyphMask, [f32; 2], Color)> {
+    let outline_width = if effects.has_outline() {
+        effects.outline_width
+    } else {
+        0.0
+    };
+    let mut layers = Vec::new();
+    if effects.has_shadow() {
+        layers.push((
+            GlyphMask::blurred(outline_width, effects.shadow_blur),
+            effects.shadow_offset,
+            effects.shadow_color,
+        ));
+    }
+    if effects.has_glow() {
+        layers.push((
+            GlyphMask::grown(outline_width, effects.glow_width),
+            [0.0, 0.0],
+            effects.glow_color,
+        ));
+    }
+    if outline_width > 0.0 {
+        layers.push((
+            GlyphMask::grown(outline_width, 1.0),
+            [0.0, 0.0],
+            effects.outline_color,
+        ));
+    }
+    layers
+}
+
+// Every pixel gets the maximum coverage of the pixels around weighted by their distance
+// (fully up to the radius, fading out to zero over the softness).
+fn grow(coverage: &[u8], width: usize, height: usize, radius: f32, softness: f32) -> Mask {
+    let padding = (radius + softness).ceil() as usize;
+    let size = 2 * padding + 1;
+    let kernel: Vec<f32> = (0..size * size)
+        .map(|i| {
+            let dx = (i % size) as f32 - padding as f32;
+            let dy = (i / size) as f32 - padding as f32;
+            let distance = (dx * dx + dy * dy).sqrt();
+            (1.0 - (distance - radius) / softness).clamp(0.0, 1.0)
+        })
+        .collect();
+
+    let mask_width = width + 2 * padding;
+    let mask_height = height + 2 * padding;
+    let mut data = vec![0u8; mask_width * mask_height];
+    for y in 0..height {
+        for x in 0..width {
+            let value = coverage[y * width + x];
+            if value == 0 {
+                continue;
+            }
+            // the kernel centered at the pixel covers the rows and columns
+            // from (x, y) to (x + size, y + size) of the mask
+            for (row, weights) in kernel.chunks_exact(size).enumerate() {
+                let start = (y + row) * mask_width + x;
+                for (mask_value, weight) in data[start..start + size].iter_mut().zip(weights) {
+                    let weighted = (value as f32 * weight).round() as u8;
+                    *mask_value = (*mask_value).max(weighted);
+                }
+            }
+        }
+    }
+
+    Mask {
+        data,
+        width: mask_width,
+        height: mask_height,
+        padding,
+    }
+}
+
+// The radius of the blur is two standard deviations.
+fn gaussian_blur(mask: Mask, radius: f32) -> Mask {
+    let half = radius.ceil() as usize;
+    if half == 0 {
+        return mask;
+    }
+    let sigma = radius / 2.0;
+    let mut kernel: Vec<f32> = (0..=2 * half)
+        .map(|i| {
+            let x = i as f32 - half as f32;
+            (-x * x / (2.0 * sigma * sigma)).exp()
+        })
+        .collect();
+    let sum: f32 = kernel.iter().sum();
+    kernel.iter_mut().for_each(|weight| *weight /= sum);
+
+    let width = mask.width + 2 * half;
+    let height = mask.height + 2 * half;
+    let mut horizontal = vec![0.0f32; width * mask.height];
+    for y in 0..mask.height {
+        for x in 0..mask.width {
+            let value = mask.data[y * mask.width + x] as f32;
+            if value == 0.0 {
+                continue;
+            }
+            let start = y * width + x;
+            for (blurred, weight) in horizontal[start..start + kernel.len()]
+                .iter_mut()
+                .zip(&kernel)
+            {
+                *blurred += value * weight;
+            }
+        }
+    }
+
+    let mut vertical = vec![0.0f32; width * height];
+    for y in 0..mask.height {
+        for (i, weight) in kernel.iter().enumerate() {
+            let row = &horizontal[y * width..(y + 1) * width];
+            let start = (y + i) * width;
+            for (blurred, value) in vertical[start..start + width].iter_mut().zip(row) {
+                *blurred += value * weight;
+            }
+        }
+    }
+
+    Mask {
+        data: vertical
+            .iter()
+            .map(|value| value.round().min(255.0) as u8)
+            .collect(),
+        width,
+        height,
+        padding: mask.padding + half,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn value(mask: &Mask, x: usize, y: usize) -> u8 {
+        mask.data[y * mask.width + x]
+    }
+
+    #[test]
+    fn grown_mask_is_padded() {
+        let mask = grow(&[255; 6], 3, 2, 2.0, 1.5);
+        assert_eq!(mask.padding, 4);
+        assert_eq!(mask.width, 3 + 8);
+        assert_eq!(mask.height, 2 + 8);
+        assert_eq!(mask.data.len(), mask.width * mask.height);
+        // nothing reaches the edges of the mask
+        assert_eq!(value(&mask, 0, 0), 0);
+        assert_eq!(value(&mask, 0, 5), 0);
+        assert_eq!(value(&mask, 5, 9), 0);
+    }
+
+    #[test]
+    fn coverage_is_grown_fully_up_to_the_radius() {
+        let mask = grow(&[255], 1, 1, 2.0, 1.0);
+        assert_eq!(mask.padding, 3);
+        for (x, y) in [(3, 3), (5, 3), (1, 3), (3, 5), (3, 1), (4, 4), (2, 2)] {
+            assert_eq!(value(&mask, x, y), 255, "({}, {})", x, y);
+        }
+        // sqrt(5) and sqrt(8) are 0.24 and 0.83 beyond the radius
+        assert_eq!(value(&mask, 5, 4), 195);
+        assert_eq!(value(&mask, 5, 5), 44);
+        assert_eq!(value(&mask, 6, 3), 0);
+    }
+
+    #[test]
+    fn edge_fades_out_over_the_softness() {
+        let mask = grow(&[255], 1, 1, 0.0, 4.0);
+        assert_eq!(mask.padding, 4);
+        let row: Vec<u8> = (4..9).map(|x| value(&mask, x, 4)).collect();
+        assert_eq!(row, [255, 191, 128, 64, 0]);
+        let column: Vec<u8> = (0..5).rev().map(|y| value(&mask, 4, y)).collect();
+        assert_eq!(column, row);
+    }
+
+    #[test]
+    fn grown_mask_keeps_the_largest_coverage() {
+        // antialiased pixel next to a covered one
+        let mask = grow(&[255, 100, 0], 3, 1, 1.0, 1.0);
+        let row: Vec<u8> = (0..mask.width)
+            .map(|x| value(&mask, x, mask.padding))
+            .collect();
+        assert_eq!(row, [0, 255, 255, 255, 100, 0, 0]);
+    }
+
+    #[test]
+    fn blur_preserves_the_coverage() {
+        let mask = Mask {
+            data: vec![255; 9],
+            width: 3,
+            height: 3,
+            padding: 1,
+        };
+        let blurred = gaussian_blur(mask, 3.0);
+        assert_eq!(blurred.padding, 4);
+        assert_eq!(blurred.width, 9);
+        assert_eq!(blurred.height, 9);
+        let total: u32 = blurred.data.iter().map(|&value| value as u32).sum();
+        // every pixel can be rounded by half
+        assert!(
+            (total as f32 - 9.0 * 255.0).abs() <= 0.5 * 81.0,
+            "{}",
+            total
+        );
+        // the blur is symmetric and the center is the brightest
+        for y in 0..9 {
+            for x in 0..9 {
+                assert_eq!(value(&blurred, x, y), value(&blurred, 8 - x, y));
+                assert_eq!(value(&blurred, x, y), value(&blurred, y, x));
+                assert!(value(&blurred, x, y) <= value(&blurred, 4, 4));
+            }
+        }
+        assert!(value(&blurred, 4, 4) < 255);
+    }
+
+    #[test]
+    fn zero_blur_keeps_the_mask() {
+        let mask = Mask {
+            data: vec![0, 255, 0, 128],
+            width: 2,
+            height: 2,
+            padding: 0,
+        };
+        let blurred = gaussian_blur(mask, 0.0);
+        assert_eq!(blurred.data, [0, 255, 0, 128]);
+        assert_eq!((blurred.width, blurred.height, blurred.padding), (2, 2, 0));
+    }
+
+    #[test]
+    fn mask_distances_are_scaled() {
+        let coverage = [255];
+        let mask = create_mask(GlyphMask::Coverage, &coverage, 1, 1, 2.0);
+        assert_eq!(mask.data, [255]);
+        assert_eq!(mask.padding, 0);
+
+        // 2 pixels of the radius and 1 pixel of the softness are doubled
+        let mask = create_mask(GlyphMask::grown(2.0, 0.0), &coverage, 1, 1, 2.0);
+        assert_eq!(mask.padding, 6);
+        assert_eq!(mask.width, 13);
+
+        // grown by 1 pixel with 1 pixel of softness and blurred by 2 pixels
+        let mask = create_mask(GlyphMask::blurred(1.0, 2.0), &coverage, 1, 1, 1.0);
+        assert_eq!(mask.padding, 4);
+        assert_eq!(mask.width, 9);
+    }
+
+    #[test]
+    fn layers_are_ordered_from_the_back() {
+        let effects = TextEffects {
+            outline_width: 2.0,
+            outline_color: [1.0, 0.0, 0.0, 1.0],
+            shadow_offset: [3.0, 4.0],
+            shadow_blur: 5.0,
+            shadow_color: [0.0, 0.0, 0.0, 0.5],
+            glow_width: 6.0,
+            glow_color: [0.0, 0.0, 1.0, 1.0],
+        };
+        let layers = effect_layers(&effects);
+        assert_eq!(
+            layers,
+            [
+                (
+                    GlyphMask::blurred(2.0, 5.0),
+                    [3.0, 4.0],
+                    [0.0, 0.0, 0.0, 0.5]
+                ),
+                (GlyphMask::grown(2.0, 6.0), [0.0, 0.0], [0.0, 0.0, 1.0, 1.0]),
+                (GlyphMask::grown(2.0, 1.0), [0.0, 0.0], [1.0, 0.0, 0.0, 1.0]),
+            ]
+        );
+    }
+
+    #[test]
+    fn hidden_effects_have_no_layers() {
+        assert!(effect_layers(&TextEffects::default()).is_empty());
+
+        // the transparent outline doesn't grow the glow
+        let effects = TextEffects {
+            outline_width: 2.0,
+            glow_width: 3.0,
+            glow_color: [1.0, 1.0, 1.0, 1.0],
+            ..Default::default()
+        };
+        assert_eq!(
+            effect_layers(&effects),
+            [(GlyphMask::grown(0.0, 3.0), [0.0, 0.0], [1.0, 1.0, 1.0, 1.0])]
+        );
+    }
+}
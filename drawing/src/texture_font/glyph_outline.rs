@@ -0,0 +1,280 @@
+//! Converts glyphs into vector paths using FreeType outline decomposition.
+//!
+//! Outlines are loaded unscaled (in font units) once per glyph, so the same
+//! outline is used for text of any size. Every face (and variation)
+//! of the font file has its own outlines, including the synthesized styles.
+
+use crate::font::FontFace;
+use crate::primitive::{PathElement, Solidity};
+use crate::shaping::ShapedGlyph;
+use crate::texture_font::bitmap_font::FontError;
+use crate::texture_font::ft_face::{open_face, synthesize};
+use crate::units::*;
+
+use freetype as ft;
+use std::collections::HashMap;
+use std::ffi::c_void;
+use std::os::raw::c_int;
+
+// Outline segment in font units (y goes up).
+#[derive(Debug, Copy, Clone)]
+enum Segment {
+    Move([f32; 2]),
+    Line([f32; 2]),
+    Cubic([f32; 2], [f32; 2], [f32; 2]),
+}
+
+#[derive(Debug)]
+struct Contour {
+    segments: Vec<Segment>,
+    solidity: Solidity,
+}
+
+// Collects the contours during the decomposition.
+#[derive(Default)]
+struct OutlineBuilder {
+    contours: Vec<(Vec<Segment>, f32)>,
+    last: [f32; 2],
+}
+
+impl OutlineBuilder {
+    fn push(&mut self, segment: Segment, points: &[[f32; 2]]) {
+        if let Segment::Move(_) = segment {
+            self.contours.push((Vec::new(), 0.0));
+        }
+        let last = self.last;
+        if let Some((segments, area)) = self.contours.last_mut() {
+            // twice the signed area of the control polygon (enough to know the direction)
+            let mut prev = last;
+            for point in points {
+                *area += prev[0] * point[1] - point[0] * prev[1];
+                prev = *point;
+            }
+            segments.push(segment);
+        }
+        if let Some(point) = points.last() {
+            self.last = *point;
+        }
+    }
+}
+
+fn to_point(vector: *const ft::ffi::FT_Vector) -> [f32; 2] {
+    let vector = unsafe { &*vector };
+    [vector.x as f32, vector.y as f32]
+}
+
+fn builder<'a>(user: *mut c_void) -> &'a mut OutlineBuilder {
+    unsafe { &mut *(user as *mut OutlineBuilder) }
+}
+
+extern "C" fn move_to(to: *const ft::ffi::FT_Vector, user: *mut c_void) -> c_int {
+    let builder = builder(user);
+    let to = to_point(to);
+    builder.last = to;
+    builder.push(Segment::Move(to), &[]);
+    0
+}
+
+extern "C" fn line_to(to: *const ft::ffi::FT_Vector, user: *mut c_void) -> c_int {
+    let to = to_point(to);
+    builder(user).push(Segment::Line(to), &[to]);
+    0
+}
+
+extern "C" fn conic_to(
+    control: *const ft::ffi::FT_Vector,
+    to: *const ft::ffi::FT_Vector,
+    user: *mut c_void,
+) -> c_int {
+    let builder = builder(user);
+    let from = builder.last;
+    let control = to_point(control);
+    let to = to_point(to);
+    // quadratic curves are converted to the equal cubic ones
+    let c1 = [
+        from[0] + 2.0 / 3.0 * (control[0] - from[0]),
+        from[1] + 2.0 / 3.0 * (control[1] - from[1]),
+    ];
+    let c2 = [
+        to[0] + 2.0 / 3.0 * (control[0] - to[0]),
+        to[1] + 2.0 / 3.0 * (control[1] - to[1]),
+    ];
+    builder.push(Segment::Cubic(c1, c2, to), &[control, to]);
+    0
+}
+
+extern "C" fn cubic_to(
+    control1: *const ft::ffi::FT_Vector,
+    control2: *const ft::ffi::FT_Vector,
+    to: *const ft::ffi::FT_Vector,
+    user: *mut c_void,
+) -> c_int {
+    let c1 = to_point(control1);
+    let c2 = to_point(control2);
+    let to = to_point(to);
+    builder(user).push(Segment::Cubic(c1, c2, to), &[c1, c2, to]);
+    0
+}
+
+/// Outlines of the glyphs of one font.
+#[derive(Debug, Default)]
+pub struct GlyphOutlines {
+    outlines: HashMap<u32, Vec<Contour>>,
+    units_per_em: f32,
+}
+
+impl GlyphOutlines {
+    pub fn new() -> Self {
+        GlyphOutlines::default()
+    }
+
+    /// Makes sure the outlines of the glyphs are known.
+    /// The font file is parsed only if some outline is missing.
+    pub fn load_glyphs(
+        &mut self,
+        data: &[u8],
+        font_face: &FontFace,
+        glyph_ids: &[u32],
+    ) -> Result<(), FontError> {
+        let mut face = None;
+        for &glyph_id in glyph_ids {
+            if glyph_id == 0 || self.outlines.contains_key(&glyph_id) {
+                continue;
+            }
+            let face = match face {
+                Some(ref mut face) => face,
+                None => {
+                    let new_face = open_face(data, font_face)?;
+                    self.units_per_em = new_face.em_size() as f32;
+                    face.insert(new_face)
+                }
+            };
+
+            face.load_glyph(glyph_id, ft::face::LoadFlag::NO_SCALE)?;
+            synthesize(face, font_face.synthesis, true)?;
+            let slot = face.glyph().raw();
+            let mut contours = Vec::new();
+            if slot.format == ft::ffi::FT_GLYPH_FORMAT_OUTLINE {
+                let outline =
+                    &slot.outline as *const ft::ffi::FT_Outline as *mut ft::ffi::FT_Outline;
+                let funcs = ft::ffi::FT_Outline_Funcs {
+                    move_to,
+                    line_to,
+                    conic_to,
+                    cubic_to,
+                    shift: 0,
+                    delta: 0,
+                };
+                let mut builder = OutlineBuilder::default();
+                let (error, orientation) = unsafe {
+                    (
+                        ft::ffi::FT_Outline_Decompose(
+                            outline,
+                            &funcs,
+                            &mut builder as *mut OutlineBuilder as *mut c_void,
+                        ),
+                        ft::ffi::FT_Outline_Get_Orientation(outline),
+                    )
+                };
+                if error != 0 {
+                    return Err(FontError::FreetypeError(error.into()));
+                }
+
+                // contours filled to the right (clockwise) are solid in TrueType fonts,
+                // contours filled to the left are solid in PostScript fonts
+                let clockwise_solid = orientation == ft::ffi::FT_ORIENTATION_TRUETYPE;
+                for (segments, area) in builder.contours {
+                    let solid = (area < 0.0) == clockwise_solid;
+                    contours.push(Contour {
+                        segments,
+                        solidity: if solid {
+                            Solidity::Solid
+                        } else {
+                            Solidity::Hole
+                        },
+                    });
+                }
+            }
+            self.outlines.insert(glyph_id, contours);
+        }
+        Ok(())
+    }
+
+    /// Appends the outlines of the shaped lines to the path.
+    ///
+    /// The outlines have to be loaded first with `load_glyphs()`.
+    ///
+    /// # Arguments
+    ///
+    /// * `lines` - shaped lines of text
+    /// * `size` - font size in pixels
+    /// * `pos` - top left corner of the first line
+    /// * `baseline` - distance from the top of the line to the baseline
+    /// * `line_height` - distance between the tops of two consecutive lines
+    pub fn add_to_path(
+        &self,
+        lines: &[Vec<ShapedGlyph>],
+        size: f32,
+        pos: PixelPoint,
+        baseline: f32,
+        line_height: f32,
+        path: &mut Vec<PathElement>,
+    ) {
+        let glyph_count = lines.iter().map(|line| line.len()).sum();
+        let glyph_transforms = vec![PixelTransform::translation(pos.x, pos.y); glyph_count];
+        self.add_transformed_to_path(lines, size, baseline, line_height, &glyph_transforms, path);
+    }
+
+    /// Same as `add_to_path()` but every glyph is moved by its own transform from
+    /// the position it would have if the lines were added at the origin
+    /// (in the order of the glyphs of the lines).
+    pub fn add_transformed_to_path(
+        &self,
+        lines: &[Vec<ShapedGlyph>],
+        size: f32,
+        baseline: f32,
+        line_height: f32,
+        glyph_transforms: &[PixelTransform],
+        path: &mut Vec<PathElement>,
+    ) {
+        if self.units_per_em <= 0.0 {
+            return;
+        }
+        let scale = size / self.units_per_em;
+        let mut glyph_transforms = glyph_transforms.iter();
+        let mut y = baseline;
+        for line in lines {
+            let mut x = 0.0;
+            for glyph in line {
+                let glyph_transform = glyph_transforms.next();
+                if let (Some(contours), Some(glyph_transform)) =
+                    (self.outlines.get(&glyph.glyph_id), glyph_transform)
+                {
+                    let origin_x = x + glyph.x_offset;
+                    let origin_y = y + glyph.y_offset;
+                    let point = |p: [f32; 2]| {
+                        glyph_transform.transform_point(PixelPoint::new(
+                            origin_x + p[0] * scale,
+                            origin_y - p[1] * scale,
+                        ))
+                    };
+                    for contour in contours {
+                        for segment in &contour.segments {
+                            path.push(match *segment {
+                                Segment::Move(p) => PathElement::MoveTo(point(p)),
+                                Segment::Line(p) => PathElement::LineTo(point(p)),
+                                Segment::Cubic(c1, c2, p) => {
+                                    PathElement::BezierTo(point(c1), point(c2), point(p))
+                                }
+                            });
+                        }
+                        path.push(PathElement::ClosePath);
+                        path.push(PathElement::Solidity(contour.solidity));
+                    }
+                }
+                x += glyph.x_advance;
+            }
+            y += line_height;
+        }
+    }
+}
@@ -1,5 +1,28 @@
+#[cfg(feature = "freetype")]
 mod bitmap_font;
+mod bm_font;
+#[cfg(feature = "freetype")]
+mod color_glyph;
+#[cfg(feature = "freetype")]
+mod distance_field;
+#[cfg(feature = "freetype")]
 mod font_size_renderer;
+#[cfg(feature = "freetype")]
+mod ft_face;
+mod glyph_atlas;
+mod glyph_mask;
+#[cfg(feature = "freetype")]
+mod glyph_outline;
+#[cfg(feature = "freetype")]
+mod sdf_font;
+#[cfg(feature = "freetype")]
+mod shaped_text_cache;
+#[cfg(feature = "freetype")]
 mod texture_font;
 
+pub use self::bm_font::*;
+pub use self::glyph_atlas::*;
+#[cfg(feature = "freetype")]
+pub use self::sdf_font::*;
+#[cfg(feature = "freetype")]
 pub use self::texture_font::TextureFont;
@@ -0,0 +1,659 @@
+use anyhow::Result;
+
+use crate::backend::*;
+use crate::clipping::clip_image;
+use crate::color::*;
+use crate::font::*;
+use crate::primitive::PathElement;
+use crate::shaping::*;
+use crate::texture_font::bitmap_font::{BitmapFont, FontError};
+use crate::texture_font::distance_field::coverage_to_distance_field;
+use crate::texture_font::font_size_renderer::Error;
+use crate::texture_font::ft_face::{open_face, synthesize};
+use crate::texture_font::glyph_atlas::*;
+use crate::texture_font::glyph_outline::GlyphOutlines;
+use crate::texture_font::shaped_text_cache::*;
+use crate::units::*;
+use crate::vertical::*;
+
+use freetype as ft;
+use freetype::bitmap::PixelMode;
+use std::collections::HashMap;
+use std::iter::once;
+use std::sync::Arc;
+
+/// Size (in pixels) of the glyphs stored as distance fields.
+pub const SDF_BASE_SIZE: u32 = 48;
+
+/// Max distance (in pixels of the base size) from the glyph edge stored in the distance field.
+pub const SDF_SPREAD: u32 = 6;
+
+// glyphs are rasterized this many times bigger before computing the distances
+const UPSCALE: u32 = 4;
+
+// Glyph metrics in pixels of the base size.
+struct SdfGlyph {
+    // position of the distance field relative to the pen at the top of the line
+    x_offset: f32,
+    y_offset: f32,
+    x_advance: f32,
+    // size of the distance field in texels
+    width: u16,
+    height: u16,
+    vertical: VerticalGlyphMetrics,
+}
+
+struct SdfGlyphProvider<'a> {
+    char_map: &'a HashMap<char, u32>,
+    glyphs: &'a HashMap<u32, SdfGlyph>,
+    scale: f32,
+}
+
+impl GlyphProvider for SdfGlyphProvider<'_> {
+    fn glyph_index(&self, ch: char) -> Option<u32> {
+        self.char_map.get(&ch).copied()
+    }
+
+    fn glyph_advance(&self, glyph_id: u32) -> f32 {
+        self.glyphs
+            .get(&glyph_id)
+            .map_or(0.0, |glyph| glyph.x_advance * self.scale)
+    }
+
+    fn glyph_vertical_metrics(&self, glyph_id: u32) -> VerticalGlyphMetrics {
+        self.glyphs
+            .get(&glyph_id)
+            .map_or(VerticalGlyphMetrics::default(), |glyph| {
+                VerticalGlyphMetrics {
+                    advance: glyph.vertical.advance * self.scale,
+                    x_offset: glyph.vertical.x_offset * self.scale,
+                    y_offset: glyph.vertical.y_offset * self.scale,
+                }
+            })
+    }
+}
+
+// Glyphs of one face (and variation) of the font file.
+struct SdfFace {
+    char_map: Arc<HashMap<char, u32>>,
+    glyphs: HashMap<u32, SdfGlyph>,
+    // metrics of the base size
+    metrics: FontMetrics,
+    outlines: GlyphOutlines,
+}
+
+impl SdfFace {
+    fn new(data: &[u8], font_face: &FontFace, char_map: Arc<HashMap<char, u32>>) -> Result<Self> {
+        let face = load_face(data, font_face).map_err(Error::from)?;
+        let metrics = BitmapFont::face_metrics(&face, &char_map, (SDF_BASE_SIZE * UPSCALE) as f32)
+            .scale(1.0 / UPSCALE as f32);
+        Ok(SdfFace {
+            char_map,
+            glyphs: HashMap::new(),
+            metrics,
+            outlines: GlyphOutlines::new(),
+        })
+    }
+
+    // Makes sure the metrics of the glyphs are known and their distance fields
+    // are in the atlas.
+    fn load_glyphs<D: Device>(
+        &mut self,
+        data: &[u8],
+        font_face: &FontFace,
+        atlas: &mut GlyphAtlas<D>,
+        glyph_ids: &[u32],
+    ) -> Result<(), FontError> {
+        let mut face = None;
+        for &glyph_id in glyph_ids {
+            let key = GlyphKey {
+                face: *font_face,
+                font_size: FontSize::new(SDF_BASE_SIZE as f32),
+                glyph_id,
+                subpixel: 0,
+                mask: GlyphMask::Coverage,
+            };
+            let loaded = glyph_id == 0
+                || match self.glyphs.get(&glyph_id) {
+                    Some(glyph) => {
+                        glyph.width == 0 || glyph.height == 0 || atlas.find(&key).is_some()
+                    }
+                    None => false,
+                };
+            if loaded {
+                continue;
+            }
+
+            let face = match face {
+                Some(ref mut face) => face,
+                None => face.insert(load_face(data, font_face)?),
+            };
+            // hinting would distort the outlines that are scaled later
+            face.load_glyph(glyph_id, ft::face::LoadFlag::NO_HINTING)?;
+            synthesize(face, font_face.synthesis, false)?;
+            face.glyph().render_glyph(ft::RenderMode::Normal)?;
+            let ft_glyph = face.glyph();
+            let bitmap = ft_glyph.bitmap();
+            let upscale = UPSCALE as f32;
+            let spread = SDF_SPREAD as f32;
+            let metrics = ft_glyph.metrics();
+            let mut glyph = SdfGlyph {
+                x_offset: ft_glyph.bitmap_left() as f32 / upscale - spread,
+                y_offset: SDF_BASE_SIZE as f32 - ft_glyph.bitmap_top() as f32 / upscale - spread,
+                x_advance: ft_glyph.linear_hori_advance() as f32 / 65536.0 / upscale,
+                width: 0,
+                height: 0,
+                // the horizontal origin relative to the vertical one
+                vertical: VerticalGlyphMetrics {
+                    advance: ft_glyph.linear_vert_advance() as f32 / 65536.0 / upscale,
+                    x_offset: (metrics.vertBearingX - metrics.horiBearingX) as f32 / 64.0 / upscale,
+                    y_offset: (metrics.vertBearingY + metrics.horiBearingY) as f32 / 64.0 / upscale,
+                },
+            };
+
+            // color bitmaps (like emoji) and monochrome bitmaps (of fonts with fixed
+            // sizes only) have no distance field
+            if bitmap.width() > 0 && bitmap.rows() > 0 && bitmap.pixel_mode()? == PixelMode::Gray {
+                let field = coverage_to_distance_field(
+                    &gray_bitmap_data(&bitmap),
+                    bitmap.width() as usize,
+                    bitmap.rows() as usize,
+                    UPSCALE as usize,
+                    SDF_SPREAD as usize,
+                );
+                // glyphs that don't fit now are inserted again with the next batch
+                glyph.width = field.width as u16;
+                glyph.height = field.height as u16;
+                atlas.insert(key, glyph.width, glyph.height, &field.data);
+            }
+            self.glyphs.insert(glyph_id, glyph);
+        }
+        Ok(())
+    }
+
+    fn line_height(&self, size: f32) -> f32 {
+        self.metrics.line_height * size / SDF_BASE_SIZE as f32
+    }
+}
+
+// Copies the rows of the grayscale bitmap without the padding at their ends.
+fn gray_bitmap_data(bitmap: &ft::Bitmap) -> Vec<u8> {
+    let width = bitmap.width() as usize;
+    let rows = bitmap.rows() as usize;
+    let pitch = bitmap.pitch().unsigned_abs() as usize;
+    let mut data = Vec::with_capacity(width * rows);
+    for row in bitmap.buffer().chunks(pitch).take(rows) {
+        data.extend_from_slice(&row[..width]);
+    }
+    data
+}
+
+// Draws the glyphs (added to the atlas by `add_glyphs` moved by the offset with the color)
+// with the effects of the font parameters.
+fn draw_glyphs<D: Device>(
+    atlas: &mut GlyphAtlas<D>,
+    device: &mut D,
+    target: &D::RenderTarget,
+    color: &Color,
+    font_params: &FontParams,
+    transform: UnknownToDeviceTransform,
+    add_glyphs: impl Fn(&mut GlyphAtlas<D>, [f32; 2], Color),
+) -> Result<()> {
+    let effects = &font_params.effects;
+    let scale = font_params.font_size().get() / SDF_BASE_SIZE as f32;
+    // distances of the shader are in texels of the distance field,
+    // the effects have to fade out before the edge of the field
+    let spread = SDF_SPREAD as f32;
+    let outline_width = if effects.has_outline() {
+        (effects.outline_width / scale).min(spread - 1.0)
+    } else {
+        0.0
+    };
+    let params = DistanceFieldParams {
+        distance_range: 2.0 * spread,
+        outline_width,
+        outline_color: effects.outline_color,
+        glow_width: if effects.has_glow() {
+            (effects.glow_width / scale).min(spread - outline_width)
+        } else {
+            0.0
+        },
+        glow_color: effects.glow_color,
+        softness: 0.0,
+    };
+    if effects.has_shadow() {
+        // the shadow of the glyphs with the outline
+        add_glyphs(atlas, effects.shadow_offset, effects.shadow_color);
+        let shadow_params = DistanceFieldParams {
+            outline_color: effects.shadow_color,
+            glow_width: 0.0,
+            softness: (effects.shadow_blur / scale).min(spread - outline_width),
+            ..params
+        };
+        atlas.draw_distance_field_at(device, target, &shadow_params, transform)?;
+    }
+    add_glyphs(atlas, [0.0, 0.0], *color);
+    atlas.draw_distance_field_at(device, target, &params, transform)
+}
+
+fn load_face(data: &[u8], font_face: &FontFace) -> Result<ft::Face, FontError> {
+    let face = open_face(data, font_face)?;
+    face.set_pixel_sizes(0, SDF_BASE_SIZE * UPSCALE)?;
+    Ok(face)
+}
+
+/// Font drawn from signed distance fields.
+///
+/// Every glyph is converted to a distance field once (at `SDF_BASE_SIZE`)
+/// and is drawn crisp at any size, scale, rotation and subpixel position
+/// with the distance field shader of the device.
+///
+/// The outline and the glow of the text (see `TextEffects`) are drawn by the shader
+/// with the text, the shadow is drawn before the text from the same glyphs
+/// with softened edges. The outline and the glow together (or the outline and
+/// the shadow blur) are limited to `SDF_SPREAD` pixels scaled
+/// by `font size / SDF_BASE_SIZE`.
+pub struct SdfFont<D: Device> {
+    bytes: Vec<u8>,
+    // characters of the faces of the font collection
+    char_maps: HashMap<u32, Arc<HashMap<char, u32>>>,
+    faces: HashMap<FontFace, SdfFace>,
+    atlas: GlyphAtlas<D>,
+    shaped_texts: ShapedTextCache<FontParams>,
+}
+
+impl<D: Device> SdfFont<D> {
+    pub fn glyph_atlas(&self) -> &GlyphAtlas<D> {
+        &self.atlas
+    }
+
+    pub fn glyph_atlas_mut(&mut self) -> &mut GlyphAtlas<D> {
+        &mut self.atlas
+    }
+
+    fn get_or_create_face(&mut self, font_face: FontFace) -> Result<&mut SdfFace> {
+        if !self.faces.contains_key(&font_face) {
+            let char_map = self.get_or_load_char_map(font_face.index)?;
+            let face = SdfFace::new(&self.bytes, &font_face, char_map)?;
+            Ok(self.faces.entry(font_face).or_insert(face))
+        } else {
+            Ok(self.faces.get_mut(&font_face).unwrap())
+        }
+    }
+
+    // characters of the face of the font collection
+    fn get_or_load_char_map(&mut self, index: u32) -> Result<Arc<HashMap<char, u32>>> {
+        match self.char_maps.get(&index) {
+            Some(char_map) => Ok(char_map.clone()),
+            None => {
+                let char_map =
+                    BitmapFont::char_map_from_bytes(&self.bytes, index).map_err(Error::from)?;
+                Ok(self
+                    .char_maps
+                    .entry(index)
+                    .or_insert(Arc::new(char_map))
+                    .clone())
+            }
+        }
+    }
+
+    // Shapes the text (or takes it from the cache) and makes sure all its glyphs
+    // are in the atlas.
+    fn shape(&mut self, params: FontParams, text: &str) -> Result<Vec<Vec<ShapedGlyph>>> {
+        let size = params.font_size().get();
+        self.atlas.next_batch();
+        self.get_or_create_face(params.face)?;
+        let face = self.faces.get_mut(&params.face).unwrap();
+
+        let lines = match self.shaped_texts.get(&params, text) {
+            Some(lines) => lines,
+            None => {
+                // advances of the characters are needed for shaping without the shaping engine
+                let glyph_ids = unique_glyphs(
+                    text.chars()
+                        .chain(once(' '))
+                        .filter_map(|ch| face.char_map.get(&ch).copied()),
+                );
+                face.load_glyphs(&self.bytes, &params.face, &mut self.atlas, &glyph_ids)
+                    .map_err(Error::from)?;
+
+                let provider = SdfGlyphProvider {
+                    char_map: &face.char_map,
+                    glyphs: &face.glyphs,
+                    scale: size / SDF_BASE_SIZE as f32,
+                };
+                let shape = if params.writing_mode.is_vertical() {
+                    shape_vertical_line
+                } else {
+                    shape_line
+                };
+                let shaping_face = ShapingFace::new(&self.bytes, &params.face);
+                let lines: Vec<Vec<ShapedGlyph>> = text
+                    .split('\n')
+                    .map(|line| shape(&shaping_face, size, &provider, line, params.direction))
+                    .collect();
+                self.shaped_texts.insert(params, text, lines.clone());
+                lines
+            }
+        };
+
+        // shaping may produce glyphs without characters assigned (like ligatures)
+        let glyph_ids = unique_glyphs(lines.iter().flatten().map(|glyph| glyph.glyph_id));
+        face.load_glyphs(&self.bytes, &params.face, &mut self.atlas, &glyph_ids)
+            .map_err(Error::from)?;
+
+        Ok(lines)
+    }
+}
+
+impl<D: Device> Font<D> for SdfFont<D> {
+    fn create(bytes: Vec<u8>) -> Result<Self> {
+        let mut font = SdfFont {
+            bytes,
+            char_maps: HashMap::new(),
+            faces: HashMap::new(),
+            atlas: GlyphAtlas::new(DEFAULT_PAGE_SIZE, DEFAULT_MEMORY_BUDGET),
+            shaped_texts: ShapedTextCache::new(SHAPED_TEXT_CACHE_SIZE),
+        };
+        // the first face is always loaded to make sure the font is valid
+        font.get_or_create_face(FontFace::default())?;
+        Ok(font)
+    }
+
+    fn has_char(&mut self, face: FontFace, ch: char) -> bool {
+        match self.get_or_load_char_map(face.index) {
+            Ok(char_map) => char_map.contains_key(&ch),
+            Err(_) => {
+                // the face is not parsed again for every character
+                self.char_maps.insert(face.index, Arc::default());
+                false
+            }
+        }
+    }
+
+    fn draw(
+        &mut self,
+        device: &mut D,
+        target: &D::RenderTarget,
+        color: &Color,
+        text: &str,
+        pos: Point,
+        clipping_rect: Rect,
+        font_params: FontParams,
+        transform: UnknownToDeviceTransform,
+    ) -> Result<()> {
+        if font_params.writing_mode.is_vertical() {
+            let params = TextDrawParams::new(pos, clipping_rect, font_params, transform);
+            return draw_vertical(self, device, target, GlyphFill::Color(color), text, params);
+        }
+        let size = font_params.font_size().get();
+        let lines = self.shape(font_params, text)?;
+        let scale = size / SDF_BASE_SIZE as f32;
+        let face = &self.faces[&font_params.face];
+        let line_height = face.line_height(size);
+
+        // page, rectangle and texture coordinates of every glyph
+        let mut glyph_rects = Vec::new();
+        let mut y = pos.y;
+        for line in &lines {
+            let mut x = pos.x;
+            for shaped_glyph in line {
+                let key = GlyphKey {
+                    face: font_params.face,
+                    font_size: FontSize::new(SDF_BASE_SIZE as f32),
+                    glyph_id: shaped_glyph.glyph_id,
+                    subpixel: 0,
+                    mask: GlyphMask::Coverage,
+                };
+                let glyph = face.glyphs.get(&shaped_glyph.glyph_id);
+                if let (Some(glyph), Some(atlas_glyph)) = (glyph, self.atlas.find(&key)) {
+                    if shaped_glyph.glyph_id != 0 {
+                        glyph_rects.push((
+                            atlas_glyph.page,
+                            [
+                                x + shaped_glyph.x_offset + glyph.x_offset * scale,
+                                y + shaped_glyph.y_offset + glyph.y_offset * scale,
+                                glyph.width as f32 * scale,
+                                glyph.height as f32 * scale,
+                            ],
+                            atlas_glyph.uv,
+                        ));
+                    }
+                }
+                x += shaped_glyph.x_advance;
+            }
+            y += line_height;
+        }
+
+        draw_glyphs(
+            &mut self.atlas,
+            device,
+            target,
+            color,
+            &font_params,
+            transform,
+            |atlas, offset, color| {
+                for (page, rect, uv) in &glyph_rects {
+                    if let Some(clipped) = clip_image(
+                        rect[0] + offset[0],
+                        rect[1] + offset[1],
+                        rect[2],
+                        rect[3],
+                        clipping_rect.origin.x,
+                        clipping_rect.origin.y,
+                        clipping_rect.size.width,
+                        clipping_rect.size.height,
+                        uv,
+                    ) {
+                        atlas.add_image(*page, clipped, color);
+                    }
+                }
+            },
+        )
+    }
+
+    fn draw_glyphs_transformed(
+        &mut self,
+        device: &mut D,
+        target: &D::RenderTarget,
+        fill: GlyphFill<D::Texture>,
+        text: &str,
+        glyph_transforms: &[Option<PixelTransform>],
+        params: TextDrawParams,
+    ) -> Result<()> {
+        let TextDrawParams {
+            pos,
+            clipping_rect,
+            font_params,
+            transform,
+            ..
+        } = params;
+        let size = font_params.font_size().get();
+        let lines = self.shape(font_params, text)?;
+        let scale = size / SDF_BASE_SIZE as f32;
+        let face = &self.faces[&font_params.face];
+        let line_height = face.line_height(size);
+        let glyph_transforms = in_columns(
+            &lines,
+            font_params.writing_mode,
+            &face.metrics.scale(scale),
+            glyph_transforms,
+            pos,
+        );
+
+        // page, corners and texture coordinates of every glyph
+        let mut glyph_quads = Vec::new();
+        let mut glyph_transforms = glyph_transforms.iter();
+        let mut y = 0.0;
+        for line in &lines {
+            let mut x = 0.0;
+            for shaped_glyph in line {
+                let glyph_x = x + shaped_glyph.x_offset;
+                let advance_center =
+                    PixelPoint::new(x + shaped_glyph.x_advance * 0.5, y + line_height * 0.5);
+                x += shaped_glyph.x_advance;
+                let glyph_transform = match glyph_transforms.next() {
+                    Some(Some(glyph_transform)) if shaped_glyph.glyph_id != 0 => glyph_transform,
+                    _ => continue,
+                };
+                let center = glyph_transform.transform_point(advance_center);
+                if !clipping_rect.contains(center.to_untyped()) {
+                    continue;
+                }
+                let key = GlyphKey {
+                    face: font_params.face,
+                    font_size: FontSize::new(SDF_BASE_SIZE as f32),
+                    glyph_id: shaped_glyph.glyph_id,
+                    subpixel: 0,
+                    mask: GlyphMask::Coverage,
+                };
+                let glyph = face.glyphs.get(&shaped_glyph.glyph_id);
+                if let (Some(glyph), Some(atlas_glyph)) = (glyph, self.atlas.find(&key)) {
+                    let left = glyph_x + glyph.x_offset * scale;
+                    let top = y + shaped_glyph.y_offset + glyph.y_offset * scale;
+                    let right = left + glyph.width as f32 * scale;
+                    let bottom = top + glyph.height as f32 * scale;
+                    let corners = [[left, top], [left, bottom], [right, bottom], [right, top]].map(
+                        |[x, y]| {
+                            glyph_transform
+                                .transform_point(PixelPoint::new(x, y))
+                                .to_array()
+                        },
+                    );
+                    glyph_quads.push((atlas_glyph.page, corners, atlas_glyph.uv));
+                }
+            }
+            y += line_height;
+        }
+
+        // distance fields are drawn with the color of the fill
+        draw_glyphs(
+            &mut self.atlas,
+            device,
+            target,
+            &fill.color(),
+            &font_params,
+            transform,
+            |atlas, offset, color| {
+                for (page, corners, uv) in &glyph_quads {
+                    let corners = corners.map(|[x, y]| [x + offset[0], y + offset[1]]);
+                    atlas.add_quad(*page, corners, *uv, color);
+                }
+            },
+        )
+    }
+
+    fn get_dimensions(&mut self, params: FontParams, text: &str) -> Result<(u16, u16)> {
+        let size = params.font_size().get();
+        let lines = self.shape(params, text)?;
+        let height = lines.len() as f32 * self.faces[&params.face].line_height(size);
+        if params.writing_mode.is_vertical() {
+            // columns of the line height
+            return Ok((height.round() as u16, text_width(&lines).round() as u16));
+        }
+        Ok((text_width(&lines).round() as u16, height.round() as u16))
+    }
+
+    fn get_metrics(&mut self, params: FontParams) -> Result<FontMetrics> {
+        let face = self.get_or_create_face(params.face)?;
+        Ok(face
+            .metrics
+            .scale(params.font_size().get() / SDF_BASE_SIZE as f32))
+    }
+
+    fn get_glyph_bounds(&mut self, params: FontParams, text: &str) -> Result<Vec<GlyphBounds>> {
+        let size = params.font_size().get();
+        let lines = self.shape(params, text)?;
+        let scale = size / SDF_BASE_SIZE as f32;
+        let spread = SDF_SPREAD as f32;
+        let face = &self.faces[&params.face];
+        let bounds = glyph_bounds(
+            text,
+            &lines,
+            params.direction,
+            face.line_height(size),
+            |glyph_id| {
+                // the distance field is padded by the spread on every side
+                face.glyphs
+                    .get(&glyph_id)
+                    .filter(|glyph| glyph.width > 0 && glyph.height > 0)
+                    .map(|glyph| {
+                        Rect::new(
+                            Point::new(
+                                (glyph.x_offset + spread) * scale,
+                                (glyph.y_offset + spread) * scale,
+                            ),
+                            Size::new(
+                                (glyph.width as f32 - 2.0 * spread) * scale,
+                                (glyph.height as f32 - 2.0 * spread) * scale,
+                            ),
+                        )
+                    })
+            },
+        );
+        if params.writing_mode.is_vertical() {
+            return Ok(column_bounds(
+                bounds,
+                &lines,
+                params.writing_mode,
+                &face.metrics.scale(scale),
+            ));
+        }
+        Ok(bounds)
+    }
+
+    fn get_outline(
+        &mut self,
+        params: FontParams,
+        text: &str,
+        pos: PixelPoint,
+    ) -> Result<Vec<PathElement>> {
+        let size = params.font_size().get();
+        let lines = self.shape(params, text)?;
+        let glyph_ids = unique_glyphs(lines.iter().flatten().map(|glyph| glyph.glyph_id));
+        let face = self.faces.get_mut(&params.face).unwrap();
+        face.outlines
+            .load_glyphs(&self.bytes, &params.face, &glyph_ids)
+            .map_err(Error::from)?;
+
+        let metrics = face.metrics.scale(size / SDF_BASE_SIZE as f32);
+        let mut path = Vec::new();
+        if params.writing_mode.is_vertical() {
+            let glyph_transforms: Vec<PixelTransform> =
+                column_transforms(&lines, params.writing_mode, &metrics)
+                    .into_iter()
+                    .map(|(transform, _)| transform.then_translate(pos.to_vector()))
+                    .collect();
+            face.outlines.add_transformed_to_path(
+                &lines,
+                size,
+                metrics.baseline,
+                metrics.line_height,
+                &glyph_transforms,
+                &mut path,
+            );
+        } else {
+            face.outlines.add_to_path(
+                &lines,
+                size,
+                pos,
+                metrics.baseline,
+                metrics.line_height,
+                &mut path,
+            );
+        }
+        Ok(path)
+    }
+
+    fn get_dimensions_each_char(
+        &mut self,
+        params: FontParams,
+        text: &str,
+    ) -> Result<(Vec<i16>, u16)> {
+        let size = params.font_size().get();
+        let lines = self.shape(params, text)?;
+        let widths = char_widths(text, &lines);
+        let line_height = self.faces[&params.face].line_height(size);
+        Ok((widths, line_height.round() as u16))
+    }
+}
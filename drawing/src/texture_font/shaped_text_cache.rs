@@ -0,0 +1,151 @@
+use crate::shaping::ShapedGlyph;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+
+/// Number of texts kept by the fonts after they are shaped.
+pub const SHAPED_TEXT_CACHE_SIZE: usize = 256;
+
+/// Shaped lines of the recently used texts.
+///
+/// The text is usually measured and drawn every frame with the same parameters,
+/// so it is shaped only once. Texts not used while `capacity` other texts
+/// were shaped are dropped.
+pub struct ShapedTextCache<K> {
+    capacity: usize,
+    // entries are moved from the older generation when used and the older
+    // generation is dropped when the recent one is full
+    recent: HashMap<u64, ShapedText<K>>,
+    older: HashMap<u64, ShapedText<K>>,
+}
+
+struct ShapedText<K> {
+    key: K,
+    text: String,
+    lines: Vec<Vec<ShapedGlyph>>,
+}
+
+impl<K: Hash + Eq> ShapedTextCache<K> {
+    pub fn new(capacity: usize) -> Self {
+        ShapedTextCache {
+            capacity,
+            recent: HashMap::new(),
+            older: HashMap::new(),
+        }
+    }
+
+    /// Returns the lines of the text shaped with the given parameters.
+    pub fn get(&mut self, key: &K, text: &str) -> Option<Vec<Vec<ShapedGlyph>>> {
+        let hash = Self::hash(key, text);
+        let matches = |entry: &ShapedText<K>| entry.key == *key && entry.text == text;
+        if let Some(entry) = self.recent.get(&hash) {
+            return if matches(entry) {
+                Some(entry.lines.clone())
+            } else {
+                None
+            };
+        }
+        match self.older.remove(&hash) {
+            Some(entry) if matches(&entry) => {
+                let lines = entry.lines.clone();
+                self.store(hash, entry);
+                Some(lines)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn insert(&mut self, key: K, text: &str, lines: Vec<Vec<ShapedGlyph>>) {
+        let hash = Self::hash(&key, text);
+        self.older.remove(&hash);
+        self.store(
+            hash,
+            ShapedText {
+                key,
+                text: text.to_string(),
+                lines,
+            },
+        );
+    }
+
+    pub fn clear(&mut self) {
+        self.recent.clear();
+        self.older.clear();
+    }
+
+    fn store(&mut self, hash: u64, entry: ShapedText<K>) {
+        if self.recent.len() >= self.capacity {
+            self.older = std::mem::take(&mut self.recent);
+        }
+        self.recent.insert(hash, entry);
+    }
+
+    fn hash(key: &K, text: &str) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        text.hash(&mut hasher);
+        hasher.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(glyph_id: u32) -> Vec<Vec<ShapedGlyph>> {
+        vec![vec![ShapedGlyph {
+            glyph_id,
+            cluster: 0,
+            x_advance: 10.0,
+            x_offset: 0.0,
+            y_offset: 0.0,
+            upright: false,
+        }]]
+    }
+
+    fn glyph_id(lines: Option<Vec<Vec<ShapedGlyph>>>) -> Option<u32> {
+        lines.map(|lines| lines[0][0].glyph_id)
+    }
+
+    #[test]
+    fn texts_are_found_by_key_and_text() {
+        let mut cache = ShapedTextCache::new(4);
+        cache.insert(1, "a", lines(1));
+        cache.insert(2, "a", lines(2));
+        cache.insert(1, "b", lines(3));
+        assert_eq!(glyph_id(cache.get(&1, "a")), Some(1));
+        assert_eq!(glyph_id(cache.get(&2, "a")), Some(2));
+        assert_eq!(glyph_id(cache.get(&1, "b")), Some(3));
+        assert_eq!(glyph_id(cache.get(&2, "b")), None);
+
+        cache.insert(1, "a", lines(4));
+        assert_eq!(glyph_id(cache.get(&1, "a")), Some(4));
+    }
+
+    #[test]
+    fn recently_used_texts_are_kept() {
+        let mut cache = ShapedTextCache::new(2);
+        cache.insert(0, "a", lines(1));
+        cache.insert(0, "b", lines(2));
+        // "a" and "b" are moved to the older generation
+        cache.insert(0, "c", lines(3));
+        // "a" is used, "b" is not
+        assert_eq!(glyph_id(cache.get(&0, "a")), Some(1));
+        // the older generation with "b" is dropped
+        cache.insert(0, "d", lines(4));
+        assert_eq!(glyph_id(cache.get(&0, "b")), None);
+        assert_eq!(glyph_id(cache.get(&0, "a")), Some(1));
+        assert_eq!(glyph_id(cache.get(&0, "c")), Some(3));
+        assert_eq!(glyph_id(cache.get(&0, "d")), Some(4));
+    }
+
+    #[test]
+    fn clear_removes_all_texts() {
+        let mut cache = ShapedTextCache::new(1);
+        cache.insert(0, "a", lines(1));
+        cache.insert(0, "b", lines(2));
+        cache.clear();
+        assert_eq!(glyph_id(cache.get(&0, "a")), None);
+        assert_eq!(glyph_id(cache.get(&0, "b")), None);
+    }
+}
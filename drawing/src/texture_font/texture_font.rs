@@ -3,39 +3,270 @@ use anyhow::Result;
 use crate::backend::*;
 use crate::color::*;
 use crate::font::*;
-use crate::texture_font::font_size_renderer::FontSizeRenderer;
+use crate::paint::Paint;
+use crate::primitive::PathElement;
+use crate::shaping::*;
+use crate::texture_font::bitmap_font::BitmapFont;
+use crate::texture_font::font_size_renderer::{Error, FontSizeRenderer};
+use crate::texture_font::glyph_atlas::*;
+use crate::texture_font::glyph_mask::effect_layers;
+use crate::texture_font::glyph_outline::GlyphOutlines;
+use crate::texture_font::shaped_text_cache::*;
 use crate::units::*;
+use crate::vertical::*;
 
 use std::collections::HashMap;
+use std::iter::once;
+use std::sync::Arc;
 
 pub struct TextureFont<D: Device> {
-    bytes: Vec<u8>,
-    font_renderers: HashMap<u8, FontSizeRenderer<D>>,
+    bytes: Arc<[u8]>,
+    // characters of the faces of the font collection
+    char_maps: HashMap<u32, Arc<HashMap<char, u32>>>,
+    font_renderers: HashMap<(FontFace, FontSize, TextAntialiasing), FontSizeRenderer>,
+    atlas: GlyphAtlas<D>,
+    color_atlas: GlyphAtlas<D>,
+    lcd_atlas: GlyphAtlas<D>,
+    outlines: HashMap<FontFace, GlyphOutlines>,
+    rendering: TextRendering,
+    shaped_texts: ShapedTextCache<(FontParams, TextAntialiasing)>,
 }
 
 impl<D: Device> TextureFont<D> {
-    fn get_or_create_font_renderer(&mut self, size: u8) -> Result<&mut FontSizeRenderer<D>> {
-        if !self.font_renderers.contains_key(&size) {
-            let renderer = self.create_font_renderer(size)?;
-            Ok(self.font_renderers.entry(size).or_insert(renderer))
+    /// Glyphs of all sizes are rasterized on first use into the atlas.
+    pub fn glyph_atlas(&self) -> &GlyphAtlas<D> {
+        &self.atlas
+    }
+
+    pub fn glyph_atlas_mut(&mut self) -> &mut GlyphAtlas<D> {
+        &mut self.atlas
+    }
+
+    /// Color glyphs (like emoji) are rasterized into the separate RGBA atlas.
+    pub fn color_glyph_atlas(&self) -> &GlyphAtlas<D> {
+        &self.color_atlas
+    }
+
+    pub fn color_glyph_atlas_mut(&mut self) -> &mut GlyphAtlas<D> {
+        &mut self.color_atlas
+    }
+
+    /// Glyphs rendered for LCD screens are rasterized into the separate RGBA atlas.
+    pub fn lcd_glyph_atlas(&self) -> &GlyphAtlas<D> {
+        &self.lcd_atlas
+    }
+
+    pub fn lcd_glyph_atlas_mut(&mut self) -> &mut GlyphAtlas<D> {
+        &mut self.lcd_atlas
+    }
+
+    pub fn text_rendering(&self) -> &TextRendering {
+        &self.rendering
+    }
+
+    /// Sets how the glyphs are rasterized.
+    ///
+    /// All glyphs are removed from the atlases if the rendering changes.
+    /// Text filled with a paint is always rendered with grayscale antialiasing.
+    pub fn set_text_rendering(&mut self, rendering: TextRendering) {
+        if rendering != self.rendering {
+            self.rendering = rendering;
+            self.font_renderers.clear();
+            self.shaped_texts.clear();
+            self.atlas.clear();
+            self.color_atlas.clear();
+            self.lcd_atlas.clear();
+        }
+    }
+
+    fn get_or_create_font_renderer(
+        &mut self,
+        params: FontParams,
+        antialiasing: TextAntialiasing,
+    ) -> Result<&mut FontSizeRenderer> {
+        let key = (params.face, params.font_size(), antialiasing);
+        if !self.font_renderers.contains_key(&key) {
+            let renderer = self.create_font_renderer(key)?;
+            Ok(self.font_renderers.entry(key).or_insert(renderer))
         } else {
-            Ok(self.font_renderers.get_mut(&size).unwrap())
+            Ok(self.font_renderers.get_mut(&key).unwrap())
         }
     }
 
-    fn create_font_renderer(&self, size: u8) -> Result<FontSizeRenderer<D>> {
-        Ok(FontSizeRenderer::new(&self.bytes, size)?)
+    fn create_font_renderer(
+        &mut self,
+        (face, size, antialiasing): (FontFace, FontSize, TextAntialiasing),
+    ) -> Result<FontSizeRenderer> {
+        let char_map = self.get_or_load_char_map(face.index)?;
+        let rendering = TextRendering {
+            antialiasing,
+            ..self.rendering
+        };
+        Ok(FontSizeRenderer::new(
+            self.bytes.clone(),
+            face,
+            size,
+            rendering,
+            char_map,
+        )?)
+    }
+
+    // characters of the face of the font collection
+    fn get_or_load_char_map(&mut self, index: u32) -> Result<Arc<HashMap<char, u32>>> {
+        match self.char_maps.get(&index) {
+            Some(char_map) => Ok(char_map.clone()),
+            None => {
+                let char_map =
+                    BitmapFont::char_map_from_bytes(&self.bytes, index).map_err(Error::from)?;
+                Ok(self
+                    .char_maps
+                    .entry(index)
+                    .or_insert(Arc::new(char_map))
+                    .clone())
+            }
+        }
+    }
+
+    // Shapes the text (or takes it from the cache) and makes sure all its glyphs
+    // are in the atlas (at the whole pixel positions).
+    fn shape(
+        &mut self,
+        params: FontParams,
+        antialiasing: TextAntialiasing,
+        text: &str,
+    ) -> Result<Vec<Vec<ShapedGlyph>>> {
+        let size = params.font_size();
+        self.atlas.next_batch();
+        self.color_atlas.next_batch();
+        self.lcd_atlas.next_batch();
+        self.get_or_create_font_renderer(params, antialiasing)?;
+        let renderer = self
+            .font_renderers
+            .get_mut(&(params.face, size, antialiasing))
+            .unwrap();
+        let atlas = match antialiasing {
+            TextAntialiasing::Grayscale => &mut self.atlas,
+            _ => &mut self.lcd_atlas,
+        };
+
+        let lines = match self.shaped_texts.get(&(params, antialiasing), text) {
+            Some(lines) => lines,
+            None => {
+                // advances of the characters are needed for shaping without the shaping engine
+                let glyph_ids = unique_glyphs(
+                    text.chars()
+                        .chain(once(' '))
+                        .filter_map(|ch| renderer.get_bitmap_font().glyph_index(ch)),
+                );
+                renderer.load_glyphs(&glyph_ids, atlas, &mut self.color_atlas)?;
+
+                let shape = if params.writing_mode.is_vertical() {
+                    shape_vertical_line
+                } else {
+                    shape_line
+                };
+                let face = ShapingFace::new(&self.bytes, &params.face);
+                let lines: Vec<Vec<ShapedGlyph>> = text
+                    .split('\n')
+                    .map(|line| {
+                        shape(
+                            &face,
+                            size.get(),
+                            renderer.get_bitmap_font(),
+                            line,
+                            params.direction,
+                        )
+                    })
+                    .collect();
+                self.shaped_texts
+                    .insert((params, antialiasing), text, lines.clone());
+                lines
+            }
+        };
+
+        // shaping may produce glyphs without characters assigned (like ligatures)
+        let glyph_ids = unique_glyphs(lines.iter().flatten().map(|glyph| glyph.glyph_id));
+        renderer.load_glyphs(&glyph_ids, atlas, &mut self.color_atlas)?;
+
+        Ok(lines)
+    }
+
+    // Draws the effects of the shaped text (the text has to be drawn after them).
+    fn draw_effects(
+        &mut self,
+        device: &mut D,
+        target: &D::RenderTarget,
+        lines: &[Vec<ShapedGlyph>],
+        params: &TextDrawParams,
+        antialiasing: TextAntialiasing,
+    ) -> Result<()> {
+        let TextDrawParams {
+            pos,
+            clipping_rect,
+            font_params,
+            transform,
+            ..
+        } = *params;
+        let renderer = self
+            .font_renderers
+            .get_mut(&(font_params.face, font_params.font_size(), antialiasing))
+            .unwrap();
+        for (mask, offset, color) in effect_layers(&font_params.effects) {
+            renderer.add_mask(
+                &mut self.atlas,
+                lines,
+                [pos.x + offset[0], pos.y + offset[1]],
+                [
+                    clipping_rect.origin.x,
+                    clipping_rect.origin.y,
+                    clipping_rect.size.width,
+                    clipping_rect.size.height,
+                ],
+                mask,
+                color,
+            )?;
+            self.atlas.draw_at(device, target, transform)?;
+        }
+        Ok(())
     }
 }
 
 impl<D: Device> Font<D> for TextureFont<D> {
     fn create(bytes: Vec<u8>) -> Result<Self> {
+        // the first face is always loaded to make sure the font is valid
+        let char_map = BitmapFont::char_map_from_bytes(&bytes, 0).map_err(Error::from)?;
         Ok(TextureFont {
-            bytes,
+            bytes: Arc::from(bytes),
+            char_maps: once((0, Arc::new(char_map))).collect(),
             font_renderers: HashMap::new(),
+            atlas: GlyphAtlas::new(DEFAULT_PAGE_SIZE, DEFAULT_MEMORY_BUDGET),
+            color_atlas: GlyphAtlas::with_format(
+                DEFAULT_PAGE_SIZE,
+                DEFAULT_MEMORY_BUDGET,
+                ColorFormat::RGBA,
+            ),
+            lcd_atlas: GlyphAtlas::with_format(
+                DEFAULT_PAGE_SIZE,
+                DEFAULT_MEMORY_BUDGET,
+                ColorFormat::RGBA,
+            ),
+            outlines: HashMap::new(),
+            rendering: TextRendering::default(),
+            shaped_texts: ShapedTextCache::new(SHAPED_TEXT_CACHE_SIZE),
         })
     }
 
+    fn has_char(&mut self, face: FontFace, ch: char) -> bool {
+        match self.get_or_load_char_map(face.index) {
+            Ok(char_map) => char_map.contains_key(&ch),
+            Err(_) => {
+                // the face is not parsed again for every character
+                self.char_maps.insert(face.index, Arc::default());
+                false
+            }
+        }
+    }
+
     fn draw(
         &mut self,
         device: &mut D,
@@ -47,10 +278,26 @@ impl<D: Device> Font<D> for TextureFont<D> {
         font_params: FontParams,
         transform: UnknownToDeviceTransform,
     ) -> Result<()> {
-        let renderer = self.get_or_create_font_renderer(font_params.size)?;
+        let params = TextDrawParams::new(pos, clipping_rect, font_params, transform);
+        if font_params.writing_mode.is_vertical() {
+            return draw_vertical(self, device, target, GlyphFill::Color(color), text, params);
+        }
+        let antialiasing = self.rendering.antialiasing;
+        let lines = self.shape(font_params, antialiasing, text)?;
+        self.draw_effects(device, target, &lines, &params, antialiasing)?;
+        let renderer = self
+            .font_renderers
+            .get_mut(&(font_params.face, font_params.font_size(), antialiasing))
+            .unwrap();
+        let atlas = match antialiasing {
+            TextAntialiasing::Grayscale => &mut self.atlas,
+            _ => &mut self.lcd_atlas,
+        };
         renderer.add(
-            text,
-            [pos.x as i32, pos.y as i32],
+            atlas,
+            &mut self.color_atlas,
+            &lines,
+            [pos.x, pos.y],
             [
                 clipping_rect.origin.x,
                 clipping_rect.origin.y,
@@ -58,15 +305,252 @@ impl<D: Device> Font<D> for TextureFont<D> {
                 clipping_rect.size.height,
             ],
             *color,
-        );
-        renderer.draw_at(device, target, transform)?;
+        )?;
+        match antialiasing {
+            TextAntialiasing::Grayscale => self.atlas.draw_at(device, target, transform)?,
+            _ => self.lcd_atlas.draw_lcd_at(device, target, transform)?,
+        }
+        self.color_atlas.draw_at(device, target, transform)?;
         Ok(())
     }
 
+    fn draw_paint(
+        &mut self,
+        device: &mut D,
+        target: &D::RenderTarget,
+        paint: &Paint,
+        paint_texture: Option<&D::Texture>,
+        text: &str,
+        params: TextDrawParams,
+    ) -> Result<()> {
+        let TextDrawParams {
+            pos,
+            clipping_rect,
+            scissor,
+            font_params,
+            transform,
+            ..
+        } = params;
+        if font_params.writing_mode.is_vertical() {
+            let fill = GlyphFill::Paint(paint, paint_texture);
+            return draw_vertical(self, device, target, fill, text, params);
+        }
+        // the paint is applied to the coverage of whole pixels
+        let antialiasing = TextAntialiasing::Grayscale;
+        let lines = self.shape(font_params, antialiasing, text)?;
+        self.draw_effects(device, target, &lines, &params, antialiasing)?;
+        let renderer = self
+            .font_renderers
+            .get_mut(&(font_params.face, font_params.font_size(), antialiasing))
+            .unwrap();
+        renderer.add(
+            &mut self.atlas,
+            &mut self.color_atlas,
+            &lines,
+            [pos.x, pos.y],
+            [
+                clipping_rect.origin.x,
+                clipping_rect.origin.y,
+                clipping_rect.size.width,
+                clipping_rect.size.height,
+            ],
+            [1.0, 1.0, 1.0, 1.0],
+        )?;
+        let paint = GlyphPaint {
+            paint,
+            texture: paint_texture,
+            scissor,
+        };
+        self.atlas
+            .draw_paint_at(device, target, &paint, transform)?;
+        // color glyphs keep their own colors
+        self.color_atlas.draw_at(device, target, transform)
+    }
+
+    fn draw_glyphs_transformed(
+        &mut self,
+        device: &mut D,
+        target: &D::RenderTarget,
+        fill: GlyphFill<D::Texture>,
+        text: &str,
+        glyph_transforms: &[Option<PixelTransform>],
+        params: TextDrawParams,
+    ) -> Result<()> {
+        let TextDrawParams {
+            pos,
+            clipping_rect,
+            scissor,
+            font_params,
+            transform,
+            ..
+        } = params;
+        // coverage of LCD subpixels would be wrong for rotated glyphs
+        let antialiasing = TextAntialiasing::Grayscale;
+        let lines = self.shape(font_params, antialiasing, text)?;
+        let renderer = self
+            .font_renderers
+            .get_mut(&(font_params.face, font_params.font_size(), antialiasing))
+            .unwrap();
+        let glyph_transforms = &in_columns(
+            &lines,
+            font_params.writing_mode,
+            renderer.get_bitmap_font().get_metrics(),
+            glyph_transforms,
+            pos,
+        );
+        let clipping_rect = [
+            clipping_rect.origin.x,
+            clipping_rect.origin.y,
+            clipping_rect.size.width,
+            clipping_rect.size.height,
+        ];
+        let glyph_ids: Vec<u32> = lines.iter().flatten().map(|glyph| glyph.glyph_id).collect();
+        for (mask, offset, color) in effect_layers(&font_params.effects) {
+            renderer.load_masks(&glyph_ids, mask, &mut self.atlas)?;
+            // the effects are moved after the glyphs are transformed
+            let offset_transforms: Vec<Option<PixelTransform>> = glyph_transforms
+                .iter()
+                .map(|glyph_transform| {
+                    glyph_transform.map(|glyph_transform| {
+                        glyph_transform.then_translate(euclid::vec2(offset[0], offset[1]))
+                    })
+                })
+                .collect();
+            renderer.add_mask_transformed(
+                &mut self.atlas,
+                &lines,
+                &offset_transforms,
+                clipping_rect,
+                mask,
+                color,
+            );
+            self.atlas.draw_filtered_at(device, target, transform)?;
+        }
+        let color = match fill {
+            GlyphFill::Color(color) => *color,
+            GlyphFill::Paint(..) => [1.0, 1.0, 1.0, 1.0],
+        };
+        renderer.add_transformed(
+            &mut self.atlas,
+            &mut self.color_atlas,
+            &lines,
+            glyph_transforms,
+            clipping_rect,
+            color,
+        );
+        match fill {
+            GlyphFill::Color(_) => self.atlas.draw_filtered_at(device, target, transform)?,
+            GlyphFill::Paint(paint, texture) => {
+                let paint = GlyphPaint {
+                    paint,
+                    texture,
+                    scissor,
+                };
+                self.atlas
+                    .draw_paint_filtered_at(device, target, &paint, transform)?
+            }
+        }
+        // color glyphs keep their own colors
+        self.color_atlas.draw_at(device, target, transform)
+    }
+
     fn get_dimensions(&mut self, params: FontParams, text: &str) -> Result<(u16, u16)> {
-        let renderer = self.get_or_create_font_renderer(params.size)?;
-        let dims = renderer.get_bitmap_font().measure(text);
-        Ok((dims.0 as u16, dims.1 as u16))
+        let antialiasing = self.rendering.antialiasing;
+        let lines = self.shape(params, antialiasing, text)?;
+        let renderer = self.get_or_create_font_renderer(params, antialiasing)?;
+        let width = text_width(&lines);
+        let height = lines.len() as i32 * renderer.get_bitmap_font().get_font_height() as i32;
+        if params.writing_mode.is_vertical() {
+            // columns of the line height
+            return Ok((height as u16, width.round() as u16));
+        }
+        Ok((width.round() as u16, height as u16))
+    }
+
+    fn get_metrics(&mut self, params: FontParams) -> Result<FontMetrics> {
+        let renderer = self.get_or_create_font_renderer(params, self.rendering.antialiasing)?;
+        Ok(*renderer.get_bitmap_font().get_metrics())
+    }
+
+    fn get_glyph_bounds(&mut self, params: FontParams, text: &str) -> Result<Vec<GlyphBounds>> {
+        let antialiasing = self.rendering.antialiasing;
+        let lines = self.shape(params, antialiasing, text)?;
+        let bitmap_font = self
+            .font_renderers
+            .get(&(params.face, params.font_size(), antialiasing))
+            .unwrap()
+            .get_bitmap_font();
+        let bounds = glyph_bounds(
+            text,
+            &lines,
+            params.direction,
+            bitmap_font.get_font_height() as f32,
+            |glyph_id| {
+                bitmap_font.find_glyph(glyph_id).map(|ch_info| {
+                    Rect::new(
+                        Point::new(ch_info.x_offset as f32, ch_info.y_offset as f32),
+                        Size::new(ch_info.width as f32, ch_info.height as f32),
+                    )
+                })
+            },
+        );
+        if params.writing_mode.is_vertical() {
+            return Ok(column_bounds(
+                bounds,
+                &lines,
+                params.writing_mode,
+                bitmap_font.get_metrics(),
+            ));
+        }
+        Ok(bounds)
+    }
+
+    fn get_outline(
+        &mut self,
+        params: FontParams,
+        text: &str,
+        pos: PixelPoint,
+    ) -> Result<Vec<PathElement>> {
+        let antialiasing = self.rendering.antialiasing;
+        let lines = self.shape(params, antialiasing, text)?;
+        let glyph_ids = unique_glyphs(lines.iter().flatten().map(|glyph| glyph.glyph_id));
+        let outlines = self.outlines.entry(params.face).or_default();
+        outlines
+            .load_glyphs(&self.bytes, &params.face, &glyph_ids)
+            .map_err(Error::from)?;
+
+        let bitmap_font = self
+            .font_renderers
+            .get(&(params.face, params.font_size(), antialiasing))
+            .unwrap()
+            .get_bitmap_font();
+        let metrics = bitmap_font.get_metrics();
+        let mut path = Vec::new();
+        if params.writing_mode.is_vertical() {
+            let glyph_transforms: Vec<PixelTransform> =
+                column_transforms(&lines, params.writing_mode, metrics)
+                    .into_iter()
+                    .map(|(transform, _)| transform.then_translate(pos.to_vector()))
+                    .collect();
+            outlines.add_transformed_to_path(
+                &lines,
+                params.font_size().get(),
+                metrics.baseline,
+                metrics.line_height,
+                &glyph_transforms,
+                &mut path,
+            );
+        } else {
+            outlines.add_to_path(
+                &lines,
+                params.font_size().get(),
+                pos,
+                metrics.baseline,
+                bitmap_font.get_font_height() as f32,
+                &mut path,
+            );
+        }
+        Ok(path)
     }
 
     fn get_dimensions_each_char(
@@ -74,8 +558,91 @@ impl<D: Device> Font<D> for TextureFont<D> {
         params: FontParams,
         text: &str,
     ) -> Result<(Vec<i16>, u16)> {
-        let renderer = self.get_or_create_font_renderer(params.size)?;
-        let dims = renderer.get_bitmap_font().measure_each_char(text);
-        Ok((dims.0, dims.1 as u16))
+        let antialiasing = self.rendering.antialiasing;
+        let lines = self.shape(params, antialiasing, text)?;
+        let renderer = self.get_or_create_font_renderer(params, antialiasing)?;
+
+        let widths = char_widths(text, &lines);
+        Ok((widths, renderer.get_bitmap_font().get_font_height()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // TrueType font with the square glyph of the character 'a'
+    // (600 units wide, 1000 units per em)
+    fn font_data() -> Vec<u8> {
+        fn be(values: &[i32]) -> Vec<u8> {
+            values
+                .iter()
+                .flat_map(|&value| (value as u16).to_be_bytes())
+                .collect()
+        }
+
+        // one contour with 4 points on the curve, coordinates are 16-bit deltas
+        let mut glyph = be(&[1, 100, 0, 500, 500, 3, 0]);
+        glyph.extend_from_slice(&[1, 1, 1, 1]);
+        glyph.extend(be(&[100, 0, 400, 0, 0, 500, 0, -500]));
+        let glyf = glyph.clone();
+        let loca = be(&[0, 0, glyph.len() as i32 / 2]);
+        let hmtx = be(&[600, 0, 600, 100]);
+        let mut head = be(&[1, 0, 1, 0, 0, 0, 0x5F0F, 0x3CF5, 0, 1000]);
+        head.extend(vec![0; 16]);
+        head.extend(be(&[100, 0, 500, 500, 0, 8, 2, 0, 0]));
+        let mut hhea = be(&[1, 0, 800, -200, 0, 600, 0, 0, 500, 1, 0, 0]);
+        hhea.extend(vec![0; 10]);
+        hhea.extend(be(&[2]));
+        let mut maxp = be(&[1, 0, 2, 4, 1, 0, 0, 2]);
+        maxp.extend(vec![0; 16]);
+        // format 4 subtable with the segment of 'a' and the final one
+        let mut cmap = be(&[0, 1, 3, 1, 0, 12]);
+        cmap.extend(be(&[4, 32, 0, 4, 4, 1, 0]));
+        cmap.extend(be(&['a' as i32, 0xFFFF, 0, 'a' as i32, 0xFFFF]));
+        cmap.extend(be(&[1 - 'a' as i32, 1, 0, 0]));
+        let name = be(&[0, 0, 6]);
+        let mut post = be(&[3, 0]);
+        post.extend(vec![0; 28]);
+
+        let tables: [(&[u8; 4], Vec<u8>); 9] = [
+            (b"cmap", cmap),
+            (b"glyf", glyf),
+            (b"head", head),
+            (b"hhea", hhea),
+            (b"hmtx", hmtx),
+            (b"loca", loca),
+            (b"maxp", maxp),
+            (b"name", name),
+            (b"post", post),
+        ];
+        let mut data = be(&[1, 0, tables.len() as i32, 128, 3, 16]);
+        let mut offset = 12 + 16 * tables.len();
+        for (tag, table) in &tables {
+            data.extend_from_slice(*tag);
+            data.extend_from_slice(&0u32.to_be_bytes());
+            data.extend_from_slice(&(offset as u32).to_be_bytes());
+            data.extend_from_slice(&(table.len() as u32).to_be_bytes());
+            offset += (table.len() + 3) & !3;
+        }
+        for (_, table) in &tables {
+            data.extend_from_slice(table);
+            data.resize((data.len() + 3) & !3, 0);
+        }
+        data
+    }
+
+    #[test]
+    fn synthesized_bold_text_is_wider() {
+        let mut font = TextureFont::<TestDevice>::create(font_data()).unwrap();
+        let params = FontParams::new(24.0);
+        let bold = params.with_synthesis(FontSynthesis {
+            bold: true,
+            oblique: false,
+        });
+        let (width, _) = font.get_dimensions(params, "aaaa").unwrap();
+        let (bold_width, _) = font.get_dimensions(bold, "aaaa").unwrap();
+        // every glyph is wider by 1/24 em
+        assert!(bold_width >= width + 4, "{bold_width} <= {width}");
     }
 }
@@ -48,6 +48,24 @@ impl Transformation for Vec<Primitive> {
                     clipping_rect.translate(offset);
                 }
 
+                Primitive::TextLayout {
+                    ref mut position,
+                    ref mut clipping_rect,
+                    ..
+                } => {
+                    position.translate(offset);
+                    clipping_rect.translate(offset);
+                }
+
+                Primitive::RichText {
+                    ref mut position,
+                    ref mut clipping_rect,
+                    ..
+                } => {
+                    position.translate(offset);
+                    clipping_rect.translate(offset);
+                }
+
                 Primitive::Stroke { ref mut path, .. } => path.translate(offset),
 
                 Primitive::StrokeStyled { ref mut path, .. } => path.translate(offset),
@@ -0,0 +1,375 @@
+//! Vertical text (like Japanese or Chinese written in columns).
+//!
+//! Lines of vertical text are columns going from top to bottom. Characters
+//! of East Asian scripts and most symbols are drawn upright with the vertical
+//! metrics of the font (`vhea`/`vmtx`) and its vertical alternates (the `vert`
+//! feature, with the `shaping` feature only), other characters (like Latin
+//! or Arabic) are drawn rotated 90° clockwise, as given by the Unicode vertical
+//! orientation data (UAX #50).
+//!
+//! Fonts shape every column like a line of horizontal text (see `shape_vertical_line()`)
+//! and lay the glyphs out horizontally, the transforms returned by `column_transforms()`
+//! move them to their columns.
+//!
+//! `TextLayout`, rich text and text along a path are always horizontal.
+
+use anyhow::Result;
+
+use crate::backend::Device;
+use crate::font::{Font, FontMetrics, GlyphFill, TextDrawParams};
+use crate::shaping::{GlyphBounds, ShapedGlyph};
+use crate::units::*;
+
+/// Direction of the lines and the order they go in.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub enum WritingMode {
+    /// Lines from left to right (or right to left), stacked from top to bottom.
+    #[default]
+    HorizontalTb,
+    /// Columns from top to bottom, the first one on the right (Japanese and Chinese).
+    VerticalRl,
+    /// Columns from top to bottom, the first one on the left (Mongolian).
+    VerticalLr,
+}
+
+impl WritingMode {
+    pub fn is_vertical(self) -> bool {
+        self != WritingMode::HorizontalTb
+    }
+}
+
+/// Orientation of the character in vertical text (Unicode `Vertical_Orientation` property).
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum VerticalOrientation {
+    /// Drawn upright (like ideographs).
+    Upright,
+    /// Drawn rotated 90° clockwise (like Latin letters).
+    Rotated,
+    /// Drawn upright with the vertical alternate glyph (like small kana
+    /// or ideographic full stop).
+    TransformedUpright,
+    /// Drawn upright with the vertical alternate glyph, rotated if the font
+    /// has none (like brackets).
+    TransformedRotated,
+}
+
+// Characters that are not rotated (condensed from `VerticalOrientation.txt`
+// of Unicode 15, unassigned code points of the ranges are included).
+const ORIENTATIONS: &[(u32, u32, VerticalOrientation)] = {
+    use VerticalOrientation::*;
+    &[
+        (0x00A7, 0x00A7, Upright),
+        (0x00A9, 0x00A9, Upright),
+        (0x00AE, 0x00AE, Upright),
+        (0x00B1, 0x00B1, Upright),
+        (0x00BC, 0x00BE, Upright),
+        (0x00D7, 0x00D7, Upright),
+        (0x00F7, 0x00F7, Upright),
+        (0x02EA, 0x02EB, Upright),
+        (0x1100, 0x11FF, Upright),
+        (0x1401, 0x167F, Upright),
+        (0x18B0, 0x18FF, Upright),
+        (0x2016, 0x2016, Upright),
+        (0x2020, 0x2021, Upright),
+        (0x2030, 0x2031, Upright),
+        (0x203B, 0x203C, Upright),
+        (0x2042, 0x2042, Upright),
+        (0x2047, 0x2049, Upright),
+        (0x2051, 0x2051, Upright),
+        (0x20DD, 0x20E0, Upright),
+        (0x20E2, 0x20E4, Upright),
+        (0x2100, 0x2101, Upright),
+        (0x2103, 0x2109, Upright),
+        (0x210F, 0x210F, Upright),
+        (0x2113, 0x2114, Upright),
+        (0x2116, 0x2117, Upright),
+        (0x211E, 0x2123, Upright),
+        (0x2125, 0x2125, Upright),
+        (0x2127, 0x2127, Upright),
+        (0x2129, 0x2129, Upright),
+        (0x212E, 0x212E, Upright),
+        (0x2135, 0x213F, Upright),
+        (0x2145, 0x214A, Upright),
+        (0x214C, 0x214D, Upright),
+        (0x214F, 0x2189, Upright),
+        (0x218C, 0x218F, Upright),
+        (0x221E, 0x221E, Upright),
+        (0x2234, 0x2235, Upright),
+        (0x2300, 0x2307, Upright),
+        (0x230C, 0x231F, Upright),
+        (0x2324, 0x2328, Upright),
+        (0x2329, 0x232A, TransformedRotated),
+        (0x232B, 0x237D, Upright),
+        (0x2394, 0x2394, Upright),
+        (0x2396, 0x239A, Upright),
+        (0x23BE, 0x23CD, Upright),
+        (0x23CF, 0x23CF, Upright),
+        (0x23D1, 0x23DB, Upright),
+        (0x23E2, 0x2422, Upright),
+        (0x2424, 0x24FF, Upright),
+        (0x25A0, 0x2619, Upright),
+        (0x2620, 0x2767, Upright),
+        (0x2776, 0x2793, Upright),
+        (0x2B12, 0x2B2F, Upright),
+        (0x2B50, 0x2B59, Upright),
+        (0x2BB8, 0x2BD1, Upright),
+        (0x2BD3, 0x2BEB, Upright),
+        (0x2BF0, 0x2BFF, Upright),
+        (0x2E80, 0x2FFF, Upright),
+        (0x3000, 0x3000, Upright),
+        (0x3001, 0x3002, TransformedUpright),
+        (0x3003, 0x3007, Upright),
+        (0x3008, 0x3011, TransformedRotated),
+        (0x3012, 0x3013, Upright),
+        (0x3014, 0x301F, TransformedRotated),
+        (0x3020, 0x302F, Upright),
+        (0x3030, 0x3030, TransformedRotated),
+        (0x3031, 0x3040, Upright),
+        (0x3041, 0x3041, TransformedUpright),
+        (0x3042, 0x3042, Upright),
+        (0x3043, 0x3043, TransformedUpright),
+        (0x3044, 0x3044, Upright),
+        (0x3045, 0x3045, TransformedUpright),
+        (0x3046, 0x3046, Upright),
+        (0x3047, 0x3047, TransformedUpright),
+        (0x3048, 0x3048, Upright),
+        (0x3049, 0x3049, TransformedUpright),
+        (0x304A, 0x3062, Upright),
+        (0x3063, 0x3063, TransformedUpright),
+        (0x3064, 0x3082, Upright),
+        (0x3083, 0x3083, TransformedUpright),
+        (0x3084, 0x3084, Upright),
+        (0x3085, 0x3085, TransformedUpright),
+        (0x3086, 0x3086, Upright),
+        (0x3087, 0x3087, TransformedUpright),
+        (0x3088, 0x308D, Upright),
+        (0x308E, 0x308E, TransformedUpright),
+        (0x308F, 0x3094, Upright),
+        (0x3095, 0x3096, TransformedUpright),
+        (0x3097, 0x309A, Upright),
+        (0x309B, 0x309C, TransformedUpright),
+        (0x309D, 0x309F, Upright),
+        (0x30A0, 0x30A0, TransformedRotated),
+        (0x30A1, 0x30A1, TransformedUpright),
+        (0x30A2, 0x30A2, Upright),
+        (0x30A3, 0x30A3, TransformedUpright),
+        (0x30A4, 0x30A4, Upright),
+        (0x30A5, 0x30A5, TransformedUpright),
+        (0x30A6, 0x30A6, Upright),
+        (0x30A7, 0x30A7, TransformedUpright),
+        (0x30A8, 0x30A8, Upright),
+        (0x30A9, 0x30A9, TransformedUpright),
+        (0x30AA, 0x30C2, Upright),
+        (0x30C3, 0x30C3, TransformedUpright),
+        (0x30C4, 0x30E2, Upright),
+        (0x30E3, 0x30E3, TransformedUpright),
+        (0x30E4, 0x30E4, Upright),
+        (0x30E5, 0x30E5, TransformedUpright),
+        (0x30E6, 0x30E6, Upright),
+        (0x30E7, 0x30E7, TransformedUpright),
+        (0x30E8, 0x30ED, Upright),
+        (0x30EE, 0x30EE, TransformedUpright),
+        (0x30EF, 0x30F4, Upright),
+        (0x30F5, 0x30F6, TransformedUpright),
+        (0x30F7, 0x30FB, Upright),
+        (0x30FC, 0x30FC, TransformedRotated),
+        (0x30FD, 0x31EF, Upright),
+        (0x31F0, 0x31FF, TransformedUpright),
+        (0x3200, 0x32FF, Upright),
+        (0x3300, 0x3357, TransformedUpright),
+        (0x3358, 0x337A, Upright),
+        (0x337B, 0x337F, TransformedUpright),
+        (0x3380, 0xA4CF, Upright),
+        (0xA960, 0xA97F, Upright),
+        (0xAC00, 0xD7FF, Upright),
+        (0xE000, 0xFAFF, Upright),
+        (0xFE10, 0xFE1F, Upright),
+        (0xFE30, 0xFE4F, Upright),
+        (0xFE50, 0xFE52, TransformedUpright),
+        (0xFE53, 0xFE57, Upright),
+        (0xFE59, 0xFE5E, TransformedRotated),
+        (0xFE5F, 0xFE62, Upright),
+        (0xFE64, 0xFE6F, Upright),
+        (0xFF01, 0xFF01, TransformedUpright),
+        (0xFF02, 0xFF07, Upright),
+        (0xFF08, 0xFF09, TransformedRotated),
+        (0xFF0A, 0xFF0B, Upright),
+        (0xFF0C, 0xFF0C, TransformedUpright),
+        (0xFF0E, 0xFF0E, TransformedUpright),
+        (0xFF0F, 0xFF19, Upright),
+        (0xFF1A, 0xFF1B, TransformedRotated),
+        (0xFF1F, 0xFF1F, TransformedUpright),
+        (0xFF20, 0xFF3A, Upright),
+        (0xFF3B, 0xFF3B, TransformedRotated),
+        (0xFF3C, 0xFF3C, Upright),
+        (0xFF3D, 0xFF3D, TransformedRotated),
+        (0xFF3E, 0xFF3E, Upright),
+        (0xFF3F, 0xFF3F, TransformedRotated),
+        (0xFF40, 0xFF5A, Upright),
+        (0xFF5B, 0xFF60, TransformedRotated),
+        (0xFFE0, 0xFFE2, Upright),
+        (0xFFE3, 0xFFE3, TransformedRotated),
+        (0xFFE4, 0xFFE7, Upright),
+        (0x13000, 0x1345F, Upright),
+        (0x16FE0, 0x18AFF, Upright),
+        (0x1B000, 0x1B2FF, Upright),
+        (0x1D000, 0x1D1FF, Upright),
+        (0x1D2E0, 0x1D37F, Upright),
+        (0x1F000, 0x1F7FF, Upright),
+        (0x1F900, 0x1FAFF, Upright),
+        (0x20000, 0x3FFFD, Upright),
+        (0xF0000, 0x10FFFD, Upright),
+    ]
+};
+
+/// Returns the orientation of the character in vertical text.
+pub fn vertical_orientation(ch: char) -> VerticalOrientation {
+    let code = ch as u32;
+    let i = ORIENTATIONS.partition_point(|&(_, end, _)| end < code);
+    match ORIENTATIONS.get(i) {
+        Some(&(start, _, orientation)) if start <= code => orientation,
+        _ => VerticalOrientation::Rotated,
+    }
+}
+
+/// Returns the transforms moving the glyphs of the vertical text from their positions
+/// in the lines laid out horizontally (one below another, starting at the origin)
+/// to their columns, and the cells of the glyphs in the columns (the width
+/// of the column and the advance of the glyph, relative to the top left corner
+/// of the text), in the order of the glyphs of the lines.
+///
+/// Upright glyphs have their horizontal origin at the offsets from the pen
+/// on the center line of the column, rotated glyphs have the center of the em box
+/// there. Columns are placed at whole pixels.
+///
+/// # Arguments
+///
+/// * `lines` - lines shaped with `shape_vertical_line()`
+/// * `writing_mode` - order of the columns
+/// * `metrics` - metrics of the font (the line height is the width of the columns)
+pub fn column_transforms(
+    lines: &[Vec<ShapedGlyph>],
+    writing_mode: WritingMode,
+    metrics: &FontMetrics,
+) -> Vec<(PixelTransform, Rect)> {
+    let line_height = metrics.line_height;
+    // distance from the top of the line to the center of the em box
+    let em_center = metrics.baseline - (metrics.ascent - metrics.descent) * 0.5;
+    let mut res = Vec::new();
+    for (i, line) in lines.iter().enumerate() {
+        let column = match writing_mode {
+            WritingMode::VerticalRl => lines.len() - 1 - i,
+            _ => i,
+        };
+        let left = column as f32 * line_height;
+        let center = (left + line_height * 0.5).round();
+        let y = i as f32 * line_height;
+        let mut pen = 0.0f32;
+        for glyph in line {
+            // the glyphs are placed at whole pixels before they are transformed
+            let pen_y = pen.round();
+            let transform = if glyph.upright {
+                PixelTransform::translation(center - pen_y, pen_y - metrics.baseline - y)
+            } else {
+                PixelTransform::translation(-pen_y, -(y + em_center).round())
+                    .then_rotate(euclid::Angle::frac_pi_2())
+                    .then_translate(euclid::vec2(center, pen_y))
+            };
+            let cell = Rect::new(
+                Point::new(left, pen),
+                Size::new(line_height, glyph.x_advance),
+            );
+            res.push((transform, cell));
+            pen += glyph.x_advance;
+        }
+    }
+    res
+}
+
+/// Moves the glyph bounds of the lines laid out horizontally to the columns
+/// (see `column_transforms()`).
+pub fn column_bounds(
+    bounds: Vec<GlyphBounds>,
+    lines: &[Vec<ShapedGlyph>],
+    writing_mode: WritingMode,
+    metrics: &FontMetrics,
+) -> Vec<GlyphBounds> {
+    bounds
+        .into_iter()
+        .zip(column_transforms(lines, writing_mode, metrics))
+        .map(|(glyph, (transform, cell))| GlyphBounds {
+            advance_rect: cell,
+            ink_rect: transform
+                .outer_transformed_rect(&PixelRect::from_untyped(&glyph.ink_rect))
+                .to_untyped(),
+            ..glyph
+        })
+        .collect()
+}
+
+/// Returns the transforms of the glyphs placed like horizontal text followed by
+/// the glyph transforms moved to the position of the text (for vertical text,
+/// horizontal text has the glyph transforms only moved).
+pub fn in_columns(
+    lines: &[Vec<ShapedGlyph>],
+    writing_mode: WritingMode,
+    metrics: &FontMetrics,
+    glyph_transforms: &[Option<PixelTransform>],
+    pos: Point,
+) -> Vec<Option<PixelTransform>> {
+    let offset = pos.to_vector().cast_unit();
+    let glyph_transforms = glyph_transforms.iter().map(|glyph_transform| {
+        glyph_transform.map(|glyph_transform| glyph_transform.then_translate(offset))
+    });
+    if !writing_mode.is_vertical() {
+        return glyph_transforms.collect();
+    }
+    column_transforms(lines, writing_mode, metrics)
+        .into_iter()
+        .zip(glyph_transforms)
+        .map(|((column_transform, _), glyph_transform)| {
+            glyph_transform.map(|glyph_transform| column_transform.then(&glyph_transform))
+        })
+        .collect()
+}
+
+/// Draws the text in a vertical writing mode with `Font::draw_glyphs_transformed()`
+/// of the font (for fonts placing the glyphs with `in_columns()` there).
+///
+/// The text is placed at whole pixels. Glyphs are not cut by the clipping rectangle,
+/// glyphs with the center of the cell outside of it are not drawn.
+pub fn draw_vertical<D: Device, F: Font<D> + ?Sized>(
+    font: &mut F,
+    device: &mut D,
+    target: &D::RenderTarget,
+    fill: GlyphFill<D::Texture>,
+    text: &str,
+    params: TextDrawParams,
+) -> Result<()> {
+    let font_params = params.font_params;
+    let pos = params.pos.round();
+    let line_height = font.get_metrics(font_params)?.line_height;
+    let glyph_transforms: Vec<Option<PixelTransform>> = font
+        .get_glyph_bounds(font_params, text)?
+        .iter()
+        .map(|glyph| {
+            let center = glyph.advance_rect.center() + pos.to_vector();
+            if params.clipping_rect.contains(center) {
+                Some(PixelTransform::identity())
+            } else {
+                None
+            }
+        })
+        .collect();
+    // the centers of the glyphs laid out horizontally are off their cells
+    // (the glyphs were filtered above)
+    let clipping_rect = params.clipping_rect.inflate(line_height, line_height);
+    font.draw_glyphs_transformed(
+        device,
+        target,
+        fill,
+        text,
+        &glyph_transforms,
+        params.with_pos(pos).with_clipping_rect(clipping_rect),
+    )
+}
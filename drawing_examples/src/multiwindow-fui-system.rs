@@ -348,6 +348,7 @@ pub fn draw(
             size: PixelThickness::new(10.0f32),
             text: "Hello World!! yyy ąęśżółw,. 01234567890 abcdefghijk ABCDEFGHIJK XYZ xyz"
                 .to_string(),
+            style: TextStyle::default(),
         },
         Primitive::Text {
             resource_key: "F1".to_string(),
@@ -357,6 +358,7 @@ pub fn draw(
             size: PixelThickness::new(12.0f32),
             text: "Hello World!! yyy ąęśżółw,.\n01234567890 abcdefghijk ABCDEFGHIJK XYZ xyz"
                 .to_string(),
+            style: TextStyle::default(),
         },
         Primitive::Text {
             resource_key: "F1".to_string(),
@@ -366,6 +368,7 @@ pub fn draw(
             size: PixelThickness::new(14.0f32),
             text: "Hello World!! yyy ąęśżółw,.\n01234567890 abcdefghijk\nABCDEFGHIJK XYZ xyz"
                 .to_string(),
+            style: TextStyle::default(),
         },
         Primitive::Text {
             resource_key: "F1".to_string(),
@@ -375,6 +378,7 @@ pub fn draw(
             size: PixelThickness::new(16.0f32),
             text: "Hello World!! yyy ąęśżółw,. 01234567890 abcdefghijk ABCDEFGHIJK XYZ xyz"
                 .to_string(),
+            style: TextStyle::default(),
         },
         Primitive::Text {
             resource_key: "F1".to_string(),
@@ -384,6 +388,7 @@ pub fn draw(
             size: PixelThickness::new(18.0f32),
             text: "Hello World!! yyy ąęśżółw,. 01234567890 abcdefghijk ABCDEFGHIJK XYZ xyz"
                 .to_string(),
+            style: TextStyle::default(),
         },
         Primitive::Fill {
             path: vec![
@@ -463,6 +468,7 @@ pub fn draw(
                     clipping_rect,
                     size: PixelThickness::new(22.0f32),
                     text: "Render target test".to_string(),
+                    style: TextStyle::default(),
                 },
             ],
         },
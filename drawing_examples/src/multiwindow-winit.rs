@@ -2,7 +2,7 @@
 
 use drawing::backend::{Device, RenderTarget};
 use drawing::font::Font;
-use drawing::primitive::Primitive;
+use drawing::primitive::{Primitive, TextStyle};
 use drawing::renderer::Renderer;
 use drawing::resources::Resources;
 use drawing::units::*;
@@ -130,6 +130,7 @@ fn draw_window(
             ),
             size: PixelThickness::new(20.0f32),
             text: text.to_string(),
+            style: TextStyle::default(),
         }];
 
         // make current context
@@ -318,6 +318,7 @@ pub fn draw(
             size: PixelThickness::new(10.0f32),
             text: "Hello World!! yyy ąęśżółw,. 01234567890 abcdefghijk ABCDEFGHIJK XYZ xyz"
                 .to_string(),
+            style: TextStyle::default(),
         },
         Primitive::Text {
             resource_key: "F1".to_string(),
@@ -327,6 +328,7 @@ pub fn draw(
             size: PixelThickness::new(12.0f32),
             text: "Hello World!! yyy ąęśżółw,.\n01234567890 abcdefghijk ABCDEFGHIJK XYZ xyz"
                 .to_string(),
+            style: TextStyle::default(),
         },
         Primitive::Text {
             resource_key: "F1".to_string(),
@@ -336,6 +338,7 @@ pub fn draw(
             size: PixelThickness::new(14.0f32),
             text: "Hello World!! yyy ąęśżółw,.\n01234567890 abcdefghijk\nABCDEFGHIJK XYZ xyz"
                 .to_string(),
+            style: TextStyle::default(),
         },
         Primitive::Text {
             resource_key: "F1".to_string(),
@@ -345,6 +348,7 @@ pub fn draw(
             size: PixelThickness::new(16.0f32),
             text: "Hello World!! yyy ąęśżółw,. 01234567890 abcdefghijk ABCDEFGHIJK XYZ xyz"
                 .to_string(),
+            style: TextStyle::default(),
         },
         Primitive::Text {
             resource_key: "F1".to_string(),
@@ -354,6 +358,7 @@ pub fn draw(
             size: PixelThickness::new(18.0f32),
             text: "Hello World!! yyy ąęśżółw,. 01234567890 abcdefghijk ABCDEFGHIJK XYZ xyz"
                 .to_string(),
+            style: TextStyle::default(),
         },
         Primitive::Fill {
             path: vec![
@@ -433,6 +438,7 @@ pub fn draw(
                     clipping_rect,
                     size: PixelThickness::new(22.0f32),
                     text: "Render target test".to_string(),
+                    style: TextStyle::default(),
                 },
             ],
         },
@@ -204,6 +204,7 @@ fn main() {
                         size: PixelThickness::new(10.0f32),
                         text: "Hello World!! yyy ąęśżółw,. 01234567890 abcdefghijk ABCDEFGHIJK XYZ xyz"
                             .to_string(),
+                        style: TextStyle::default(),
                     },
                     Primitive::Text {
                         resource_key: "F1".to_string(),
@@ -213,6 +214,7 @@ fn main() {
                         size: PixelThickness::new(12.0f32),
                         text: "Hello World!! yyy ąęśżółw,.\n01234567890 abcdefghijk ABCDEFGHIJK XYZ xyz"
                             .to_string(),
+                        style: TextStyle::default(),
                     },
                     Primitive::Text {
                         resource_key: "F1".to_string(),
@@ -222,6 +224,7 @@ fn main() {
                         size: PixelThickness::new(14.0f32),
                         text: "Hello World!! yyy ąęśżółw,.\n01234567890 abcdefghijk\nABCDEFGHIJK XYZ xyz"
                             .to_string(),
+                        style: TextStyle::default(),
                     },
                     Primitive::Text {
                         resource_key: "F1".to_string(),
@@ -231,6 +234,7 @@ fn main() {
                         size: PixelThickness::new(16.0f32),
                         text: "Hello World!! yyy ąęśżółw,. 01234567890 abcdefghijk ABCDEFGHIJK XYZ xyz"
                             .to_string(),
+                        style: TextStyle::default(),
                     },
                     Primitive::Text {
                         resource_key: "F1".to_string(),
@@ -240,6 +244,7 @@ fn main() {
                         size: PixelThickness::new(18.0f32),
                         text: "Hello World!! yyy ąęśżółw,. 01234567890 abcdefghijk ABCDEFGHIJK XYZ xyz"
                             .to_string(),
+                        style: TextStyle::default(),
                     },
 
                     Primitive::Fill {
@@ -316,6 +321,7 @@ fn main() {
                                 clipping_rect,
                                 size: PixelThickness::new(22.0f32),
                                 text: "Render target test".to_string(),
+                                style: TextStyle::default(),
                             },
                         ],
                     }
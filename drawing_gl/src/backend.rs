@@ -7,7 +7,7 @@ use crate::pipelines::*;
 use anyhow::Result;
 use drawing::backend::*;
 use drawing::color::*;
-use drawing::path::{Bounds, Path};
+use drawing::path::FlattenedPath;
 use drawing::units::*;
 use gl::types::*;
 
@@ -23,6 +23,8 @@ pub struct GlDevice {
     colored_pipeline: Option<ColoredPipeline>,
     textured_pipeline: Option<TexturedPipeline>,
     textured_y8_pipeline: Option<TexturedY8Pipeline>,
+    textured_lcd_pipeline: Option<TexturedLcdPipeline>,
+    distance_field_pipeline: Option<DistanceFieldPipeline>,
     universal_pipeline: Option<UniversalPipeline>,
     aspect_ratio: f32,
 }
@@ -55,6 +57,12 @@ impl GlDevice {
         if self.textured_y8_pipeline.is_none() {
             self.textured_y8_pipeline = Some(TexturedY8Pipeline::new());
         }
+        if self.textured_lcd_pipeline.is_none() {
+            self.textured_lcd_pipeline = Some(TexturedLcdPipeline::new());
+        }
+        if self.distance_field_pipeline.is_none() {
+            self.distance_field_pipeline = Some(DistanceFieldPipeline::new());
+        }
         if self.universal_pipeline.is_none() {
             self.universal_pipeline = Some(UniversalPipeline::new());
         }
@@ -71,6 +79,16 @@ impl GlDevice {
                 .as_ref()
                 .unwrap()
                 .create_vbo_and_vao(),
+            textured_lcd_pipeline_buffers: self
+                .textured_lcd_pipeline
+                .as_ref()
+                .unwrap()
+                .create_vbo_and_vao(),
+            distance_field_pipeline_buffers: self
+                .distance_field_pipeline
+                .as_ref()
+                .unwrap()
+                .create_vbo_and_vao(),
             universal_pipeline_buffers: self
                 .universal_pipeline
                 .as_ref()
@@ -92,6 +110,14 @@ impl GlDevice {
             .as_mut()
             .unwrap()
             .set_buffers(gl_context_data.textured_y8_pipeline_buffers);
+        self.textured_lcd_pipeline
+            .as_mut()
+            .unwrap()
+            .set_buffers(gl_context_data.textured_lcd_pipeline_buffers);
+        self.distance_field_pipeline
+            .as_mut()
+            .unwrap()
+            .set_buffers(gl_context_data.distance_field_pipeline_buffers);
         self.universal_pipeline
             .as_mut()
             .unwrap()
@@ -233,6 +259,8 @@ impl drawing::backend::Device for GlDevice {
             colored_pipeline: None,
             textured_pipeline: None,
             textured_y8_pipeline: None,
+            textured_lcd_pipeline: None,
+            distance_field_pipeline: None,
             universal_pipeline: None,
             aspect_ratio: 1.0f32,
         })
@@ -417,6 +445,144 @@ impl drawing::backend::Device for GlDevice {
         }
     }
 
+    fn triangles_textured_lcd(
+        &mut self,
+        target: &Self::RenderTarget,
+        texture: &Self::Texture,
+        vertices: &[TexturedY8Vertex],
+        transform: UnknownToDeviceTransform,
+    ) {
+        self.set_render_target(target);
+        unsafe {
+            gl::Enable(gl::TEXTURE_2D);
+            gl::BindTexture(gl::TEXTURE_2D, texture.id);
+            // every texel is the coverage of the subpixels of one pixel
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::NEAREST as GLint);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::NEAREST as GLint);
+        }
+
+        let transform = [
+            [transform.m11, transform.m12, 0.0, 0.0],
+            [transform.m21, transform.m22, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [transform.m31, transform.m32, 0.0, 1.0],
+        ];
+
+        if let Some(ref mut pipeline) = self.textured_lcd_pipeline {
+            pipeline.apply();
+            pipeline.set_transform(&transform);
+            pipeline.set_flipped_y(texture.flipped_y);
+            pipeline.draw(vertices);
+        }
+    }
+
+    fn triangles_distance_field(
+        &mut self,
+        target: &Self::RenderTarget,
+        texture: &Self::Texture,
+        vertices: &[TexturedY8Vertex],
+        params: &DistanceFieldParams,
+        transform: UnknownToDeviceTransform,
+    ) {
+        self.set_render_target(target);
+        unsafe {
+            gl::Enable(gl::TEXTURE_2D);
+            gl::BindTexture(gl::TEXTURE_2D, texture.id);
+            // distances have to be interpolated
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as GLint);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as GLint);
+        }
+
+        let transform = [
+            [transform.m11, transform.m12, 0.0, 0.0],
+            [transform.m21, transform.m22, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [transform.m31, transform.m32, 0.0, 1.0],
+        ];
+
+        if let Some(ref mut pipeline) = self.distance_field_pipeline {
+            pipeline.apply();
+            pipeline.set_transform(&transform);
+            pipeline.set_flipped_y(texture.flipped_y);
+            pipeline.set_params(params);
+            pipeline.draw(vertices);
+        }
+    }
+
+    fn triangles_textured_y8_paint(
+        &mut self,
+        target: &Self::RenderTarget,
+        texture: &Self::Texture,
+        filtering: bool,
+        vertices: &[TexturedY8Vertex],
+        paint: &GlyphPaint<Self::Texture>,
+        transform: UnknownToDeviceTransform,
+    ) {
+        self.set_render_target(target);
+
+        let paint_texture = paint.texture;
+        let fringe_width = 1.0f32 / target.aspect_ratio;
+        let mut uniforms = Self::convert_paint(
+            paint.paint,
+            paint_texture,
+            &paint.scissor,
+            fringe_width,
+            fringe_width,
+            -1.0,
+        );
+        uniforms.type_ = if paint_texture.is_some() {
+            ShaderType::GlyphsImage as i32
+        } else {
+            ShaderType::GlyphsGradient as i32
+        };
+
+        unsafe {
+            gl::Enable(gl::TEXTURE_2D);
+            if let Some(paint_texture) = paint_texture {
+                gl::BindTexture(gl::TEXTURE_2D, paint_texture.id);
+                gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as GLint);
+                gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as GLint);
+            }
+            gl::ActiveTexture(gl::TEXTURE1);
+            gl::BindTexture(gl::TEXTURE_2D, texture.id);
+            if filtering {
+                gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as GLint);
+                gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as GLint);
+            } else {
+                gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::NEAREST as GLint);
+                gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::NEAREST as GLint);
+            }
+            gl::ActiveTexture(gl::TEXTURE0);
+        }
+
+        // the universal pipeline has no flipped_y uniform, so the coordinates are flipped here
+        let vertices: Vec<TexturedVertex> = vertices
+            .iter()
+            .map(|vertex| {
+                let tex_coords = if texture.flipped_y {
+                    [vertex.tex_coords[0], 1.0 - vertex.tex_coords[1]]
+                } else {
+                    vertex.tex_coords
+                };
+                TexturedVertex::new(vertex.pos, tex_coords, vertex.color)
+            })
+            .collect();
+
+        let transform = [
+            [transform.m11, transform.m12, 0.0, 0.0],
+            [transform.m21, transform.m22, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [transform.m31, transform.m32, 0.0, 1.0],
+        ];
+
+        if let Some(ref mut pipeline) = self.universal_pipeline {
+            pipeline.apply();
+            pipeline.set_transform(&transform);
+            pipeline.apply_frag_uniforms(&uniforms);
+            pipeline.draw(&vertices, gl::TRIANGLES);
+        }
+    }
+
     fn line(
         &mut self,
         target: &Self::RenderTarget,
@@ -441,7 +607,7 @@ impl drawing::backend::Device for GlDevice {
         paint: &Paint,
         texture: Option<&Self::Texture>,
         _filtering: bool,
-        paths: &[Path],
+        flattened_path: &FlattenedPath,
         thickness: f32,
         fringe_width: f32,
         antialiasing: bool,
@@ -487,8 +653,8 @@ impl drawing::backend::Device for GlDevice {
                         -1.0,
                     ));
                 }
-                for path in paths {
-                    let stroke_vertices = path.get_stroke();
+                for path in &flattened_path.paths {
+                    let stroke_vertices = flattened_path.get_stroke(path);
                     if !stroke_vertices.is_empty() {
                         pipeline.draw(stroke_vertices, gl::TRIANGLE_STRIP);
                     }
@@ -506,8 +672,8 @@ impl drawing::backend::Device for GlDevice {
                     ));
                     gl::StencilFunc(gl::EQUAL, 0x0, 0xff);
                     gl::StencilOp(gl::KEEP, gl::KEEP, gl::KEEP);
-                    for path in paths {
-                        let stroke_vertices = path.get_stroke();
+                    for path in &flattened_path.paths {
+                        let stroke_vertices = flattened_path.get_stroke(path);
                         if !stroke_vertices.is_empty() {
                             pipeline.draw(stroke_vertices, gl::TRIANGLE_STRIP);
                         }
@@ -518,8 +684,8 @@ impl drawing::backend::Device for GlDevice {
                 gl::ColorMask(gl::FALSE, gl::FALSE, gl::FALSE, gl::FALSE);
                 gl::StencilFunc(gl::ALWAYS, 0x0, 0xff);
                 gl::StencilOp(gl::ZERO, gl::ZERO, gl::ZERO);
-                for path in paths {
-                    let stroke_vertices = path.get_stroke();
+                for path in &flattened_path.paths {
+                    let stroke_vertices = flattened_path.get_stroke(path);
                     if !stroke_vertices.is_empty() {
                         pipeline.draw(stroke_vertices, gl::TRIANGLE_STRIP);
                     }
@@ -537,8 +703,7 @@ impl drawing::backend::Device for GlDevice {
         paint: &Paint,
         texture: Option<&Self::Texture>,
         filtering: bool,
-        paths: &[Path],
-        bounds: Bounds,
+        flattened_path: &FlattenedPath,
         fringe_width: f32,
         antialiasing: bool,
         scissor: Scissor,
@@ -561,7 +726,7 @@ impl drawing::backend::Device for GlDevice {
             }
         }
 
-        if paths.len() == 1 && paths[0].convex {
+        if flattened_path.paths.len() == 1 && flattened_path.paths[0].convex {
             // convex fill
             let uniforms =
                 Self::convert_paint(paint, texture, &scissor, fringe_width, fringe_width, -1.0);
@@ -580,14 +745,14 @@ impl drawing::backend::Device for GlDevice {
                 pipeline.apply_frag_uniforms(&uniforms);
 
                 // fill shape
-                let fill_vertices = paths[0].get_fill();
+                let fill_vertices = flattened_path.get_fill(&flattened_path.paths[0]);
                 if !fill_vertices.is_empty() {
                     pipeline.draw(fill_vertices, gl::TRIANGLE_FAN);
                 }
 
                 // antialias outline
                 if antialiasing {
-                    let stroke_vertices = paths[0].get_stroke();
+                    let stroke_vertices = flattened_path.get_stroke(&flattened_path.paths[0]);
                     if !stroke_vertices.is_empty() {
                         pipeline.draw(stroke_vertices, gl::TRIANGLE_STRIP);
                     }
@@ -624,8 +789,8 @@ impl drawing::backend::Device for GlDevice {
                     gl::StencilOpSeparate(gl::FRONT, gl::KEEP, gl::KEEP, gl::INCR_WRAP);
                     gl::StencilOpSeparate(gl::BACK, gl::KEEP, gl::KEEP, gl::DECR_WRAP);
                     gl::Disable(gl::CULL_FACE);
-                    for path in paths {
-                        let fill_vertices = path.get_fill();
+                    for path in &flattened_path.paths {
+                        let fill_vertices = flattened_path.get_fill(path);
                         if !fill_vertices.is_empty() {
                             pipeline.draw(fill_vertices, gl::TRIANGLE_FAN);
                         }
@@ -641,8 +806,8 @@ impl drawing::backend::Device for GlDevice {
                     if antialiasing {
                         gl::StencilFunc(gl::EQUAL, 0x00, 0xff);
                         gl::StencilOp(gl::KEEP, gl::KEEP, gl::KEEP);
-                        for path in paths {
-                            let stroke_vertices = path.get_stroke();
+                        for path in &flattened_path.paths {
+                            let stroke_vertices = flattened_path.get_stroke(path);
                             if !stroke_vertices.is_empty() {
                                 pipeline.draw(stroke_vertices, gl::TRIANGLE_STRIP);
                             }
@@ -652,6 +817,7 @@ impl drawing::backend::Device for GlDevice {
                     // Draw fill
                     gl::Disable(gl::CULL_FACE);
 
+                    let bounds = &flattened_path.bounds;
                     let rect_verts = vec![
                         TexturedVertex::new(
                             [bounds.max.x, bounds.max.y],
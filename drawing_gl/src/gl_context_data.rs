@@ -6,6 +6,8 @@ pub struct GlContextData {
     pub(crate) colored_pipeline_buffers: (GLuint, GLuint),
     pub(crate) textured_pipeline_buffers: (GLuint, GLuint),
     pub(crate) textured_y8_pipeline_buffers: (GLuint, GLuint),
+    pub(crate) textured_lcd_pipeline_buffers: (GLuint, GLuint),
+    pub(crate) distance_field_pipeline_buffers: (GLuint, GLuint),
     pub(crate) universal_pipeline_buffers: (GLuint, GLuint),
 }
 
@@ -21,6 +23,12 @@ impl Drop for GlContextData {
             gl::DeleteVertexArrays(1, &self.textured_y8_pipeline_buffers.1);
             gl::DeleteBuffers(1, &self.textured_y8_pipeline_buffers.0);
 
+            gl::DeleteVertexArrays(1, &self.textured_lcd_pipeline_buffers.1);
+            gl::DeleteBuffers(1, &self.textured_lcd_pipeline_buffers.0);
+
+            gl::DeleteVertexArrays(1, &self.distance_field_pipeline_buffers.1);
+            gl::DeleteBuffers(1, &self.distance_field_pipeline_buffers.0);
+
             gl::DeleteVertexArrays(1, &self.universal_pipeline_buffers.1);
             gl::DeleteBuffers(1, &self.universal_pipeline_buffers.0);
         }
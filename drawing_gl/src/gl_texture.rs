@@ -22,6 +22,7 @@ impl drawing::backend::Texture for GlTexture {
         height: u16,
     ) -> Result<()> {
         unsafe {
+            gl::BindTexture(gl::TEXTURE_2D, self.id);
             gl::TexSubImage2D(
                 gl::TEXTURE_2D,
                 0,
@@ -0,0 +1,196 @@
+use crate::utils::*;
+
+use drawing::backend::{DistanceFieldParams, TexturedY8Vertex};
+use gl::types::*;
+use std::ffi::CString;
+
+pub struct DistanceFieldPipeline {
+    program: Program,
+    vbo: GLuint,
+    vao: GLuint,
+    transform_location: GLint,
+    flipped_y_location: GLint,
+    distance_range_location: GLint,
+    outline_width_location: GLint,
+    outline_color_location: GLint,
+    glow_width_location: GLint,
+    glow_color_location: GLint,
+    softness_location: GLint,
+}
+
+impl DistanceFieldPipeline {
+    pub fn new() -> Self {
+        let vertex_shader =
+            Shader::from_vert_str(include_str!("shaders/distance_field.glslv")).unwrap();
+        let pixel_shader =
+            Shader::from_frag_str(include_str!("shaders/distance_field.glslf")).unwrap();
+        let program = Program::from_shaders(&[vertex_shader, pixel_shader]).unwrap();
+
+        let transform_location = unsafe {
+            let str = CString::new("transform").unwrap();
+            gl::GetUniformLocation(program.id(), str.as_ptr())
+        };
+        let flipped_y_location = unsafe {
+            let str = CString::new("flipped_y").unwrap();
+            gl::GetUniformLocation(program.id(), str.as_ptr())
+        };
+
+        let distance_range_location = unsafe {
+            let str = CString::new("distance_range").unwrap();
+            gl::GetUniformLocation(program.id(), str.as_ptr())
+        };
+        let outline_width_location = unsafe {
+            let str = CString::new("outline_width").unwrap();
+            gl::GetUniformLocation(program.id(), str.as_ptr())
+        };
+        let outline_color_location = unsafe {
+            let str = CString::new("outline_color").unwrap();
+            gl::GetUniformLocation(program.id(), str.as_ptr())
+        };
+        let glow_width_location = unsafe {
+            let str = CString::new("glow_width").unwrap();
+            gl::GetUniformLocation(program.id(), str.as_ptr())
+        };
+        let glow_color_location = unsafe {
+            let str = CString::new("glow_color").unwrap();
+            gl::GetUniformLocation(program.id(), str.as_ptr())
+        };
+        let softness_location = unsafe {
+            let str = CString::new("softness").unwrap();
+            gl::GetUniformLocation(program.id(), str.as_ptr())
+        };
+
+        DistanceFieldPipeline {
+            program,
+            vbo: 0,
+            vao: 0,
+            transform_location,
+            flipped_y_location,
+            distance_range_location,
+            outline_width_location,
+            outline_color_location,
+            glow_width_location,
+            glow_color_location,
+            softness_location,
+        }
+    }
+
+    pub fn set_buffers(&mut self, buffers_vbo_vba: (GLuint, GLuint)) {
+        self.vbo = buffers_vbo_vba.0;
+        self.vao = buffers_vbo_vba.1;
+    }
+
+    pub fn apply(&mut self) {
+        self.program.set_used();
+    }
+
+    pub fn set_transform(&mut self, transform: &[[f32; 4]; 4]) {
+        unsafe {
+            let ptr: *const f32 = std::mem::transmute(transform);
+            gl::UniformMatrix4fv(self.transform_location, 1, gl::FALSE, ptr);
+        }
+    }
+
+    pub fn set_flipped_y(&mut self, flipped_y: bool) {
+        unsafe {
+            gl::Uniform1i(self.flipped_y_location, if flipped_y { 1 } else { 0 });
+        }
+    }
+
+    pub fn set_params(&mut self, params: &DistanceFieldParams) {
+        unsafe {
+            gl::Uniform1f(self.distance_range_location, params.distance_range);
+            gl::Uniform1f(self.outline_width_location, params.outline_width);
+            gl::Uniform4fv(
+                self.outline_color_location,
+                1,
+                params.outline_color.as_ptr(),
+            );
+            gl::Uniform1f(self.glow_width_location, params.glow_width);
+            gl::Uniform4fv(self.glow_color_location, 1, params.glow_color.as_ptr());
+            gl::Uniform1f(self.softness_location, params.softness);
+        }
+    }
+
+    pub fn draw(&mut self, array: &[TexturedY8Vertex]) {
+        self.apply_array(array);
+        unsafe {
+            gl::BindVertexArray(self.vao);
+            gl::DrawArrays(gl::TRIANGLES, 0, array.len() as GLint);
+        }
+    }
+
+    fn apply_array(&mut self, array: &[TexturedY8Vertex]) {
+        unsafe {
+            gl::BindBuffer(gl::ARRAY_BUFFER, self.vbo);
+            gl::BufferData(
+                gl::ARRAY_BUFFER,                                                      // target
+                (array.len() * std::mem::size_of::<TexturedY8Vertex>()) as GLsizeiptr, // size of data in bytes
+                array.as_ptr() as *const GLvoid, // pointer to data
+                gl::STREAM_DRAW,                 // usage
+            );
+            gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+        }
+    }
+
+    pub fn create_vbo_and_vao(&self) -> (GLuint, GLuint) {
+        let mut vbo: GLuint = 0;
+        unsafe {
+            gl::GenBuffers(1, &mut vbo);
+        }
+
+        let mut vao: GLuint = 0;
+        unsafe {
+            gl::GenVertexArrays(1, &mut vao);
+        }
+
+        DistanceFieldPipeline::specify_layout(self.program.id(), vbo, vao);
+
+        (vbo, vao)
+    }
+
+    fn specify_layout(program_id: GLuint, vbo: GLuint, vao: GLuint) {
+        unsafe {
+            gl::BindVertexArray(vao);
+            gl::BindBuffer(gl::ARRAY_BUFFER, vbo);
+
+            let str = CString::new("in_position").unwrap();
+            let pos_attr = gl::GetAttribLocation(program_id, str.as_ptr());
+            gl::EnableVertexAttribArray(pos_attr as GLuint);
+            gl::VertexAttribPointer(
+                pos_attr as GLuint,
+                2,
+                gl::FLOAT,
+                gl::FALSE as GLboolean,
+                (8 * std::mem::size_of::<f32>()) as GLint, // stride (byte offset between consecutive attributes)
+                std::ptr::null(),
+            ); // offset of the first component
+
+            let str = CString::new("in_tex_coords").unwrap();
+            let pos_attr = gl::GetAttribLocation(program_id, str.as_ptr());
+            gl::EnableVertexAttribArray(pos_attr as GLuint);
+            gl::VertexAttribPointer(
+                pos_attr as GLuint,
+                2,
+                gl::FLOAT,
+                gl::FALSE as GLboolean,
+                (8 * std::mem::size_of::<f32>()) as GLint, // stride (byte offset between consecutive attributes)
+                (2 * std::mem::size_of::<f32>()) as *const GLvoid,
+            ); // offset of the first component
+
+            let str = CString::new("in_color").unwrap();
+            let pos_attr = gl::GetAttribLocation(program_id, str.as_ptr());
+            gl::EnableVertexAttribArray(pos_attr as GLuint);
+            gl::VertexAttribPointer(
+                pos_attr as GLuint,
+                4,
+                gl::FLOAT,
+                gl::FALSE as GLboolean,
+                (8 * std::mem::size_of::<f32>()) as GLint, // stride (byte offset between consecutive attributes)
+                (4 * std::mem::size_of::<f32>()) as *const GLvoid,
+            ); // offset of the first component
+
+            gl::BindVertexArray(0);
+        }
+    }
+}
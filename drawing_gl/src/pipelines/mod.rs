@@ -1,6 +1,12 @@
 mod colored_pipeline;
 pub use self::colored_pipeline::*;
 
+mod distance_field_pipeline;
+pub use self::distance_field_pipeline::*;
+
+mod textured_lcd_pipeline;
+pub use self::textured_lcd_pipeline::*;
+
 mod textured_pipeline;
 pub use self::textured_pipeline::*;
 
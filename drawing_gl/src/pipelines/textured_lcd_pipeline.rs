@@ -0,0 +1,214 @@
+use crate::utils::*;
+
+use drawing::backend::TexturedY8Vertex;
+use gl::types::*;
+use std::ffi::{CStr, CString};
+use std::os::raw::c_char;
+
+pub struct TexturedLcdPipeline {
+    program: Program,
+    vbo: GLuint,
+    vao: GLuint,
+    transform_location: GLint,
+    flipped_y_location: GLint,
+    channel_location: GLint,
+    // blending with the coverage of every channel in one pass
+    dual_source_blending: bool,
+}
+
+impl TexturedLcdPipeline {
+    pub fn new() -> Self {
+        let vertex_shader =
+            Shader::from_vert_str(include_str!("shaders/textured_lcd.glslv")).unwrap();
+        let pixel_shader =
+            Shader::from_frag_str(include_str!("shaders/textured_lcd.glslf")).unwrap();
+        let dual_source_blending = Self::is_dual_source_blending_supported();
+        let program = Program::from_shaders_with(&[vertex_shader, pixel_shader], |program_id| {
+            let color = CString::new("frag_color").unwrap();
+            let coverage = CString::new("frag_coverage").unwrap();
+            unsafe {
+                if dual_source_blending {
+                    gl::BindFragDataLocationIndexed(program_id, 0, 0, color.as_ptr());
+                    gl::BindFragDataLocationIndexed(program_id, 0, 1, coverage.as_ptr());
+                } else {
+                    gl::BindFragDataLocation(program_id, 0, color.as_ptr());
+                    gl::BindFragDataLocation(program_id, 1, coverage.as_ptr());
+                }
+            }
+        })
+        .unwrap();
+
+        let transform_location = unsafe {
+            let str = CString::new("transform").unwrap();
+            gl::GetUniformLocation(program.id(), str.as_ptr())
+        };
+        let flipped_y_location = unsafe {
+            let str = CString::new("flipped_y").unwrap();
+            gl::GetUniformLocation(program.id(), str.as_ptr())
+        };
+
+        let channel_location = unsafe {
+            let str = CString::new("channel").unwrap();
+            gl::GetUniformLocation(program.id(), str.as_ptr())
+        };
+
+        TexturedLcdPipeline {
+            program,
+            vbo: 0,
+            vao: 0,
+            transform_location,
+            flipped_y_location,
+            channel_location,
+            dual_source_blending,
+        }
+    }
+
+    // dual source blending is available in OpenGL 3.3 and with ARB_blend_func_extended
+    fn is_dual_source_blending_supported() -> bool {
+        unsafe {
+            let mut major = 0;
+            let mut minor = 0;
+            gl::GetIntegerv(gl::MAJOR_VERSION, &mut major);
+            gl::GetIntegerv(gl::MINOR_VERSION, &mut minor);
+            if (major, minor) >= (3, 3) {
+                return true;
+            }
+            let mut count = 0;
+            gl::GetIntegerv(gl::NUM_EXTENSIONS, &mut count);
+            (0..count as GLuint).any(|index| {
+                let name = gl::GetStringi(gl::EXTENSIONS, index);
+                !name.is_null()
+                    && CStr::from_ptr(name as *const c_char).to_bytes()
+                        == b"GL_ARB_blend_func_extended"
+            })
+        }
+    }
+
+    pub fn set_buffers(&mut self, buffers_vbo_vba: (GLuint, GLuint)) {
+        self.vbo = buffers_vbo_vba.0;
+        self.vao = buffers_vbo_vba.1;
+    }
+
+    pub fn apply(&mut self) {
+        self.program.set_used();
+    }
+
+    pub fn set_transform(&mut self, transform: &[[f32; 4]; 4]) {
+        unsafe {
+            let ptr: *const f32 = std::mem::transmute(transform);
+            gl::UniformMatrix4fv(self.transform_location, 1, gl::FALSE, ptr);
+        }
+    }
+
+    pub fn set_flipped_y(&mut self, flipped_y: bool) {
+        unsafe {
+            gl::Uniform1i(self.flipped_y_location, if flipped_y { 1 } else { 0 });
+        }
+    }
+
+    /// Draws the glyphs blending every color channel with its own coverage.
+    /// Without dual source blending every channel is drawn in a separate pass.
+    pub fn draw(&mut self, array: &[TexturedY8Vertex]) {
+        self.apply_array(array);
+        unsafe {
+            gl::BindVertexArray(self.vao);
+            if self.dual_source_blending {
+                gl::Uniform1i(self.channel_location, -1);
+                gl::BlendFuncSeparate(
+                    gl::SRC1_COLOR,
+                    gl::ONE_MINUS_SRC1_COLOR,
+                    gl::SRC1_ALPHA,
+                    gl::ONE_MINUS_SRC1_ALPHA,
+                );
+                gl::DrawArrays(gl::TRIANGLES, 0, array.len() as GLint);
+            } else {
+                for channel in 0..4 {
+                    gl::ColorMask(
+                        (channel == 0) as GLboolean,
+                        (channel == 1) as GLboolean,
+                        (channel == 2) as GLboolean,
+                        (channel == 3) as GLboolean,
+                    );
+                    gl::Uniform1i(self.channel_location, channel);
+                    gl::DrawArrays(gl::TRIANGLES, 0, array.len() as GLint);
+                }
+                gl::ColorMask(gl::TRUE, gl::TRUE, gl::TRUE, gl::TRUE);
+            }
+            gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+        }
+    }
+
+    fn apply_array(&mut self, array: &[TexturedY8Vertex]) {
+        unsafe {
+            gl::BindBuffer(gl::ARRAY_BUFFER, self.vbo);
+            gl::BufferData(
+                gl::ARRAY_BUFFER,                                                      // target
+                (array.len() * std::mem::size_of::<TexturedY8Vertex>()) as GLsizeiptr, // size of data in bytes
+                array.as_ptr() as *const GLvoid, // pointer to data
+                gl::STREAM_DRAW,                 // usage
+            );
+            gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+        }
+    }
+
+    pub fn create_vbo_and_vao(&self) -> (GLuint, GLuint) {
+        let mut vbo: GLuint = 0;
+        unsafe {
+            gl::GenBuffers(1, &mut vbo);
+        }
+
+        let mut vao: GLuint = 0;
+        unsafe {
+            gl::GenVertexArrays(1, &mut vao);
+        }
+
+        TexturedLcdPipeline::specify_layout(self.program.id(), vbo, vao);
+
+        (vbo, vao)
+    }
+
+    fn specify_layout(program_id: GLuint, vbo: GLuint, vao: GLuint) {
+        unsafe {
+            gl::BindVertexArray(vao);
+            gl::BindBuffer(gl::ARRAY_BUFFER, vbo);
+
+            let str = CString::new("in_position").unwrap();
+            let pos_attr = gl::GetAttribLocation(program_id, str.as_ptr());
+            gl::EnableVertexAttribArray(pos_attr as GLuint);
+            gl::VertexAttribPointer(
+                pos_attr as GLuint,
+                2,
+                gl::FLOAT,
+                gl::FALSE as GLboolean,
+                (8 * std::mem::size_of::<f32>()) as GLint, // stride (byte offset between consecutive attributes)
+                std::ptr::null(),
+            ); // offset of the first component
+
+            let str = CString::new("in_tex_coords").unwrap();
+            let pos_attr = gl::GetAttribLocation(program_id, str.as_ptr());
+            gl::EnableVertexAttribArray(pos_attr as GLuint);
+            gl::VertexAttribPointer(
+                pos_attr as GLuint,
+                2,
+                gl::FLOAT,
+                gl::FALSE as GLboolean,
+                (8 * std::mem::size_of::<f32>()) as GLint, // stride (byte offset between consecutive attributes)
+                (2 * std::mem::size_of::<f32>()) as *const GLvoid,
+            ); // offset of the first component
+
+            let str = CString::new("in_color").unwrap();
+            let pos_attr = gl::GetAttribLocation(program_id, str.as_ptr());
+            gl::EnableVertexAttribArray(pos_attr as GLuint);
+            gl::VertexAttribPointer(
+                pos_attr as GLuint,
+                4,
+                gl::FLOAT,
+                gl::FALSE as GLboolean,
+                (8 * std::mem::size_of::<f32>()) as GLint, // stride (byte offset between consecutive attributes)
+                (4 * std::mem::size_of::<f32>()) as *const GLvoid,
+            ); // offset of the first component
+
+            gl::BindVertexArray(0);
+        }
+    }
+}
@@ -31,6 +31,8 @@ pub enum ShaderType {
     FillImage,
     Simple,
     //Image,
+    GlyphsGradient = 4,
+    GlyphsImage,
 }
 
 pub struct UniversalPipeline {
@@ -47,6 +49,14 @@ impl UniversalPipeline {
         let pixel_shader = Shader::from_frag_str(include_str!("shaders/universal.glslf")).unwrap();
         let program = Program::from_shaders(&[vertex_shader, pixel_shader]).unwrap();
 
+        // the glyph atlas is bound to the second texture unit
+        unsafe {
+            let str = CString::new("glyph_sampler").unwrap();
+            let glyph_sampler_location = gl::GetUniformLocation(program.id(), str.as_ptr());
+            program.set_used();
+            gl::Uniform1i(glyph_sampler_location, 1);
+        }
+
         let transform_location = unsafe {
             let str = CString::new("transform").unwrap();
             gl::GetUniformLocation(program.id(), str.as_ptr())
@@ -6,6 +6,15 @@ pub struct Program {
 
 impl Program {
     pub fn from_shaders(shaders: &[Shader]) -> Result<Program, String> {
+        Self::from_shaders_with(shaders, |_| ())
+    }
+
+    /// Same as `from_shaders()` but calls the function with the id of the program
+    /// before it's linked (to bind the locations of the fragment shader outputs).
+    pub fn from_shaders_with<F>(shaders: &[Shader], before_link: F) -> Result<Program, String>
+    where
+        F: FnOnce(gl::types::GLuint),
+    {
         let program_id = unsafe { gl::CreateProgram() };
 
         for shader in shaders {
@@ -14,6 +23,8 @@ impl Program {
             }
         }
 
+        before_link(program_id);
+
         unsafe {
             gl::LinkProgram(program_id);
         }